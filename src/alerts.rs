@@ -0,0 +1,370 @@
+//! Alert state machine
+//!
+//! Each target is OK (never failed), FAILING (an incident is open) or
+//! RECOVERED (the last incident was resolved). The first failure opens an
+//! incident and sends an alert; further failures are suppressed except for a
+//! reminder every reminder interval, or an immediate alert when a failure is
+//! more severe than any before it in the incident (a near miss warning
+//! followed by an outage); the next success resolves the incident with a
+//! message giving the outage duration.
+//!
+//! The states are saved to a JSON file after every transition so a restart
+//! neither re-alerts an open incident nor forgets it.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::notify::Severity;
+
+/// Default file holding the alert states
+pub const DEFAULT_ALERT_STATE_FILE: &str = "./alert_state.json";
+
+/// Default minutes between reminders while an incident stays open
+pub const DEFAULT_REMINDER_MINS: u64 = 240;
+
+/// Alert state of one target
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum AlertState {
+    /// No failure seen yet
+    #[default]
+    Ok,
+    /// An incident is open
+    Failing {
+        /// When the first failure of the incident happened
+        since: DateTime<Utc>,
+        /// When the last alert or reminder was sent
+        last_alert: DateTime<Utc>,
+        /// Failed checks in this incident
+        failures: u32,
+        /// Highest severity alerted in this incident (incidents saved
+        /// without one are taken as critical)
+        #[serde(default = "critical")]
+        severity: Severity,
+    },
+    /// The last incident was resolved
+    Recovered {
+        /// When the incident was resolved
+        at: DateTime<Utc>,
+        /// How long the incident lasted, in seconds
+        outage_secs: i64,
+    },
+}
+
+fn critical() -> Severity {
+    Severity::Critical
+}
+
+/// What to send after a check result
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlertAction {
+    /// Nothing (healthy, or a failure within the reminder interval)
+    None,
+    /// A new incident, or one that became more severe: send the failure
+    /// alert
+    Alert,
+    /// The incident is still open and a reminder is due
+    Remind { outage: Duration, failures: u32 },
+    /// The incident is resolved
+    Resolve { outage: Duration, failures: u32 },
+}
+
+impl AlertAction {
+    /// Short name used in the check history
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertAction::None => "none",
+            AlertAction::Alert => "alert",
+            AlertAction::Remind { .. } => "remind",
+            AlertAction::Resolve { .. } => "resolve",
+        }
+    }
+}
+
+impl AlertState {
+    /// Transition on a failed check of `severity`. `reminder` is the
+    /// interval between reminders (`None` disables them).
+    pub fn on_failure(
+        &self,
+        now: DateTime<Utc>,
+        severity: Severity,
+        reminder: Option<Duration>,
+    ) -> (AlertState, AlertAction) {
+        match *self {
+            AlertState::Ok | AlertState::Recovered { .. } => (
+                AlertState::Failing { since: now, last_alert: now, failures: 1, severity },
+                AlertAction::Alert,
+            ),
+            AlertState::Failing { since, last_alert, failures, severity: alerted } => {
+                let failures = failures.saturating_add(1);
+                if severity > alerted {
+                    return (AlertState::Failing { since, last_alert: now, failures, severity }, AlertAction::Alert);
+                }
+                match reminder {
+                    Some(interval) if now - last_alert >= interval => (
+                        AlertState::Failing { since, last_alert: now, failures, severity: alerted },
+                        AlertAction::Remind { outage: now - since, failures },
+                    ),
+                    _ => (AlertState::Failing { since, last_alert, failures, severity: alerted }, AlertAction::None),
+                }
+            }
+        }
+    }
+
+    /// Transition on a successful check
+    pub fn on_success(&self, now: DateTime<Utc>) -> (AlertState, AlertAction) {
+        match *self {
+            AlertState::Failing { since, failures, .. } => {
+                let outage = now - since;
+                (
+                    AlertState::Recovered { at: now, outage_secs: outage.num_seconds() },
+                    AlertAction::Resolve { outage, failures },
+                )
+            }
+            _ => (self.clone(), AlertAction::None),
+        }
+    }
+}
+
+/// Alert states of all targets, persisted to a file
+#[derive(Debug)]
+pub struct AlertTracker {
+    path: Option<PathBuf>,
+    reminder: Option<Duration>,
+    states: Mutex<BTreeMap<String, AlertState>>,
+}
+
+impl AlertTracker {
+    /// An in-memory tracker (nothing is saved). A zero `reminder_mins`
+    /// disables reminders.
+    pub fn new(reminder_mins: u64) -> Self {
+        Self {
+            path: None,
+            reminder: (reminder_mins > 0).then(|| Duration::minutes(reminder_mins as i64)),
+            states: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Load the states saved in `path` (missing file: every target is OK)
+    pub fn load(path: impl AsRef<Path>, reminder_mins: u64) -> Result<Self> {
+        let path = path.as_ref();
+        let states = if path.exists() {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read alert state file '{}'", path.display()))?;
+            serde_json::from_str(&text)
+                .with_context(|| format!("Invalid alert state file '{}'", path.display()))?
+        } else {
+            BTreeMap::new()
+        };
+
+        for (target, state) in &states {
+            if let AlertState::Failing { since, failures, .. } = state {
+                info!("Open incident for '{}' since {} ({} failed checks)", target, since, failures);
+            }
+        }
+
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            states: Mutex::new(states),
+            ..Self::new(reminder_mins)
+        })
+    }
+
+    /// Current state of a target
+    pub fn state(&self, target: &str) -> AlertState {
+        let states = self.states.lock().unwrap_or_else(|e| e.into_inner());
+        states.get(target).cloned().unwrap_or_default()
+    }
+
+    /// Record a failed check of a target
+    pub fn record_failure(&self, target: &str, now: DateTime<Utc>, severity: Severity) -> AlertAction {
+        let reminder = self.reminder;
+        self.transition(target, |state| state.on_failure(now, severity, reminder))
+    }
+
+    /// Record a successful check of a target
+    pub fn record_success(&self, target: &str, now: DateTime<Utc>) -> AlertAction {
+        self.transition(target, |state| state.on_success(now))
+    }
+
+    fn transition<F>(&self, target: &str, f: F) -> AlertAction
+    where
+        F: FnOnce(&AlertState) -> (AlertState, AlertAction),
+    {
+        let mut states = self.states.lock().unwrap_or_else(|e| e.into_inner());
+        let current = states.get(target).cloned().unwrap_or_default();
+        let (next, action) = f(&current);
+        if next != current {
+            states.insert(target.to_string(), next);
+            self.save(&states);
+        }
+        action
+    }
+
+    /// Write the states (to a temporary file first so a crash cannot leave a
+    /// truncated file). Failures are logged: alerting goes on in memory.
+    fn save(&self, states: &BTreeMap<String, AlertState>) {
+        let Some(ref path) = self.path else {
+            return;
+        };
+        let tmp = path.with_extension("tmp");
+        let result = serde_json::to_string_pretty(states)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| Ok(std::fs::write(&tmp, json)?))
+            .and_then(|()| Ok(std::fs::rename(&tmp, path)?));
+        if let Err(e) = result {
+            warn!("Failed to save alert state to '{}': {}", path.display(), e);
+        }
+    }
+}
+
+/// Human-readable duration, e.g. `2d 3h`, `1h 5m` or `45m`
+pub fn format_duration(duration: Duration) -> String {
+    let minutes = duration.num_minutes().max(0);
+    let (days, hours, mins) = (minutes / 1440, minutes / 60 % 24, minutes % 60);
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, mins)
+    } else if minutes > 0 {
+        format!("{}m", mins)
+    } else {
+        format!("{}s", duration.num_seconds().max(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 1, 15, 8, 0, 0).unwrap() + Duration::minutes(minutes)
+    }
+
+    #[test]
+    fn test_first_failure_alerts_then_suppresses() {
+        let tracker = AlertTracker::new(0);
+        assert_eq!(tracker.record_success("main", at(0)), AlertAction::None);
+        assert_eq!(tracker.record_failure("main", at(60), Severity::Critical), AlertAction::Alert);
+        assert_eq!(tracker.record_failure("main", at(120), Severity::Critical), AlertAction::None);
+        assert_eq!(tracker.record_failure("main", at(10_000), Severity::Critical), AlertAction::None);
+        assert_eq!(
+            tracker.state("main"),
+            AlertState::Failing { since: at(60), last_alert: at(60), failures: 3, severity: Severity::Critical }
+        );
+    }
+
+    #[test]
+    fn test_reminders_repeat_at_interval() {
+        let tracker = AlertTracker::new(120);
+        assert_eq!(tracker.record_failure("main", at(0), Severity::Critical), AlertAction::Alert);
+        assert_eq!(tracker.record_failure("main", at(60), Severity::Critical), AlertAction::None);
+        assert_eq!(
+            tracker.record_failure("main", at(120), Severity::Critical),
+            AlertAction::Remind { outage: Duration::minutes(120), failures: 3 }
+        );
+        assert_eq!(tracker.record_failure("main", at(180), Severity::Critical), AlertAction::None);
+        assert_eq!(
+            tracker.record_failure("main", at(240), Severity::Critical),
+            AlertAction::Remind { outage: Duration::minutes(240), failures: 5 }
+        );
+    }
+
+    #[test]
+    fn test_warning_then_critical_alerts_at_once() {
+        let tracker = AlertTracker::new(240);
+        assert_eq!(tracker.record_failure("main", at(0), Severity::Warning), AlertAction::Alert);
+        assert_eq!(tracker.record_failure("main", at(5), Severity::Warning), AlertAction::None);
+
+        // The outage is alerted without waiting for the reminder
+        assert_eq!(tracker.record_failure("main", at(10), Severity::Critical), AlertAction::Alert);
+        assert_eq!(
+            tracker.state("main"),
+            AlertState::Failing { since: at(0), last_alert: at(10), failures: 3, severity: Severity::Critical }
+        );
+
+        // Only once, and a warning after it doesn't lower the incident
+        assert_eq!(tracker.record_failure("main", at(15), Severity::Critical), AlertAction::None);
+        assert_eq!(tracker.record_failure("main", at(20), Severity::Warning), AlertAction::None);
+        assert_eq!(tracker.record_failure("main", at(25), Severity::Critical), AlertAction::None);
+        assert_eq!(
+            tracker.record_failure("main", at(250), Severity::Warning),
+            AlertAction::Remind { outage: Duration::minutes(250), failures: 7 }
+        );
+    }
+
+    #[test]
+    fn test_incident_saved_without_severity_is_critical() {
+        let json = r#"{"main":{"state":"failing","since":"2025-01-15T08:00:00Z","last_alert":"2025-01-15T08:00:00Z","failures":1}}"#;
+        let states: BTreeMap<String, AlertState> = serde_json::from_str(json).unwrap();
+        assert!(matches!(states["main"], AlertState::Failing { severity: Severity::Critical, .. }));
+    }
+
+    #[test]
+    fn test_success_resolves_incident() {
+        let tracker = AlertTracker::new(0);
+        tracker.record_failure("main", at(0), Severity::Critical);
+        tracker.record_failure("main", at(60), Severity::Critical);
+        assert_eq!(
+            tracker.record_success("main", at(90)),
+            AlertAction::Resolve { outage: Duration::minutes(90), failures: 2 }
+        );
+        assert_eq!(tracker.state("main"), AlertState::Recovered { at: at(90), outage_secs: 5400 });
+        assert_eq!(tracker.record_success("main", at(150)), AlertAction::None);
+
+        // A new failure opens a new incident
+        assert_eq!(tracker.record_failure("main", at(200), Severity::Critical), AlertAction::Alert);
+    }
+
+    #[test]
+    fn test_targets_are_independent() {
+        let tracker = AlertTracker::new(0);
+        assert_eq!(tracker.record_failure("main", at(0), Severity::Critical), AlertAction::Alert);
+        assert_eq!(tracker.record_failure("support", at(0), Severity::Critical), AlertAction::Alert);
+        assert!(matches!(tracker.record_success("main", at(30)), AlertAction::Resolve { .. }));
+        assert_eq!(tracker.record_failure("support", at(60), Severity::Critical), AlertAction::None);
+    }
+
+    #[test]
+    fn test_state_survives_restart() {
+        let path = std::env::temp_dir().join(format!("phonecheck_alert_state_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let tracker = AlertTracker::load(&path, 0).unwrap();
+        assert_eq!(tracker.record_failure("main", at(0), Severity::Critical), AlertAction::Alert);
+        drop(tracker);
+
+        // No second alert for the open incident, and recovery is still reported
+        let tracker = AlertTracker::load(&path, 0).unwrap();
+        assert_eq!(tracker.record_failure("main", at(60), Severity::Critical), AlertAction::None);
+        assert_eq!(
+            tracker.record_success("main", at(90)),
+            AlertAction::Resolve { outage: Duration::minutes(90), failures: 2 }
+        );
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_load_rejects_corrupt_file() {
+        let path = std::env::temp_dir().join(format!("phonecheck_alert_corrupt_{}.json", std::process::id()));
+        std::fs::write(&path, "{not json").unwrap();
+        let err = AlertTracker::load(&path, 0).unwrap_err();
+        assert!(format!("{:#}", err).contains("Invalid alert state file"), "{:#}", err);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::seconds(42)), "42s");
+        assert_eq!(format_duration(Duration::minutes(45)), "45m");
+        assert_eq!(format_duration(Duration::minutes(65)), "1h 5m");
+        assert_eq!(format_duration(Duration::minutes(3 * 1440 + 125)), "3d 2h");
+    }
+}
@@ -0,0 +1,170 @@
+//! Frame-level alignment of a capture against a reference
+//!
+//! Mean pooling turns a whole clip into one vector, so a capture that is
+//! shorter than the reference, starts with a ring or ends in hold music
+//! scores lower than the same greeting heard cleanly. Here both clips are
+//! kept as Wav2Vec2 frame embeddings (one per 20 ms) and the reference is
+//! aligned to the best-matching segment of the capture with subsequence
+//! dynamic time warping (DTW): the segment may start and end anywhere in the
+//! capture, and the greeting may be played up to twice as fast or slower.
+//!
+//! Every reference frame is matched to exactly one capture frame, so the
+//! score is the mean cosine similarity of `reference.len()` frame pairs: it
+//! doesn't depend on how long the capture is or where the greeting starts.
+
+/// Duration of one Wav2Vec2 frame (320 samples at 16 kHz)
+pub const FRAME_MS: u64 = 20;
+
+/// Shortest capture segment (as a fraction of the reference) the reference
+/// may be aligned to, so a few frames can't stand in for a whole greeting
+const MIN_SPAN: f32 = 0.5;
+
+/// Where and how well the reference matched the capture
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Alignment {
+    /// Mean cosine similarity of the aligned frame pairs
+    pub score: f32,
+    /// First capture frame of the aligned segment
+    pub start_frame: usize,
+    /// Last capture frame of the aligned segment
+    pub end_frame: usize,
+}
+
+impl Alignment {
+    /// Offset of the greeting in the capture
+    pub fn offset_ms(&self) -> u64 {
+        self.start_frame as u64 * FRAME_MS
+    }
+
+    /// Duration of the aligned segment of the capture
+    pub fn duration_ms(&self) -> u64 {
+        (self.end_frame - self.start_frame + 1) as u64 * FRAME_MS
+    }
+}
+
+/// Align L2-normalized `reference` frames to the best segment of `capture`.
+/// `None` when either is empty or the capture is too short to hold the
+/// reference.
+pub fn align(reference: &[Vec<f32>], capture: &[Vec<f32>]) -> Option<Alignment> {
+    let n = reference.len();
+    let m = capture.len();
+    let min_span = ((n as f32 * MIN_SPAN).ceil() as usize).max(1);
+    if n == 0 || m < min_span {
+        return None;
+    }
+
+    // Accumulated cost (1 - cosine) and start frame of the best path ending
+    // at each capture frame, for the previous and current reference frame
+    let mut prev: Vec<(f32, usize)> = (0..m).map(|j| (1.0 - dot(&reference[0], &capture[j]), j)).collect();
+    let mut cur = vec![(0.0, 0); m];
+    for frame in &reference[1..] {
+        for j in 0..m {
+            // Reach (i, j) from (i-1, j), (i-1, j-1) or (i-1, j-2)
+            let mut best = prev[j];
+            for step in 1..=2 {
+                if j >= step && prev[j - step].0 < best.0 {
+                    best = prev[j - step];
+                }
+            }
+            cur[j] = (best.0 + 1.0 - dot(frame, &capture[j]), best.1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+
+    prev.iter()
+        .enumerate()
+        .filter(|(end, (_, start))| end + 1 - start >= min_span)
+        .min_by(|a, b| a.1 .0.total_cmp(&b.1 .0))
+        .map(|(end, &(cost, start))| Alignment {
+            score: 1.0 - cost / n as f32,
+            start_frame: start,
+            end_frame: end,
+        })
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pseudo-random unit vectors, one per "phoneme"
+    fn frame(id: usize) -> Vec<f32> {
+        let mut state = (id as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        let mut v: Vec<f32> = (0..64)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state % 2000) as f32 / 1000.0 - 1.0
+            })
+            .collect();
+        let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        v.iter_mut().for_each(|x| *x /= norm);
+        v
+    }
+
+    fn frames(ids: impl IntoIterator<Item = usize>) -> Vec<Vec<f32>> {
+        ids.into_iter().map(frame).collect()
+    }
+
+    #[test]
+    fn test_finds_the_greeting_inside_a_longer_capture() {
+        let reference = frames(0..50);
+        // A ring and 1.5 s of silence, the greeting, then hold music
+        let capture: Vec<Vec<f32>> = frames((100..175).chain(0..50).chain(200..300));
+
+        let alignment = align(&reference, &capture).unwrap();
+        assert!((alignment.score - 1.0).abs() < 1e-5, "{:?}", alignment);
+        assert_eq!(alignment.start_frame, 75);
+        assert_eq!(alignment.end_frame, 124);
+        assert_eq!(alignment.offset_ms(), 1500);
+        assert_eq!(alignment.duration_ms(), 1000);
+    }
+
+    #[test]
+    fn test_score_does_not_depend_on_capture_length() {
+        let reference = frames(0..50);
+        let short = frames((0..50).chain(60..70));
+        let long = frames((300..500).chain(0..50).chain(60..400));
+        let short = align(&reference, &short).unwrap().score;
+        let long = align(&reference, &long).unwrap().score;
+        assert!((short - long).abs() < 1e-5, "{} vs {}", short, long);
+    }
+
+    #[test]
+    fn test_tolerates_tempo_changes() {
+        let reference = frames(0..40);
+        // Played slower (every frame twice) and faster (every other frame)
+        let slow = frames((0..40).flat_map(|i| [i, i]));
+        let fast = frames((0..40).step_by(2));
+        let slow = align(&reference, &slow).unwrap();
+        assert!(slow.score > 0.99, "{:?}", slow);
+        assert!(slow.start_frame <= 1, "{:?}", slow);
+        assert!(slow.duration_ms() >= 78 * FRAME_MS, "{:?}", slow);
+        // Skipped frames are aligned to a neighbour, so half the pairs match
+        let fast = align(&reference, &fast).unwrap();
+        assert!(fast.score > 0.45, "{:?}", fast);
+        assert_eq!(fast.duration_ms(), 20 * FRAME_MS);
+    }
+
+    #[test]
+    fn test_different_audio_scores_low() {
+        let reference = frames(0..50);
+        let other = frames(500..600);
+        let matched = align(&reference, &frames(0..50)).unwrap().score;
+        let unrelated = align(&reference, &other).unwrap().score;
+        assert!(unrelated < matched - 0.3, "{} vs {}", unrelated, matched);
+    }
+
+    #[test]
+    fn test_too_short_or_empty() {
+        let reference = frames(0..50);
+        assert_eq!(align(&reference, &frames(0..24)), None);
+        assert!(align(&reference, &frames(0..25)).is_some());
+        assert_eq!(align(&[], &frames(0..10)), None);
+        assert_eq!(align(&reference, &[]), None);
+    }
+}
@@ -0,0 +1,224 @@
+//! Offline analysis of a recorded WAV file
+//!
+//! Runs the same audio checks as a live call (tone detection, then Whisper
+//! transcription and embedding similarity against each target's references)
+//! on a file such as one written by `--save-audio`. No call is made, no
+//! notification is sent and references are never written, so it can be
+//! used to tune thresholds and to triage alerts after the fact.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::Path;
+
+use crate::matcher::CrossCheck;
+use crate::phrase::PhraseMatch;
+use crate::rtp::resample::resample;
+use crate::rtp::tones::detect_tones;
+use crate::rtp::WHISPER_SAMPLE_RATE;
+use crate::speech::SpeechRecognizer;
+use crate::targets::CheckTarget;
+
+/// Sample rate the tone detector works at (as decoded from G.711)
+const TONE_SAMPLE_RATE: u32 = 8000;
+
+/// Result of analyzing one file
+#[derive(Debug, Serialize)]
+pub struct AnalysisReport {
+    pub file: String,
+    /// Sample rate of the file (it is resampled for analysis)
+    pub sample_rate: u32,
+    pub duration_ms: u64,
+    pub tones: Vec<ToneReport>,
+    /// One entry per configured target
+    pub references: Vec<ReferenceReport>,
+}
+
+/// A tone found in the audio
+#[derive(Debug, Serialize)]
+pub struct ToneReport {
+    pub tone: String,
+    pub start_ms: u64,
+    pub duration_ms: u64,
+    /// Busy, reorder or SIT: a live check would fail on this tone
+    pub failure: bool,
+}
+
+/// The audio checked against one target's references
+#[derive(Debug, Serialize)]
+pub struct ReferenceReport {
+    pub target: String,
+    /// Reference embedding path (comma-separated when the target accepts
+    /// several named greetings)
+    pub reference: String,
+    pub threshold: f32,
+    pub transcript: Option<String>,
+    /// Similarity to the closest reference
+    pub similarity: Option<f32>,
+    pub matched: bool,
+    /// Name of the reference that matched
+    pub matched_reference: Option<String>,
+    /// Where the closest reference starts in the audio (aligned and
+    /// fingerprint matchers)
+    pub offset_ms: Option<u64>,
+    /// The secondary matcher's opinion
+    pub cross_check: Option<CrossCheck>,
+    /// The expected phrase closest to the transcript and its score
+    pub phrase: Option<PhraseMatch>,
+    /// Why the check could not run (e.g. no Whisper model)
+    pub error: Option<String>,
+}
+
+/// Analyze a WAV file against the references of `targets`
+pub fn analyze_file(
+    path: impl AsRef<Path>,
+    targets: &[CheckTarget],
+    recognizer: &mut SpeechRecognizer,
+) -> Result<AnalysisReport> {
+    let path = path.as_ref();
+    let (samples, sample_rate) = crate::rtp::load_wav(path)?;
+    anyhow::ensure!(sample_rate > 0, "WAV file {:?} has no sample rate", path);
+    let audio = resample(&samples, sample_rate, WHISPER_SAMPLE_RATE);
+
+    let pcm: Vec<i16> = resample(&samples, sample_rate, TONE_SAMPLE_RATE)
+        .iter()
+        .map(|&s| (s * 32767.0).clamp(-32768.0, 32767.0) as i16)
+        .collect();
+    let tones = detect_tones(&pcm)
+        .into_iter()
+        .map(|event| ToneReport {
+            tone: event.tone.to_string(),
+            start_ms: event.start_ms,
+            duration_ms: event.duration_ms,
+            failure: event.tone.is_failure(),
+        })
+        .collect();
+
+    let references = targets
+        .iter()
+        .map(|target| {
+            // A recording may be from any time of day, so every greeting counts
+            let references = target.greeting_references();
+            let paths: Vec<&str> = references.iter().map(|(_, path)| path.as_str()).collect();
+            let mut report = ReferenceReport {
+                target: target.name.clone(),
+                reference: paths.join(", "),
+                threshold: target.threshold,
+                transcript: None,
+                similarity: None,
+                matched: false,
+                matched_reference: None,
+                offset_ms: None,
+                cross_check: None,
+                phrase: None,
+                error: None,
+            };
+            let references: Vec<(&str, &str)> =
+                references.iter().map(|(name, path)| (name.as_str(), path.as_str())).collect();
+            match recognizer.check_references(&audio, &references, &target.thresholds()) {
+                Ok(mut result) => {
+                    result.apply_phrase_policy(&target.config.phrase_policy());
+                    report.transcript = Some(result.transcript);
+                    report.similarity = result.similarity;
+                    report.matched = result.phrase_found;
+                    report.matched_reference = result.matched_reference;
+                    report.offset_ms = result.offset_ms;
+                    report.cross_check = result.cross_check;
+                    report.phrase = result.phrase;
+                }
+                Err(e) => report.error = Some(format!("{:#}", e)),
+            }
+            report
+        })
+        .collect();
+
+    Ok(AnalysisReport {
+        file: path.display().to_string(),
+        sample_rate,
+        duration_ms: samples.len() as u64 * 1000 / sample_rate as u64,
+        tones,
+        references,
+    })
+}
+
+/// Analyze a file and return the report as pretty-printed JSON
+pub fn analyze_to_json(
+    path: impl AsRef<Path>,
+    targets: &[CheckTarget],
+    recognizer: &mut SpeechRecognizer,
+) -> Result<String> {
+    let report = analyze_file(path, targets, recognizer)?;
+    serde_json::to_string_pretty(&report).context("Failed to serialize analysis report")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    /// Busy tone (480+620 Hz, 0.5 s on / 0.5 s off) at 44.1 kHz
+    fn write_busy_tone(path: &Path) {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for i in 0..44100 * 4 {
+            let t = i as f32 / 44100.0;
+            let on = ((t * 2.0) as u32).is_multiple_of(2);
+            let level = if on {
+                ((2.0 * std::f32::consts::PI * 480.0 * t).sin() + (2.0 * std::f32::consts::PI * 620.0 * t).sin()) * 6000.0
+            } else {
+                0.0
+            };
+            writer.write_sample(level as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn test_analyze_reports_tones_and_reference_errors() {
+        let path = std::env::temp_dir().join(format!("phonecheck_analyze_{}.wav", std::process::id()));
+        write_busy_tone(&path);
+
+        let mut env = HashMap::new();
+        env.insert("SIP_USERNAME", "testuser");
+        env.insert("SIP_PASSWORD", "testpass");
+        env.insert("SIP_SERVER", "sip.example.com");
+        env.insert("TARGET_PHONE", "5551234567");
+        env.insert("PUSHOVER_USER_KEY", "user123");
+        env.insert("PUSHOVER_API_TOKEN", "token456");
+        env.insert("WHISPER_MODEL_PATH", "/nonexistent/ggml-model.bin");
+        let config = Config::from_map(&env).unwrap();
+        let targets = vec![CheckTarget::from_config(Arc::new(config)).unwrap()];
+        let mut recognizer = SpeechRecognizer::new("/nonexistent/ggml-model.bin")
+            .unwrap()
+            .without_reference_updates();
+
+        let report = analyze_file(&path, &targets, &mut recognizer).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(report.sample_rate, 44100);
+        assert_eq!(report.duration_ms, 4000);
+        assert!(report.tones.iter().any(|t| t.tone == "busy tone" && t.failure), "{:?}", report.tones);
+
+        // Without a Whisper model the reference check reports why it failed
+        assert_eq!(report.references.len(), 1);
+        assert_eq!(report.references[0].target, "default");
+        assert!(!report.references[0].matched);
+        assert!(report.references[0].matched_reference.is_none());
+        assert!(report.references[0].error.is_some());
+    }
+
+    #[test]
+    fn test_missing_file_is_an_error() {
+        let mut recognizer = SpeechRecognizer::new("/nonexistent/ggml-model.bin")
+            .unwrap()
+            .without_reference_updates();
+        let err = analyze_file("/nonexistent/audio.wav", &[], &mut recognizer).unwrap_err();
+        assert!(format!("{:#}", err).contains("Failed to open WAV file"), "{:#}", err);
+    }
+}
@@ -0,0 +1,802 @@
+//! Local SIP/RTP PBX simulator for end-to-end tests
+//!
+//! A minimal UAS on UDP that answers phonecheck's calls: it answers the
+//! REGISTER and the CGNAT probe (OPTIONS), optionally challenges REGISTER and
+//! INVITE with 401 digest auth, rings, answers and streams a WAV file over
+//! RTP as PCMU or PCMA until the caller hangs up.
+//!
+//! Faults can be injected per call: `busy` (486), `unavailable` (503),
+//! `no-answer` (ring until cancelled), `one-way` (answer but send no audio),
+//! `loss=PCT` (drop that share of RTP packets), `reorder` (swap each pair of
+//! packets), `bye=MS` (hang up MS milliseconds after answering) and
+//! `transfer=MS` (MS milliseconds after answering, switch to a new RTP
+//! stream sent from another port, as when a PBX hands the call to another
+//! media server) and `early=MS` (instead of ringing, send 183 Session Progress
+//! with SDP and stream the audio as early media for MS milliseconds before
+//! answering). `none` is a healthy call. A comma-separated list applies to successive calls,
+//! the last one repeating; a new list can be written to stdin at any time.
+//!
+//! When the caller offers rtcp-mux, the answer accepts it: a Sender Report
+//! with the simulator's CNAME goes out about once a second alongside the
+//! audio, and each SR from the caller is answered at once with a Receiver
+//! Report about it, so the caller can measure the round-trip time.
+//!
+//! The bound address is printed on stdout as `listening on ADDR`, and every
+//! fault change read from stdin is acknowledged with `faults LIST`.
+//!
+//! Run with: cargo run --bin fake_pbx -- --bind 127.0.0.1:5060 --wav greeting.wav --auth user:secret
+
+use anyhow::{Context, Result};
+use phonecheck::rtp::g711::{G711Codec, G711Encoder};
+use phonecheck::rtp::resample::resample;
+use phonecheck::rtp::rtcp::{self, ReportBlock, RtcpPacket, SenderInfo};
+use phonecheck::sip::digest::{extract_authorization_header, DigestResponse};
+use phonecheck::sip::messages::{extract_rtp_address, generate_branch, generate_tag};
+use std::collections::HashMap;
+use std::io::Write;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::AsyncBufReadExt;
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+/// RTP clock rate and packetization of G.711
+const SAMPLE_RATE: u32 = 8000;
+const SAMPLES_PER_PACKET: usize = 160;
+const PACKET_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Payload type offered for RFC 4733 telephone-event
+const TELEPHONE_EVENT_PT: u8 = 101;
+
+/// RTP packets between the simulator's Sender Reports (one second)
+const PACKETS_PER_REPORT: u64 = 50;
+
+/// CNAME in the simulator's SDES
+const CNAME: &str = "fake_pbx";
+
+/// How long to wait for the ACK of a final response
+const ACK_TIMEOUT: Duration = Duration::from_secs(32);
+
+/// What the simulator does with a call
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Fault {
+    /// Answer and stream the audio
+    None,
+    /// Reject with 486 Busy Here
+    Busy,
+    /// Reject with 503 Service Unavailable
+    Unavailable,
+    /// Keep ringing until the caller cancels
+    NoAnswer,
+    /// Answer but send no RTP
+    OneWay,
+    /// Drop this percentage of RTP packets
+    Loss(u32),
+    /// Send each pair of RTP packets in swapped order
+    Reorder,
+    /// Send BYE this many milliseconds after answering
+    Bye(u64),
+    /// Switch to a new RTP stream this many milliseconds after answering
+    Transfer(u64),
+    /// Stream early media after a 183 for this many milliseconds, then answer
+    EarlyMedia(u64),
+}
+
+impl std::str::FromStr for Fault {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Some(pct) = s.strip_prefix("loss=") {
+            let pct: u32 = pct.parse().with_context(|| format!("Invalid loss percentage '{}'", pct))?;
+            anyhow::ensure!(pct <= 100, "Loss percentage must be 0-100, got {}", pct);
+            return Ok(Fault::Loss(pct));
+        }
+        if let Some(ms) = s.strip_prefix("bye=") {
+            return Ok(Fault::Bye(ms.parse().with_context(|| format!("Invalid BYE delay '{}'", ms))?));
+        }
+        if let Some(ms) = s.strip_prefix("transfer=") {
+            return Ok(Fault::Transfer(ms.parse().with_context(|| format!("Invalid transfer delay '{}'", ms))?));
+        }
+        if let Some(ms) = s.strip_prefix("early=") {
+            return Ok(Fault::EarlyMedia(ms.parse().with_context(|| format!("Invalid early media time '{}'", ms))?));
+        }
+        match s {
+            "none" | "ok" => Ok(Fault::None),
+            "busy" | "486" => Ok(Fault::Busy),
+            "unavailable" | "503" => Ok(Fault::Unavailable),
+            "no-answer" => Ok(Fault::NoAnswer),
+            "one-way" => Ok(Fault::OneWay),
+            "reorder" => Ok(Fault::Reorder),
+            _ => anyhow::bail!(
+                "Unknown fault '{}' (expected none, busy, unavailable, no-answer, one-way, loss=PCT, reorder, bye=MS, transfer=MS or early=MS)",
+                s
+            ),
+        }
+    }
+}
+
+/// Parse a comma-separated fault list
+fn parse_faults(s: &str) -> Result<Vec<Fault>> {
+    s.split(',').map(str::parse).collect()
+}
+
+/// Faults for successive calls (the last one repeats)
+struct FaultPlan {
+    faults: Vec<Fault>,
+    calls: usize,
+}
+
+impl FaultPlan {
+    fn new(faults: Vec<Fault>) -> Self {
+        Self { faults, calls: 0 }
+    }
+
+    fn next(&mut self) -> Fault {
+        let fault = self
+            .faults
+            .get(self.calls.min(self.faults.len().saturating_sub(1)))
+            .copied()
+            .unwrap_or(Fault::None);
+        self.calls += 1;
+        fault
+    }
+}
+
+struct Args {
+    bind: SocketAddr,
+    wav: Option<String>,
+    codec: G711Codec,
+    auth: Option<(String, String)>,
+    ring: Duration,
+    faults: Vec<Fault>,
+}
+
+fn parse_args() -> Result<Option<Args>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut result = Args {
+        bind: SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 5060),
+        wav: None,
+        codec: G711Codec::ULaw,
+        auth: None,
+        ring: Duration::from_millis(1000),
+        faults: vec![Fault::None],
+    };
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().with_context(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--bind" => result.bind = value()?.parse().context("Invalid --bind address")?,
+            "--wav" => result.wav = Some(value()?.clone()),
+            "--codec" => {
+                result.codec = match value()?.to_lowercase().as_str() {
+                    "pcmu" => G711Codec::ULaw,
+                    "pcma" => G711Codec::ALaw,
+                    other => anyhow::bail!("Unknown codec '{}' (expected pcmu or pcma)", other),
+                }
+            }
+            "--auth" => {
+                let (user, pass) = value()?.split_once(':').context("--auth expects USER:PASSWORD")?;
+                result.auth = Some((user.to_string(), pass.to_string()));
+            }
+            "--ring-ms" => result.ring = Duration::from_millis(value()?.parse().context("Invalid --ring-ms")?),
+            "--fault" => result.faults = parse_faults(value()?)?,
+            "--help" | "-h" => {
+                print_help();
+                return Ok(None);
+            }
+            other => anyhow::bail!("Unknown argument '{}' (see --help)", other),
+        }
+    }
+
+    Ok(Some(result))
+}
+
+fn print_help() {
+    println!("fake_pbx - local SIP/RTP PBX simulator for end-to-end tests\n");
+    println!("USAGE:");
+    println!("    fake_pbx [OPTIONS]\n");
+    println!("OPTIONS:");
+    println!("    --bind ADDR             UDP address for SIP (default 127.0.0.1:5060, port 0 picks one)");
+    println!("    --wav PATH              Audio to stream once answered, looped (default: a 600 Hz tone)");
+    println!("    --codec pcmu|pcma       Codec of the answer (default pcmu)");
+    println!("    --auth USER:PASSWORD    Challenge REGISTER and INVITE with 401 digest auth");
+    println!("    --ring-ms MS            Ringing time before answering (default 1000)");
+    println!("    --fault LIST            Faults for successive calls, the last repeating (default none):");
+    println!("                            none, busy, unavailable, no-answer, one-way, loss=PCT, reorder, bye=MS,");
+    println!("                            transfer=MS, early=MS\n");
+    println!("A new fault list can be written to stdin, one per line.");
+}
+
+/// Load a WAV file as 8 kHz mono PCM
+fn load_wav(path: &str) -> Result<Vec<i16>> {
+    let (samples, rate) = phonecheck::rtp::load_wav(path)?;
+    anyhow::ensure!(!samples.is_empty(), "WAV file '{}' has no samples", path);
+    Ok(resample(&samples, rate, SAMPLE_RATE)
+        .iter()
+        .map(|&s| (s * 32767.0).clamp(-32768.0, 32767.0) as i16)
+        .collect())
+}
+
+/// One second of a 600 Hz tone, used when no WAV file is given
+fn default_audio() -> Vec<i16> {
+    (0..SAMPLE_RATE)
+        .map(|i| {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            ((2.0 * std::f32::consts::PI * 600.0 * t).sin() * 8000.0) as i16
+        })
+        .collect()
+}
+
+/// Value of the first header called `name` (case-insensitive)
+fn header<'a>(message: &'a str, name: &str) -> Option<&'a str> {
+    message.lines().take_while(|line| !line.is_empty()).find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
+
+/// Request method, or None for a response
+fn request_method(message: &str) -> Option<&str> {
+    let first = message.lines().next()?;
+    if first.starts_with("SIP/") {
+        return None;
+    }
+    first.split_whitespace().next()
+}
+
+/// Build a response to `request`, copying its transaction headers. The top
+/// Via gets `received`/`rport` (RFC 3581) so the caller learns its address.
+fn build_response(
+    request: &str,
+    source: SocketAddr,
+    code: u16,
+    reason: &str,
+    to_tag: Option<&str>,
+    extra_headers: &[String],
+    sdp: Option<&str>,
+) -> String {
+    let mut response = format!("SIP/2.0 {} {}\r\n", code, reason);
+    let mut top_via = true;
+    for line in request.lines().take_while(|line| !line.is_empty()) {
+        let Some((key, _)) = line.split_once(':') else { continue };
+        let key = key.trim().to_ascii_lowercase();
+        match key.as_str() {
+            "via" if top_via => {
+                top_via = false;
+                let params: Vec<&str> = line.split(';').filter(|p| !p.trim().starts_with("rport")).collect();
+                response.push_str(&format!("{};received={};rport={}\r\n", params.join(";"), source.ip(), source.port()));
+            }
+            "via" | "from" | "call-id" | "cseq" => response.push_str(&format!("{}\r\n", line.trim_end())),
+            "to" => match to_tag {
+                Some(tag) if !line.to_lowercase().contains("tag=") => {
+                    response.push_str(&format!("{};tag={}\r\n", line.trim_end(), tag))
+                }
+                _ => response.push_str(&format!("{}\r\n", line.trim_end())),
+            },
+            _ => {}
+        }
+    }
+    for extra in extra_headers {
+        response.push_str(&format!("{}\r\n", extra));
+    }
+    response.push_str("Server: fake_pbx\r\n");
+    match sdp {
+        Some(body) => response.push_str(&format!(
+            "Content-Type: application/sdp\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )),
+        None => response.push_str("Content-Length: 0\r\n\r\n"),
+    }
+    response
+}
+
+/// SDP answer with a single G.711 codec plus telephone-event, accepting
+/// rtcp-mux if it was offered
+fn build_sdp_answer(media: SocketAddr, payload_type: u8, rtcp_mux: bool) -> String {
+    let session: u32 = rand::random();
+    let codec = if payload_type == 0 { "PCMU" } else { "PCMA" };
+    let mux = if rtcp_mux { "a=rtcp-mux\r\n" } else { "" };
+    format!(
+        "v=0\r\n\
+         o=fake_pbx {} {} IN IP4 {}\r\n\
+         s=fake_pbx\r\n\
+         c=IN IP4 {}\r\n\
+         t=0 0\r\n\
+         m=audio {} RTP/AVP {} {}\r\n\
+         a=rtpmap:{} {}/8000\r\n\
+         a=rtpmap:{} telephone-event/8000\r\n\
+         a=fmtp:{} 0-16\r\n\
+         a=ptime:20\r\n\
+         {}\
+         a=sendrecv\r\n",
+        session,
+        session,
+        media.ip(),
+        media.ip(),
+        media.port(),
+        payload_type,
+        TELEPHONE_EVENT_PT,
+        payload_type,
+        codec,
+        TELEPHONE_EVENT_PT,
+        TELEPHONE_EVENT_PT,
+        mux
+    )
+}
+
+/// In-dialog requests routed to a call's task
+enum CallEvent {
+    Ack,
+    Bye { request: String, source: SocketAddr },
+    Cancel { request: String, source: SocketAddr },
+}
+
+/// State shared by the SIP loop and the call tasks
+struct Pbx {
+    socket: UdpSocket,
+    media_ip: IpAddr,
+    codec: G711Codec,
+    auth: Option<(String, String)>,
+    nonce: String,
+    ring: Duration,
+    audio: Vec<i16>,
+    faults: Mutex<FaultPlan>,
+}
+
+impl Pbx {
+    async fn send(&self, message: &str, dest: SocketAddr) {
+        debug!("Sending to {}:\n{}", dest, message);
+        if let Err(e) = self.socket.send_to(message.as_bytes(), dest).await {
+            warn!("Failed to send to {}: {}", dest, e);
+        }
+    }
+
+    async fn respond(&self, request: &str, source: SocketAddr, code: u16, reason: &str) {
+        self.send(&build_response(request, source, code, reason, None, &[], None), source).await;
+    }
+
+    /// Whether the request carries valid credentials (always true without --auth)
+    fn authorized(&self, request: &str, method: &str) -> bool {
+        let Some((ref username, ref password)) = self.auth else {
+            return true;
+        };
+        extract_authorization_header(request)
+            .and_then(|h| DigestResponse::parse(&h))
+            .map(|d| d.username == *username && d.nonce == self.nonce && d.verify(password, method))
+            .unwrap_or(false)
+    }
+
+    async fn challenge(&self, request: &str, source: SocketAddr) {
+        let challenge = format!("WWW-Authenticate: Digest realm=\"fake_pbx\", nonce=\"{}\", algorithm=MD5", self.nonce);
+        let to_tag = generate_tag();
+        self.send(&build_response(request, source, 401, "Unauthorized", Some(&to_tag), &[challenge], None), source)
+            .await;
+    }
+}
+
+/// Handle one INVITE dialog from ringing to hang-up
+async fn run_call(pbx: Arc<Pbx>, invite: String, source: SocketAddr, mut events: mpsc::UnboundedReceiver<CallEvent>) {
+    let fault = pbx.faults.lock().unwrap_or_else(|e| e.into_inner()).next();
+    let call_id = header(&invite, "Call-ID").unwrap_or("?").to_string();
+    info!("Call {} from {}: {:?}", call_id, source, fault);
+
+    let to_tag = generate_tag();
+    let reply = |code: u16, reason: &str, extra: &[String], sdp: Option<&str>| {
+        build_response(&invite, source, code, reason, Some(&to_tag), extra, sdp)
+    };
+
+    let rejection = match fault {
+        Fault::Busy => Some((486, "Busy Here")),
+        Fault::Unavailable => Some((503, "Service Unavailable")),
+        _ => None,
+    };
+    if let Some((code, reason)) = rejection {
+        pbx.send(&reply(code, reason, &[], None), source).await;
+        wait_for_ack(&mut events).await;
+        return;
+    }
+
+    let rtp = match UdpSocket::bind(SocketAddr::new(pbx.media_ip, 0)).await {
+        Ok(socket) => socket,
+        Err(e) => {
+            warn!("Failed to bind RTP socket: {}", e);
+            pbx.send(&reply(500, "Server Internal Error", &[], None), source).await;
+            return;
+        }
+    };
+    let media = rtp.local_addr().unwrap_or_else(|_| SocketAddr::new(pbx.media_ip, 0));
+    let encoder = G711Encoder::new(pbx.codec);
+    let rtcp_mux = invite.lines().any(|line| line.trim() == "a=rtcp-mux");
+    let sdp = build_sdp_answer(media, encoder.payload_type(), rtcp_mux);
+
+    // Send media where the caller's SDP offer asked for it
+    let dest = match extract_rtp_address(&invite) {
+        Some(addr) if addr.ip().is_unspecified() => SocketAddr::new(source.ip(), addr.port()),
+        Some(addr) => addr,
+        None => {
+            warn!("Call {} has no media address in its offer", call_id);
+            SocketAddr::new(source.ip(), 0)
+        }
+    };
+
+    let early_media = match fault {
+        Fault::EarlyMedia(ms) => Some(Duration::from_millis(ms)),
+        _ => None,
+    };
+    match early_media {
+        Some(_) => pbx.send(&reply(183, "Session Progress", &[], Some(&sdp)), source).await,
+        None => pbx.send(&reply(180, "Ringing", &[], None), source).await,
+    }
+
+    // Ring (forever for no-answer) or play early media unless the caller
+    // gives up first. Early media is its own stream, as from an
+    // announcement server.
+    let ring = async {
+        if fault == Fault::NoAnswer {
+            std::future::pending::<()>().await;
+        }
+        match early_media {
+            Some(duration) if dest.port() != 0 => {
+                let early = stream_audio(&rtp, dest, &pbx.audio, &encoder, Fault::None, rand::random(), false);
+                let _ = tokio::time::timeout(duration, early).await;
+            }
+            Some(duration) => tokio::time::sleep(duration).await,
+            None => tokio::time::sleep(pbx.ring).await,
+        }
+    };
+    tokio::pin!(ring);
+    loop {
+        tokio::select! {
+            _ = &mut ring => break,
+            event = events.recv() => match event {
+                Some(CallEvent::Cancel { request, source: from }) => {
+                    info!("Call {} cancelled while ringing", call_id);
+                    pbx.respond(&request, from, 200, "OK").await;
+                    pbx.send(&reply(487, "Request Terminated", &[], None), source).await;
+                    wait_for_ack(&mut events).await;
+                    return;
+                }
+                Some(CallEvent::Bye { request, source: from }) => {
+                    pbx.respond(&request, from, 481, "Call/Transaction Does Not Exist").await;
+                }
+                Some(CallEvent::Ack) => {}
+                None => return,
+            },
+        }
+    }
+
+    let contact = format!("Contact: <sip:fake_pbx@{}>", pbx.socket.local_addr().map(|a| a.to_string()).unwrap_or_default());
+    pbx.send(&reply(200, "OK", &[contact], Some(&sdp)), source).await;
+    info!("Call {} answered, media on {}", call_id, media);
+
+    if !wait_for_ack(&mut events).await {
+        warn!("Call {} was never acknowledged", call_id);
+        return;
+    }
+
+    let hang_up_after = match fault {
+        Fault::Bye(ms) => Some(Duration::from_millis(ms)),
+        _ => None,
+    };
+    let ssrc: u32 = rand::random();
+    let stream = async {
+        if fault == Fault::OneWay || dest.port() == 0 {
+            std::future::pending::<()>().await;
+        }
+        stream_audio(&rtp, dest, &pbx.audio, &encoder, fault, ssrc, rtcp_mux).await;
+    };
+    let hang_up = async {
+        match hang_up_after {
+            Some(delay) => tokio::time::sleep(delay).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::pin!(stream);
+    tokio::pin!(hang_up);
+
+    let mut buf = [0u8; 2048];
+    loop {
+        tokio::select! {
+            _ = &mut stream => {}
+            received = rtp.recv_from(&mut buf) => {
+                if let Ok((len, _)) = received {
+                    if rtcp_mux && rtcp::is_rtcp(&buf[..len]) {
+                        answer_sender_reports(&rtp, dest, ssrc, &buf[..len]).await;
+                    }
+                }
+            }
+            _ = &mut hang_up => {
+                info!("Call {}: hanging up mid-call", call_id);
+                pbx.send(&build_bye(&invite, &to_tag, pbx.socket.local_addr().ok()), source).await;
+                return;
+            }
+            event = events.recv() => match event {
+                Some(CallEvent::Bye { request, source: from }) => {
+                    info!("Call {} ended by caller", call_id);
+                    pbx.respond(&request, from, 200, "OK").await;
+                    return;
+                }
+                Some(CallEvent::Cancel { request, source: from }) => {
+                    // Too late to cancel an answered call
+                    pbx.respond(&request, from, 200, "OK").await;
+                }
+                Some(CallEvent::Ack) => {}
+                None => return,
+            },
+        }
+    }
+}
+
+/// Wait for the ACK of a final response. False if the call ended first.
+async fn wait_for_ack(events: &mut mpsc::UnboundedReceiver<CallEvent>) -> bool {
+    let wait = async {
+        while let Some(event) = events.recv().await {
+            if let CallEvent::Ack = event {
+                return true;
+            }
+        }
+        false
+    };
+    tokio::time::timeout(ACK_TIMEOUT, wait).await.unwrap_or(false)
+}
+
+/// BYE sent by the simulator to end an answered call
+fn build_bye(invite: &str, to_tag: &str, local: Option<SocketAddr>) -> String {
+    let from = header(invite, "To").unwrap_or("<sip:fake_pbx>");
+    let to = header(invite, "From").unwrap_or("<sip:phonecheck>");
+    let target = header(invite, "Contact")
+        .or(Some(to))
+        .map(|c| c.trim_start_matches(|ch| ch != '<').trim_start_matches('<'))
+        .and_then(|c| c.split('>').next())
+        .unwrap_or("sip:phonecheck");
+    let local = local.map(|a| a.to_string()).unwrap_or_default();
+    format!(
+        "BYE {} SIP/2.0\r\n\
+         Via: SIP/2.0/UDP {};branch={}\r\n\
+         Max-Forwards: 70\r\n\
+         From: {};tag={}\r\n\
+         To: {}\r\n\
+         Call-ID: {}\r\n\
+         CSeq: 1 BYE\r\n\
+         Content-Length: 0\r\n\
+         \r\n",
+        target,
+        local,
+        generate_branch(),
+        from,
+        to_tag,
+        to,
+        header(invite, "Call-ID").unwrap_or("")
+    )
+}
+
+/// Answer each SR in a compound packet from the caller with an RR about
+/// the caller, sent at once so DLSR is zero
+async fn answer_sender_reports(rtp: &UdpSocket, dest: SocketAddr, ssrc: u32, data: &[u8]) {
+    for packet in rtcp::parse_compound(data) {
+        match packet {
+            RtcpPacket::SenderReport { ssrc: caller, sender, .. } => {
+                let block = ReportBlock { ssrc: caller, last_sr: rtcp::compact_ntp(sender.ntp_timestamp), ..Default::default() };
+                let report = rtcp::to_bytes(&[
+                    RtcpPacket::ReceiverReport { ssrc, reports: vec![block] },
+                    RtcpPacket::SourceDescription(vec![(ssrc, Some(CNAME.to_string()))]),
+                ]);
+                if let Err(e) = rtp.send_to(&report, dest).await {
+                    warn!("Failed to send RTCP to {}: {}", dest, e);
+                }
+            }
+            RtcpPacket::Bye { .. } => debug!("RTCP BYE from caller"),
+            _ => {}
+        }
+    }
+}
+
+/// Stream the audio (looped) in 20 ms RTP packets, applying packet faults,
+/// with a Sender Report every second when `rtcp_mux`. Runs until the future
+/// is dropped.
+async fn stream_audio(rtp: &UdpSocket, dest: SocketAddr, audio: &[i16], encoder: &G711Encoder, fault: Fault, mut ssrc: u32, rtcp_mux: bool) {
+    let mut sequence: u16 = rand::random::<u16>() & 0x7FFF;
+    let mut timestamp: u32 = rand::random();
+    let mut offset = 0;
+    let mut held: Option<Vec<u8>> = None;
+    let mut interval = tokio::time::interval(PACKET_INTERVAL);
+    // After a transfer: the other media server's socket, and the index of
+    // the first packet it sent
+    let mut media_server: Option<UdpSocket> = None;
+    let mut stream_start = 0u64;
+
+    for index in 0u64.. {
+        interval.tick().await;
+
+        if matches!(fault, Fault::Transfer(ms) if index == ms / PACKET_INTERVAL.as_millis() as u64) {
+            let ip = rtp.local_addr().map_or(IpAddr::V4(Ipv4Addr::LOCALHOST), |a| a.ip());
+            match UdpSocket::bind(SocketAddr::new(ip, 0)).await {
+                Ok(socket) => {
+                    // A new SSRC whose sequence numbers restart behind the
+                    // old stream's
+                    ssrc = rand::random();
+                    sequence = sequence.wrapping_sub(0x4000);
+                    timestamp = rand::random();
+                    stream_start = index;
+                    info!("Transferring RTP to SSRC {:08x} from {:?}", ssrc, socket.local_addr());
+                    media_server = Some(socket);
+                }
+                Err(e) => warn!("Failed to bind a socket for the transfer: {}", e),
+            }
+        }
+        let socket = media_server.as_ref().unwrap_or(rtp);
+
+        let frame: Vec<i16> = (0..SAMPLES_PER_PACKET).map(|i| audio[(offset + i) % audio.len()]).collect();
+        offset = (offset + SAMPLES_PER_PACKET) % audio.len();
+
+        let mut packet = Vec::with_capacity(12 + SAMPLES_PER_PACKET);
+        packet.push(0x80);
+        packet.push(encoder.payload_type() | if index == stream_start { 0x80 } else { 0 });
+        packet.extend_from_slice(&sequence.to_be_bytes());
+        packet.extend_from_slice(&timestamp.to_be_bytes());
+        packet.extend_from_slice(&ssrc.to_be_bytes());
+        packet.extend_from_slice(&encoder.encode(&frame));
+        sequence = sequence.wrapping_add(1);
+        timestamp = timestamp.wrapping_add(SAMPLES_PER_PACKET as u32);
+
+        let outgoing = match fault {
+            // Evenly spread drops: packet i goes when the running share of
+            // drops would fall behind the percentage
+            Fault::Loss(pct) if (index + 1) * pct as u64 / 100 > index * pct as u64 / 100 => vec![],
+            Fault::Reorder => match held.take() {
+                Some(previous) => vec![packet, previous],
+                None => {
+                    held = Some(packet);
+                    vec![]
+                }
+            },
+            _ => vec![packet],
+        };
+        for packet in outgoing {
+            if let Err(e) = socket.send_to(&packet, dest).await {
+                warn!("Failed to send RTP to {}: {}", dest, e);
+            }
+        }
+
+        if rtcp_mux && (index + 1) % PACKETS_PER_REPORT == 0 {
+            let sent = (index + 1 - stream_start) as u32;
+            let report = rtcp::to_bytes(&[
+                RtcpPacket::SenderReport {
+                    ssrc,
+                    sender: SenderInfo {
+                        ntp_timestamp: rtcp::ntp_now(),
+                        rtp_timestamp: timestamp,
+                        packet_count: sent,
+                        octet_count: sent * SAMPLES_PER_PACKET as u32,
+                    },
+                    reports: vec![],
+                },
+                RtcpPacket::SourceDescription(vec![(ssrc, Some(CNAME.to_string()))]),
+            ]);
+            if let Err(e) = socket.send_to(&report, dest).await {
+                warn!("Failed to send RTCP to {}: {}", dest, e);
+            }
+        }
+    }
+}
+
+/// Read new fault lists from stdin, one per line
+async fn read_fault_commands(pbx: Arc<Pbx>) {
+    let mut lines = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_faults(&line) {
+            Ok(faults) => {
+                info!("Faults for the next calls: {:?}", faults);
+                *pbx.faults.lock().unwrap_or_else(|e| e.into_inner()) = FaultPlan::new(faults);
+                println!("faults {}", line.trim());
+                let _ = std::io::stdout().flush();
+            }
+            Err(e) => warn!("{:#}", e),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::from_default_env().add_directive("fake_pbx=info".parse().unwrap()),
+        )
+        .init();
+
+    let Some(args) = parse_args()? else {
+        return Ok(());
+    };
+
+    let audio = match args.wav {
+        Some(ref path) => load_wav(path)?,
+        None => default_audio(),
+    };
+    let socket = UdpSocket::bind(args.bind)
+        .await
+        .with_context(|| format!("Failed to bind SIP socket on {}", args.bind))?;
+    let local = socket.local_addr()?;
+    let media_ip = if local.ip().is_unspecified() { IpAddr::V4(Ipv4Addr::LOCALHOST) } else { local.ip() };
+
+    let pbx = Arc::new(Pbx {
+        socket,
+        media_ip,
+        codec: args.codec,
+        auth: args.auth,
+        nonce: format!("{:016x}", rand::random::<u64>()),
+        ring: args.ring,
+        audio,
+        faults: Mutex::new(FaultPlan::new(args.faults)),
+    });
+
+    info!("fake_pbx listening on {} ({:?}, {} ms of audio)", local, args.codec, pbx.audio.len() as u32 * 1000 / SAMPLE_RATE);
+    println!("listening on {}", local);
+    std::io::stdout().flush()?;
+
+    tokio::spawn(read_fault_commands(pbx.clone()));
+
+    let mut calls: HashMap<String, mpsc::UnboundedSender<CallEvent>> = HashMap::new();
+    let mut buf = vec![0u8; 65536];
+    loop {
+        let (len, source) = pbx.socket.recv_from(&mut buf).await?;
+        let message = String::from_utf8_lossy(&buf[..len]).to_string();
+        let Some(method) = request_method(&message).map(str::to_string) else {
+            debug!("Response from {}: {}", source, message.lines().next().unwrap_or(""));
+            continue;
+        };
+        let call_id = header(&message, "Call-ID").unwrap_or("").to_string();
+        debug!("{} from {}", method, source);
+
+        calls.retain(|_, events| !events.is_closed());
+        match method.as_str() {
+            "REGISTER" => {
+                if pbx.authorized(&message, "REGISTER") {
+                    let to_tag = generate_tag();
+                    let extra = vec!["Expires: 3600".to_string()];
+                    pbx.send(&build_response(&message, source, 200, "OK", Some(&to_tag), &extra, None), source).await;
+                } else {
+                    pbx.challenge(&message, source).await;
+                }
+            }
+            "OPTIONS" => pbx.respond(&message, source, 200, "OK").await,
+            "INVITE" => {
+                if calls.contains_key(&call_id) {
+                    // Retransmission: the call task already answered
+                    continue;
+                }
+                if !pbx.authorized(&message, "INVITE") {
+                    pbx.challenge(&message, source).await;
+                    continue;
+                }
+                pbx.respond(&message, source, 100, "Trying").await;
+                let (tx, rx) = mpsc::unbounded_channel();
+                calls.insert(call_id, tx);
+                tokio::spawn(run_call(pbx.clone(), message, source, rx));
+            }
+            "ACK" | "BYE" | "CANCEL" => {
+                let event = match method.as_str() {
+                    "ACK" => CallEvent::Ack,
+                    "BYE" => CallEvent::Bye { request: message.clone(), source },
+                    _ => CallEvent::Cancel { request: message.clone(), source },
+                };
+                let delivered = calls.get(&call_id).map(|events| events.send(event).is_ok()).unwrap_or(false);
+                if !delivered && method != "ACK" {
+                    pbx.respond(&message, source, 481, "Call/Transaction Does Not Exist").await;
+                }
+            }
+            _ => {
+                let allow = vec!["Allow: INVITE, ACK, CANCEL, BYE, OPTIONS, REGISTER".to_string()];
+                pbx.send(&build_response(&message, source, 405, "Method Not Allowed", None, &allow, None), source).await;
+            }
+        }
+    }
+}
@@ -1,20 +1,67 @@
 //! Command-line argument parsing for PhoneCheck
 
+/// Subcommands that run instead of the monitor
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Analyze a recorded WAV file offline and print the result as JSON
+    Analyze { path: String },
+    /// Manage the versioned references of a target's greeting, named
+    /// greeting or IVR step
+    Reference {
+        action: ReferenceAction,
+        /// Target name (required when the checks file lists several)
+        target: Option<String>,
+        /// IVR capture step instead of the greeting
+        step: Option<usize>,
+        /// Named greeting (e.g. `night`) instead of the greeting
+        name: Option<String>,
+    },
+}
+
+/// `phonecheck reference` actions
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReferenceAction {
+    /// Enroll a new version from a WAV file
+    EnrollWav(String),
+    /// Enroll a new version from a live call
+    EnrollCall,
+    List,
+    /// Show one version (default: the active one)
+    Show(Option<u32>),
+    /// Compare two versions or WAV files
+    Compare(String, String),
+    /// Activate a version (default: the one before the active one)
+    Rollback(Option<u32>),
+    /// Make the candidate captured by a check a new active version
+    Approve,
+    /// Discard the candidate captured by a check
+    Reject,
+    /// Add a header to reference files written before the file format had one
+    Migrate,
+}
+
 /// Parse command line arguments
 pub struct Args {
     pub once: bool,
     pub validate: bool,
     pub help: bool,
     pub save_audio: Option<String>,
+    pub command: Option<Command>,
 }
 
 pub fn parse_args() -> Args {
     let args: Vec<String> = std::env::args().collect();
+    parse_args_from(&args)
+}
+
+/// Parse arguments (including the program name in `args[0]`)
+pub fn parse_args_from(args: &[String]) -> Args {
     let mut result = Args {
         once: false,
         validate: false,
         help: false,
         save_audio: None,
+        command: None,
     };
 
     let mut i = 1;
@@ -31,6 +78,25 @@ pub fn parse_args() -> Args {
                     result.save_audio = Some("captured_audio.wav".to_string());
                 }
             }
+            "analyze" if result.command.is_none() => {
+                if i + 1 < args.len() {
+                    i += 1;
+                    result.command = Some(Command::Analyze {
+                        path: args[i].clone(),
+                    });
+                } else {
+                    // Nothing to analyze: show usage instead
+                    result.help = true;
+                }
+            }
+            "reference" if result.command.is_none() => {
+                // The rest of the arguments belong to the subcommand
+                match parse_reference(&args[i + 1..]) {
+                    Some(command) => result.command = Some(command),
+                    None => result.help = true,
+                }
+                break;
+            }
             _ => {}
         }
         i += 1;
@@ -39,10 +105,86 @@ pub fn parse_args() -> Args {
     result
 }
 
+/// Parse `reference <action> [ARGS] [--target NAME] [--step N | --name NAME]`
+fn parse_reference(args: &[String]) -> Option<Command> {
+    let mut target = None;
+    let mut step = None;
+    let mut name = None;
+    let mut enroll_wav = None;
+    let mut enroll_call = false;
+    let mut positional = Vec::new();
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--target" => {
+                i += 1;
+                target = Some(args.get(i)?.clone());
+            }
+            "--step" => {
+                i += 1;
+                step = Some(args.get(i)?.parse().ok().filter(|&n| n > 0)?);
+            }
+            "--name" => {
+                i += 1;
+                name = Some(args.get(i)?.clone());
+            }
+            "--from-wav" => {
+                i += 1;
+                enroll_wav = Some(args.get(i)?.clone());
+            }
+            "--from-call" => enroll_call = true,
+            arg => positional.push(arg),
+        }
+        i += 1;
+    }
+    if step.is_some() && name.is_some() {
+        return None;
+    }
+
+    let version = |arg: Option<&&str>| -> Option<Option<u32>> {
+        match arg {
+            Some(v) => v.parse().ok().map(Some),
+            None => Some(None),
+        }
+    };
+    let action = match positional.as_slice() {
+        ["enroll"] => match (enroll_wav, enroll_call) {
+            (Some(path), false) => ReferenceAction::EnrollWav(path),
+            (None, true) => ReferenceAction::EnrollCall,
+            _ => return None,
+        },
+        ["list"] => ReferenceAction::List,
+        ["show", rest @ ..] if rest.len() <= 1 => ReferenceAction::Show(version(rest.first())?),
+        ["compare", a, b] => ReferenceAction::Compare(a.to_string(), b.to_string()),
+        ["rollback", rest @ ..] if rest.len() <= 1 => ReferenceAction::Rollback(version(rest.first())?),
+        ["approve"] => ReferenceAction::Approve,
+        ["reject"] => ReferenceAction::Reject,
+        ["migrate"] => ReferenceAction::Migrate,
+        _ => return None,
+    };
+    Some(Command::Reference { action, target, step, name })
+}
+
 pub fn print_help() {
     println!("PhoneCheck - PBX Health Monitor\n");
     println!("USAGE:");
-    println!("    phonecheck [OPTIONS]\n");
+    println!("    phonecheck [OPTIONS]");
+    println!("    phonecheck analyze <FILE.wav>");
+    println!("    phonecheck reference <ACTION> [--target NAME] [--step N | --name NAME]\n");
+    println!("COMMANDS:");
+    println!("    analyze <FILE.wav>      Check a recording against the configured references and");
+    println!("                            print transcript, similarity and tones as JSON (no call is made)");
+    println!("    reference enroll --from-wav <FILE.wav> | --from-call");
+    println!("                            Save a new reference version and make it active");
+    println!("    reference list          List reference versions (* marks the active one)");
+    println!("    reference show [N]      Show a version's transcript, source and models");
+    println!("    reference compare <A> <B>");
+    println!("                            Similarity of two versions (numbers) or WAV files");
+    println!("    reference rollback [N]  Activate version N (default: the previous version)");
+    println!("    reference approve       Use the greeting a check captured when there was no reference");
+    println!("    reference reject        Discard that captured greeting");
+    println!("    reference migrate       Add a model header to legacy reference files\n");
     println!("OPTIONS:");
     println!("    --once                  Run a single check and exit");
     println!("    --validate              Validate configuration and exit");
@@ -64,6 +206,7 @@ mod tests {
         assert!(!result.validate);
         assert!(!result.help);
         assert!(result.save_audio.is_none());
+        assert!(result.command.is_none());
     }
 
     #[test]
@@ -123,44 +266,108 @@ mod tests {
         assert_eq!(result.save_audio, Some("test.wav".to_string()));
     }
 
-    /// Helper to test parse_args by setting the env args
-    fn parse_args_internal(args: &[String]) -> Args {
-        // Temporarily replace std::env::args with our test args
-        // Note: This is a simple test - in a more sophisticated setup,
-        // we'd use a proper CLI library with clap that supports testing
-        let old_args: Vec<String> = std::env::args().collect();
-
-        // Use a closure with custom args
-        let result = {
-            let mut result = Args {
-                once: false,
-                validate: false,
-                help: false,
-                save_audio: None,
-            };
-
-            let mut i = 1;
-            while i < args.len() {
-                match args[i].as_str() {
-                    "--once" => result.once = true,
-                    "--validate" => result.validate = true,
-                    "--help" | "-h" => result.help = true,
-                    "--save-audio" => {
-                        if i + 1 < args.len() {
-                            i += 1;
-                            result.save_audio = Some(args[i].clone());
-                        } else {
-                            result.save_audio = Some("captured_audio.wav".to_string());
-                        }
-                    }
-                    _ => {}
-                }
-                i += 1;
-            }
+    #[test]
+    fn test_parse_args_analyze() {
+        let args = vec![
+            "phonecheck".to_string(),
+            "analyze".to_string(),
+            "recording.wav".to_string(),
+        ];
+        let result = parse_args_internal(&args);
+        assert_eq!(
+            result.command,
+            Some(Command::Analyze {
+                path: "recording.wav".to_string()
+            })
+        );
+        assert!(!result.help);
 
-            result
-        };
+        // Missing file shows help
+        let args = vec!["phonecheck".to_string(), "analyze".to_string()];
+        let result = parse_args_internal(&args);
+        assert!(result.command.is_none());
+        assert!(result.help);
+    }
 
-        result
+    fn reference_args(rest: &[&str]) -> Args {
+        let mut args = vec!["phonecheck".to_string(), "reference".to_string()];
+        args.extend(rest.iter().map(|s| s.to_string()));
+        parse_args_internal(&args)
+    }
+
+    #[test]
+    fn test_parse_reference_actions() {
+        let cases: Vec<(&[&str], ReferenceAction)> = vec![
+            (&["enroll", "--from-wav", "greeting.wav"], ReferenceAction::EnrollWav("greeting.wav".to_string())),
+            (&["enroll", "--from-call"], ReferenceAction::EnrollCall),
+            (&["list"], ReferenceAction::List),
+            (&["show"], ReferenceAction::Show(None)),
+            (&["show", "3"], ReferenceAction::Show(Some(3))),
+            (&["compare", "1", "new.wav"], ReferenceAction::Compare("1".to_string(), "new.wav".to_string())),
+            (&["rollback"], ReferenceAction::Rollback(None)),
+            (&["rollback", "2"], ReferenceAction::Rollback(Some(2))),
+            (&["approve"], ReferenceAction::Approve),
+            (&["reject"], ReferenceAction::Reject),
+            (&["migrate"], ReferenceAction::Migrate),
+        ];
+        for (rest, action) in cases {
+            let result = reference_args(rest);
+            assert_eq!(
+                result.command,
+                Some(Command::Reference { action, target: None, step: None, name: None }),
+                "{:?}",
+                rest
+            );
+            assert!(!result.help);
+        }
+    }
+
+    #[test]
+    fn test_parse_reference_target_and_step() {
+        let result = reference_args(&["list", "--target", "support", "--step", "2"]);
+        assert_eq!(
+            result.command,
+            Some(Command::Reference {
+                action: ReferenceAction::List,
+                target: Some("support".to_string()),
+                step: Some(2),
+                name: None,
+            })
+        );
+
+        let result = reference_args(&["approve", "--target", "main", "--name", "night"]);
+        assert_eq!(
+            result.command,
+            Some(Command::Reference {
+                action: ReferenceAction::Approve,
+                target: Some("main".to_string()),
+                step: None,
+                name: Some("night".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_reference_invalid_shows_help() {
+        for rest in [
+            &[][..],
+            &["enroll"],
+            &["enroll", "--from-wav", "a.wav", "--from-call"],
+            &["show", "latest"],
+            &["compare", "1"],
+            &["list", "--step", "0"],
+            &["list", "--target"],
+            &["list", "--name"],
+            &["list", "--step", "2", "--name", "night"],
+            &["prune"],
+        ] {
+            let result = reference_args(rest);
+            assert!(result.command.is_none(), "{:?}", rest);
+            assert!(result.help, "{:?}", rest);
+        }
+    }
+
+    fn parse_args_internal(args: &[String]) -> Args {
+        parse_args_from(args)
     }
 }
@@ -8,6 +8,21 @@ use std::env;
 use std::net::ToSocketAddrs;
 use std::path::Path;
 
+use crate::alerts::DEFAULT_ALERT_STATE_FILE;
+use crate::history::DEFAULT_HISTORY_FILE;
+use crate::ivr::IvrScript;
+use crate::notify::email::{SmtpConfig, SmtpSecurity};
+use crate::notify::ntfy::{NtfyConfig, NTFY_URL};
+use crate::notify::pushover::PUSHOVER_API_URL;
+use crate::notify::sms::{SmsConfig, VOIPMS_API_URL};
+use crate::notify::Route;
+use crate::schedule::{Schedule, DEFAULT_CRON, DEFAULT_TIMEZONE};
+use crate::matcher::Matcher;
+use crate::phrase::{MatchPolicy, PhrasePolicy};
+use crate::rtp::jitter::JitterMode;
+use crate::rtp::plc::Concealment;
+use crate::speech::Thresholds;
+
 /// Typed configuration keys
 ///
 /// Using an enum for config keys provides compile-time safety
@@ -25,11 +40,71 @@ pub enum ConfigKey {
 
     // Detection settings
     ExpectedPhrase,
+    // How the transcript's match with the expected phrase combines with the
+    // audio matcher (audio, transcript, either, both or weighted)
+    MatchPolicy,
+    PhraseThreshold,
+    TranscriptWeight,
+    // Similarity the greeting must reach, and an optional lower similarity
+    // from which a miss is only a warning
+    SimilarityThreshold,
+    WarningThreshold,
     ListenDurationSecs,
+    RingTimeoutSecs,
 
     // Pushover notifications
     PushoverUserKey,
     PushoverApiToken,
+    PushoverApiUrl,
+
+    // Additional notification sinks (optional)
+    WebhookUrl,
+    SlackWebhookUrl,
+    NtfyUrl,
+    NtfyTopic,
+    NtfyToken,
+    SmtpHost,
+    SmtpPort,
+    SmtpSecurity,
+    SmtpUsername,
+    SmtpPassword,
+    SmtpFrom,
+    SmtpTo,
+    VoipmsApiUrl,
+    VoipmsApiUsername,
+    VoipmsApiPassword,
+    SmsDid,
+    SmsTo,
+
+    // Alert routing rules (optional, e.g. "pushover; sms severity=critical")
+    NotifyRoutes,
+
+    // Confirmation policy: re-dial after a failure and alert only if
+    // CONFIRM_FAILURES of CONFIRM_ATTEMPTS calls fail
+    ConfirmAttempts,
+    ConfirmFailures,
+    ConfirmDelaySecs,
+
+    // Alert state file and reminder interval while an incident is open
+    AlertStateFile,
+    AlertReminderMins,
+
+    // Check history file, rotation size and rotated files kept
+    HistoryFile,
+    HistoryMaxMb,
+    HistoryFiles,
+
+    // Largest cosine distance from the approved reference that checks may
+    // move the active reference, and the similarity a check needs to do so
+    ReferenceMaxDrift,
+    ReferenceUpdateThreshold,
+
+    // How audio is compared with references (embedding, aligned or
+    // fingerprint), and an optional second matcher to cross-check it
+    Matcher,
+    SecondaryMatcher,
+    // Fraction of a reference's landmarks the fingerprint matcher needs
+    FingerprintThreshold,
 
     // Whisper model path (GGML format, e.g., ggml-base.en.bin)
     WhisperModelPath,
@@ -40,8 +115,31 @@ pub enum ConfigKey {
     // Minimum audio duration in milliseconds
     MinAudioDurationMs,
 
+    // Estimated MOS below which a call is reported as degraded (optional)
+    MinMos,
+
+    // How lost RTP packets are filled in (zero or waveform)
+    PacketLossConcealment,
+
+    // Jitter buffer mode (fixed or adaptive)
+    JitterBuffer,
+
     // Health check HTTP server port (optional, disabled if not set)
     HealthPort,
+
+    // Bearer token for POST /references/approve (optional, route disabled if not set)
+    ApproveToken,
+
+    // IVR navigation script (optional, e.g. "wait 3s, send 2, capture 8s")
+    IvrScript,
+
+    // Checks file listing several targets (optional, TOML)
+    ChecksFile,
+
+    // Check schedule (cron expressions, IANA time zone, excluded dates file)
+    Schedule,
+    ScheduleTimezone,
+    ExcludedDatesFile,
 }
 
 impl ConfigKey {
@@ -54,13 +152,60 @@ impl ConfigKey {
             ConfigKey::SipPort => "SIP_PORT",
             ConfigKey::TargetPhone => "TARGET_PHONE",
             ConfigKey::ExpectedPhrase => "EXPECTED_PHRASE",
+            ConfigKey::MatchPolicy => "MATCH_POLICY",
+            ConfigKey::PhraseThreshold => "PHRASE_THRESHOLD",
+            ConfigKey::TranscriptWeight => "TRANSCRIPT_WEIGHT",
+            ConfigKey::SimilarityThreshold => "SIMILARITY_THRESHOLD",
+            ConfigKey::WarningThreshold => "WARNING_THRESHOLD",
             ConfigKey::ListenDurationSecs => "LISTEN_DURATION_SECS",
+            ConfigKey::RingTimeoutSecs => "RING_TIMEOUT_SECS",
             ConfigKey::PushoverUserKey => "PUSHOVER_USER_KEY",
             ConfigKey::PushoverApiToken => "PUSHOVER_API_TOKEN",
+            ConfigKey::PushoverApiUrl => "PUSHOVER_API_URL",
+            ConfigKey::WebhookUrl => "WEBHOOK_URL",
+            ConfigKey::SlackWebhookUrl => "SLACK_WEBHOOK_URL",
+            ConfigKey::NtfyUrl => "NTFY_URL",
+            ConfigKey::NtfyTopic => "NTFY_TOPIC",
+            ConfigKey::NtfyToken => "NTFY_TOKEN",
+            ConfigKey::SmtpHost => "SMTP_HOST",
+            ConfigKey::SmtpPort => "SMTP_PORT",
+            ConfigKey::SmtpSecurity => "SMTP_SECURITY",
+            ConfigKey::SmtpUsername => "SMTP_USERNAME",
+            ConfigKey::SmtpPassword => "SMTP_PASSWORD",
+            ConfigKey::SmtpFrom => "SMTP_FROM",
+            ConfigKey::SmtpTo => "SMTP_TO",
+            ConfigKey::VoipmsApiUrl => "VOIPMS_API_URL",
+            ConfigKey::VoipmsApiUsername => "VOIPMS_API_USERNAME",
+            ConfigKey::VoipmsApiPassword => "VOIPMS_API_PASSWORD",
+            ConfigKey::SmsDid => "SMS_DID",
+            ConfigKey::SmsTo => "SMS_TO",
+            ConfigKey::NotifyRoutes => "NOTIFY_ROUTES",
+            ConfigKey::ConfirmAttempts => "CONFIRM_ATTEMPTS",
+            ConfigKey::ConfirmFailures => "CONFIRM_FAILURES",
+            ConfigKey::ConfirmDelaySecs => "CONFIRM_DELAY_SECS",
+            ConfigKey::AlertStateFile => "ALERT_STATE_FILE",
+            ConfigKey::AlertReminderMins => "ALERT_REMINDER_MINS",
+            ConfigKey::HistoryFile => "HISTORY_FILE",
+            ConfigKey::HistoryMaxMb => "HISTORY_MAX_MB",
+            ConfigKey::HistoryFiles => "HISTORY_FILES",
+            ConfigKey::ReferenceMaxDrift => "REFERENCE_MAX_DRIFT",
+            ConfigKey::ReferenceUpdateThreshold => "REFERENCE_UPDATE_THRESHOLD",
+            ConfigKey::Matcher => "MATCHER",
+            ConfigKey::SecondaryMatcher => "SECONDARY_MATCHER",
+            ConfigKey::FingerprintThreshold => "FINGERPRINT_THRESHOLD",
             ConfigKey::WhisperModelPath => "WHISPER_MODEL_PATH",
             ConfigKey::StunServer => "STUN_SERVER",
             ConfigKey::MinAudioDurationMs => "MIN_AUDIO_DURATION_MS",
+            ConfigKey::MinMos => "MIN_MOS",
+            ConfigKey::PacketLossConcealment => "PACKET_LOSS_CONCEALMENT",
+            ConfigKey::JitterBuffer => "JITTER_BUFFER",
             ConfigKey::HealthPort => "HEALTH_PORT",
+            ConfigKey::ApproveToken => "APPROVE_TOKEN",
+            ConfigKey::IvrScript => "IVR_SCRIPT",
+            ConfigKey::ChecksFile => "CHECKS_FILE",
+            ConfigKey::Schedule => "SCHEDULE",
+            ConfigKey::ScheduleTimezone => "SCHEDULE_TIMEZONE",
+            ConfigKey::ExcludedDatesFile => "EXCLUDED_DATES_FILE",
         }
     }
 
@@ -82,9 +227,34 @@ impl ConfigKey {
         match self {
             ConfigKey::SipPort => Some("5060"),
             ConfigKey::ExpectedPhrase => Some("thank you for calling cubic machinery"),
+            ConfigKey::MatchPolicy => Some("audio"),
+            ConfigKey::PhraseThreshold => Some("0.8"),
+            ConfigKey::TranscriptWeight => Some("0.5"),
+            ConfigKey::SimilarityThreshold => Some("0.75"),
             ConfigKey::ListenDurationSecs => Some("10"),
+            ConfigKey::RingTimeoutSecs => Some("30"),
             ConfigKey::WhisperModelPath => Some("./models/ggml-base.en.bin"),
             ConfigKey::MinAudioDurationMs => Some("500"),
+            ConfigKey::PacketLossConcealment => Some("waveform"),
+            ConfigKey::JitterBuffer => Some("fixed"),
+            ConfigKey::Schedule => Some(DEFAULT_CRON),
+            ConfigKey::ScheduleTimezone => Some(DEFAULT_TIMEZONE),
+            ConfigKey::PushoverApiUrl => Some(PUSHOVER_API_URL),
+            ConfigKey::NtfyUrl => Some(NTFY_URL),
+            ConfigKey::SmtpSecurity => Some("starttls"),
+            ConfigKey::VoipmsApiUrl => Some(VOIPMS_API_URL),
+            ConfigKey::ConfirmAttempts => Some("2"),
+            ConfigKey::ConfirmFailures => Some("2"),
+            ConfigKey::ConfirmDelaySecs => Some("30"),
+            ConfigKey::AlertStateFile => Some(DEFAULT_ALERT_STATE_FILE),
+            ConfigKey::AlertReminderMins => Some("240"),
+            ConfigKey::HistoryFile => Some(DEFAULT_HISTORY_FILE),
+            ConfigKey::HistoryMaxMb => Some("10"),
+            ConfigKey::HistoryFiles => Some("5"),
+            ConfigKey::ReferenceMaxDrift => Some("0.1"),
+            ConfigKey::ReferenceUpdateThreshold => Some("0.95"),
+            ConfigKey::Matcher => Some("embedding"),
+            ConfigKey::FingerprintThreshold => Some("0.25"),
             _ => None,
         }
     }
@@ -105,12 +275,66 @@ pub struct Config {
     pub target_phone: String,
 
     // Detection settings
+    // One or more phrases separated by `|`
     pub expected_phrase: String,
+    pub match_policy: MatchPolicy,
+    // Score the transcript must reach against the closest expected phrase
+    pub phrase_threshold: f32,
+    // Share of the transcript in the `weighted` policy
+    pub transcript_weight: f32,
+    // Similarity the greeting must reach (the default for every target)
+    pub similarity_threshold: f32,
+    // Misses scoring at least this are reported as warnings, not alerts
+    pub warning_threshold: Option<f32>,
     pub listen_duration_secs: u64,
+    // How long to let the target ring before sending CANCEL
+    pub ring_timeout_secs: u64,
 
     // Pushover notifications
     pub pushover_user_key: String,
     pub pushover_api_token: String,
+    // Pushover endpoint (overridable for testing against a local server)
+    pub pushover_api_url: String,
+
+    // Additional notification sinks (each enabled when its settings are present)
+    pub webhook_url: Option<String>,
+    pub slack_webhook_url: Option<String>,
+    pub ntfy: Option<NtfyConfig>,
+    pub smtp: Option<SmtpConfig>,
+    pub sms: Option<SmsConfig>,
+
+    // Which sinks receive which alerts (empty: every sink gets every alert)
+    pub notify_routes: Vec<Route>,
+
+    // Calls per check, failed calls that confirm a failure, and the pause
+    // before re-dialing
+    pub confirm_attempts: u32,
+    pub confirm_failures: u32,
+    pub confirm_delay_secs: u64,
+
+    // Where open incidents are saved so restarts neither re-alert nor forget them
+    pub alert_state_file: String,
+    // Minutes between reminders while an incident is open (0 disables)
+    pub alert_reminder_mins: u64,
+
+    // Every check is appended to this JSONL file, rotated at history_max_mb
+    // keeping history_files old files
+    pub history_file: String,
+    pub history_max_mb: u64,
+    pub history_files: u32,
+
+    // Checks may replace the active reference with a close match only while
+    // it stays within this cosine distance of the approved (anchor) version
+    pub reference_max_drift: f32,
+    // ...and only when it is at least this similar to the active reference
+    pub reference_update_threshold: f32,
+
+    // Mean-pooled embedding similarity, frame-level alignment that finds
+    // the greeting anywhere in the audio, or model-free fingerprints
+    pub matcher: Matcher,
+    // Scored alongside the matcher; disagreements are logged, not alerted
+    pub secondary_matcher: Option<Matcher>,
+    pub fingerprint_threshold: f32,
 
     // Whisper model path (GGML format, e.g., ggml-base.en.bin)
     pub whisper_model_path: String,
@@ -122,9 +346,41 @@ pub struct Config {
     // Default: 500ms (catches brief noise vs actual greeting)
     pub min_audio_duration_ms: u64,
 
+    // Estimated MOS (E-model) a call must reach; lower is a "degraded"
+    // warning even when the greeting matches (optional, disabled if not set)
+    pub min_mos: Option<f32>,
+
+    // Fill for lost packets: silence or G.711 Appendix I waveform
+    // substitution (default), keeping the captured audio's timing
+    pub packet_loss_concealment: Concealment,
+
+    // Fixed jitter buffer (default), or the adaptive one sized from the
+    // measured jitter
+    pub jitter_buffer: JitterMode,
+
     // Health check HTTP server port (optional, disabled if not set)
     // When set, exposes /health, /ready, and /metrics endpoints
     pub health_port: Option<u16>,
+
+    // Token that must be sent as `Authorization: Bearer <token>` to approve
+    // references over HTTP (optional, approval is CLI-only if not set)
+    pub approve_token: Option<String>,
+
+    // IVR navigation script run after answer (optional)
+    // When set, each capture step is matched against its own reference
+    pub ivr_script: Option<IvrScript>,
+
+    // Checks file listing several targets (optional)
+    // When set, TARGET_PHONE is not required and the settings above are
+    // defaults for each target
+    pub checks_file: Option<String>,
+
+    // When checks run: cron expressions in an IANA time zone
+    // Default: hourly 8am-5pm America/Los_Angeles, every day
+    pub schedule: Schedule,
+
+    // iCalendar or date-list file of dates to skip (optional)
+    pub excluded_dates_file: Option<String>,
 }
 
 impl Config {
@@ -138,6 +394,8 @@ impl Config {
     where
         F: Fn(ConfigKey) -> Option<String>,
     {
+        let checks_file = get(ConfigKey::ChecksFile).filter(|s| !s.trim().is_empty());
+
         Ok(Config {
             sip_username: get(ConfigKey::SipUsername).context(ConfigKey::SipUsername.env_var())?,
             sip_password: get(ConfigKey::SipPassword).context(ConfigKey::SipPassword.env_var())?,
@@ -147,21 +405,94 @@ impl Config {
                 .parse()
                 .context(format!("{} must be a valid port number", ConfigKey::SipPort.env_var()))?,
 
-            target_phone: get(ConfigKey::TargetPhone)
-                .context(ConfigKey::TargetPhone.env_var())?,
+            target_phone: match get(ConfigKey::TargetPhone) {
+                Some(phone) => phone,
+                // Each target in the checks file has its own number
+                None if checks_file.is_some() => String::new(),
+                None => bail!(ConfigKey::TargetPhone.env_var()),
+            },
 
             expected_phrase: get(ConfigKey::ExpectedPhrase)
                 .unwrap_or_else(|| ConfigKey::ExpectedPhrase.default_value().unwrap().to_string())
                 .to_lowercase(),
+            match_policy: get_or_default(&get, ConfigKey::MatchPolicy).parse()?,
+            phrase_threshold: get_or_default(&get, ConfigKey::PhraseThreshold)
+                .parse()
+                .context(format!("{} must be a number", ConfigKey::PhraseThreshold.env_var()))?,
+            transcript_weight: get_or_default(&get, ConfigKey::TranscriptWeight)
+                .parse()
+                .context(format!("{} must be a number", ConfigKey::TranscriptWeight.env_var()))?,
+            similarity_threshold: get_or_default(&get, ConfigKey::SimilarityThreshold)
+                .parse()
+                .context(format!("{} must be a number", ConfigKey::SimilarityThreshold.env_var()))?,
+            warning_threshold: get_optional(&get, ConfigKey::WarningThreshold)
+                .map(|threshold| threshold.parse())
+                .transpose()
+                .context(format!("{} must be a number", ConfigKey::WarningThreshold.env_var()))?,
             listen_duration_secs: get(ConfigKey::ListenDurationSecs)
                 .unwrap_or_else(|| ConfigKey::ListenDurationSecs.default_value().unwrap().to_string())
                 .parse()
                 .unwrap_or(10),
+            ring_timeout_secs: get(ConfigKey::RingTimeoutSecs)
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(30),
 
             pushover_user_key: get(ConfigKey::PushoverUserKey)
                 .context(ConfigKey::PushoverUserKey.env_var())?,
             pushover_api_token: get(ConfigKey::PushoverApiToken)
                 .context(ConfigKey::PushoverApiToken.env_var())?,
+            pushover_api_url: get_or_default(&get, ConfigKey::PushoverApiUrl),
+
+            webhook_url: get_optional(&get, ConfigKey::WebhookUrl),
+            slack_webhook_url: get_optional(&get, ConfigKey::SlackWebhookUrl),
+            ntfy: ntfy_config(&get),
+            smtp: smtp_config(&get)?,
+            sms: sms_config(&get)?,
+
+            notify_routes: match get_optional(&get, ConfigKey::NotifyRoutes) {
+                Some(routes) => Route::parse_list(&routes)
+                    .context(format!("{} is not valid", ConfigKey::NotifyRoutes.env_var()))?,
+                None => Vec::new(),
+            },
+
+            confirm_attempts: get_or_default(&get, ConfigKey::ConfirmAttempts)
+                .parse()
+                .context(format!("{} must be a number", ConfigKey::ConfirmAttempts.env_var()))?,
+            confirm_failures: get_or_default(&get, ConfigKey::ConfirmFailures)
+                .parse()
+                .context(format!("{} must be a number", ConfigKey::ConfirmFailures.env_var()))?,
+            confirm_delay_secs: get_or_default(&get, ConfigKey::ConfirmDelaySecs)
+                .parse()
+                .context(format!("{} must be a number of seconds", ConfigKey::ConfirmDelaySecs.env_var()))?,
+
+            alert_state_file: get_or_default(&get, ConfigKey::AlertStateFile),
+            alert_reminder_mins: get_or_default(&get, ConfigKey::AlertReminderMins)
+                .parse()
+                .context(format!("{} must be a number of minutes", ConfigKey::AlertReminderMins.env_var()))?,
+
+            history_file: get_or_default(&get, ConfigKey::HistoryFile),
+            history_max_mb: get_or_default(&get, ConfigKey::HistoryMaxMb)
+                .parse()
+                .context(format!("{} must be a number of megabytes", ConfigKey::HistoryMaxMb.env_var()))?,
+            history_files: get_or_default(&get, ConfigKey::HistoryFiles)
+                .parse()
+                .context(format!("{} must be a number", ConfigKey::HistoryFiles.env_var()))?,
+
+            reference_max_drift: get_or_default(&get, ConfigKey::ReferenceMaxDrift)
+                .parse()
+                .context(format!("{} must be a number", ConfigKey::ReferenceMaxDrift.env_var()))?,
+            reference_update_threshold: get_or_default(&get, ConfigKey::ReferenceUpdateThreshold)
+                .parse()
+                .context(format!("{} must be a number", ConfigKey::ReferenceUpdateThreshold.env_var()))?,
+
+            matcher: get_or_default(&get, ConfigKey::Matcher).parse()?,
+            secondary_matcher: get_optional(&get, ConfigKey::SecondaryMatcher)
+                .map(|matcher| matcher.parse())
+                .transpose()
+                .context(format!("{} is not valid", ConfigKey::SecondaryMatcher.env_var()))?,
+            fingerprint_threshold: get_or_default(&get, ConfigKey::FingerprintThreshold)
+                .parse()
+                .context(format!("{} must be a number", ConfigKey::FingerprintThreshold.env_var()))?,
 
             whisper_model_path: get(ConfigKey::WhisperModelPath)
                 .unwrap_or_else(|| {
@@ -177,7 +508,36 @@ impl Config {
                 .and_then(|s| s.parse().ok())
                 .unwrap_or(500),
 
+            min_mos: get_optional(&get, ConfigKey::MinMos)
+                .map(|mos| mos.parse())
+                .transpose()
+                .context(format!("{} must be a number", ConfigKey::MinMos.env_var()))?,
+
+            packet_loss_concealment: get_or_default(&get, ConfigKey::PacketLossConcealment).parse()?,
+            jitter_buffer: get_or_default(&get, ConfigKey::JitterBuffer).parse()?,
+
             health_port: get(ConfigKey::HealthPort).and_then(|s| s.parse().ok()),
+            approve_token: get_optional(&get, ConfigKey::ApproveToken),
+
+            ivr_script: get(ConfigKey::IvrScript)
+                .filter(|s| !s.trim().is_empty())
+                .map(|s| IvrScript::parse(&s))
+                .transpose()
+                .context(format!("{} is not a valid IVR script", ConfigKey::IvrScript.env_var()))?,
+
+            checks_file,
+
+            schedule: Schedule::new(
+                &get(ConfigKey::Schedule).unwrap_or_else(|| DEFAULT_CRON.to_string()),
+                &get(ConfigKey::ScheduleTimezone).unwrap_or_else(|| DEFAULT_TIMEZONE.to_string()),
+            )
+            .context(format!(
+                "{}/{} is not a valid schedule",
+                ConfigKey::Schedule.env_var(),
+                ConfigKey::ScheduleTimezone.env_var()
+            ))?,
+
+            excluded_dates_file: get(ConfigKey::ExcludedDatesFile).filter(|s| !s.trim().is_empty()),
         })
     }
 
@@ -187,6 +547,26 @@ impl Config {
         Self::from_getter(|key| map.get(key.env_var()).map(|v| v.to_string()))
     }
 
+    /// How the transcript is matched against the expected phrases
+    pub fn phrase_policy(&self) -> PhrasePolicy<'_> {
+        PhrasePolicy {
+            policy: self.match_policy,
+            phrases: self.expected_phrase.split('|').map(str::trim).filter(|p| !p.is_empty()).collect(),
+            threshold: self.phrase_threshold,
+            transcript_weight: self.transcript_weight,
+        }
+    }
+
+    /// Scores checks are held to
+    pub fn thresholds(&self) -> Thresholds {
+        Thresholds {
+            similarity: self.similarity_threshold,
+            warning: self.warning_threshold,
+            fingerprint: self.fingerprint_threshold,
+            update: self.reference_update_threshold,
+        }
+    }
+
     /// Validate configuration values at startup.
     /// Returns Ok(()) if all validations pass, or Err with details of what failed.
     pub fn validate(&self) -> Result<()> {
@@ -210,7 +590,7 @@ impl Config {
         }
 
         // Validate phone number (10 digits for voip.ms)
-        if !Self::is_valid_phone(&self.target_phone) {
+        if self.checks_file.is_none() && !Self::is_valid_phone(&self.target_phone) {
             errors.push(format!(
                 "TARGET_PHONE '{}' invalid. Expected 10 digits.",
                 self.target_phone
@@ -220,6 +600,37 @@ impl Config {
         // Validate expected phrase is not empty
         if self.expected_phrase.trim().is_empty() {
             errors.push("EXPECTED_PHRASE cannot be empty.".to_string());
+        } else if self.expected_phrase.split('|').any(|phrase| phrase.trim().is_empty()) {
+            errors.push(format!("EXPECTED_PHRASE '{}' has an empty phrase between '|'.", self.expected_phrase));
+        }
+
+        if !(0.0..=1.0).contains(&self.phrase_threshold) {
+            errors.push(format!("PHRASE_THRESHOLD={} must be between 0 and 1.", self.phrase_threshold));
+        }
+        if !(0.0..=1.0).contains(&self.transcript_weight) {
+            errors.push(format!("TRANSCRIPT_WEIGHT={} must be between 0 and 1.", self.transcript_weight));
+        }
+
+        // Validate the similarity thresholds: warning < similarity <= update
+        if !(self.similarity_threshold > 0.0 && self.similarity_threshold <= 1.0) {
+            errors.push(format!(
+                "SIMILARITY_THRESHOLD={} must be greater than 0 and at most 1.",
+                self.similarity_threshold
+            ));
+        }
+        if let Some(warning) = self.warning_threshold {
+            if !(warning > 0.0 && warning < self.similarity_threshold) {
+                errors.push(format!(
+                    "WARNING_THRESHOLD={} must be greater than 0 and below SIMILARITY_THRESHOLD ({}).",
+                    warning, self.similarity_threshold
+                ));
+            }
+        }
+        if !(self.similarity_threshold..=1.0).contains(&self.reference_update_threshold) {
+            errors.push(format!(
+                "REFERENCE_UPDATE_THRESHOLD={} must be between SIMILARITY_THRESHOLD ({}) and 1.",
+                self.reference_update_threshold, self.similarity_threshold
+            ));
         }
 
         // Validate listen duration is reasonable
@@ -232,6 +643,130 @@ impl Config {
             ));
         }
 
+        // Shorter audio counts as none, so the call must be able to hear more
+        if self.min_audio_duration_ms > self.listen_duration_secs * 1000 {
+            errors.push(format!(
+                "MIN_AUDIO_DURATION_MS={} must not exceed LISTEN_DURATION_SECS ({}s).",
+                self.min_audio_duration_ms, self.listen_duration_secs
+            ));
+        }
+
+        if let Some(mos) = self.min_mos {
+            if !(1.0..=4.5).contains(&mos) {
+                errors.push(format!("MIN_MOS={} must be between 1 and 4.5.", mos));
+            }
+        }
+
+        // Validate ring timeout is reasonable
+        if self.ring_timeout_secs == 0 {
+            errors.push("RING_TIMEOUT_SECS must be greater than 0.".to_string());
+        } else if self.ring_timeout_secs > 300 {
+            errors.push(format!(
+                "RING_TIMEOUT_SECS={} seems too long (max recommended: 300).",
+                self.ring_timeout_secs
+            ));
+        }
+
+        // Validate the confirmation policy
+        if self.confirm_attempts == 0 || self.confirm_attempts > 10 {
+            errors.push(format!("CONFIRM_ATTEMPTS={} must be between 1 and 10.", self.confirm_attempts));
+        }
+        if self.confirm_failures == 0 || self.confirm_failures > self.confirm_attempts {
+            errors.push(format!(
+                "CONFIRM_FAILURES={} must be between 1 and CONFIRM_ATTEMPTS ({}).",
+                self.confirm_failures, self.confirm_attempts
+            ));
+        }
+        if self.confirm_delay_secs > 600 {
+            errors.push(format!(
+                "CONFIRM_DELAY_SECS={} seems too long (max recommended: 600).",
+                self.confirm_delay_secs
+            ));
+        }
+
+        // Validate the history rotation size
+        if self.history_max_mb == 0 {
+            errors.push("HISTORY_MAX_MB must be greater than 0.".to_string());
+        }
+
+        // Validate the reference drift limit (0 stops checks updating references)
+        if !(0.0..=1.0).contains(&self.reference_max_drift) {
+            errors.push(format!("REFERENCE_MAX_DRIFT={} must be between 0 and 1.", self.reference_max_drift));
+        }
+
+        // A cross-check by the deciding matcher itself tells nothing
+        if self.secondary_matcher == Some(self.matcher) {
+            errors.push(format!("SECONDARY_MATCHER={} must differ from MATCHER.", self.matcher));
+        }
+
+        if !(0.0..=1.0).contains(&self.fingerprint_threshold) || self.fingerprint_threshold == 0.0 {
+            errors.push(format!(
+                "FINGERPRINT_THRESHOLD={} must be greater than 0 and at most 1.",
+                self.fingerprint_threshold
+            ));
+        }
+
+        // Validate IVR script captures something and fits in a call
+        if let Some(ref script) = self.ivr_script {
+            if script.captures() == 0 {
+                errors.push("IVR_SCRIPT must contain at least one capture step.".to_string());
+            }
+            let total_secs = script.total_duration().as_secs();
+            if total_secs > 300 {
+                errors.push(format!(
+                    "IVR_SCRIPT runs for {}s, which seems too long (max recommended: 300).",
+                    total_secs
+                ));
+            }
+        }
+
+        // Validate notification endpoints are HTTP(S) URLs
+        let urls = [
+            (ConfigKey::PushoverApiUrl, Some(&self.pushover_api_url)),
+            (ConfigKey::WebhookUrl, self.webhook_url.as_ref()),
+            (ConfigKey::SlackWebhookUrl, self.slack_webhook_url.as_ref()),
+            (ConfigKey::NtfyUrl, self.ntfy.as_ref().map(|n| &n.url)),
+            (ConfigKey::VoipmsApiUrl, self.sms.as_ref().map(|s| &s.api_url)),
+        ];
+        for (key, url) in urls {
+            if let Some(url) = url {
+                if !(url.starts_with("http://") || url.starts_with("https://")) {
+                    errors.push(format!("{} '{}' must start with http:// or https://.", key.env_var(), url));
+                }
+            }
+        }
+
+        // Validate SMS numbers (10 digits for voip.ms)
+        if let Some(ref sms) = self.sms {
+            if !Self::is_valid_phone(&sms.did) {
+                errors.push(format!("SMS_DID '{}' invalid. Expected 10 digits.", sms.did));
+            }
+            for number in &sms.to {
+                if !Self::is_valid_phone(number) {
+                    errors.push(format!("SMS_TO number '{}' invalid. Expected 10 digits.", number));
+                }
+            }
+        }
+
+        // Validate the sinks can be built (email addresses) and routes name configured sinks
+        if let Err(e) = crate::notify::Notifier::new(self) {
+            errors.push(format!("{:#}", e));
+        }
+
+        // Validate the excluded dates file parses
+        if let Some(ref path) = self.excluded_dates_file {
+            if let Err(e) = crate::schedule::ExcludedDates::load(path) {
+                errors.push(format!("EXCLUDED_DATES_FILE: {:#}", e));
+            }
+        }
+
+        // Validate the checks file parses and every target is sensible
+        if let Some(ref path) = self.checks_file {
+            if let Err(e) = crate::targets::load_checks_file(path, self) {
+                errors.push(format!("CHECKS_FILE: {:#}", e));
+            }
+        }
+
         if errors.is_empty() {
             Ok(())
         } else {
@@ -243,12 +778,97 @@ impl Config {
     }
 
     /// Check if a phone number is valid (10 digits for voip.ms)
-    fn is_valid_phone(phone: &str) -> bool {
+    pub(crate) fn is_valid_phone(phone: &str) -> bool {
         let digit_count = phone.chars().filter(|c| c.is_ascii_digit()).count();
         digit_count == 10
     }
 }
 
+/// Value of an optional key, treating an empty value as unset
+fn get_optional<F>(get: &F, key: ConfigKey) -> Option<String>
+where
+    F: Fn(ConfigKey) -> Option<String>,
+{
+    get(key).filter(|s| !s.trim().is_empty())
+}
+
+fn get_or_default<F>(get: &F, key: ConfigKey) -> String
+where
+    F: Fn(ConfigKey) -> Option<String>,
+{
+    get_optional(get, key).unwrap_or_else(|| key.default_value().unwrap_or_default().to_string())
+}
+
+/// A key that must be set once its sink is enabled by `enabled_by`
+fn get_required_with<F>(get: &F, key: ConfigKey, enabled_by: ConfigKey) -> Result<String>
+where
+    F: Fn(ConfigKey) -> Option<String>,
+{
+    get_optional(get, key).with_context(|| format!("{} is required when {} is set", key.env_var(), enabled_by.env_var()))
+}
+
+/// Comma-separated list (addresses, phone numbers)
+fn split_list(s: &str) -> Vec<String> {
+    s.split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect()
+}
+
+/// ntfy is enabled by NTFY_TOPIC
+fn ntfy_config<F>(get: &F) -> Option<NtfyConfig>
+where
+    F: Fn(ConfigKey) -> Option<String>,
+{
+    Some(NtfyConfig {
+        topic: get_optional(get, ConfigKey::NtfyTopic)?,
+        url: get_or_default(get, ConfigKey::NtfyUrl),
+        token: get_optional(get, ConfigKey::NtfyToken),
+    })
+}
+
+/// Email is enabled by SMTP_HOST
+fn smtp_config<F>(get: &F) -> Result<Option<SmtpConfig>>
+where
+    F: Fn(ConfigKey) -> Option<String>,
+{
+    let Some(host) = get_optional(get, ConfigKey::SmtpHost) else {
+        return Ok(None);
+    };
+    let security: SmtpSecurity = get_or_default(get, ConfigKey::SmtpSecurity).parse()?;
+    let port = match get_optional(get, ConfigKey::SmtpPort) {
+        Some(port) => port
+            .parse()
+            .context(format!("{} must be a valid port number", ConfigKey::SmtpPort.env_var()))?,
+        None => security.default_port(),
+    };
+
+    Ok(Some(SmtpConfig {
+        host,
+        port,
+        security,
+        username: get_optional(get, ConfigKey::SmtpUsername),
+        password: get_optional(get, ConfigKey::SmtpPassword),
+        from: get_required_with(get, ConfigKey::SmtpFrom, ConfigKey::SmtpHost)?,
+        to: split_list(&get_required_with(get, ConfigKey::SmtpTo, ConfigKey::SmtpHost)?),
+    }))
+}
+
+/// SMS is enabled by SMS_TO
+fn sms_config<F>(get: &F) -> Result<Option<SmsConfig>>
+where
+    F: Fn(ConfigKey) -> Option<String>,
+{
+    let Some(to) = get_optional(get, ConfigKey::SmsTo) else {
+        return Ok(None);
+    };
+
+    Ok(Some(SmsConfig {
+        api_url: get_or_default(get, ConfigKey::VoipmsApiUrl),
+        api_username: get_required_with(get, ConfigKey::VoipmsApiUsername, ConfigKey::SmsTo)?,
+        api_password: get_required_with(get, ConfigKey::VoipmsApiPassword, ConfigKey::SmsTo)?,
+        did: get_required_with(get, ConfigKey::SmsDid, ConfigKey::SmsTo)?,
+        to: split_list(&to),
+    }))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -346,6 +966,39 @@ mod tests {
         assert_eq!(config.expected_phrase, "hello world");
     }
 
+    #[test]
+    fn test_phrase_policy() {
+        let config = Config::from_map(&minimal_valid_env()).expect("should parse");
+        let policy = config.phrase_policy();
+        assert_eq!(policy.policy, MatchPolicy::Audio);
+        assert_eq!(policy.phrases, vec!["thank you for calling cubic machinery"]);
+        assert_eq!(policy.threshold, crate::phrase::DEFAULT_PHRASE_THRESHOLD);
+        assert_eq!(policy.transcript_weight, 0.5);
+
+        let mut env = minimal_valid_env();
+        env.insert("EXPECTED_PHRASE", "Thank you for calling | You've reached Cubic");
+        env.insert("MATCH_POLICY", "weighted");
+        env.insert("PHRASE_THRESHOLD", "0.7");
+        env.insert("TRANSCRIPT_WEIGHT", "0.3");
+        let config = Config::from_map(&env).unwrap();
+        let policy = config.phrase_policy();
+        assert_eq!(policy.policy, MatchPolicy::Weighted);
+        assert_eq!(policy.phrases, vec!["thank you for calling", "you've reached cubic"]);
+        assert_eq!((policy.threshold, policy.transcript_weight), (0.7, 0.3));
+
+        env.insert("EXPECTED_PHRASE", "hello||goodbye");
+        env.insert("PHRASE_THRESHOLD", "1.5");
+        env.insert("TRANSCRIPT_WEIGHT", "-1");
+        let err = Config::from_map(&env).unwrap().validate().unwrap_err().to_string();
+        assert!(err.contains("empty phrase"), "{}", err);
+        assert!(err.contains("PHRASE_THRESHOLD=1.5"), "{}", err);
+        assert!(err.contains("TRANSCRIPT_WEIGHT=-1"), "{}", err);
+
+        env.insert("MATCH_POLICY", "any");
+        let err = Config::from_map(&env).unwrap_err().to_string();
+        assert!(err.contains("Unknown match policy 'any'"), "{}", err);
+    }
+
     #[test]
     fn test_listen_duration_custom() {
         let mut env = minimal_valid_env();
@@ -446,6 +1099,56 @@ mod tests {
         assert!(err.contains("too long"), "error should mention duration too long: {}", err);
     }
 
+    #[test]
+    fn test_ring_timeout_default_and_custom() {
+        let mut env = minimal_valid_env();
+        let config = Config::from_map(&env).expect("should parse");
+        assert_eq!(config.ring_timeout_secs, 30);
+
+        env.insert("RING_TIMEOUT_SECS", "45");
+        let config = Config::from_map(&env).expect("should parse");
+        assert_eq!(config.ring_timeout_secs, 45);
+    }
+
+    #[test]
+    fn test_validation_zero_ring_timeout() {
+        let mut env = minimal_valid_env();
+        env.insert("RING_TIMEOUT_SECS", "0");
+        let config = Config::from_map(&env).expect("should parse");
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("RING_TIMEOUT_SECS"), "error should mention ring timeout: {}", err);
+    }
+
+    #[test]
+    fn test_ivr_script_parsed() {
+        let mut env = minimal_valid_env();
+        let config = Config::from_map(&env).expect("should parse");
+        assert!(config.ivr_script.is_none());
+
+        env.insert("IVR_SCRIPT", "wait 3s, send 2, wait 1s, capture 8s");
+        let config = Config::from_map(&env).expect("should parse");
+        let script = config.ivr_script.expect("script should be set");
+        assert_eq!(script.steps.len(), 4);
+        assert_eq!(script.captures(), 1);
+    }
+
+    #[test]
+    fn test_invalid_ivr_script() {
+        let mut env = minimal_valid_env();
+        env.insert("IVR_SCRIPT", "wait 3s, press 2");
+        let err = Config::from_map(&env).unwrap_err().to_string();
+        assert!(err.contains("IVR_SCRIPT"), "error should mention IVR_SCRIPT: {}", err);
+    }
+
+    #[test]
+    fn test_validation_ivr_script_without_capture() {
+        let mut env = minimal_valid_env();
+        env.insert("IVR_SCRIPT", "wait 3s, send 2");
+        let config = Config::from_map(&env).expect("should parse");
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("capture step"), "error should mention capture step: {}", err);
+    }
+
     #[test]
     fn test_validation_invalid_target_phone() {
         let mut env = minimal_valid_env();
@@ -457,6 +1160,302 @@ mod tests {
         assert!(err.contains("TARGET_PHONE"), "error should mention invalid phone: {}", err);
     }
 
+    #[test]
+    fn test_checks_file_makes_target_phone_optional() {
+        let mut env = minimal_valid_env();
+        env.remove("TARGET_PHONE");
+        env.insert("CHECKS_FILE", "./checks.toml");
+        let config = Config::from_map(&env).expect("should parse without TARGET_PHONE");
+        assert_eq!(config.checks_file.as_deref(), Some("./checks.toml"));
+        assert!(config.target_phone.is_empty());
+    }
+
+    #[test]
+    fn test_schedule_default_and_custom() {
+        let mut env = minimal_valid_env();
+        let config = Config::from_map(&env).expect("should parse");
+        assert_eq!(config.schedule, Schedule::business_hours());
+
+        env.insert("SCHEDULE", "*/15 8 * * MON-FRI; 0 9-17 * * MON-FRI");
+        env.insert("SCHEDULE_TIMEZONE", "America/New_York");
+        let config = Config::from_map(&env).expect("should parse");
+        assert_eq!(config.schedule.timezone(), chrono_tz::America::New_York);
+        assert_eq!(config.schedule.cron(), "*/15 8 * * MON-FRI; 0 9-17 * * MON-FRI");
+    }
+
+    #[test]
+    fn test_invalid_schedule() {
+        let mut env = minimal_valid_env();
+        env.insert("SCHEDULE_TIMEZONE", "Eastern");
+        let err = Config::from_map(&env).unwrap_err().to_string();
+        assert!(err.contains("SCHEDULE"), "error should mention SCHEDULE: {}", err);
+
+        let mut env = minimal_valid_env();
+        env.insert("SCHEDULE", "every hour");
+        assert!(Config::from_map(&env).is_err());
+    }
+
+    #[test]
+    fn test_validation_confirm_policy() {
+        let mut env = minimal_valid_env();
+        env.insert("CONFIRM_ATTEMPTS", "3");
+        env.insert("CONFIRM_FAILURES", "4");
+        let config = Config::from_map(&env).expect("should parse");
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("CONFIRM_FAILURES=4"), "error should mention CONFIRM_FAILURES: {}", err);
+
+        env.insert("CONFIRM_ATTEMPTS", "0");
+        env.insert("CONFIRM_FAILURES", "1");
+        let config = Config::from_map(&env).expect("should parse");
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("CONFIRM_ATTEMPTS=0"), "error should mention CONFIRM_ATTEMPTS: {}", err);
+    }
+
+    #[test]
+    fn test_reference_max_drift() {
+        let config = Config::from_map(&minimal_valid_env()).expect("should parse");
+        assert_eq!(config.reference_max_drift, 0.1);
+
+        let mut env = minimal_valid_env();
+        env.insert("REFERENCE_MAX_DRIFT", "1.5");
+        let config = Config::from_map(&env).expect("should parse");
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("REFERENCE_MAX_DRIFT=1.5"), "error should mention REFERENCE_MAX_DRIFT: {}", err);
+
+        env.insert("REFERENCE_MAX_DRIFT", "far");
+        assert!(Config::from_map(&env).is_err());
+    }
+
+    #[test]
+    fn test_thresholds() {
+        let config = Config::from_map(&minimal_valid_env()).expect("should parse");
+        assert_eq!(config.thresholds(), Thresholds::default());
+
+        let mut env = minimal_valid_env();
+        env.insert("SIMILARITY_THRESHOLD", "0.8");
+        env.insert("WARNING_THRESHOLD", "0.7");
+        env.insert("REFERENCE_UPDATE_THRESHOLD", "0.9");
+        let config = Config::from_map(&env).expect("should parse");
+        assert_eq!((config.thresholds().similarity, config.thresholds().warning), (0.8, Some(0.7)));
+        assert_eq!(config.thresholds().update, 0.9);
+        let err = config.validate().unwrap_err().to_string();
+        assert!(!err.contains("THRESHOLD="), "thresholds should be valid: {}", err);
+
+        // The warning band sits below the threshold, updates above it
+        env.insert("WARNING_THRESHOLD", "0.85");
+        env.insert("REFERENCE_UPDATE_THRESHOLD", "0.75");
+        let err = Config::from_map(&env).unwrap().validate().unwrap_err().to_string();
+        assert!(err.contains("WARNING_THRESHOLD=0.85"), "error should mention WARNING_THRESHOLD: {}", err);
+        assert!(err.contains("REFERENCE_UPDATE_THRESHOLD=0.75"), "error should mention REFERENCE_UPDATE_THRESHOLD: {}", err);
+
+        env.insert("SIMILARITY_THRESHOLD", "0");
+        let err = Config::from_map(&env).unwrap().validate().unwrap_err().to_string();
+        assert!(err.contains("SIMILARITY_THRESHOLD=0"), "error should mention SIMILARITY_THRESHOLD: {}", err);
+
+        env.insert("WARNING_THRESHOLD", "low");
+        assert!(Config::from_map(&env).is_err());
+    }
+
+    #[test]
+    fn test_min_mos() {
+        assert_eq!(Config::from_map(&minimal_valid_env()).unwrap().min_mos, None);
+
+        let mut env = minimal_valid_env();
+        env.insert("MIN_MOS", "3.6");
+        assert_eq!(Config::from_map(&env).unwrap().min_mos, Some(3.6));
+
+        env.insert("MIN_MOS", "5");
+        let err = Config::from_map(&env).unwrap().validate().unwrap_err().to_string();
+        assert!(err.contains("MIN_MOS=5"), "error should mention MIN_MOS: {}", err);
+
+        env.insert("MIN_MOS", "good");
+        assert!(Config::from_map(&env).is_err());
+    }
+
+    #[test]
+    fn test_packet_loss_concealment() {
+        let config = Config::from_map(&minimal_valid_env()).unwrap();
+        assert_eq!(config.packet_loss_concealment, Concealment::Waveform);
+
+        let mut env = minimal_valid_env();
+        env.insert("PACKET_LOSS_CONCEALMENT", "zero");
+        assert_eq!(Config::from_map(&env).unwrap().packet_loss_concealment, Concealment::Zero);
+
+        env.insert("PACKET_LOSS_CONCEALMENT", "repeat");
+        let err = Config::from_map(&env).unwrap_err().to_string();
+        assert!(err.contains("'repeat'"), "{}", err);
+    }
+
+    #[test]
+    fn test_jitter_buffer_mode() {
+        let config = Config::from_map(&minimal_valid_env()).unwrap();
+        assert_eq!(config.jitter_buffer, JitterMode::Fixed);
+
+        let mut env = minimal_valid_env();
+        env.insert("JITTER_BUFFER", "Adaptive");
+        assert_eq!(Config::from_map(&env).unwrap().jitter_buffer, JitterMode::adaptive());
+
+        env.insert("JITTER_BUFFER", "dynamic");
+        let err = Config::from_map(&env).unwrap_err().to_string();
+        assert!(err.contains("'dynamic'"), "{}", err);
+    }
+
+    #[test]
+    fn test_min_audio_duration_fits_the_call() {
+        let mut env = minimal_valid_env();
+        env.insert("LISTEN_DURATION_SECS", "5");
+        env.insert("MIN_AUDIO_DURATION_MS", "6000");
+        let err = Config::from_map(&env).unwrap().validate().unwrap_err().to_string();
+        assert!(err.contains("MIN_AUDIO_DURATION_MS=6000"), "error should mention MIN_AUDIO_DURATION_MS: {}", err);
+    }
+
+    #[test]
+    fn test_matcher() {
+        let config = Config::from_map(&minimal_valid_env()).expect("should parse");
+        assert_eq!(config.matcher, Matcher::Embedding);
+
+        let mut env = minimal_valid_env();
+        env.insert("MATCHER", "aligned");
+        assert_eq!(Config::from_map(&env).unwrap().matcher, Matcher::Aligned);
+
+        env.insert("MATCHER", "dtw");
+        let err = Config::from_map(&env).unwrap_err().to_string();
+        assert!(err.contains("Unknown matcher 'dtw'"), "{}", err);
+    }
+
+    #[test]
+    fn test_secondary_matcher() {
+        let config = Config::from_map(&minimal_valid_env()).expect("should parse");
+        assert_eq!(config.secondary_matcher, None);
+        assert_eq!(config.fingerprint_threshold, crate::fingerprint::DEFAULT_FINGERPRINT_THRESHOLD);
+
+        let mut env = minimal_valid_env();
+        env.insert("SECONDARY_MATCHER", "fingerprint");
+        env.insert("FINGERPRINT_THRESHOLD", "0.3");
+        let config = Config::from_map(&env).unwrap();
+        assert_eq!(config.secondary_matcher, Some(Matcher::Fingerprint));
+        assert_eq!(config.fingerprint_threshold, 0.3);
+        let errors = config.validate().err().map(|e| e.to_string()).unwrap_or_default();
+        assert!(!errors.contains("SECONDARY_MATCHER") && !errors.contains("FINGERPRINT_THRESHOLD"), "{}", errors);
+
+        env.insert("MATCHER", "fingerprint");
+        let err = Config::from_map(&env).unwrap().validate().unwrap_err().to_string();
+        assert!(err.contains("SECONDARY_MATCHER=fingerprint must differ"), "{}", err);
+
+        env.insert("SECONDARY_MATCHER", "");
+        env.insert("FINGERPRINT_THRESHOLD", "0");
+        let config = Config::from_map(&env).unwrap();
+        assert_eq!(config.secondary_matcher, None);
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("FINGERPRINT_THRESHOLD=0"), "{}", err);
+
+        env.insert("SECONDARY_MATCHER", "shazam");
+        assert!(Config::from_map(&env).is_err());
+    }
+
+    #[test]
+    fn test_alert_settings() {
+        let config = Config::from_map(&minimal_valid_env()).expect("should parse");
+        assert_eq!(config.alert_state_file, "./alert_state.json");
+        assert_eq!(config.alert_reminder_mins, 240);
+
+        let mut env = minimal_valid_env();
+        env.insert("ALERT_REMINDER_MINS", "0");
+        env.insert("ALERT_STATE_FILE", "/var/lib/phonecheck/alerts.json");
+        let config = Config::from_map(&env).expect("should parse");
+        assert_eq!(config.alert_reminder_mins, 0);
+        assert_eq!(config.alert_state_file, "/var/lib/phonecheck/alerts.json");
+
+        env.insert("ALERT_REMINDER_MINS", "hourly");
+        let err = Config::from_map(&env).unwrap_err().to_string();
+        assert!(err.contains("ALERT_REMINDER_MINS"), "error should mention ALERT_REMINDER_MINS: {}", err);
+    }
+
+    #[test]
+    fn test_notification_sinks_default_to_disabled() {
+        let config = Config::from_map(&minimal_valid_env()).expect("should parse");
+        assert_eq!(config.pushover_api_url, PUSHOVER_API_URL);
+        assert!(config.webhook_url.is_none());
+        assert!(config.slack_webhook_url.is_none());
+        assert!(config.ntfy.is_none());
+        assert!(config.smtp.is_none());
+        assert!(config.sms.is_none());
+        assert!(config.notify_routes.is_empty());
+    }
+
+    #[test]
+    fn test_notification_sinks_from_env() {
+        let mut env = minimal_valid_env();
+        env.insert("NTFY_TOPIC", "phonecheck");
+        env.insert("SMTP_HOST", "smtp.example.com");
+        env.insert("SMTP_SECURITY", "tls");
+        env.insert("SMTP_FROM", "alerts@example.com");
+        env.insert("SMTP_TO", "ops@example.com, oncall@example.com");
+        env.insert("SMS_TO", "9095550101,9095550102");
+        env.insert("SMS_DID", "9095550100");
+        env.insert("VOIPMS_API_USERNAME", "me@example.com");
+        env.insert("VOIPMS_API_PASSWORD", "apipass");
+        env.insert("NOTIFY_ROUTES", "pushover; sms severity=critical");
+        let config = Config::from_map(&env).expect("should parse");
+
+        let ntfy = config.ntfy.unwrap();
+        assert_eq!(ntfy.url, NTFY_URL);
+        assert_eq!(ntfy.topic, "phonecheck");
+
+        let smtp = config.smtp.unwrap();
+        assert_eq!(smtp.security, SmtpSecurity::Tls);
+        assert_eq!(smtp.port, 465);
+        assert_eq!(smtp.to, vec!["ops@example.com", "oncall@example.com"]);
+
+        let sms = config.sms.unwrap();
+        assert_eq!(sms.api_url, VOIPMS_API_URL);
+        assert_eq!(sms.to.len(), 2);
+        assert_eq!(config.notify_routes.len(), 2);
+    }
+
+    #[test]
+    fn test_notification_sink_missing_settings() {
+        let mut env = minimal_valid_env();
+        env.insert("SMTP_HOST", "smtp.example.com");
+        let err = Config::from_map(&env).unwrap_err().to_string();
+        assert!(err.contains("SMTP_FROM"), "error should mention SMTP_FROM: {}", err);
+
+        let mut env = minimal_valid_env();
+        env.insert("SMS_TO", "9095550101");
+        let err = Config::from_map(&env).unwrap_err().to_string();
+        assert!(err.contains("VOIPMS_API_USERNAME"), "error should mention VOIPMS_API_USERNAME: {}", err);
+
+        let mut env = minimal_valid_env();
+        env.insert("NOTIFY_ROUTES", "pushover severity=loud");
+        let err = Config::from_map(&env).unwrap_err().to_string();
+        assert!(err.contains("NOTIFY_ROUTES"), "error should mention NOTIFY_ROUTES: {}", err);
+    }
+
+    #[test]
+    fn test_validation_notification_settings() {
+        let mut env = minimal_valid_env();
+        env.insert("SLACK_WEBHOOK_URL", "hooks.slack.com/services/x");
+        env.insert("SMS_TO", "555");
+        env.insert("SMS_DID", "9095550100");
+        env.insert("VOIPMS_API_USERNAME", "me@example.com");
+        env.insert("VOIPMS_API_PASSWORD", "apipass");
+        env.insert("NOTIFY_ROUTES", "email severity=critical");
+        let config = Config::from_map(&env).expect("should parse");
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("SLACK_WEBHOOK_URL"), "error should mention SLACK_WEBHOOK_URL: {}", err);
+        assert!(err.contains("SMS_TO number '555'"), "error should mention SMS_TO: {}", err);
+        assert!(err.contains("sink 'email'"), "error should mention the unknown sink: {}", err);
+    }
+
+    #[test]
+    fn test_validation_missing_checks_file() {
+        let mut env = minimal_valid_env();
+        env.insert("CHECKS_FILE", "/nonexistent/phonecheck-checks.toml");
+        let config = Config::from_map(&env).expect("should parse");
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("CHECKS_FILE"), "error should mention CHECKS_FILE: {}", err);
+    }
+
     #[test]
     fn test_config_key_env_var() {
         // Test that all keys have env var names
@@ -468,13 +1467,60 @@ mod tests {
             SipPort,
             TargetPhone,
             ExpectedPhrase,
+            MatchPolicy,
+            PhraseThreshold,
+            TranscriptWeight,
+            SimilarityThreshold,
+            WarningThreshold,
             ListenDurationSecs,
+            RingTimeoutSecs,
             PushoverUserKey,
             PushoverApiToken,
             WhisperModelPath,
             StunServer,
             MinAudioDurationMs,
+            MinMos,
+            PacketLossConcealment,
+            JitterBuffer,
             HealthPort,
+            ApproveToken,
+            IvrScript,
+            ChecksFile,
+            Schedule,
+            ScheduleTimezone,
+            ExcludedDatesFile,
+            PushoverApiUrl,
+            WebhookUrl,
+            SlackWebhookUrl,
+            NtfyUrl,
+            NtfyTopic,
+            NtfyToken,
+            SmtpHost,
+            SmtpPort,
+            SmtpSecurity,
+            SmtpUsername,
+            SmtpPassword,
+            SmtpFrom,
+            SmtpTo,
+            VoipmsApiUrl,
+            VoipmsApiUsername,
+            VoipmsApiPassword,
+            SmsDid,
+            SmsTo,
+            NotifyRoutes,
+            ConfirmAttempts,
+            ConfirmFailures,
+            ConfirmDelaySecs,
+            AlertStateFile,
+            AlertReminderMins,
+            HistoryFile,
+            HistoryMaxMb,
+            HistoryFiles,
+            ReferenceMaxDrift,
+            ReferenceUpdateThreshold,
+            Matcher,
+            SecondaryMatcher,
+            FingerprintThreshold,
         ] {
             assert!(!key.env_var().is_empty(), "{:?} env var is empty", key);
         }
@@ -503,8 +1549,19 @@ mod tests {
         assert_eq!(SipPort.default_value(), Some("5060"));
         assert_eq!(ExpectedPhrase.default_value(), Some("thank you for calling cubic machinery"));
         assert_eq!(ListenDurationSecs.default_value(), Some("10"));
+        assert_eq!(RingTimeoutSecs.default_value(), Some("30"));
         assert_eq!(WhisperModelPath.default_value(), Some("./models/ggml-base.en.bin"));
         assert_eq!(MinAudioDurationMs.default_value(), Some("500"));
+        assert_eq!(PacketLossConcealment.default_value(), Some("waveform"));
+        assert_eq!(JitterBuffer.default_value(), Some("fixed"));
+        assert_eq!(PushoverApiUrl.default_value(), Some("https://api.pushover.net/1/messages.json"));
+        assert_eq!(VoipmsApiUrl.default_value(), Some("https://voip.ms/api/v1/rest.php"));
+        assert_eq!(ConfirmAttempts.default_value(), Some("2"));
+        assert_eq!(ConfirmFailures.default_value(), Some("2"));
+        assert_eq!(AlertStateFile.default_value(), Some("./alert_state.json"));
+        assert_eq!(AlertReminderMins.default_value(), Some("240"));
+        assert_eq!(HistoryFile.default_value(), Some("./history.jsonl"));
+        assert_eq!(HistoryMaxMb.default_value(), Some("10"));
 
         // Keys without defaults
         assert!(SipUsername.default_value().is_none());
@@ -68,6 +68,31 @@ impl AudioEmbedder {
             return Ok(vec![0.0; 768]);
         }
 
+        // Mean pool across time dimension (axis 0 of [time, 768])
+        let mean_embedding = self
+            .frames(audio)?
+            .mean_axis(Axis(0)) // Mean across time -> [768]
+            .context("Failed to compute mean")?;
+
+        let (embedding, _offset) = mean_embedding.into_raw_vec_and_offset();
+        normalize(embedding)
+    }
+
+    /// Compute one L2-normalized embedding per Wav2Vec2 frame (20 ms) of
+    /// f32 samples (16kHz mono), for frame-level alignment
+    pub fn embed_frames(&mut self, audio: &[f32]) -> Result<Vec<Vec<f32>>> {
+        if audio.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        self.frames(audio)?
+            .outer_iter()
+            .map(|frame| normalize(frame.to_vec()))
+            .collect()
+    }
+
+    /// Run the model, returning its hidden states as [time, 768]
+    fn frames(&mut self, audio: &[f32]) -> Result<ndarray::Array2<f32>> {
         // Create input tensor [1, audio_len]
         let audio_len = audio.len();
         let input_array = ndarray::Array2::from_shape_vec((1, audio_len), audio.to_vec())?;
@@ -87,44 +112,11 @@ impl AudioEmbedder {
             shape[0], shape[1], shape[2]
         );
 
-        // Mean pool across time dimension (axis 1)
-        let mean_embedding = output
+        output
             .index_axis(Axis(0), 0) // Remove batch dimension -> [time, 768]
-            .mean_axis(Axis(0)) // Mean across time -> [768]
-            .context("Failed to compute mean")?;
-
-        let (mut embedding, _offset) = mean_embedding.into_raw_vec_and_offset();
-
-        // L2 normalize with validation for NaN/Inf
-        let norm: f32 = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
-
-        // Validate for NaN or Inf values that would corrupt normalization
-        if norm.is_nan() || norm.is_infinite() {
-            anyhow::bail!(
-                "Embedding normalization produced NaN/Inf: norm={}. \
-                 Check Wav2Vec2 model output - may be corrupted or invalid input.",
-                norm
-            );
-        }
-
-        // Also check individual values for NaN/Inf before normalization
-        for (i, &val) in embedding.iter().enumerate() {
-            if val.is_nan() || val.is_infinite() {
-                anyhow::bail!(
-                    "Embedding contains NaN/Inf at index {}: {}. \
-                     Check Wav2Vec2 model output - may be corrupted or invalid input.",
-                    i, val
-                );
-            }
-        }
-
-        if norm > 1e-8 {
-            for x in &mut embedding {
-                *x /= norm;
-            }
-        }
-
-        Ok(embedding)
+            .to_owned()
+            .into_dimensionality()
+            .context("Unexpected Wav2Vec2 output shape")
     }
 
     /// Compute cosine similarity between two embeddings
@@ -145,6 +137,39 @@ impl AudioEmbedder {
     }
 }
 
+/// L2 normalize with validation for NaN/Inf
+fn normalize(mut embedding: Vec<f32>) -> Result<Vec<f32>> {
+    let norm: f32 = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+    // Validate for NaN or Inf values that would corrupt normalization
+    if norm.is_nan() || norm.is_infinite() {
+        anyhow::bail!(
+            "Embedding normalization produced NaN/Inf: norm={}. \
+             Check Wav2Vec2 model output - may be corrupted or invalid input.",
+            norm
+        );
+    }
+
+    // Also check individual values for NaN/Inf before normalization
+    for (i, &val) in embedding.iter().enumerate() {
+        if val.is_nan() || val.is_infinite() {
+            anyhow::bail!(
+                "Embedding contains NaN/Inf at index {}: {}. \
+                 Check Wav2Vec2 model output - may be corrupted or invalid input.",
+                i, val
+            );
+        }
+    }
+
+    if norm > 1e-8 {
+        for x in &mut embedding {
+            *x /= norm;
+        }
+    }
+
+    Ok(embedding)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -335,13 +360,11 @@ mod tests {
             return;
         }
 
-        let bytes = std::fs::read(path).expect("Failed to read reference embedding");
-
-        // Convert to floats
-        let floats: Vec<f32> = bytes
-            .chunks_exact(4)
-            .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
-            .collect();
+        // Header (or legacy size) and checksum are validated on read
+        let floats = crate::embedding_file::read(path)
+            .expect("Failed to read reference embedding")
+            .unwrap()
+            .into_embedding();
 
         // Check dimension
         assert_eq!(floats.len(), 768, "Reference embedding should have 768 dimensions");
@@ -384,11 +407,10 @@ mod tests {
             return;
         }
 
-        let bytes = std::fs::read(path).expect("Failed to read reference embedding");
-        let floats: Vec<f32> = bytes
-            .chunks_exact(4)
-            .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
-            .collect();
+        let floats = crate::embedding_file::read(path)
+            .expect("Failed to read reference embedding")
+            .unwrap()
+            .into_embedding();
 
         // Self-similarity should be exactly 1.0 (or very close due to floating point)
         let self_sim = AudioEmbedder::cosine_similarity(&floats, &floats);
@@ -500,11 +522,10 @@ mod tests {
         }
 
         // Load reference embedding
-        let ref_bytes = std::fs::read(ref_path).expect("Failed to read reference");
-        let reference: Vec<f32> = ref_bytes
-            .chunks_exact(4)
-            .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
-            .collect();
+        let reference = crate::embedding_file::read(ref_path)
+            .expect("Failed to read reference")
+            .unwrap()
+            .into_embedding();
 
         // Load test audio
         let mut reader = hound::WavReader::open(audio_path).expect("Failed to open WAV");
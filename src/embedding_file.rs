@@ -0,0 +1,382 @@
+//! Reference embedding file format
+//!
+//! A reference file is an 88-byte header followed by `dimension` f32 values
+//! (all little-endian):
+//!
+//! | Offset | Size | Field |
+//! |--------|------|-------|
+//! | 0  | 4  | magic `PCEM` |
+//! | 4  | 2  | format version (1) |
+//! | 6  | 1  | pooling (0 = mean over frames) |
+//! | 7  | 1  | reserved (0) |
+//! | 8  | 4  | dimension |
+//! | 12 | 4  | sample rate of the embedded audio |
+//! | 16 | 8  | creation time (Unix seconds) |
+//! | 24 | 32 | SHA-256 of the ONNX model that produced it |
+//! | 56 | 32 | SHA-256 of the header (this field zeroed) and the values |
+//!
+//! Files written before this format are bare 768 f32 values. They are still
+//! read (as [`StoredEmbedding::Legacy`]) but can't be tied to a model;
+//! `phonecheck reference migrate` rewrites them with a header.
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+use crate::rtp::WHISPER_SAMPLE_RATE;
+
+const MAGIC: &[u8; 4] = b"PCEM";
+
+/// Current format version
+pub const FORMAT_VERSION: u16 = 1;
+
+const HEADER_LEN: usize = 88;
+const CHECKSUM_OFFSET: usize = 56;
+
+/// Dimension of legacy (headerless) files: Wav2Vec2 base hidden size
+pub const LEGACY_DIMENSION: usize = 768;
+
+/// SHA-256 of a model file
+pub type ModelHash = [u8; 32];
+
+/// How frame embeddings were pooled into one vector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pooling {
+    Mean,
+}
+
+impl Pooling {
+    fn to_byte(self) -> u8 {
+        match self {
+            Pooling::Mean => 0,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Pooling::Mean),
+            _ => None,
+        }
+    }
+}
+
+/// A reference embedding with its header
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbeddingFile {
+    pub model_sha256: ModelHash,
+    pub pooling: Pooling,
+    pub sample_rate: u32,
+    pub created_at: DateTime<Utc>,
+    pub embedding: Vec<f32>,
+}
+
+impl EmbeddingFile {
+    /// A mean-pooled 16 kHz embedding created now
+    pub fn new(embedding: Vec<f32>, model_sha256: ModelHash) -> Self {
+        Self {
+            model_sha256,
+            pooling: Pooling::Mean,
+            sample_rate: WHISPER_SAMPLE_RATE,
+            // Whole seconds, as stored
+            created_at: Utc.timestamp_opt(Utc::now().timestamp(), 0).unwrap(),
+            embedding,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.embedding.len() * 4);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.push(self.pooling.to_byte());
+        bytes.push(0);
+        bytes.extend_from_slice(&(self.embedding.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&self.created_at.timestamp().to_le_bytes());
+        bytes.extend_from_slice(&self.model_sha256);
+        bytes.extend_from_slice(&[0; 32]);
+        bytes.extend(self.embedding.iter().flat_map(|f| f.to_le_bytes()));
+
+        let checksum = Sha256::digest(&bytes);
+        bytes[CHECKSUM_OFFSET..HEADER_LEN].copy_from_slice(&checksum);
+        bytes
+    }
+}
+
+/// Contents of a reference file
+#[derive(Debug, Clone, PartialEq)]
+pub enum StoredEmbedding {
+    Current(EmbeddingFile),
+    /// Headerless file from before the format existed
+    Legacy(Vec<f32>),
+}
+
+impl StoredEmbedding {
+    pub fn embedding(&self) -> &[f32] {
+        match self {
+            StoredEmbedding::Current(file) => &file.embedding,
+            StoredEmbedding::Legacy(embedding) => embedding,
+        }
+    }
+
+    pub fn into_embedding(self) -> Vec<f32> {
+        match self {
+            StoredEmbedding::Current(file) => file.embedding,
+            StoredEmbedding::Legacy(embedding) => embedding,
+        }
+    }
+
+    /// Model that produced the embedding (unknown for legacy files)
+    pub fn model_sha256(&self) -> Option<&ModelHash> {
+        match self {
+            StoredEmbedding::Current(file) => Some(&file.model_sha256),
+            StoredEmbedding::Legacy(_) => None,
+        }
+    }
+
+    /// Fail unless the embedding was produced by the model with `expected`
+    /// hash (legacy files can't be checked and pass)
+    pub fn verify_model(&self, expected: &ModelHash) -> Result<()> {
+        match self.model_sha256() {
+            Some(actual) if actual != expected => bail!(
+                "Reference was computed by model {} but the loaded model is {}; re-enroll it with the current model",
+                short_hex(actual),
+                short_hex(expected)
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Parse a reference file
+pub fn parse(bytes: &[u8]) -> Result<StoredEmbedding> {
+    if !bytes.starts_with(MAGIC) {
+        if bytes.len() == LEGACY_DIMENSION * 4 {
+            return Ok(StoredEmbedding::Legacy(floats(bytes)));
+        }
+        bail!(
+            "Not a reference embedding: no header and {} bytes (a legacy file has {})",
+            bytes.len(),
+            LEGACY_DIMENSION * 4
+        );
+    }
+    if bytes.len() < HEADER_LEN {
+        bail!("Truncated reference embedding header ({} bytes)", bytes.len());
+    }
+
+    let u32_at = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    if version != FORMAT_VERSION {
+        bail!("Unsupported reference format version {} (expected {})", version, FORMAT_VERSION);
+    }
+    let pooling = Pooling::from_byte(bytes[6]).with_context(|| format!("Unknown pooling method {}", bytes[6]))?;
+    let dimension = u32_at(8) as usize;
+    if bytes.len() != HEADER_LEN + dimension * 4 {
+        bail!(
+            "Reference embedding is {} bytes but its header describes {} dimensions ({} bytes)",
+            bytes.len(),
+            dimension,
+            HEADER_LEN + dimension * 4
+        );
+    }
+
+    let mut unsummed = bytes.to_vec();
+    unsummed[CHECKSUM_OFFSET..HEADER_LEN].fill(0);
+    if Sha256::digest(&unsummed)[..] != bytes[CHECKSUM_OFFSET..HEADER_LEN] {
+        bail!("Reference embedding checksum mismatch (file is corrupted)");
+    }
+
+    let seconds = i64::from_le_bytes(bytes[16..24].try_into().unwrap());
+    Ok(StoredEmbedding::Current(EmbeddingFile {
+        model_sha256: bytes[24..56].try_into().unwrap(),
+        pooling,
+        sample_rate: u32_at(12),
+        created_at: Utc.timestamp_opt(seconds, 0).single().context("Invalid creation time")?,
+        embedding: floats(&bytes[HEADER_LEN..]),
+    }))
+}
+
+fn floats(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
+/// Read a reference file; `None` if it doesn't exist
+pub fn read(path: impl AsRef<Path>) -> Result<Option<StoredEmbedding>> {
+    let path = path.as_ref();
+    match std::fs::read(path) {
+        Ok(bytes) => parse(&bytes).with_context(|| format!("Invalid reference file {}", path.display())).map(Some),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Write a reference file, creating its directory
+pub fn write(path: impl AsRef<Path>, file: &EmbeddingFile) -> Result<()> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, file.to_bytes()).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// SHA-256 of a file (read in chunks: models are hundreds of MB)
+pub fn sha256_file(path: impl AsRef<Path>) -> Result<ModelHash> {
+    let path = path.as_ref();
+    let mut file = std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(hasher.finalize().into())
+}
+
+/// Lowercase hex of a hash
+pub fn hex(hash: &ModelHash) -> String {
+    hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// First 12 hex digits, for messages
+pub fn short_hex(hash: &ModelHash) -> String {
+    hex(hash)[..12].to_string()
+}
+
+/// Rewrite every legacy `.bin` file under `dir` (recursively) with a header
+/// naming `model_sha256`, keeping its modification time as the creation
+/// time. Returns the files migrated.
+pub fn migrate_dir(dir: impl AsRef<Path>, model_sha256: &ModelHash) -> Result<Vec<PathBuf>> {
+    let mut migrated = Vec::new();
+    let mut pending = vec![dir.as_ref().to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "bin") {
+                // Model files (e.g. Whisper GGML) also end in .bin; only
+                // headerless files of the legacy size are references
+                let legacy_size = std::fs::metadata(&path).is_ok_and(|m| m.len() == (LEGACY_DIMENSION * 4) as u64);
+                if !legacy_size {
+                    continue;
+                }
+                if let Ok(Some(StoredEmbedding::Legacy(embedding))) = read(&path) {
+                    let mut file = EmbeddingFile::new(embedding, *model_sha256);
+                    if let Ok(modified) = std::fs::metadata(&path).and_then(|m| m.modified()) {
+                        file.created_at = Utc.timestamp_opt(DateTime::<Utc>::from(modified).timestamp(), 0).unwrap();
+                    }
+                    write(&path, &file)?;
+                    migrated.push(path);
+                }
+            }
+        }
+    }
+    migrated.sort();
+    Ok(migrated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> EmbeddingFile {
+        EmbeddingFile::new((0..768).map(|i| i as f32 / 768.0).collect(), [7; 32])
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let file = sample();
+        let bytes = file.to_bytes();
+        assert_eq!(bytes.len(), HEADER_LEN + 768 * 4);
+        assert_eq!(&bytes[..4], b"PCEM");
+        assert_eq!(parse(&bytes).unwrap(), StoredEmbedding::Current(file));
+    }
+
+    #[test]
+    fn test_other_dimensions_are_described_by_the_header() {
+        let file = EmbeddingFile::new(vec![0.5; 1024], [1; 32]);
+        assert_eq!(parse(&file.to_bytes()).unwrap().embedding().len(), 1024);
+    }
+
+    #[test]
+    fn test_legacy_file() {
+        let embedding = vec![0.25f32; 768];
+        let bytes: Vec<u8> = embedding.iter().flat_map(|f| f.to_le_bytes()).collect();
+        let stored = parse(&bytes).unwrap();
+        assert_eq!(stored, StoredEmbedding::Legacy(embedding));
+        assert_eq!(stored.model_sha256(), None);
+        assert!(stored.verify_model(&[7; 32]).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_damaged_files() {
+        let bytes = sample().to_bytes();
+        let err = |bytes: &[u8]| format!("{:#}", parse(bytes).unwrap_err());
+
+        // Truncated (e.g. a copy cut short)
+        assert!(err(&bytes[..bytes.len() - 4]).contains("describes 768 dimensions"));
+        assert!(err(&bytes[..40]).contains("Truncated"));
+
+        // Flipped bit in a value
+        let mut corrupted = bytes.clone();
+        corrupted[HEADER_LEN + 10] ^= 1;
+        assert!(err(&corrupted).contains("checksum"));
+
+        let mut future = bytes.clone();
+        future[4] = 9;
+        assert!(err(&future).contains("version 9"));
+
+        // Wrong-size headerless file
+        assert!(err(&[0u8; 40]).contains("Not a reference embedding"));
+    }
+
+    #[test]
+    fn test_verify_model() {
+        let stored = parse(&sample().to_bytes()).unwrap();
+        assert!(stored.verify_model(&[7; 32]).is_ok());
+        let err = stored.verify_model(&[8; 32]).unwrap_err().to_string();
+        assert!(err.contains("070707070707"), "{}", err);
+        assert!(err.contains("080808080808"), "{}", err);
+    }
+
+    #[test]
+    fn test_sha256_file() {
+        let path = std::env::temp_dir().join(format!("phonecheck_sha_{}", std::process::id()));
+        std::fs::write(&path, b"abc").unwrap();
+        assert_eq!(
+            hex(&sha256_file(&path).unwrap()),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_migrate_dir() {
+        let dir = std::env::temp_dir().join(format!("phonecheck_migrate_{}", std::process::id()));
+        let nested = dir.join("versions/greeting/0001");
+        std::fs::create_dir_all(&nested).unwrap();
+        let legacy: Vec<u8> = [0.5f32; 768].iter().flat_map(|f| f.to_le_bytes()).collect();
+        std::fs::write(dir.join("reference_embedding.bin"), &legacy).unwrap();
+        std::fs::write(nested.join("embedding.bin"), &legacy).unwrap();
+        // Not references: a model file and an already migrated reference
+        std::fs::write(dir.join("ggml-tiny.bin"), b"ggml model").unwrap();
+        write(dir.join("reference_embedding_step1.bin"), &sample()).unwrap();
+
+        let migrated = migrate_dir(&dir, &[3; 32]).unwrap();
+        assert_eq!(migrated, vec![dir.join("reference_embedding.bin"), nested.join("embedding.bin")]);
+        let stored = read(dir.join("reference_embedding.bin")).unwrap().unwrap();
+        assert_eq!(stored.model_sha256(), Some(&[3; 32]));
+        assert_eq!(stored.embedding(), &[0.5; 768][..]);
+        assert_eq!(std::fs::read(dir.join("ggml-tiny.bin")).unwrap(), b"ggml model");
+        assert_eq!(read(dir.join("reference_embedding_step1.bin")).unwrap().unwrap().model_sha256(), Some(&[7; 32]));
+
+        // Nothing left to do
+        assert!(migrate_dir(&dir, &[3; 32]).unwrap().is_empty());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_read_missing_file() {
+        assert_eq!(read("/nonexistent/reference_embedding.bin").unwrap(), None);
+    }
+}
@@ -0,0 +1,356 @@
+//! Acoustic fingerprints by landmark hashing
+//!
+//! A model-free way to recognise a recording, in the style of Shazam: the
+//! spectrogram of the 8 kHz telephone audio is reduced to its most prominent
+//! peaks, and each peak is paired with a few peaks shortly after it. A pair
+//! is hashed as (frequency, frequency, time difference), which survives
+//! G.711, level changes and line noise far better than the raw spectrum.
+//!
+//! The same greeting played again shares many landmarks with the reference,
+//! all at one time offset (where the greeting starts in the capture);
+//! different audio only shares chance collisions scattered over many offsets.
+//! The score is the fraction of the reference's landmarks found at the best
+//! offset, so it doesn't depend on how long the capture is.
+
+use std::collections::HashMap;
+
+use crate::rtp::resample::resample;
+
+/// Sample rate fingerprints are computed at (the telephone band)
+pub const SAMPLE_RATE: u32 = 8000;
+
+/// Default fraction of the reference's landmarks a capture must share
+pub const DEFAULT_FINGERPRINT_THRESHOLD: f32 = 0.25;
+
+/// FFT window (64 ms, 15.6 Hz bins)
+const WINDOW: usize = 512;
+
+/// Hop between spectrogram frames
+const HOP: usize = 128;
+
+/// Duration of one spectrogram frame hop
+pub const HOP_MS: u64 = HOP as u64 * 1000 / SAMPLE_RATE as u64;
+
+/// Spectrum bins considered: 300-3400 Hz, the band a phone line carries
+const MIN_BIN: usize = 300 * WINDOW / SAMPLE_RATE as usize;
+const MAX_BIN: usize = 3400 * WINDOW / SAMPLE_RATE as usize;
+
+/// A peak is the loudest point within this many bins and frames
+const PEAK_BINS: usize = 10;
+const PEAK_FRAMES: usize = 4;
+
+/// A peak must stand this far (natural log of magnitude) above its frame's
+/// mean, so noise floors and flat spectra produce no peaks
+const PEAK_PROMINENCE: f32 = 1.5;
+
+/// Frames quieter than this RMS (about -54 dBFS) produce no peaks
+const SILENCE_RMS: f32 = 0.002;
+
+/// Peaks paired with each anchor, and how far after it they may be
+const FAN_OUT: usize = 5;
+const MAX_PAIR_FRAMES: usize = 40;
+const MAX_PAIR_BINS: usize = 64;
+
+/// Landmarks of one recording
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Fingerprint {
+    landmarks: Vec<Landmark>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Landmark {
+    hash: u32,
+    /// Spectrogram frame of the anchor peak
+    frame: u32,
+}
+
+/// How well a capture matched a reference fingerprint
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FingerprintMatch {
+    /// Fraction of the reference's landmarks found at `offset_frames`
+    pub score: f32,
+    /// Capture frame minus reference frame of the matching landmarks
+    /// (negative if the capture starts after the reference does)
+    pub offset_frames: i64,
+}
+
+impl FingerprintMatch {
+    /// Offset of the reference in the capture, if it starts inside it
+    pub fn offset_ms(&self) -> Option<u64> {
+        u64::try_from(self.offset_frames).ok().map(|frames| frames * HOP_MS)
+    }
+}
+
+impl Fingerprint {
+    /// Fingerprint mono audio at any sample rate
+    pub fn compute(audio: &[f32], sample_rate: u32) -> Self {
+        let audio = resample(audio, sample_rate, SAMPLE_RATE);
+        let peaks = find_peaks(&spectrogram(&audio));
+        Self { landmarks: pair_peaks(&peaks) }
+    }
+
+    /// Number of landmarks
+    pub fn len(&self) -> usize {
+        self.landmarks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.landmarks.is_empty()
+    }
+
+    /// Find this (reference) fingerprint in `capture`. `None` when the
+    /// reference has no landmarks to look for.
+    pub fn find_in(&self, capture: &Fingerprint) -> Option<FingerprintMatch> {
+        if self.landmarks.is_empty() {
+            return None;
+        }
+        let mut index: HashMap<u32, Vec<u32>> = HashMap::new();
+        for landmark in &self.landmarks {
+            index.entry(landmark.hash).or_default().push(landmark.frame);
+        }
+
+        let mut offsets: HashMap<i64, u32> = HashMap::new();
+        for landmark in &capture.landmarks {
+            for &frame in index.get(&landmark.hash).into_iter().flatten() {
+                *offsets.entry(landmark.frame as i64 - frame as i64).or_default() += 1;
+            }
+        }
+
+        // Peaks can move by a frame after coding and noise, so neighbouring
+        // offsets count towards each other
+        let count = |offset: i64| offsets.get(&offset).copied().unwrap_or(0);
+        let (offset_frames, matched) = offsets
+            .keys()
+            .map(|&offset| (offset, count(offset - 1) + count(offset) + count(offset + 1)))
+            .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+            .unwrap_or((0, 0));
+
+        Some(FingerprintMatch {
+            score: (matched as f32 / self.landmarks.len() as f32).min(1.0),
+            offset_frames,
+        })
+    }
+}
+
+/// Log-magnitude spectrogram over `MIN_BIN..=MAX_BIN`; silent frames are
+/// empty
+fn spectrogram(audio: &[f32]) -> Vec<Vec<f32>> {
+    if audio.len() < WINDOW {
+        return Vec::new();
+    }
+    let hann: Vec<f32> = (0..WINDOW)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / WINDOW as f32).cos())
+        .collect();
+    let mut re = vec![0.0; WINDOW];
+    let mut im = vec![0.0; WINDOW];
+
+    (0..=(audio.len() - WINDOW) / HOP)
+        .map(|frame| {
+            let samples = &audio[frame * HOP..frame * HOP + WINDOW];
+            let rms = (samples.iter().map(|s| s * s).sum::<f32>() / WINDOW as f32).sqrt();
+            if rms < SILENCE_RMS {
+                return Vec::new();
+            }
+            for (i, (&s, w)) in samples.iter().zip(&hann).enumerate() {
+                re[i] = s * w;
+                im[i] = 0.0;
+            }
+            fft(&mut re, &mut im);
+            (MIN_BIN..=MAX_BIN).map(|bin| (re[bin].hypot(im[bin]) + 1e-6).ln()).collect()
+        })
+        .collect()
+}
+
+/// In-place iterative radix-2 FFT (`re.len()` must be a power of two)
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * std::f32::consts::PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Spectral peaks as (frame, bin) in frame order
+fn find_peaks(spectrogram: &[Vec<f32>]) -> Vec<(usize, usize)> {
+    let mut peaks = Vec::new();
+    for (t, frame) in spectrogram.iter().enumerate() {
+        if frame.is_empty() {
+            continue;
+        }
+        let floor = frame.iter().sum::<f32>() / frame.len() as f32 + PEAK_PROMINENCE;
+        for (f, &value) in frame.iter().enumerate() {
+            if value < floor {
+                continue;
+            }
+            let bins = f.saturating_sub(PEAK_BINS)..(f + PEAK_BINS + 1).min(frame.len());
+            // Cheap test first: the loudest bin of its own frame
+            if frame[bins.clone()].iter().any(|&v| v > value) {
+                continue;
+            }
+            let frames = t.saturating_sub(PEAK_FRAMES)..(t + PEAK_FRAMES + 1).min(spectrogram.len());
+            let loudest = spectrogram[frames].iter().all(|other| {
+                other.is_empty() || other[bins.clone()].iter().all(|&v| v <= value)
+            });
+            // Equal neighbours (a flat top) keep only the first
+            let first = frame[bins.start..f].iter().all(|&v| v < value);
+            if loudest && first {
+                peaks.push((t, f));
+            }
+        }
+    }
+    peaks
+}
+
+/// Hash each peak with the next `FAN_OUT` peaks in its target zone
+fn pair_peaks(peaks: &[(usize, usize)]) -> Vec<Landmark> {
+    let mut landmarks = Vec::new();
+    for (i, &(t1, f1)) in peaks.iter().enumerate() {
+        let targets = peaks[i + 1..]
+            .iter()
+            .take_while(|&&(t2, _)| t2 - t1 <= MAX_PAIR_FRAMES)
+            .filter(|&&(t2, f2)| t2 > t1 && f1.abs_diff(f2) <= MAX_PAIR_BINS)
+            .take(FAN_OUT);
+        for &(t2, f2) in targets {
+            landmarks.push(Landmark {
+                hash: (f1 as u32) << 16 | (f2 as u32) << 8 | (t2 - t1) as u32,
+                frame: t1 as u32,
+            });
+        }
+    }
+    landmarks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtp::g711::{G711Codec, G711Decoder, G711Encoder};
+
+    /// A "greeting": two tones at a time, changing every 80 ms
+    fn melody(seed: u64, seconds: f32) -> Vec<f32> {
+        let mut state = (seed + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let note = (0.08 * SAMPLE_RATE as f32) as usize;
+        let notes = (seconds / 0.08) as usize;
+        let mut audio = Vec::with_capacity(notes * note);
+        for _ in 0..notes {
+            let a = 350.0 + (next() % 3000) as f32;
+            let b = 350.0 + (next() % 3000) as f32;
+            for i in 0..note {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                let phase = 2.0 * std::f32::consts::PI * t;
+                audio.push(0.2 * (phase * a).sin() + 0.15 * (phase * b).sin());
+            }
+        }
+        audio
+    }
+
+    fn noise(seed: u64, len: usize, level: f32) -> Vec<f32> {
+        let mut state = seed.wrapping_mul(0x2545_F491_4F6C_DD1D) | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                level * ((state % 2000) as f32 / 1000.0 - 1.0)
+            })
+            .collect()
+    }
+
+    /// Round-trip through G.711 u-law, as a call would
+    fn telephone(audio: &[f32]) -> Vec<f32> {
+        let pcm: Vec<i16> = audio.iter().map(|&s| (s * 32767.0).clamp(-32768.0, 32767.0) as i16).collect();
+        let encoded = G711Encoder::new(G711Codec::ULaw).encode(&pcm);
+        G711Decoder::pcm_to_f32(&G711Decoder::new(G711Codec::ULaw).decode(&encoded))
+    }
+
+    #[test]
+    fn test_fft_matches_direct_dft() {
+        let signal: Vec<f32> = (0..16).map(|i| ((i * 7 % 5) as f32 - 2.0) * 0.3).collect();
+        let (mut re, mut im) = (signal.clone(), vec![0.0; 16]);
+        fft(&mut re, &mut im);
+        for k in 0..16 {
+            let (mut dft_re, mut dft_im) = (0.0f32, 0.0f32);
+            for (n, &x) in signal.iter().enumerate() {
+                let angle = -2.0 * std::f32::consts::PI * (k * n) as f32 / 16.0;
+                dft_re += x * angle.cos();
+                dft_im += x * angle.sin();
+            }
+            assert!((re[k] - dft_re).abs() < 1e-4 && (im[k] - dft_im).abs() < 1e-4, "bin {}", k);
+        }
+    }
+
+    #[test]
+    fn test_finds_the_greeting_over_a_phone_line() {
+        let greeting = melody(1, 3.0);
+        let reference = Fingerprint::compute(&greeting, SAMPLE_RATE);
+        assert!(reference.len() > 100, "{} landmarks", reference.len());
+
+        // A second of line noise, the greeting, then other audio
+        let mut capture = noise(7, SAMPLE_RATE as usize, 0.01);
+        capture.extend(greeting.iter().zip(noise(8, greeting.len(), 0.01)).map(|(s, n)| s + n));
+        capture.extend(melody(2, 2.0));
+        let found = reference.find_in(&Fingerprint::compute(&telephone(&capture), SAMPLE_RATE)).unwrap();
+
+        assert!(found.score > 0.4, "{:?}", found);
+        assert!(found.offset_ms().unwrap().abs_diff(1000) <= HOP_MS, "{:?}", found);
+    }
+
+    #[test]
+    fn test_different_audio_scores_low() {
+        let reference = Fingerprint::compute(&melody(1, 3.0), SAMPLE_RATE);
+        let other = Fingerprint::compute(&melody(3, 5.0), SAMPLE_RATE);
+        let found = reference.find_in(&other).unwrap();
+        assert!(found.score < 0.05, "{:?}", found);
+    }
+
+    #[test]
+    fn test_resamples_to_the_telephone_band() {
+        let greeting = melody(4, 2.0);
+        let reference = Fingerprint::compute(&greeting, SAMPLE_RATE);
+        let wideband = Fingerprint::compute(&resample(&greeting, SAMPLE_RATE, 16000), 16000);
+        assert!(reference.find_in(&wideband).unwrap().score > 0.5);
+    }
+
+    #[test]
+    fn test_silence_has_no_landmarks() {
+        let silence = Fingerprint::compute(&vec![0.0; SAMPLE_RATE as usize], SAMPLE_RATE);
+        assert!(silence.is_empty());
+        assert_eq!(silence.find_in(&Fingerprint::compute(&melody(1, 1.0), SAMPLE_RATE)), None);
+
+        let reference = Fingerprint::compute(&melody(1, 1.0), SAMPLE_RATE);
+        assert_eq!(reference.find_in(&silence).unwrap().score, 0.0);
+        assert!(Fingerprint::compute(&[0.1; 100], SAMPLE_RATE).is_empty());
+    }
+}
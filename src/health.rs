@@ -1,9 +1,10 @@
 //! Health check HTTP endpoint
 //! Provides a simple /health endpoint for monitoring systems (Kubernetes, load balancers, etc.)
 
+use std::collections::BTreeMap;
 use std::net::SocketAddr;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpListener;
@@ -11,6 +12,10 @@ use tokio::time::timeout;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 
+use crate::history::{CheckRecord, HistoryQuery, HistoryStore, ReferenceChange, ReferenceChangeKind};
+use crate::reference::ReferenceStore;
+use crate::rtp::emodel::CallQuality;
+
 /// Timeout for reading HTTP request (prevents slow-loris attacks)
 const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
 
@@ -38,6 +43,22 @@ impl Default for HealthStatus {
     }
 }
 
+/// Outcomes of individual calls (a check re-dials to confirm a failure, so
+/// it may make several)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AttemptCounts {
+    pub successful: u64,
+    pub failed: u64,
+}
+
+/// How often one of a target's accepted references matched
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReferenceMatches {
+    pub count: u64,
+    /// Similarity of the most recent match
+    pub last_similarity: f32,
+}
+
 /// Shared health metrics that can be updated from the check loop
 #[derive(Debug)]
 pub struct HealthMetrics {
@@ -45,6 +66,21 @@ pub struct HealthMetrics {
     checks_failed: AtomicU64,
     last_check_time: AtomicU64,
     last_check_ok: std::sync::atomic::AtomicBool,
+    /// Per-target status, keyed by target name (for multi-target checks files)
+    targets: Mutex<BTreeMap<String, HealthStatus>>,
+    /// Per-target call attempt outcomes
+    attempts: Mutex<BTreeMap<String, AttemptCounts>>,
+    /// Matches per (target, reference name)
+    reference_matches: Mutex<BTreeMap<(String, String), ReferenceMatches>>,
+    /// E-model rating of the last call to each target that received audio
+    call_quality: Mutex<BTreeMap<String, CallQuality>>,
+    /// Persistent record of every check (served on /history)
+    history: Option<HistoryStore>,
+    /// Reference directory of each target, for approving candidates over HTTP
+    reference_dirs: BTreeMap<String, String>,
+    /// Bearer token required to approve over HTTP (approval is disabled
+    /// without one)
+    approve_token: Option<String>,
 }
 
 impl Default for HealthMetrics {
@@ -54,6 +90,13 @@ impl Default for HealthMetrics {
             checks_failed: AtomicU64::new(0),
             last_check_time: AtomicU64::new(0),
             last_check_ok: std::sync::atomic::AtomicBool::new(true), // Assume healthy until proven otherwise
+            targets: Mutex::new(BTreeMap::new()),
+            attempts: Mutex::new(BTreeMap::new()),
+            reference_matches: Mutex::new(BTreeMap::new()),
+            call_quality: Mutex::new(BTreeMap::new()),
+            history: None,
+            reference_dirs: BTreeMap::new(),
+            approve_token: None,
         }
     }
 }
@@ -63,6 +106,55 @@ impl HealthMetrics {
         Self::default()
     }
 
+    /// Metrics that also persist every check to a history store
+    pub fn with_history(history: HistoryStore) -> Self {
+        Self { history: Some(history), ..Self::default() }
+    }
+
+    /// Allow candidates of these targets' references (name, reference
+    /// directory) to be approved on /references/approve
+    pub fn with_reference_dirs(mut self, dirs: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.reference_dirs = dirs.into_iter().collect();
+        self
+    }
+
+    /// Require `Authorization: Bearer <token>` on /references/approve
+    /// (`None` leaves the route disabled)
+    pub fn with_approve_token(mut self, token: Option<String>) -> Self {
+        self.approve_token = token;
+        self
+    }
+
+    /// The history store, if one is configured
+    pub fn history(&self) -> Option<&HistoryStore> {
+        self.history.as_ref()
+    }
+
+    /// Append a check to the history (no-op without a store). Returns the
+    /// record's id.
+    pub fn record_check(&self, record: CheckRecord) -> Option<u64> {
+        let history = self.history.as_ref()?;
+        match history.append(record) {
+            Ok(id) => Some(id),
+            Err(e) => {
+                warn!("Failed to record check history: {:#}", e);
+                None
+            }
+        }
+    }
+
+    /// Append a reference change to the history (no-op without a store)
+    pub fn record_reference_change(&self, change: ReferenceChange) -> Option<u64> {
+        let history = self.history.as_ref()?;
+        match history.append_reference_change(change) {
+            Ok(id) => Some(id),
+            Err(e) => {
+                warn!("Failed to record reference change: {:#}", e);
+                None
+            }
+        }
+    }
+
     /// Record a successful check
     pub fn record_success(&self) {
         self.checks_successful.fetch_add(1, Ordering::Relaxed);
@@ -89,6 +181,79 @@ impl HealthMetrics {
         self.last_check_ok.store(false, Ordering::Relaxed);
     }
 
+    /// Record a successful check of a named target (also counted in the totals)
+    pub fn record_success_for(&self, target: &str) {
+        self.record_success();
+        self.record_target(target, true);
+    }
+
+    /// Record a failed check of a named target (also counted in the totals)
+    pub fn record_failure_for(&self, target: &str) {
+        self.record_failure();
+        self.record_target(target, false);
+    }
+
+    /// Record the outcome of one call attempt of a named target
+    pub fn record_attempt_for(&self, target: &str, ok: bool) {
+        let mut attempts = self.attempts.lock().unwrap_or_else(|e| e.into_inner());
+        let counts = attempts.entry(target.to_string()).or_default();
+        if ok {
+            counts.successful += 1;
+        } else {
+            counts.failed += 1;
+        }
+    }
+
+    /// Get the call attempt outcomes of every target, sorted by name
+    pub fn attempt_counts(&self) -> Vec<(String, AttemptCounts)> {
+        let attempts = self.attempts.lock().unwrap_or_else(|e| e.into_inner());
+        attempts
+            .iter()
+            .map(|(name, counts)| (name.clone(), counts.clone()))
+            .collect()
+    }
+
+    /// Record that a check of `target` matched the reference `reference`
+    pub fn record_reference_match(&self, target: &str, reference: &str, similarity: f32) {
+        let mut matches = self.reference_matches.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = matches.entry((target.to_string(), reference.to_string())).or_default();
+        entry.count += 1;
+        entry.last_similarity = similarity;
+    }
+
+    /// Get the matches of every (target, reference), sorted by name
+    pub fn reference_matches(&self) -> Vec<(String, String, ReferenceMatches)> {
+        let matches = self.reference_matches.lock().unwrap_or_else(|e| e.into_inner());
+        matches
+            .iter()
+            .map(|((target, reference), m)| (target.clone(), reference.clone(), m.clone()))
+            .collect()
+    }
+
+    /// Record the E-model rating of a call to `target`
+    pub fn record_call_quality(&self, target: &str, quality: CallQuality) {
+        let mut qualities = self.call_quality.lock().unwrap_or_else(|e| e.into_inner());
+        qualities.insert(target.to_string(), quality);
+    }
+
+    /// Get the rating of the last call to every target, sorted by name
+    pub fn call_qualities(&self) -> Vec<(String, CallQuality)> {
+        let qualities = self.call_quality.lock().unwrap_or_else(|e| e.into_inner());
+        qualities.iter().map(|(name, q)| (name.clone(), *q)).collect()
+    }
+
+    fn record_target(&self, target: &str, ok: bool) {
+        let mut targets = self.targets.lock().unwrap_or_else(|e| e.into_inner());
+        let status = targets.entry(target.to_string()).or_default();
+        if ok {
+            status.checks_successful += 1;
+        } else {
+            status.checks_failed += 1;
+        }
+        status.last_check_time = self.last_check_time.load(Ordering::Relaxed);
+        status.last_check_ok = ok;
+    }
+
     /// Get current health status
     pub fn status(&self) -> HealthStatus {
         HealthStatus {
@@ -98,6 +263,21 @@ impl HealthMetrics {
             last_check_ok: self.last_check_ok.load(Ordering::Relaxed),
         }
     }
+
+    /// Get the health status of a single target (default if never checked)
+    pub fn target_status(&self, target: &str) -> HealthStatus {
+        let targets = self.targets.lock().unwrap_or_else(|e| e.into_inner());
+        targets.get(target).cloned().unwrap_or_default()
+    }
+
+    /// Get the health status of every target checked so far, sorted by name
+    pub fn target_statuses(&self) -> Vec<(String, HealthStatus)> {
+        let targets = self.targets.lock().unwrap_or_else(|e| e.into_inner());
+        targets
+            .iter()
+            .map(|(name, status)| (name.clone(), status.clone()))
+            .collect()
+    }
 }
 
 /// Run the health check HTTP server
@@ -164,17 +344,23 @@ async fn handle_request(
 
     let request = String::from_utf8_lossy(&buf[..n]);
 
-    // Parse the request line to get the path
-    let path = request
+    // Parse the request line to get the method, path and query string
+    let mut request_line = request.lines().next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or("GET");
+    let target = request_line.next().unwrap_or("/");
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let authorization = request
         .lines()
-        .next()
-        .and_then(|line| line.split_whitespace().nth(1))
-        .unwrap_or("/");
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("authorization"))
+        .map(|(_, value)| value.trim());
 
     let response = match path {
         "/health" | "/healthz" | "/health/" => {
             let status = metrics.status();
-            build_health_response(&status)
+            build_health_response(&status, &metrics.target_statuses())
         }
         "/ready" | "/readyz" | "/ready/" => {
             // Readiness logic for Kubernetes compatibility:
@@ -182,8 +368,15 @@ async fn handle_request(
             //   This prevents pods from being killed before the first check completes.
             // - After first check: ready = last_check_ok (based on actual check results)
             // Note: For stricter behavior, use /health which always returns 200 with status.
+            // With several targets, every target's last check must have succeeded.
             let status = metrics.status();
-            if status.last_check_ok || status.last_check_time == 0 {
+            let targets = metrics.target_statuses();
+            let ready = if targets.is_empty() {
+                status.last_check_ok || status.last_check_time == 0
+            } else {
+                targets.iter().all(|(_, t)| t.last_check_ok)
+            };
+            if ready {
                 build_ready_response(true)
             } else {
                 build_ready_response(false)
@@ -191,7 +384,19 @@ async fn handle_request(
         }
         "/metrics" => {
             let status = metrics.status();
-            build_metrics_response(&status)
+            build_metrics_response(
+                &status,
+                &metrics.target_statuses(),
+                &metrics.attempt_counts(),
+                &metrics.reference_matches(),
+                &metrics.call_qualities(),
+            )
+        }
+        "/history" | "/history/" => build_history_response(metrics.history(), query),
+        "/history/references" => build_reference_history_response(metrics.history(), query),
+        "/references/approve" => build_approve_response(metrics, method, query, authorization),
+        _ if path.starts_with("/history/") => {
+            build_history_record_response(metrics.history(), &path["/history/".len()..])
         }
         _ => build_not_found_response(),
     };
@@ -202,14 +407,28 @@ async fn handle_request(
     Ok(())
 }
 
-fn build_health_response(status: &HealthStatus) -> String {
-    let body = format!(
-        r#"{{"status":"healthy","checks_successful":{},"checks_failed":{},"last_check_time":{},"last_check_ok":{}}}"#,
+fn build_health_response(status: &HealthStatus, targets: &[(String, HealthStatus)]) -> String {
+    let mut body = format!(
+        r#"{{"status":"healthy","checks_successful":{},"checks_failed":{},"last_check_time":{},"last_check_ok":{}"#,
         status.checks_successful,
         status.checks_failed,
         status.last_check_time,
         status.last_check_ok
     );
+    if !targets.is_empty() {
+        // Target names are restricted to [A-Za-z0-9_-], so no JSON escaping is needed
+        let entries: Vec<String> = targets
+            .iter()
+            .map(|(name, t)| {
+                format!(
+                    r#""{}":{{"checks_successful":{},"checks_failed":{},"last_check_time":{},"last_check_ok":{}}}"#,
+                    name, t.checks_successful, t.checks_failed, t.last_check_time, t.last_check_ok
+                )
+            })
+            .collect();
+        body.push_str(&format!(r#","targets":{{{}}}"#, entries.join(",")));
+    }
+    body.push('}');
 
     format!(
         "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
@@ -234,9 +453,15 @@ fn build_ready_response(ready: bool) -> String {
     )
 }
 
-fn build_metrics_response(status: &HealthStatus) -> String {
+fn build_metrics_response(
+    status: &HealthStatus,
+    targets: &[(String, HealthStatus)],
+    attempts: &[(String, AttemptCounts)],
+    references: &[(String, String, ReferenceMatches)],
+    qualities: &[(String, CallQuality)],
+) -> String {
     // Prometheus-compatible metrics format
-    let body = format!(
+    let mut body = format!(
         "# HELP phonecheck_checks_total Total number of checks performed\n\
          # TYPE phonecheck_checks_total counter\n\
          phonecheck_checks_total{{result=\"success\"}} {}\n\
@@ -253,6 +478,95 @@ fn build_metrics_response(status: &HealthStatus) -> String {
         if status.last_check_ok { 1 } else { 0 }
     );
 
+    if !targets.is_empty() {
+        body.push_str(
+            "# HELP phonecheck_target_checks_total Number of checks performed per target\n\
+             # TYPE phonecheck_target_checks_total counter\n",
+        );
+        for (name, t) in targets {
+            body.push_str(&format!(
+                "phonecheck_target_checks_total{{target=\"{name}\",result=\"success\"}} {}\n\
+                 phonecheck_target_checks_total{{target=\"{name}\",result=\"failure\"}} {}\n",
+                t.checks_successful, t.checks_failed
+            ));
+        }
+        body.push_str(
+            "# HELP phonecheck_target_last_check_timestamp Unix timestamp of the last check per target\n\
+             # TYPE phonecheck_target_last_check_timestamp gauge\n",
+        );
+        for (name, t) in targets {
+            body.push_str(&format!(
+                "phonecheck_target_last_check_timestamp{{target=\"{}\"}} {}\n",
+                name, t.last_check_time
+            ));
+        }
+        body.push_str(
+            "# HELP phonecheck_target_last_check_ok Whether the last check of each target succeeded (1) or failed (0)\n\
+             # TYPE phonecheck_target_last_check_ok gauge\n",
+        );
+        for (name, t) in targets {
+            body.push_str(&format!(
+                "phonecheck_target_last_check_ok{{target=\"{}\"}} {}\n",
+                name,
+                if t.last_check_ok { 1 } else { 0 }
+            ));
+        }
+    }
+
+    if !attempts.is_empty() {
+        body.push_str(
+            "# HELP phonecheck_attempts_total Number of calls made per target, including confirmation re-dials\n\
+             # TYPE phonecheck_attempts_total counter\n",
+        );
+        for (name, a) in attempts {
+            body.push_str(&format!(
+                "phonecheck_attempts_total{{target=\"{name}\",result=\"success\"}} {}\n\
+                 phonecheck_attempts_total{{target=\"{name}\",result=\"failure\"}} {}\n",
+                a.successful, a.failed
+            ));
+        }
+    }
+
+    if !references.is_empty() {
+        body.push_str(
+            "# HELP phonecheck_reference_matches_total Number of checks each accepted reference matched\n\
+             # TYPE phonecheck_reference_matches_total counter\n",
+        );
+        for (target, reference, m) in references {
+            body.push_str(&format!(
+                "phonecheck_reference_matches_total{{target=\"{}\",reference=\"{}\"}} {}\n",
+                target, reference, m.count
+            ));
+        }
+        body.push_str(
+            "# HELP phonecheck_reference_similarity Embedding similarity of the last match of each reference\n\
+             # TYPE phonecheck_reference_similarity gauge\n",
+        );
+        for (target, reference, m) in references {
+            body.push_str(&format!(
+                "phonecheck_reference_similarity{{target=\"{}\",reference=\"{}\"}} {:.4}\n",
+                target, reference, m.last_similarity
+            ));
+        }
+    }
+
+    if !qualities.is_empty() {
+        body.push_str(
+            "# HELP phonecheck_call_mos Estimated MOS (ITU-T G.107 E-model) of the last call to each target\n\
+             # TYPE phonecheck_call_mos gauge\n",
+        );
+        for (target, q) in qualities {
+            body.push_str(&format!("phonecheck_call_mos{{target=\"{}\"}} {:.2}\n", target, q.mos));
+        }
+        body.push_str(
+            "# HELP phonecheck_call_r_factor E-model transmission rating of the last call to each target\n\
+             # TYPE phonecheck_call_r_factor gauge\n",
+        );
+        for (target, q) in qualities {
+            body.push_str(&format!("phonecheck_call_r_factor{{target=\"{}\"}} {:.1}\n", target, q.r_factor));
+        }
+    }
+
     format!(
         "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
         body.len(),
@@ -260,6 +574,146 @@ fn build_metrics_response(status: &HealthStatus) -> String {
     )
 }
 
+fn build_history_response(history: Option<&HistoryStore>, query: &str) -> String {
+    let Some(history) = history else {
+        return build_not_found_response();
+    };
+    match HistoryQuery::parse(query) {
+        Ok(query) => {
+            let body = serde_json::json!({ "records": history.query(&query) });
+            build_json_response(200, "OK", &body.to_string())
+        }
+        Err(e) => {
+            let body = serde_json::json!({ "error": format!("{:#}", e) });
+            build_json_response(400, "Bad Request", &body.to_string())
+        }
+    }
+}
+
+fn build_history_record_response(history: Option<&HistoryStore>, id: &str) -> String {
+    let record = id.parse().ok().and_then(|id| history?.get(id));
+    match record.and_then(|r| serde_json::to_string(&r).ok()) {
+        Some(body) => build_json_response(200, "OK", &body),
+        None => build_not_found_response(),
+    }
+}
+
+fn build_reference_history_response(history: Option<&HistoryStore>, query: &str) -> String {
+    let Some(history) = history else {
+        return build_not_found_response();
+    };
+    match HistoryQuery::parse(query) {
+        Ok(query) => {
+            let body = serde_json::json!({ "changes": history.reference_changes(&query) });
+            build_json_response(200, "OK", &body.to_string())
+        }
+        Err(e) => {
+            let body = serde_json::json!({ "error": format!("{:#}", e) });
+            build_json_response(400, "Bad Request", &body.to_string())
+        }
+    }
+}
+
+/// Approve the candidate reference of `target=<name>` (the only target by
+/// default) and, with `step=N` or `reference=<name>`, an IVR capture step or
+/// a named greeting. Needs the configured token as a bearer token in
+/// `authorization`.
+fn build_approve_response(metrics: &HealthMetrics, method: &str, query: &str, authorization: Option<&str>) -> String {
+    if method != "POST" {
+        let body = serde_json::json!({ "error": "Use POST to approve a reference" });
+        return build_json_response(405, "Method Not Allowed", &body.to_string());
+    }
+    let Some(ref token) = metrics.approve_token else {
+        let body = serde_json::json!({ "error": "Approval over HTTP is disabled (set APPROVE_TOKEN)" });
+        return build_json_response(403, "Forbidden", &body.to_string());
+    };
+    let presented = authorization.and_then(|value| value.strip_prefix("Bearer ")).map(str::trim);
+    if !presented.is_some_and(|presented| tokens_match(presented, token)) {
+        let body = r#"{"error":"Unauthorized"}"#;
+        return format!(
+            "HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Bearer\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+    }
+
+    let mut target = None;
+    let mut step = None;
+    let mut reference = None;
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        match pair.split_once('=').unwrap_or((pair, "")) {
+            ("target", value) => target = Some(value),
+            ("reference", value) => {
+                if value.is_empty() || !value.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                    let body = serde_json::json!({ "error": format!("Invalid reference '{}'", value) });
+                    return build_json_response(400, "Bad Request", &body.to_string());
+                }
+                reference = Some(value);
+            }
+            ("step", value) => match value.parse::<usize>() {
+                Ok(n) if n > 0 => step = Some(n),
+                _ => {
+                    let body = serde_json::json!({ "error": format!("Invalid step '{}'", value) });
+                    return build_json_response(400, "Bad Request", &body.to_string());
+                }
+            },
+            _ => {}
+        }
+    }
+    if step.is_some() && reference.is_some() {
+        let body = serde_json::json!({ "error": "Give either step or reference, not both" });
+        return build_json_response(400, "Bad Request", &body.to_string());
+    }
+
+    let dir = match target {
+        Some(name) => metrics.reference_dirs.get_key_value(name),
+        None if metrics.reference_dirs.len() == 1 => metrics.reference_dirs.iter().next(),
+        None => {
+            let body = serde_json::json!({ "error": "Name the target to approve (target=<name>)" });
+            return build_json_response(400, "Bad Request", &body.to_string());
+        }
+    };
+    let Some((name, dir)) = dir else {
+        return build_not_found_response();
+    };
+
+    let store = match reference {
+        Some(reference) => ReferenceStore::named(dir, reference),
+        None => ReferenceStore::new(dir, step),
+    };
+    match store.approve() {
+        Ok(meta) => {
+            info!("Approved reference {} version {} of target '{}' over HTTP", store.slot(), meta.version, name);
+            let mut change = ReferenceChange::new(name, store.slot(), ReferenceChangeKind::Approved, "http");
+            change.version = Some(meta.version);
+            change.transcript = meta.transcript.clone();
+            metrics.record_reference_change(change);
+            let body = serde_json::json!({ "target": name, "reference": store.slot(), "approved": meta });
+            build_json_response(200, "OK", &body.to_string())
+        }
+        Err(e) => {
+            let body = serde_json::json!({ "error": format!("{:#}", e) });
+            build_json_response(409, "Conflict", &body.to_string())
+        }
+    }
+}
+
+/// Compare tokens in time independent of where they differ
+fn tokens_match(presented: &str, expected: &str) -> bool {
+    presented.len() == expected.len()
+        && presented.bytes().zip(expected.bytes()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn build_json_response(status_code: u16, status_text: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status_code,
+        status_text,
+        body.len(),
+        body
+    )
+}
+
 fn build_not_found_response() -> String {
     let body = r#"{"error":"Not Found"}"#;
     format!(
@@ -272,6 +726,7 @@ fn build_not_found_response() -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::rtp::plc::Concealment;
 
     #[test]
     fn test_health_metrics_default() {
@@ -331,7 +786,7 @@ mod tests {
             last_check_ok: true,
         };
 
-        let response = build_health_response(&status);
+        let response = build_health_response(&status, &[]);
         assert!(response.starts_with("HTTP/1.1 200 OK"));
         assert!(response.contains("application/json"));
         assert!(response.contains("\"checks_successful\":5"));
@@ -361,7 +816,7 @@ mod tests {
             last_check_ok: true,
         };
 
-        let response = build_metrics_response(&status);
+        let response = build_metrics_response(&status, &[], &[], &[], &[]);
         assert!(response.starts_with("HTTP/1.1 200 OK"));
         assert!(response.contains("text/plain"));
         assert!(response.contains("phonecheck_checks_total{result=\"success\"} 10"));
@@ -369,6 +824,223 @@ mod tests {
         assert!(response.contains("phonecheck_last_check_ok 1"));
     }
 
+    #[test]
+    fn test_health_metrics_per_target() {
+        let metrics = HealthMetrics::new();
+        metrics.record_success_for("main");
+        metrics.record_failure_for("support");
+        metrics.record_success_for("main");
+
+        let main = metrics.target_status("main");
+        assert_eq!(main.checks_successful, 2);
+        assert!(main.last_check_ok);
+
+        let support = metrics.target_status("support");
+        assert_eq!(support.checks_failed, 1);
+        assert!(!support.last_check_ok);
+
+        // Unknown targets report the optimistic default
+        assert!(metrics.target_status("after-hours").last_check_ok);
+
+        // Totals include every target
+        let status = metrics.status();
+        assert_eq!(status.checks_successful, 2);
+        assert_eq!(status.checks_failed, 1);
+
+        let names: Vec<String> = metrics.target_statuses().into_iter().map(|(n, _)| n).collect();
+        assert_eq!(names, vec!["main", "support"]);
+    }
+
+    #[test]
+    fn test_health_metrics_attempts() {
+        let metrics = HealthMetrics::new();
+        metrics.record_attempt_for("main", false);
+        metrics.record_attempt_for("main", true);
+        metrics.record_success_for("main");
+
+        assert_eq!(
+            metrics.attempt_counts(),
+            vec![("main".to_string(), AttemptCounts { successful: 1, failed: 1 })]
+        );
+        // Attempts are not counted as checks
+        assert_eq!(metrics.status().checks_failed, 0);
+
+        metrics.record_reference_match("main", "day", 0.88);
+        metrics.record_reference_match("main", "day", 0.93);
+        assert_eq!(
+            metrics.reference_matches(),
+            vec![("main".to_string(), "day".to_string(), ReferenceMatches { count: 2, last_similarity: 0.93 })]
+        );
+
+        let quality = CallQuality::estimate(0.0, 0.0, None, Concealment::Zero);
+        metrics.record_call_quality("main", CallQuality::estimate(0.1, 0.0, None, Concealment::Zero));
+        metrics.record_call_quality("main", quality);
+        assert_eq!(metrics.call_qualities(), vec![("main".to_string(), quality)]);
+    }
+
+    #[test]
+    fn test_build_responses_with_targets() {
+        let status = HealthStatus {
+            checks_successful: 3,
+            checks_failed: 1,
+            last_check_time: 1234567890,
+            last_check_ok: false,
+        };
+        let main = HealthStatus {
+            checks_successful: 3,
+            last_check_time: 1234567000,
+            ..Default::default()
+        };
+        let support = HealthStatus {
+            checks_failed: 1,
+            last_check_time: 1234567890,
+            last_check_ok: false,
+            ..Default::default()
+        };
+        let targets = vec![("main".to_string(), main), ("support".to_string(), support)];
+
+        let attempts = vec![("support".to_string(), AttemptCounts { successful: 1, failed: 2 })];
+
+        let references = vec![(
+            "main".to_string(),
+            "night".to_string(),
+            ReferenceMatches { count: 4, last_similarity: 0.91 },
+        )];
+
+        let qualities = vec![("main".to_string(), CallQuality { r_factor: 75.3, mos: 3.84 })];
+
+        let response = build_metrics_response(&status, &targets, &attempts, &references, &qualities);
+        assert!(response.contains("phonecheck_call_mos{target=\"main\"} 3.84"));
+        assert!(response.contains("phonecheck_call_r_factor{target=\"main\"} 75.3"));
+        assert!(response.contains("phonecheck_reference_matches_total{target=\"main\",reference=\"night\"} 4"));
+        assert!(response.contains("phonecheck_reference_similarity{target=\"main\",reference=\"night\"} 0.9100"));
+        assert!(response.contains("phonecheck_checks_total{result=\"success\"} 3"));
+        assert!(response.contains("phonecheck_attempts_total{target=\"support\",result=\"failure\"} 2"));
+        assert!(response.contains("phonecheck_target_checks_total{target=\"main\",result=\"success\"} 3"));
+        assert!(response.contains("phonecheck_target_checks_total{target=\"support\",result=\"failure\"} 1"));
+        assert!(response.contains("phonecheck_target_last_check_ok{target=\"main\"} 1"));
+        assert!(response.contains("phonecheck_target_last_check_ok{target=\"support\"} 0"));
+
+        let response = build_health_response(&status, &targets);
+        let body = response.split("\r\n\r\n").nth(1).unwrap();
+        let json: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(json["targets"]["main"]["checks_successful"], 3);
+        assert_eq!(json["targets"]["support"]["last_check_ok"], false);
+    }
+
+    #[test]
+    fn test_build_history_responses() {
+        use crate::history::AttemptRecord;
+
+        let path = std::env::temp_dir().join(format!("phonecheck_health_history_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let store = HistoryStore::open(&path, 1 << 20, 0).unwrap();
+        let record = CheckRecord {
+            id: 0,
+            timestamp: chrono::Utc::now(),
+            target: "main".to_string(),
+            ok: true,
+            message: None,
+            alert: "none".to_string(),
+            attempts: vec![AttemptRecord { ok: true, sip_status: Some(200), ..Default::default() }],
+        };
+        store.append(record.clone()).unwrap();
+        store.append(CheckRecord { target: "support".to_string(), ..record }).unwrap();
+
+        let response = build_history_response(Some(&store), "target=support");
+        let body = response.split("\r\n\r\n").nth(1).unwrap();
+        let json: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(json["records"].as_array().unwrap().len(), 1);
+        assert_eq!(json["records"][0]["id"], 2);
+
+        let response = build_history_record_response(Some(&store), "1");
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains(r#""sip_status":200"#));
+
+        assert!(build_history_record_response(Some(&store), "7").starts_with("HTTP/1.1 404"));
+        assert!(build_history_record_response(Some(&store), "abc").starts_with("HTTP/1.1 404"));
+        assert!(build_history_response(Some(&store), "since=never").starts_with("HTTP/1.1 400"));
+        assert!(build_history_response(None, "").starts_with("HTTP/1.1 404"));
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_approve_reference_over_http() {
+        use crate::reference::{Enrollment, ReferenceSource};
+
+        let dir = std::env::temp_dir().join(format!("phonecheck_health_approve_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let dir_str = dir.to_str().unwrap().to_string();
+        let history = HistoryStore::open(dir.join("history.jsonl"), 1 << 20, 0).unwrap();
+        let metrics = HealthMetrics::with_history(history)
+            .with_reference_dirs([("main".to_string(), dir_str.clone()), ("support".to_string(), dir_str.clone())])
+            .with_approve_token(Some("s3cret".to_string()));
+        let auth = Some("Bearer s3cret");
+
+        let store = ReferenceStore::new(&dir_str, None);
+        store
+            .propose(Enrollment {
+                embedding: &[0.5; 768],
+                audio: &[0.0; 1600],
+                transcript: Some("thank you for calling".to_string()),
+                source: ReferenceSource::Check,
+                embedder: String::new(),
+                whisper: String::new(),
+                model_sha256: [7; 32],
+            })
+            .unwrap();
+
+        assert!(build_approve_response(&metrics, "GET", "target=main", auth).starts_with("HTTP/1.1 405"));
+        // Without the token, or with the wrong one, nothing is approved
+        assert!(build_approve_response(&metrics, "POST", "target=main", None).starts_with("HTTP/1.1 401"));
+        assert!(build_approve_response(&metrics, "POST", "target=main", Some("Bearer s3cre")).starts_with("HTTP/1.1 401"));
+        assert!(build_approve_response(&metrics, "POST", "target=main", Some("s3cret")).starts_with("HTTP/1.1 401"));
+        let disabled = HealthMetrics::new().with_reference_dirs([("main".to_string(), dir_str.clone())]);
+        assert!(build_approve_response(&disabled, "POST", "target=main", auth).starts_with("HTTP/1.1 403"));
+        assert_eq!(store.active_version(), None);
+        assert!(build_approve_response(&metrics, "POST", "", auth).starts_with("HTTP/1.1 400"));
+        assert!(build_approve_response(&metrics, "POST", "target=main&step=0", auth).starts_with("HTTP/1.1 400"));
+        assert!(build_approve_response(&metrics, "POST", "target=sales", auth).starts_with("HTTP/1.1 404"));
+        // Step 1 has no candidate
+        assert!(build_approve_response(&metrics, "POST", "target=main&step=1", auth).starts_with("HTTP/1.1 409"));
+
+        let response = build_approve_response(&metrics, "POST", "target=main", auth);
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.contains(r#""version":1"#));
+        assert_eq!(store.active_version(), Some(1));
+
+        // The approval is in the history
+        let response = build_reference_history_response(metrics.history(), "target=main");
+        let body = response.split("\r\n\r\n").nth(1).unwrap();
+        let json: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(json["changes"][0]["change"], "approved");
+        assert_eq!(json["changes"][0]["by"], "http");
+        assert_eq!(json["changes"][0]["transcript"], "thank you for calling");
+
+        // Named greetings are approved by name
+        let night = ReferenceStore::named(&dir_str, "night");
+        night
+            .propose(Enrollment {
+                embedding: &[0.25; 768],
+                audio: &[0.0; 1600],
+                transcript: Some("we are closed".to_string()),
+                source: ReferenceSource::Check,
+                embedder: String::new(),
+                whisper: String::new(),
+                model_sha256: [7; 32],
+            })
+            .unwrap();
+        assert!(build_approve_response(&metrics, "POST", "target=main&reference=../x", auth).starts_with("HTTP/1.1 400"));
+        assert!(build_approve_response(&metrics, "POST", "target=main&reference=night&step=1", auth).starts_with("HTTP/1.1 400"));
+        let response = build_approve_response(&metrics, "POST", "target=main&reference=night", auth);
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.contains(r#""reference":"night""#));
+        assert_eq!(night.active_version(), Some(1));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn test_build_not_found_response() {
         let response = build_not_found_response();
@@ -488,7 +1160,7 @@ mod proptests {
                 last_check_time: time,
                 last_check_ok: ok,
             };
-            let response = build_health_response(&status);
+            let response = build_health_response(&status, &[]);
             prop_assert!(response.starts_with("HTTP/1.1 200 OK"));
             prop_assert!(response.contains("Content-Type: application/json"));
             prop_assert!(response.contains("Content-Length:"));
@@ -506,7 +1178,7 @@ mod proptests {
                 last_check_time: 12345,
                 last_check_ok: true,
             };
-            let response = build_metrics_response(&status);
+            let response = build_metrics_response(&status, &[], &[], &[], &[]);
             // Use assert! instead of prop_assert! for string patterns with special chars
             assert!(response.contains("phonecheck_checks_total"));
             assert!(response.contains("# TYPE"));
@@ -0,0 +1,562 @@
+//! Check history
+//!
+//! Every check is appended as one JSON line to a history file, which is
+//! rotated (`history.jsonl` -> `history.jsonl.1` -> ...) once it grows past a
+//! size limit. Records carry an increasing id, the outcome and alert decision,
+//! and per-call details (SIP status, timings, packet counts, the RTP streams
+//! of calls that switched media servers, similarity and transcript), so
+//! questions like "when did the greeting change?" can be answered from the
+//! `/history` endpoint. Changes to reference embeddings
+//! (candidates, approvals, enrollments, rollbacks and the bounded updates
+//! made by checks) are written to the same file and share the id sequence.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::warn;
+
+use crate::matcher::CrossCheck;
+use crate::rtp::emodel::CallQuality;
+use crate::rtp::rtcp::CallStats;
+use crate::rtp::stream::{RtpStream, StreamSpan};
+
+/// Default history file
+pub const DEFAULT_HISTORY_FILE: &str = "./history.jsonl";
+
+/// Default size at which the history file is rotated (MB)
+pub const DEFAULT_HISTORY_MAX_MB: u64 = 10;
+
+/// Default number of rotated files kept besides the current one
+pub const DEFAULT_HISTORY_FILES: u32 = 5;
+
+/// Most records returned by one query
+pub const MAX_QUERY_LIMIT: usize = 1000;
+
+/// One check: its outcome, what was alerted and every call made
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckRecord {
+    /// Assigned by the store when the record is appended
+    #[serde(default)]
+    pub id: u64,
+    pub timestamp: DateTime<Utc>,
+    pub target: String,
+    /// Whether the check passed (after confirmation re-dials)
+    pub ok: bool,
+    /// Alert message for a failed check
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// What the alert state machine did: `alert`, `remind`, `resolve`,
+    /// `suppressed` or `none`
+    pub alert: String,
+    pub attempts: Vec<AttemptRecord>,
+}
+
+/// One call made by a check
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AttemptRecord {
+    pub ok: bool,
+    /// Final SIP status of the INVITE
+    pub sip_status: Option<u16>,
+    /// Provisional status that carried early media
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub early_media_status: Option<u16>,
+    /// Why the attempt failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Time from INVITE to the 200 OK
+    pub answer_ms: Option<u64>,
+    /// Time for the whole call
+    pub call_ms: u64,
+    /// Audio received (answered audio, or early media when unanswered)
+    pub audio_ms: u64,
+    pub packets: PacketCounts,
+    /// Loss, jitter and round-trip time (calls that received RTP)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub call_stats: Option<CallStats>,
+    /// E-model rating of the call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<CallQuality>,
+    /// RTP streams heard, when the call switched between them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub streams: Vec<RtpStream>,
+    /// Which stream each stretch of the audio came from, when it switched
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stream_timeline: Vec<StreamSpan>,
+    /// Embedding similarity of the greeting (of the last IVR step matched)
+    pub similarity: Option<f32>,
+    /// Name of the accepted greeting reference that matched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matched_reference: Option<String>,
+    /// Where the greeting started in the audio (aligned and fingerprint
+    /// matchers)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset_ms: Option<u64>,
+    /// Score of the transcript against the closest expected phrase
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phrase_score: Option<f32>,
+    /// The secondary matcher's opinion of the greeting
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cross_check: Option<CrossCheck>,
+    pub transcript: Option<String>,
+}
+
+/// RTP packet counts of one call
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PacketCounts {
+    pub received: u64,
+    pub lost: u64,
+    /// Duplicates and packets that arrived too late to play
+    pub dropped: u64,
+    /// Lost frames filled in by packet loss concealment
+    #[serde(default)]
+    pub concealed: u64,
+}
+
+/// What happened to a reference
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReferenceChangeKind {
+    /// A check found no approved reference and saved its audio as a candidate
+    Proposed,
+    /// The candidate became a version
+    Approved,
+    /// The candidate was discarded
+    Rejected,
+    /// A check replaced the active copy with a close match, within the
+    /// drift limit of the anchor
+    Updated,
+    /// A version was enrolled from a file or a call
+    Enrolled,
+    /// An older version was activated
+    RolledBack,
+}
+
+/// One change to a target's reference
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReferenceChange {
+    /// Assigned by the store when the change is appended
+    #[serde(default)]
+    pub id: u64,
+    pub timestamp: DateTime<Utc>,
+    pub target: String,
+    /// `greeting` or `stepN`
+    pub reference: String,
+    pub change: ReferenceChangeKind,
+    /// Version activated (approve, enroll, rollback)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+    /// Similarity of the new embedding to the one it replaced
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<f32>,
+    /// Similarity of the new embedding to the anchor (the active version)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anchor_similarity: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transcript: Option<String>,
+    /// Who made the change: `check`, `cli` or `http`
+    pub by: String,
+}
+
+impl ReferenceChange {
+    pub fn new(target: &str, reference: &str, change: ReferenceChangeKind, by: &str) -> Self {
+        Self {
+            id: 0,
+            timestamp: Utc::now(),
+            target: target.to_string(),
+            reference: reference.to_string(),
+            change,
+            version: None,
+            similarity: None,
+            anchor_similarity: None,
+            transcript: None,
+            by: by.to_string(),
+        }
+    }
+}
+
+/// One line of the history file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+enum Entry {
+    Check(CheckRecord),
+    Reference(ReferenceChange),
+}
+
+impl Entry {
+    fn id(&self) -> u64 {
+        match self {
+            Entry::Check(record) => record.id,
+            Entry::Reference(change) => change.id,
+        }
+    }
+
+    fn set_id(&mut self, id: u64) {
+        match self {
+            Entry::Check(record) => record.id = id,
+            Entry::Reference(change) => change.id = id,
+        }
+    }
+}
+
+/// Filters for a history query
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HistoryQuery {
+    pub target: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Only failed (`Some(false)`) or passed (`Some(true)`) checks
+    pub ok: Option<bool>,
+    /// Most records to return (newest first)
+    pub limit: usize,
+}
+
+impl HistoryQuery {
+    /// Parse `target=main&since=2025-01-01T00:00:00Z&limit=20` (unknown
+    /// parameters are ignored)
+    pub fn parse(query: &str) -> Result<Self> {
+        let mut q = HistoryQuery { limit: 100, ..Default::default() };
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            match key {
+                "target" => q.target = Some(value.to_string()),
+                "since" => q.since = Some(parse_time(value)?),
+                "until" => q.until = Some(parse_time(value)?),
+                "ok" => q.ok = Some(value.parse().with_context(|| format!("Invalid ok '{}'", value))?),
+                "limit" => {
+                    let limit: usize = value.parse().with_context(|| format!("Invalid limit '{}'", value))?;
+                    q.limit = limit.min(MAX_QUERY_LIMIT);
+                }
+                _ => {}
+            }
+        }
+        Ok(q)
+    }
+
+    fn matches(&self, record: &CheckRecord) -> bool {
+        self.matches_at(&record.target, record.timestamp) && self.ok.is_none_or(|ok| ok == record.ok)
+    }
+
+    fn matches_at(&self, target: &str, timestamp: DateTime<Utc>) -> bool {
+        self.target.as_ref().is_none_or(|t| t == target)
+            && self.since.is_none_or(|since| timestamp >= since)
+            && self.until.is_none_or(|until| timestamp <= until)
+    }
+}
+
+fn parse_time(value: &str) -> Result<DateTime<Utc>> {
+    // `+` in a query string is an encoded space; accept `%3A` for `:` too
+    let value = value.replace("%3A", ":").replace("%3a", ":").replace(' ', "+");
+    if let Ok(time) = DateTime::parse_from_rfc3339(&value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    let date = chrono::NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+        .with_context(|| format!("Invalid time '{}' (expected RFC 3339 or YYYY-MM-DD)", value))?;
+    Ok(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
+}
+
+/// Append-only JSONL history with size-based rotation
+#[derive(Debug)]
+pub struct HistoryStore {
+    path: PathBuf,
+    max_bytes: u64,
+    keep_files: u32,
+    /// Id of the next record (guards appends and rotation)
+    next_id: Mutex<u64>,
+}
+
+impl HistoryStore {
+    /// Open (or start) the history at `path`. The file is rotated once it
+    /// exceeds `max_bytes`, keeping `keep_files` rotated files.
+    pub fn open(path: impl AsRef<Path>, max_bytes: u64, keep_files: u32) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).with_context(|| format!("Failed to create history directory {:?}", dir))?;
+        }
+
+        let store = Self { path, max_bytes, keep_files, next_id: Mutex::new(1) };
+        let last_id = store.files().iter().find_map(|file| last_id(file));
+        *store.next_id.lock().unwrap_or_else(|e| e.into_inner()) = last_id.map_or(1, |id| id + 1);
+        Ok(store)
+    }
+
+    /// Append a record, assigning its id
+    pub fn append(&self, record: CheckRecord) -> Result<u64> {
+        self.append_entry(Entry::Check(record))
+    }
+
+    /// Append a reference change, assigning its id
+    pub fn append_reference_change(&self, change: ReferenceChange) -> Result<u64> {
+        self.append_entry(Entry::Reference(change))
+    }
+
+    fn append_entry(&self, mut entry: Entry) -> Result<u64> {
+        let mut next_id = self.next_id.lock().unwrap_or_else(|e| e.into_inner());
+        let id = *next_id;
+        entry.set_id(id);
+
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+
+        if fs::metadata(&self.path).map(|m| m.len() + line.len() as u64 > self.max_bytes).unwrap_or(false) {
+            self.rotate()?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open history file {:?}", self.path))?;
+        file.write_all(line.as_bytes())
+            .with_context(|| format!("Failed to write history file {:?}", self.path))?;
+
+        *next_id += 1;
+        Ok(id)
+    }
+
+    /// Records matching the query, newest first
+    pub fn query(&self, query: &HistoryQuery) -> Vec<CheckRecord> {
+        self.find(query.limit, |entry| match entry {
+            Entry::Check(record) if query.matches(&record) => Some(record),
+            _ => None,
+        })
+    }
+
+    /// Reference changes matching the query's target and time range, newest
+    /// first
+    pub fn reference_changes(&self, query: &HistoryQuery) -> Vec<ReferenceChange> {
+        self.find(query.limit, |entry| match entry {
+            Entry::Reference(change) if query.matches_at(&change.target, change.timestamp) => Some(change),
+            _ => None,
+        })
+    }
+
+    /// Up to `limit` entries picked by `select`, newest first
+    fn find<T>(&self, limit: usize, mut select: impl FnMut(Entry) -> Option<T>) -> Vec<T> {
+        let mut found = Vec::new();
+        for file in self.files() {
+            let mut entries = read_entries(&file);
+            entries.reverse();
+            for entry in entries {
+                if found.len() >= limit {
+                    return found;
+                }
+                if let Some(item) = select(entry) {
+                    found.push(item);
+                }
+            }
+        }
+        found
+    }
+
+    /// A single record by id
+    pub fn get(&self, id: u64) -> Option<CheckRecord> {
+        self.files().iter().find_map(|file| {
+            read_entries(file).into_iter().find_map(|entry| match entry {
+                Entry::Check(record) if record.id == id => Some(record),
+                _ => None,
+            })
+        })
+    }
+
+    /// Existing history files, newest first
+    fn files(&self) -> Vec<PathBuf> {
+        std::iter::once(self.path.clone())
+            .chain((1..=self.keep_files).map(|n| self.rotated(n)))
+            .filter(|p| p.exists())
+            .collect()
+    }
+
+    fn rotated(&self, n: u32) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    /// Shift `history.jsonl.N` to `.N+1` (dropping the oldest) and start a
+    /// new current file
+    fn rotate(&self) -> Result<()> {
+        if self.keep_files == 0 {
+            return fs::remove_file(&self.path).context("Failed to truncate history file");
+        }
+        let _ = fs::remove_file(self.rotated(self.keep_files));
+        for n in (1..self.keep_files).rev() {
+            let from = self.rotated(n);
+            if from.exists() {
+                fs::rename(&from, self.rotated(n + 1)).context("Failed to rotate history file")?;
+            }
+        }
+        fs::rename(&self.path, self.rotated(1)).context("Failed to rotate history file")
+    }
+}
+
+/// Every parseable entry in a file, oldest first
+fn read_entries(path: &Path) -> Vec<Entry> {
+    let Ok(file) = File::open(path) else {
+        return Vec::new();
+    };
+    BufReader::new(file)
+        .lines()
+        .map_while(|line| line.ok())
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(&line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                warn!("Skipping bad history line in {:?}: {}", path, e);
+                None
+            }
+        })
+        .collect()
+}
+
+fn last_id(path: &Path) -> Option<u64> {
+    read_entries(path).iter().map(Entry::id).max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("phonecheck_history_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("history.jsonl")
+    }
+
+    fn record(target: &str, hour: u32, ok: bool) -> CheckRecord {
+        CheckRecord {
+            id: 0,
+            timestamp: Utc.with_ymd_and_hms(2025, 1, 15, hour, 0, 0).unwrap(),
+            target: target.to_string(),
+            ok,
+            message: (!ok).then(|| "PhoneCheck ALERT: Call did not connect".to_string()),
+            alert: if ok { "none" } else { "alert" }.to_string(),
+            attempts: vec![AttemptRecord {
+                ok,
+                sip_status: Some(if ok { 200 } else { 486 }),
+                transcript: ok.then(|| "thank you for calling".to_string()),
+                similarity: ok.then_some(0.91),
+                matched_reference: ok.then(|| "day".to_string()),
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn test_append_assigns_ids_and_queries_newest_first() {
+        let path = temp_path("append");
+        let store = HistoryStore::open(&path, 1 << 20, 2).unwrap();
+        assert_eq!(store.append(record("main", 8, true)).unwrap(), 1);
+        assert_eq!(store.append(record("support", 9, false)).unwrap(), 2);
+        assert_eq!(store.append(record("main", 10, false)).unwrap(), 3);
+
+        let all = store.query(&HistoryQuery { limit: 10, ..Default::default() });
+        assert_eq!(all.iter().map(|r| r.id).collect::<Vec<_>>(), vec![3, 2, 1]);
+
+        let main = store.query(&HistoryQuery { target: Some("main".to_string()), limit: 10, ..Default::default() });
+        assert_eq!(main.iter().map(|r| r.id).collect::<Vec<_>>(), vec![3, 1]);
+
+        let failed = store.query(&HistoryQuery { ok: Some(false), limit: 1, ..Default::default() });
+        assert_eq!(failed.iter().map(|r| r.id).collect::<Vec<_>>(), vec![3]);
+
+        assert_eq!(store.get(2).unwrap().attempts[0].sip_status, Some(486));
+        assert!(store.get(9).is_none());
+
+        // Ids continue after a restart
+        drop(store);
+        let store = HistoryStore::open(&path, 1 << 20, 2).unwrap();
+        assert_eq!(store.append(record("main", 11, true)).unwrap(), 4);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_rotation_keeps_limited_files() {
+        let path = temp_path("rotate");
+        let line_len = serde_json::to_string(&record("main", 8, true)).unwrap().len() as u64 + 1;
+        // Two records per file, two rotated files
+        let store = HistoryStore::open(&path, line_len * 2 + 10, 2).unwrap();
+        for hour in 0..8 {
+            store.append(record("main", hour, true)).unwrap();
+        }
+
+        assert_eq!(store.files().len(), 3);
+        let ids: Vec<u64> = store.query(&HistoryQuery { limit: 100, ..Default::default() }).iter().map(|r| r.id).collect();
+        assert_eq!(ids, vec![8, 7, 6, 5, 4, 3]);
+        assert!(store.get(2).is_none());
+        assert!(store.get(3).is_some());
+
+        // The next id survives the current file having just been rotated
+        drop(store);
+        let store = HistoryStore::open(&path, line_len * 2 + 10, 2).unwrap();
+        assert_eq!(store.append(record("main", 9, true)).unwrap(), 9);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_reference_changes_share_the_file() {
+        let path = temp_path("reference");
+        let store = HistoryStore::open(&path, 1 << 20, 1).unwrap();
+        store.append(record("main", 8, true)).unwrap();
+        let mut change = ReferenceChange::new("main", "greeting", ReferenceChangeKind::Updated, "check");
+        change.similarity = Some(0.97);
+        change.anchor_similarity = Some(0.93);
+        assert_eq!(store.append_reference_change(change).unwrap(), 2);
+        store
+            .append_reference_change(ReferenceChange::new("support", "step1", ReferenceChangeKind::Proposed, "check"))
+            .unwrap();
+        store.append(record("main", 9, false)).unwrap();
+
+        // Checks and reference changes are queried separately
+        let checks: Vec<u64> = store.query(&HistoryQuery { limit: 10, ..Default::default() }).iter().map(|r| r.id).collect();
+        assert_eq!(checks, vec![4, 1]);
+        assert!(store.get(2).is_none());
+
+        let changes = store.reference_changes(&HistoryQuery { limit: 10, ..Default::default() });
+        assert_eq!(changes.iter().map(|c| c.id).collect::<Vec<_>>(), vec![3, 2]);
+        assert_eq!(changes[1].change, ReferenceChangeKind::Updated);
+        assert_eq!(changes[1].anchor_similarity, Some(0.93));
+
+        let main = store.reference_changes(&HistoryQuery { target: Some("main".to_string()), limit: 10, ..Default::default() });
+        assert_eq!(main.len(), 1);
+
+        // Ids continue after a restart that ends on a reference change
+        store
+            .append_reference_change(ReferenceChange::new("main", "greeting", ReferenceChangeKind::Approved, "cli"))
+            .unwrap();
+        drop(store);
+        let store = HistoryStore::open(&path, 1 << 20, 1).unwrap();
+        assert_eq!(store.append(record("main", 10, true)).unwrap(), 6);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let q = HistoryQuery::parse("target=main&since=2025-01-15&until=2025-01-16T12%3A00%3A00Z&ok=false&limit=5000").unwrap();
+        assert_eq!(q.target.as_deref(), Some("main"));
+        assert_eq!(q.since, Some(Utc.with_ymd_and_hms(2025, 1, 15, 0, 0, 0).unwrap()));
+        assert_eq!(q.until, Some(Utc.with_ymd_and_hms(2025, 1, 16, 12, 0, 0).unwrap()));
+        assert_eq!(q.ok, Some(false));
+        assert_eq!(q.limit, MAX_QUERY_LIMIT);
+
+        assert_eq!(HistoryQuery::parse("").unwrap().limit, 100);
+        assert!(HistoryQuery::parse("since=yesterday").is_err());
+        assert!(HistoryQuery::parse("limit=-1").is_err());
+    }
+
+    #[test]
+    fn test_query_time_range() {
+        let path = temp_path("range");
+        let store = HistoryStore::open(&path, 1 << 20, 1).unwrap();
+        for hour in 8..12 {
+            store.append(record("main", hour, true)).unwrap();
+        }
+        let q = HistoryQuery::parse("since=2025-01-15T09:00:00Z&until=2025-01-15T10:00:00Z").unwrap();
+        let ids: Vec<u64> = store.query(&q).iter().map(|r| r.id).collect();
+        assert_eq!(ids, vec![3, 2]);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,208 @@
+//! IVR navigation scripts
+//!
+//! Describes how to walk an auto-attendant after the call is answered:
+//! wait for the menu, press digits, and capture the audio that follows.
+//!
+//! Script syntax is a comma (or semicolon) separated list of steps:
+//! ```text
+//! wait 3s, send 2, wait 1s, capture 8s
+//! ```
+//! Durations accept `s` or `ms` suffixes (`1.5s`, `500ms`). Each capture step
+//! is matched against its own reference embedding, numbered from 1.
+
+use anyhow::{bail, Context, Result};
+use std::fmt;
+use std::time::Duration;
+
+use crate::rtp::dtmf;
+
+/// A single step of an IVR script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IvrStep {
+    /// Listen without keeping the audio
+    Wait(Duration),
+    /// Send DTMF digits as RFC 4733 telephone-events
+    SendDigits(String),
+    /// Listen and keep the audio for matching
+    Capture(Duration),
+}
+
+impl IvrStep {
+    /// Approximate wall-clock time this step takes
+    pub fn duration(&self) -> Duration {
+        match self {
+            IvrStep::Wait(d) | IvrStep::Capture(d) => *d,
+            IvrStep::SendDigits(digits) => {
+                (dtmf::DIGIT_DURATION + dtmf::INTER_DIGIT_GAP) * digits.len() as u32
+            }
+        }
+    }
+}
+
+impl fmt::Display for IvrStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IvrStep::Wait(d) => write!(f, "wait {}ms", d.as_millis()),
+            IvrStep::SendDigits(digits) => write!(f, "send {}", digits),
+            IvrStep::Capture(d) => write!(f, "capture {}ms", d.as_millis()),
+        }
+    }
+}
+
+/// Parsed IVR script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IvrScript {
+    pub steps: Vec<IvrStep>,
+}
+
+impl IvrScript {
+    /// Parse a script such as `wait 3s, send 2, wait 1s, capture 8s`
+    pub fn parse(script: &str) -> Result<Self> {
+        let mut steps = Vec::new();
+
+        for raw in script.split([',', ';']) {
+            let raw = raw.trim();
+            if raw.is_empty() {
+                continue;
+            }
+
+            let (verb, arg) = raw
+                .split_once(char::is_whitespace)
+                .map(|(v, a)| (v, a.trim()))
+                .with_context(|| format!("IVR step '{}' is missing an argument", raw))?;
+
+            let step = match verb.to_ascii_lowercase().as_str() {
+                "wait" => IvrStep::Wait(parse_duration(arg)?),
+                "capture" => IvrStep::Capture(parse_duration(arg)?),
+                "send" => {
+                    if !dtmf::is_valid_digits(arg) {
+                        bail!("IVR step '{}': digits must be 0-9, *, # or A-D", raw);
+                    }
+                    IvrStep::SendDigits(arg.to_ascii_uppercase())
+                }
+                _ => bail!("Unknown IVR step '{}' (expected wait, send or capture)", raw),
+            };
+            steps.push(step);
+        }
+
+        if steps.is_empty() {
+            bail!("IVR script is empty");
+        }
+
+        Ok(Self { steps })
+    }
+
+    /// Number of capture steps (one reference embedding each)
+    pub fn captures(&self) -> usize {
+        self.steps
+            .iter()
+            .filter(|s| matches!(s, IvrStep::Capture(_)))
+            .count()
+    }
+
+    /// Approximate total time the script runs after answer
+    pub fn total_duration(&self) -> Duration {
+        self.steps.iter().map(IvrStep::duration).sum()
+    }
+}
+
+impl fmt::Display for IvrScript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let steps: Vec<String> = self.steps.iter().map(|s| s.to_string()).collect();
+        write!(f, "{}", steps.join(", "))
+    }
+}
+
+/// Parse a duration like `3s`, `1.5s` or `500ms`
+fn parse_duration(s: &str) -> Result<Duration> {
+    let s = s.trim().to_ascii_lowercase();
+    let (number, scale) = if let Some(ms) = s.strip_suffix("ms") {
+        (ms, 0.001)
+    } else if let Some(secs) = s.strip_suffix('s') {
+        (secs, 1.0)
+    } else {
+        bail!("Duration '{}' needs a unit (s or ms)", s);
+    };
+
+    let value: f64 = number
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid duration '{}'", s))?;
+    if !value.is_finite() || value <= 0.0 {
+        bail!("Duration '{}' must be positive", s);
+    }
+
+    Ok(Duration::from_secs_f64(value * scale))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_example_script() {
+        let script = IvrScript::parse("wait 3s, send 2, wait 1s, capture 8s").unwrap();
+        assert_eq!(
+            script.steps,
+            vec![
+                IvrStep::Wait(Duration::from_secs(3)),
+                IvrStep::SendDigits("2".to_string()),
+                IvrStep::Wait(Duration::from_secs(1)),
+                IvrStep::Capture(Duration::from_secs(8)),
+            ]
+        );
+        assert_eq!(script.captures(), 1);
+    }
+
+    #[test]
+    fn test_parse_units_and_separators() {
+        let script = IvrScript::parse("WAIT 500ms; send 12#;capture 1.5s,").unwrap();
+        assert_eq!(
+            script.steps,
+            vec![
+                IvrStep::Wait(Duration::from_millis(500)),
+                IvrStep::SendDigits("12#".to_string()),
+                IvrStep::Capture(Duration::from_millis(1500)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_steps() {
+        assert!(IvrScript::parse("").is_err());
+        assert!(IvrScript::parse("wait").is_err());
+        assert!(IvrScript::parse("wait 3").is_err());
+        assert!(IvrScript::parse("wait -1s").is_err());
+        assert!(IvrScript::parse("send 2x").is_err());
+        assert!(IvrScript::parse("dial 2").is_err());
+    }
+
+    #[test]
+    fn test_total_duration() {
+        let script = IvrScript::parse("wait 3s, send 2, capture 8s").unwrap();
+        assert_eq!(
+            script.total_duration(),
+            Duration::from_secs(11) + dtmf::DIGIT_DURATION + dtmf::INTER_DIGIT_GAP
+        );
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        let script = IvrScript::parse("wait 3s, send 2, capture 8s").unwrap();
+        assert_eq!(IvrScript::parse(&script.to_string()).unwrap(), script);
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        /// Parsing arbitrary input never panics
+        #[test]
+        fn parse_never_panics(s in ".{0,100}") {
+            let _ = IvrScript::parse(&s);
+        }
+    }
+}
@@ -2,16 +2,28 @@
 //!
 //! This module exports internal components for integration testing.
 
+pub mod alerts;
+pub mod alignment;
+pub mod analyze;
 pub mod cli;
 pub mod config;
 pub mod embedding;
+pub mod embedding_file;
+pub mod fingerprint;
 pub mod health;
+pub mod history;
+pub mod ivr;
+pub mod matcher;
 pub mod model_manager;
 pub mod notify;
 pub mod orchestrator;
+pub mod phrase;
 pub mod redact;
+pub mod reference;
 pub mod rtp;
+pub mod schedule;
 pub mod scheduler;
 pub mod sip;
 pub mod speech;
 pub mod stun;
+pub mod targets;
@@ -1,18 +1,28 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use fs2::FileExt;
 use std::fs::File;
 use std::sync::Arc;
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
-use phonecheck::cli::{parse_args, print_help};
+use phonecheck::alerts::AlertTracker;
+use phonecheck::analyze;
+use phonecheck::cli::{parse_args, print_help, Command, ReferenceAction};
 use phonecheck::config::Config;
 use phonecheck::health::{self, HealthMetrics};
+use phonecheck::history::{HistoryStore, ReferenceChange, ReferenceChangeKind};
 use phonecheck::notify::Notifier;
 use phonecheck::orchestrator;
+use phonecheck::embedding::AudioEmbedder;
+use phonecheck::embedding_file;
+use phonecheck::model_manager::EMBEDDER_MODEL_PATH;
 use phonecheck::redact;
-use phonecheck::scheduler::run_scheduler;
+use phonecheck::reference::{self, Enrollment, ReferenceSource, ReferenceStore};
+use phonecheck::rtp::{resample::resample, WHISPER_SAMPLE_RATE};
+use phonecheck::sip::SipClient;
+use phonecheck::scheduler::{self, run_scheduler_for};
 use phonecheck::speech::SpeechRecognizer;
+use phonecheck::targets::{self, CheckTarget};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -23,6 +33,14 @@ async fn main() -> Result<()> {
         return Ok(());
     }
 
+    match args.command {
+        Some(Command::Analyze { path }) => return run_analyze(&path),
+        Some(Command::Reference { action, target, step, name }) => {
+            return run_reference(action, target.as_deref(), step, name.as_deref()).await
+        }
+        None => {}
+    }
+
     // Acquire singleton lock (skip for --validate since it doesn't make calls)
     let _lock_file = if !args.validate {
         let lock_path = std::env::temp_dir().join("phonecheck.lock");
@@ -51,10 +69,15 @@ async fn main() -> Result<()> {
     // Load configuration
     let config = Config::from_env()?;
     info!("Configuration loaded");
-    info!("  Target phone: {}", redact::phone_number(&config.target_phone));
     info!("  SIP server: {}:{}", config.sip_server, config.sip_port);
-    info!("  Expected phrase: \"{}\"", config.expected_phrase);
-    info!("  Listen duration: {}s", config.listen_duration_secs);
+    match config.checks_file {
+        Some(ref path) => info!("  Checks file: {}", path),
+        None => {
+            info!("  Target phone: {}", redact::phone_number(&config.target_phone));
+            info!("  Expected phrase: \"{}\" (match policy: {})", config.expected_phrase, config.match_policy);
+            info!("  Listen duration: {}s", config.listen_duration_secs);
+        }
+    }
 
     // Handle --validate mode
     if args.validate {
@@ -74,17 +97,48 @@ async fn main() -> Result<()> {
     // Wrap config in Arc for sharing (do this early)
     let config = Arc::new(config);
 
+    // Resolve the targets to check (one per checks file entry, or the environment target)
+    let targets: Arc<Vec<CheckTarget>> = Arc::new(targets::load_targets(&config)?);
+    if config.checks_file.is_some() {
+        for target in targets.iter() {
+            info!(
+                "  Target {}: {} (schedule {}, listen {}s, threshold {:.2})",
+                target.name,
+                redact::phone_number(&target.config.target_phone),
+                target.schedule,
+                target.config.listen_duration_secs,
+                target.threshold
+            );
+        }
+    }
+
     // Initialize speech recognizer (Mutex for interior mutability - embedding model needs &mut)
-    let recognizer = Arc::new(std::sync::Mutex::new(SpeechRecognizer::new(
-        &config.whisper_model_path,
-    )?));
+    let recognizer = Arc::new(std::sync::Mutex::new(
+        SpeechRecognizer::new(&config.whisper_model_path)?
+            .with_matcher(config.matcher)
+            .with_secondary_matcher(config.secondary_matcher)
+            .with_max_drift(config.reference_max_drift),
+    ));
 
     // Initialize notifier
-    let notifier = Arc::new(Notifier::new(&config));
-    info!("Pushover notifier configured");
+    let notifier = Arc::new(Notifier::new(&config)?);
+    info!("Notifications: {}", notifier.sink_names().join(", "));
 
-    // Initialize health metrics
-    let health_metrics = Arc::new(HealthMetrics::new());
+    // Restore open incidents so a restart neither re-alerts nor forgets them
+    let alerts = Arc::new(AlertTracker::load(&config.alert_state_file, config.alert_reminder_mins)?);
+
+    // Initialize health metrics, recording every check to the history file
+    let history = HistoryStore::open(
+        &config.history_file,
+        config.history_max_mb * 1024 * 1024,
+        config.history_files,
+    )?;
+    info!("Check history: {}", config.history_file);
+    let health_metrics = Arc::new(
+        HealthMetrics::with_history(history)
+            .with_reference_dirs(targets.iter().map(|t| (t.name.clone(), t.reference_dir.clone())))
+            .with_approve_token(config.approve_token.clone()),
+    );
 
     // Start health check server if configured
     let health_cancel = CancellationToken::new();
@@ -100,19 +154,26 @@ async fn main() -> Result<()> {
     if args.once {
         info!("Running single check (--once mode)");
         let cancel_token = CancellationToken::new();
-        orchestrator::run_check(&config, recognizer.as_ref(), &notifier, &health_metrics, cancel_token, args.save_audio.as_deref()).await;
+        orchestrator::run_checks(&targets, recognizer.as_ref(), &notifier, &alerts, &health_metrics, cancel_token, args.save_audio.as_deref()).await;
         health_cancel.cancel();
         return Ok(());
     }
 
     // Start scheduler - the closure receives a cancellation token for graceful shutdown
-    run_scheduler(|cancel_token| {
-        let config = config.clone();
+    // and checks the targets whose schedule is due now
+    let schedules: Vec<_> = targets.iter().map(|t| t.schedule.clone()).collect();
+    run_scheduler_for(&schedules, |cancel_token| {
+        let due: Vec<CheckTarget> = targets
+            .iter()
+            .filter(|t| scheduler::is_check_due(&t.schedule))
+            .cloned()
+            .collect();
         let recognizer = recognizer.clone();
         let notifier = notifier.clone();
+        let alerts = alerts.clone();
         let health_metrics = health_metrics.clone();
         async move {
-            orchestrator::run_check(&config, recognizer.as_ref(), &notifier, &health_metrics, cancel_token, None).await;
+            orchestrator::run_checks(&due, recognizer.as_ref(), &notifier, &alerts, &health_metrics, cancel_token, None).await;
         }
     })
     .await;
@@ -121,3 +182,220 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Logging for subcommands: warnings only, on stderr, so stdout carries
+/// just the command's output
+fn init_command_logging() {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::from_default_env()
+                .add_directive("phonecheck=warn".parse().unwrap()),
+        )
+        .with_writer(std::io::stderr)
+        .init();
+}
+
+/// Offline analysis: no lock, no SIP and no notifications; only the JSON
+/// report goes to stdout so it can be piped
+fn run_analyze(path: &str) -> Result<()> {
+    let _ = dotenvy::dotenv();
+    init_command_logging();
+
+    let config = Arc::new(Config::from_env()?);
+    let targets = targets::load_targets(&config)?;
+    let mut recognizer = SpeechRecognizer::new(&config.whisper_model_path)?
+        .with_matcher(config.matcher)
+        .with_secondary_matcher(config.secondary_matcher)
+        .without_reference_updates();
+
+    println!("{}", analyze::analyze_to_json(path, &targets, &mut recognizer)?);
+    Ok(())
+}
+
+/// `phonecheck reference ...`: manage one target's reference versions
+async fn run_reference(
+    action: ReferenceAction,
+    target: Option<&str>,
+    step: Option<usize>,
+    name: Option<&str>,
+) -> Result<()> {
+    let _ = dotenvy::dotenv();
+    init_command_logging();
+
+    let config = Arc::new(Config::from_env()?);
+    let targets = targets::load_targets(&config)?;
+    if action == ReferenceAction::Migrate {
+        return migrate_references(&targets, target);
+    }
+    let target = targets::find_target(&targets, target)?;
+    let store = match name {
+        Some(name) => ReferenceStore::named(&target.reference_dir, &target.find_reference(name)?.name),
+        None => ReferenceStore::new(&target.reference_dir, step),
+    };
+    let recognizer = || -> Result<SpeechRecognizer> {
+        Ok(SpeechRecognizer::new(&config.whisper_model_path)?.without_reference_updates())
+    };
+
+    match action {
+        ReferenceAction::List => print!("{}", reference::format_list(&store)?),
+        ReferenceAction::Show(version) => {
+            let version = match version {
+                Some(version) => version,
+                None => store.active_version().context("No reference versions yet (run `reference enroll`)")?,
+            };
+            print!("{}", reference::format_meta(&store, &store.get(version)?));
+        }
+        ReferenceAction::Compare(a, b) => {
+            let similarity = match (a.parse::<u32>(), b.parse::<u32>()) {
+                (Ok(a), Ok(b)) => store.compare(a, b)?,
+                _ => {
+                    let mut recognizer = recognizer()?;
+                    let a = comparison_embedding(&store, &a, &mut recognizer)?;
+                    let b = comparison_embedding(&store, &b, &mut recognizer)?;
+                    AudioEmbedder::cosine_similarity(&a, &b)
+                }
+            };
+            println!("Similarity: {:.4} (threshold {:.2})", similarity, target.threshold);
+        }
+        ReferenceAction::Rollback(version) => {
+            let version = store.rollback(version)?;
+            println!("Activated version {} ({})", version, store.active_path().display());
+            record_reference_change(&config, target, &store, ReferenceChangeKind::RolledBack, Some(version), None);
+        }
+        ReferenceAction::Approve => {
+            let meta = store.approve()?;
+            print!("{}", reference::format_meta(&store, &meta));
+            record_reference_change(&config, target, &store, ReferenceChangeKind::Approved, Some(meta.version), meta.transcript);
+        }
+        ReferenceAction::Reject => {
+            let meta = store.reject()?;
+            println!("Discarded the candidate captured {}", meta.created_at.to_rfc3339());
+            record_reference_change(&config, target, &store, ReferenceChangeKind::Rejected, None, meta.transcript);
+        }
+        ReferenceAction::Migrate => unreachable!("handled above"),
+        ReferenceAction::EnrollWav(path) => {
+            let audio = load_audio(&path)?;
+            let meta = enroll(&store, &mut recognizer()?, &audio, ReferenceSource::Wav { path }, &config)?;
+            print!("{}", reference::format_meta(&store, &meta));
+            record_reference_change(&config, target, &store, ReferenceChangeKind::Enrolled, Some(meta.version), meta.transcript);
+        }
+        ReferenceAction::EnrollCall => {
+            let mut recognizer = recognizer()?;
+            let audio = capture_call(target, step).await?;
+            let meta = enroll(&store, &mut recognizer, &audio, ReferenceSource::Call, &config)?;
+            print!("{}", reference::format_meta(&store, &meta));
+            record_reference_change(&config, target, &store, ReferenceChangeKind::Enrolled, Some(meta.version), meta.transcript);
+        }
+    }
+    Ok(())
+}
+
+/// Log a change made from the command line to the check history (the change
+/// itself has already been made, so a history error is only a warning)
+fn record_reference_change(
+    config: &Config,
+    target: &CheckTarget,
+    store: &ReferenceStore,
+    kind: ReferenceChangeKind,
+    version: Option<u32>,
+    transcript: Option<String>,
+) {
+    let mut change = ReferenceChange::new(&target.name, store.slot(), kind, "cli");
+    change.version = version;
+    change.transcript = transcript;
+    let result = HistoryStore::open(&config.history_file, config.history_max_mb * 1024 * 1024, config.history_files)
+        .and_then(|history| history.append_reference_change(change));
+    if let Err(e) = result {
+        warn!("Failed to record the change in the history: {:#}", e);
+    }
+}
+
+/// A WAV file resampled to 16 kHz mono
+fn load_audio(path: &str) -> Result<Vec<f32>> {
+    let (samples, rate) = phonecheck::rtp::load_wav(path)?;
+    Ok(resample(&samples, rate, WHISPER_SAMPLE_RATE))
+}
+
+/// Embedding of a reference version (a number) or a WAV file
+fn comparison_embedding(store: &ReferenceStore, arg: &str, recognizer: &mut SpeechRecognizer) -> Result<Vec<f32>> {
+    match arg.parse::<u32>() {
+        Ok(version) => store.embedding(version),
+        Err(_) => recognizer.compute_embedding(&load_audio(arg)?),
+    }
+}
+
+/// Transcribe and embed `audio` and save it as the new active version
+fn enroll(
+    store: &ReferenceStore,
+    recognizer: &mut SpeechRecognizer,
+    audio: &[f32],
+    source: ReferenceSource,
+    config: &Config,
+) -> Result<reference::ReferenceMeta> {
+    anyhow::ensure!(!audio.is_empty(), "No audio to enroll");
+    let embedding = recognizer.compute_embedding(audio).context("Failed to compute embedding")?;
+    let model_sha256 = recognizer.embedder_sha256()?;
+    let transcript = match recognizer.transcribe_audio(audio) {
+        Ok(transcript) => Some(transcript),
+        Err(e) => {
+            warn!("Transcription failed: {}", e);
+            None
+        }
+    };
+    store.enroll(Enrollment {
+        embedding: &embedding,
+        audio,
+        transcript,
+        source,
+        embedder: EMBEDDER_MODEL_PATH.to_string(),
+        whisper: config.whisper_model_path.clone(),
+        model_sha256,
+    })
+}
+
+/// Rewrite legacy reference files of the named target (default: every
+/// target) with a header naming the installed embedder model
+fn migrate_references(targets: &[CheckTarget], name: Option<&str>) -> Result<()> {
+    let selected: Vec<&CheckTarget> = match name {
+        Some(_) => vec![targets::find_target(targets, name)?],
+        None => targets.iter().collect(),
+    };
+    let model_sha256 = embedding_file::sha256_file(EMBEDDER_MODEL_PATH)
+        .context("The embedder model is needed to stamp migrated references")?;
+
+    let mut dirs: Vec<&str> = selected.iter().map(|t| t.reference_dir.as_str()).collect();
+    dirs.sort();
+    dirs.dedup();
+    let mut migrated = 0;
+    for dir in dirs {
+        if !std::path::Path::new(dir).is_dir() {
+            continue;
+        }
+        for path in embedding_file::migrate_dir(dir, &model_sha256)? {
+            println!("Migrated {}", path.display());
+            migrated += 1;
+        }
+    }
+    println!("{} reference file(s) migrated (model {})", migrated, embedding_file::short_hex(&model_sha256));
+    Ok(())
+}
+
+/// Call the target and return its greeting (or the audio of an IVR capture
+/// step)
+async fn capture_call(target: &CheckTarget, step: Option<usize>) -> Result<Vec<f32>> {
+    let sip_client = SipClient::new(Arc::clone(&target.config)).await?;
+    let listen_duration = std::time::Duration::from_secs(target.config.listen_duration_secs);
+    let result = sip_client.make_test_call_cancellable(listen_duration, CancellationToken::new()).await?;
+    if !result.connected {
+        bail!("Call did not connect: {}", result.error.as_deref().unwrap_or("Unknown error"));
+    }
+    match step {
+        Some(step) => result
+            .captures
+            .into_iter()
+            .nth(step - 1)
+            .with_context(|| format!("The call has no IVR capture step {}", step)),
+        None if result.audio_received => Ok(result.audio_samples),
+        None => bail!("Call connected but no audio received"),
+    }
+}
@@ -0,0 +1,270 @@
+/// Ways of comparing captured audio with a reference recording
+///
+/// Every matcher turns 16 kHz mono audio into [`Features`] and scores a
+/// capture's features against a reference's. The mean-embedding and aligned
+/// matchers need the Wav2Vec2 model; the fingerprint matcher is pure Rust,
+/// so it works on machines without ONNX and can cross-check the others.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use tracing::debug;
+
+use crate::alignment;
+use crate::embedding::AudioEmbedder;
+use crate::fingerprint::Fingerprint;
+use crate::model_manager::ModelManager;
+use crate::rtp::WHISPER_SAMPLE_RATE;
+
+/// How audio is compared with a reference
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Matcher {
+    /// Cosine similarity of mean-pooled Wav2Vec2 embeddings
+    #[default]
+    Embedding,
+    /// Wav2Vec2 frame embeddings aligned with subsequence DTW, which finds
+    /// the greeting anywhere in the audio (see [`crate::alignment`])
+    Aligned,
+    /// Spectral landmark fingerprints, no model needed (see
+    /// [`crate::fingerprint`])
+    Fingerprint,
+}
+
+impl Matcher {
+    /// Whether the matcher needs the Wav2Vec2 embedder
+    pub fn needs_embedder(self) -> bool {
+        self != Matcher::Fingerprint
+    }
+
+    /// A matcher of this kind, using the models behind `model_path`
+    pub fn build(self, model_path: &str) -> Box<dyn AudioMatcher> {
+        match self {
+            Matcher::Embedding => Box::new(EmbeddingMatcher { model_path: model_path.to_string() }),
+            Matcher::Aligned => Box::new(AlignedMatcher { model_path: model_path.to_string() }),
+            Matcher::Fingerprint => Box::new(FingerprintMatcher),
+        }
+    }
+}
+
+impl FromStr for Matcher {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "embedding" => Ok(Matcher::Embedding),
+            "aligned" => Ok(Matcher::Aligned),
+            "fingerprint" => Ok(Matcher::Fingerprint),
+            other => bail!("Unknown matcher '{}' (expected embedding, aligned or fingerprint)", other),
+        }
+    }
+}
+
+impl fmt::Display for Matcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Matcher::Embedding => "embedding",
+            Matcher::Aligned => "aligned",
+            Matcher::Fingerprint => "fingerprint",
+        })
+    }
+}
+
+/// What a matcher extracts from audio
+#[derive(Debug, Clone, PartialEq)]
+pub enum Features {
+    /// Mean-pooled, L2-normalized Wav2Vec2 embedding
+    Embedding(Vec<f32>),
+    /// L2-normalized Wav2Vec2 frame embeddings
+    Frames(Vec<Vec<f32>>),
+    Fingerprint(Fingerprint),
+}
+
+/// How well a capture matched a reference
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MatchScore {
+    /// Similarity on the matcher's own scale (higher is closer)
+    pub similarity: f32,
+    /// Where the reference starts in the capture, if the matcher locates it
+    pub offset_ms: Option<u64>,
+}
+
+/// A second matcher's opinion of a check, recorded next to the verdict
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CrossCheck {
+    pub matcher: Matcher,
+    pub similarity: f32,
+    /// Whether the similarity reached the second matcher's threshold
+    pub matched: bool,
+}
+
+/// Compares captured audio with a reference
+pub trait AudioMatcher: Send {
+    fn kind(&self) -> Matcher;
+
+    /// Features of 16 kHz mono audio
+    fn analyze(&mut self, audio: &[f32]) -> Result<Features>;
+
+    /// Score `capture` against `reference` (both from this matcher); a
+    /// capture that can't hold the reference scores 0
+    fn compare(&self, reference: &Features, capture: &Features) -> Result<MatchScore>;
+}
+
+/// Run `f` on the singleton model manager
+fn with_models<T>(model_path: &str, f: impl FnOnce(&mut ModelManager) -> Result<T>) -> Result<T> {
+    let mut guard = ModelManager::get(model_path)
+        .and_then(|m| m.lock().ok())
+        .context("Failed to access ModelManager for embedding")?;
+    f(guard.as_mut().context("ModelManager not initialized")?)
+}
+
+fn mismatched(matcher: Matcher) -> anyhow::Error {
+    anyhow::anyhow!("The {} matcher can't compare features from another matcher", matcher)
+}
+
+struct EmbeddingMatcher {
+    model_path: String,
+}
+
+impl AudioMatcher for EmbeddingMatcher {
+    fn kind(&self) -> Matcher {
+        Matcher::Embedding
+    }
+
+    fn analyze(&mut self, audio: &[f32]) -> Result<Features> {
+        with_models(&self.model_path, |models| models.embed(audio)).map(Features::Embedding)
+    }
+
+    fn compare(&self, reference: &Features, capture: &Features) -> Result<MatchScore> {
+        let (Features::Embedding(reference), Features::Embedding(capture)) = (reference, capture) else {
+            return Err(mismatched(self.kind()));
+        };
+        Ok(MatchScore {
+            similarity: AudioEmbedder::cosine_similarity(reference, capture),
+            offset_ms: None,
+        })
+    }
+}
+
+struct AlignedMatcher {
+    model_path: String,
+}
+
+impl AudioMatcher for AlignedMatcher {
+    fn kind(&self) -> Matcher {
+        Matcher::Aligned
+    }
+
+    fn analyze(&mut self, audio: &[f32]) -> Result<Features> {
+        with_models(&self.model_path, |models| models.embed_frames(audio)).map(Features::Frames)
+    }
+
+    fn compare(&self, reference: &Features, capture: &Features) -> Result<MatchScore> {
+        let (Features::Frames(reference), Features::Frames(capture)) = (reference, capture) else {
+            return Err(mismatched(self.kind()));
+        };
+        let Some(alignment) = alignment::align(reference, capture) else {
+            debug!("Audio is too short to align with the reference");
+            return Ok(MatchScore { similarity: 0.0, offset_ms: None });
+        };
+        debug!(
+            "Aligned reference at {:.2}s ({:.2}s of audio)",
+            alignment.offset_ms() as f64 / 1000.0,
+            alignment.duration_ms() as f64 / 1000.0
+        );
+        Ok(MatchScore {
+            similarity: alignment.score,
+            offset_ms: Some(alignment.offset_ms()),
+        })
+    }
+}
+
+struct FingerprintMatcher;
+
+impl AudioMatcher for FingerprintMatcher {
+    fn kind(&self) -> Matcher {
+        Matcher::Fingerprint
+    }
+
+    fn analyze(&mut self, audio: &[f32]) -> Result<Features> {
+        Ok(Features::Fingerprint(Fingerprint::compute(audio, WHISPER_SAMPLE_RATE)))
+    }
+
+    fn compare(&self, reference: &Features, capture: &Features) -> Result<MatchScore> {
+        let (Features::Fingerprint(reference), Features::Fingerprint(capture)) = (reference, capture) else {
+            return Err(mismatched(self.kind()));
+        };
+        let found = reference
+            .find_in(capture)
+            .context("The reference has no landmarks (is its audio silent?)")?;
+        debug!(
+            "Fingerprint: {:.4} of {} landmarks at offset {} frames",
+            found.score,
+            reference.len(),
+            found.offset_frames
+        );
+        Ok(MatchScore {
+            similarity: found.score,
+            offset_ms: found.offset_ms(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_matcher() {
+        assert_eq!("embedding".parse::<Matcher>().unwrap(), Matcher::Embedding);
+        assert_eq!(" Aligned ".parse::<Matcher>().unwrap(), Matcher::Aligned);
+        assert_eq!("FINGERPRINT".parse::<Matcher>().unwrap(), Matcher::Fingerprint);
+        assert_eq!(Matcher::Aligned.to_string(), "aligned");
+        assert_eq!(Matcher::Fingerprint.to_string(), "fingerprint");
+        assert!("dtw".parse::<Matcher>().is_err());
+    }
+
+    #[test]
+    fn test_fingerprint_matcher_needs_no_model() {
+        assert!(!Matcher::Fingerprint.needs_embedder());
+        assert!(Matcher::Aligned.needs_embedder());
+
+        // Two seconds of notes, a new pitch every 100 ms
+        let audio: Vec<f32> = (0..WHISPER_SAMPLE_RATE as usize * 2)
+            .map(|i| {
+                let t = i as f32 / WHISPER_SAMPLE_RATE as f32;
+                let pitch = 500.0 + ((i / 1600) * 37 % 25) as f32 * 100.0;
+                0.3 * (2.0 * std::f32::consts::PI * pitch * t).sin()
+            })
+            .collect();
+        let mut matcher = Matcher::Fingerprint.build("/nonexistent/ggml-model.bin");
+        let reference = matcher.analyze(&audio).unwrap();
+        let mut capture = vec![0.0; WHISPER_SAMPLE_RATE as usize / 2];
+        capture.extend(&audio);
+        let capture = matcher.analyze(&capture).unwrap();
+
+        let score = matcher.compare(&reference, &capture).unwrap();
+        assert!(score.similarity > 0.5, "{:?}", score);
+        assert!(score.offset_ms.unwrap().abs_diff(500) <= 16, "{:?}", score);
+    }
+
+    #[test]
+    fn test_compare_rejects_other_matchers_features() {
+        let matcher = Matcher::Fingerprint.build("/nonexistent/ggml-model.bin");
+        let embedding = Features::Embedding(vec![1.0, 0.0]);
+        assert!(matcher.compare(&embedding, &embedding).is_err());
+        let matcher = Matcher::Embedding.build("/nonexistent/ggml-model.bin");
+        let score = matcher.compare(&embedding, &embedding).unwrap();
+        assert!((score.similarity - 1.0).abs() < 1e-6);
+        assert_eq!(score.offset_ms, None);
+    }
+
+    #[test]
+    fn test_cross_check_serializes_the_matcher_name() {
+        let check = CrossCheck { matcher: Matcher::Fingerprint, similarity: 0.5, matched: true };
+        let json = serde_json::to_string(&check).unwrap();
+        assert!(json.contains("\"matcher\":\"fingerprint\""), "{}", json);
+        assert_eq!(serde_json::from_str::<CrossCheck>(&json).unwrap(), check);
+    }
+}
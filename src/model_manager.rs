@@ -8,11 +8,39 @@ use std::sync::Mutex;
 use tracing::{debug, info, warn};
 
 use crate::embedding::AudioEmbedder;
+use crate::embedding_file::{self, EmbeddingFile, ModelHash};
 use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
 
 /// Default path for the reference embedding cache
 pub const REFERENCE_EMBEDDING_PATH: &str = "./models/reference_embedding.bin";
 
+/// Wav2Vec2 encoder used for audio embeddings
+pub const EMBEDDER_MODEL_PATH: &str = "./models/wav2vec2_encoder.onnx";
+
+/// Directory holding the default reference set
+pub const DEFAULT_REFERENCE_DIR: &str = "./models";
+
+/// Reference embedding path for an IVR capture step (numbered from 1)
+pub fn step_reference_path(step: usize) -> String {
+    reference_path_in(DEFAULT_REFERENCE_DIR, Some(step))
+}
+
+/// Reference embedding path within a reference set directory, for the main
+/// greeting (`None`) or an IVR capture step
+pub fn reference_path_in(dir: &str, step: Option<usize>) -> String {
+    let dir = dir.trim_end_matches('/');
+    match step {
+        None => format!("{}/reference_embedding.bin", dir),
+        Some(step) => format!("{}/reference_embedding_step{}.bin", dir, step),
+    }
+}
+
+/// Reference embedding path of a named greeting (e.g. `night`) within a
+/// reference set directory
+pub fn named_reference_path_in(dir: &str, name: &str) -> String {
+    format!("{}/reference_embedding_{}.bin", dir.trim_end_matches('/'), name)
+}
+
 /// Singleton model manager
 ///
 /// Holds both Whisper and Wav2Vec2 models, loading them once per process.
@@ -20,6 +48,8 @@ pub const REFERENCE_EMBEDDING_PATH: &str = "./models/reference_embedding.bin";
 pub struct ModelManager {
     whisper_ctx: WhisperContext,
     embedder: Option<AudioEmbedder>,
+    /// SHA-256 of the Wav2Vec2 model, stamped into saved references
+    embedder_sha256: Option<ModelHash>,
     whisper_model_path: String,
 }
 
@@ -59,20 +89,21 @@ impl ModelManager {
         };
 
         // Try to load Wav2Vec2 embedder (optional)
-        let embedder = match Self::load_embedder() {
-            Ok(e) => {
-                info!("Wav2Vec2 embedder loaded successfully");
-                Some(e)
+        let (embedder, embedder_sha256) = match Self::load_embedder() {
+            Ok((e, sha256)) => {
+                info!("Wav2Vec2 embedder loaded successfully (sha256 {})", embedding_file::short_hex(&sha256));
+                (Some(e), Some(sha256))
             }
             Err(e) => {
                 warn!("Wav2Vec2 embedder not available: {}", e);
-                None
+                (None, None)
             }
         };
 
         Some(Self {
             whisper_ctx,
             embedder,
+            embedder_sha256,
             whisper_model_path: whisper_model_path.to_string(),
         })
     }
@@ -103,10 +134,12 @@ impl ModelManager {
         Ok(ctx)
     }
 
-    /// Load Wav2Vec2 embedder from disk
-    fn load_embedder() -> Result<AudioEmbedder> {
-        AudioEmbedder::new("./models/wav2vec2_encoder.onnx")
-            .context("Failed to load Wav2Vec2 embedder")
+    /// Load Wav2Vec2 embedder from disk, with the model's hash
+    fn load_embedder() -> Result<(AudioEmbedder, ModelHash)> {
+        let embedder = AudioEmbedder::new(EMBEDDER_MODEL_PATH)
+            .context("Failed to load Wav2Vec2 embedder")?;
+        let sha256 = embedding_file::sha256_file(EMBEDDER_MODEL_PATH)?;
+        Ok((embedder, sha256))
     }
 
     /// Transcribe audio using Whisper
@@ -157,51 +190,55 @@ impl ModelManager {
         embedder.embed(audio_samples)
     }
 
+    /// Compute per-frame audio embeddings using Wav2Vec2
+    pub fn embed_frames(&mut self, audio_samples: &[f32]) -> Result<Vec<Vec<f32>>> {
+        self.embedder
+            .as_mut()
+            .context("Wav2Vec2 embedder not available")?
+            .embed_frames(audio_samples)
+    }
+
     /// Check if Wav2Vec2 embedder is available
     pub fn has_embedder(&self) -> bool {
         self.embedder.is_some()
     }
 
+    /// SHA-256 of the loaded Wav2Vec2 model
+    pub fn embedder_sha256(&self) -> Option<ModelHash> {
+        self.embedder_sha256
+    }
+
     /// Load cached reference embedding from disk
     pub fn load_reference_embedding() -> Option<Vec<f32>> {
-        let path = std::path::Path::new(REFERENCE_EMBEDDING_PATH);
-        if !path.exists() {
-            return None;
-        }
+        Self::load_reference_embedding_from(REFERENCE_EMBEDDING_PATH)
+    }
 
-        match std::fs::read(path) {
-            Ok(bytes) => {
-                if bytes.len() % 4 != 0 {
-                    warn!("Invalid reference embedding file size");
-                    return None;
-                }
-                let floats: Vec<f32> = bytes
-                    .chunks_exact(4)
-                    .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
-                    .collect();
-                if floats.len() == 768 {
-                    info!("Loaded cached reference embedding ({} dimensions)", floats.len());
-                    Some(floats)
-                } else {
-                    warn!("Reference embedding has wrong dimension: {} (expected 768)", floats.len());
-                    None
-                }
+    /// Load a reference embedding from a specific file (missing or invalid
+    /// files give `None`; use [`embedding_file::read`] to see why)
+    pub fn load_reference_embedding_from(path: &str) -> Option<Vec<f32>> {
+        match embedding_file::read(path) {
+            Ok(Some(stored)) => {
+                info!("Loaded cached reference embedding ({} dimensions)", stored.embedding().len());
+                Some(stored.into_embedding())
             }
+            Ok(None) => None,
             Err(e) => {
-                warn!("Failed to read reference embedding: {}", e);
+                warn!("Failed to read reference embedding: {:#}", e);
                 None
             }
         }
     }
 
     /// Save reference embedding to disk
-    pub fn save_reference_embedding(embedding: &[f32]) -> Result<()> {
-        let bytes: Vec<u8> = embedding
-            .iter()
-            .flat_map(|f| f.to_le_bytes())
-            .collect();
-        std::fs::write(REFERENCE_EMBEDDING_PATH, bytes)?;
-        info!("Saved reference embedding to {}", REFERENCE_EMBEDDING_PATH);
+    pub fn save_reference_embedding(embedding: &[f32], model_sha256: &ModelHash) -> Result<()> {
+        Self::save_reference_embedding_to(REFERENCE_EMBEDDING_PATH, embedding, model_sha256)
+    }
+
+    /// Save a reference embedding to a specific file, stamped with the hash
+    /// of the model that computed it
+    pub fn save_reference_embedding_to(path: &str, embedding: &[f32], model_sha256: &ModelHash) -> Result<()> {
+        embedding_file::write(path, &EmbeddingFile::new(embedding.to_vec(), *model_sha256))?;
+        info!("Saved reference embedding to {}", path);
         Ok(())
     }
 }
@@ -241,4 +278,55 @@ mod tests {
 
         assert!(true); // Placeholder - integration tests would verify actual behavior
     }
+
+    #[test]
+    fn test_step_reference_path() {
+        assert_eq!(step_reference_path(1), "./models/reference_embedding_step1.bin");
+        assert_ne!(step_reference_path(1), REFERENCE_EMBEDDING_PATH);
+    }
+
+    #[test]
+    fn test_reference_path_in() {
+        assert_eq!(reference_path_in(DEFAULT_REFERENCE_DIR, None), REFERENCE_EMBEDDING_PATH);
+        assert_eq!(
+            reference_path_in("./models/support/", Some(2)),
+            "./models/support/reference_embedding_step2.bin"
+        );
+        assert_eq!(
+            named_reference_path_in("./models/main", "night"),
+            "./models/main/reference_embedding_night.bin"
+        );
+    }
+
+    #[test]
+    fn test_reference_embedding_round_trip() {
+        let path = std::env::temp_dir().join(format!("phonecheck_ref_{}.bin", std::process::id()));
+        let path = path.to_str().unwrap();
+        let embedding: Vec<f32> = (0..768).map(|i| i as f32 / 768.0).collect();
+
+        ModelManager::save_reference_embedding_to(path, &embedding, &[1; 32]).unwrap();
+        assert_eq!(ModelManager::load_reference_embedding_from(path), Some(embedding.clone()));
+
+        // Legacy headerless files are still read
+        let legacy: Vec<u8> = embedding.iter().flat_map(|f| f.to_le_bytes()).collect();
+        std::fs::write(path, legacy).unwrap();
+        assert_eq!(ModelManager::load_reference_embedding_from(path), Some(embedding));
+
+        // Wrong dimension is rejected
+        std::fs::write(path, [0u8; 40]).unwrap();
+        assert_eq!(ModelManager::load_reference_embedding_from(path), None);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_save_reference_creates_directory() {
+        let dir = std::env::temp_dir().join(format!("phonecheck_refdir_{}", std::process::id()));
+        let path = reference_path_in(dir.to_str().unwrap(), None);
+
+        ModelManager::save_reference_embedding_to(&path, &[0.5; 768], &[1; 32]).unwrap();
+        assert!(ModelManager::load_reference_embedding_from(&path).is_some());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
 }
@@ -0,0 +1,181 @@
+/// SMTP email
+///
+/// Sends a plain-text email per alert through an SMTP relay, using STARTTLS
+/// (port 587), implicit TLS (port 465) or, for local relays and testing, an
+/// unencrypted connection.
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::{Alert, NotificationSink, ALERT_TITLE};
+
+/// Timeout for one SMTP exchange
+const SMTP_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// How the SMTP connection is secured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpSecurity {
+    /// Plain connection upgraded with STARTTLS
+    StartTls,
+    /// TLS from the start (SMTPS)
+    Tls,
+    /// No encryption (local relays only)
+    None,
+}
+
+impl SmtpSecurity {
+    pub fn default_port(&self) -> u16 {
+        match self {
+            SmtpSecurity::StartTls => 587,
+            SmtpSecurity::Tls => 465,
+            SmtpSecurity::None => 25,
+        }
+    }
+}
+
+impl FromStr for SmtpSecurity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "starttls" => Ok(SmtpSecurity::StartTls),
+            "tls" => Ok(SmtpSecurity::Tls),
+            "none" => Ok(SmtpSecurity::None),
+            other => bail!("Unknown SMTP security '{}' (expected starttls, tls or none)", other),
+        }
+    }
+}
+
+/// SMTP relay, credentials and addresses
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+pub struct EmailSink {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+}
+
+impl EmailSink {
+    /// Set up the transport and parse the addresses (does not connect)
+    pub fn new(config: &SmtpConfig) -> Result<Self> {
+        let builder = match config.security {
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+                .context("Failed to set up SMTP STARTTLS")?,
+            SmtpSecurity::Tls => {
+                AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host).context("Failed to set up SMTP TLS")?
+            }
+            SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+        };
+        let mut builder = builder.port(config.port).timeout(Some(SMTP_TIMEOUT));
+        if let Some(ref username) = config.username {
+            let password = config.password.clone().unwrap_or_default();
+            builder = builder.credentials(Credentials::new(username.clone(), password));
+        }
+
+        let from = config
+            .from
+            .parse()
+            .with_context(|| format!("Invalid SMTP_FROM address '{}'", config.from))?;
+        let to = config
+            .to
+            .iter()
+            .map(|addr| addr.parse().with_context(|| format!("Invalid SMTP_TO address '{}'", addr)))
+            .collect::<Result<Vec<Mailbox>>>()?;
+        if to.is_empty() {
+            bail!("SMTP_TO needs at least one address");
+        }
+
+        Ok(Self { transport: builder.build(), from, to })
+    }
+
+    fn message(&self, alert: &Alert) -> Result<Message> {
+        let mut builder = Message::builder()
+            .from(self.from.clone())
+            .subject(subject(alert))
+            .header(ContentType::TEXT_PLAIN);
+        for to in &self.to {
+            builder = builder.to(to.clone());
+        }
+        builder.body(alert.message.clone()).context("Failed to build email")
+    }
+}
+
+/// Subject line: title, severity and target
+fn subject(alert: &Alert) -> String {
+    format!("{} ({}): {}", ALERT_TITLE, alert.severity, alert.target)
+}
+
+#[async_trait]
+impl NotificationSink for EmailSink {
+    fn name(&self) -> &str {
+        "email"
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        let message = self.message(alert)?;
+        self.transport.send(message).await.context("Failed to send email")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::Severity;
+
+    fn config() -> SmtpConfig {
+        SmtpConfig {
+            host: "smtp.example.com".to_string(),
+            port: 587,
+            security: SmtpSecurity::StartTls,
+            username: Some("alerts".to_string()),
+            password: Some("secret".to_string()),
+            from: "PhoneCheck <alerts@example.com>".to_string(),
+            to: vec!["ops@example.com".to_string(), "oncall@example.com".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_security_parse_and_ports() {
+        assert_eq!("STARTTLS".parse::<SmtpSecurity>().unwrap(), SmtpSecurity::StartTls);
+        assert_eq!("tls".parse::<SmtpSecurity>().unwrap().default_port(), 465);
+        assert_eq!("none".parse::<SmtpSecurity>().unwrap().default_port(), 25);
+        assert!("ssl".parse::<SmtpSecurity>().is_err());
+    }
+
+    #[test]
+    fn test_message_headers() {
+        let sink = EmailSink::new(&config()).unwrap();
+        let alert = Alert::new(Severity::Critical, "main", "Call did not connect");
+        let raw = String::from_utf8(sink.message(&alert).unwrap().formatted()).unwrap();
+        assert!(raw.contains("Subject: PhoneCheck Alert (critical): main"), "{}", raw);
+        assert!(raw.contains("To: ops@example.com, oncall@example.com"), "{}", raw);
+        assert!(raw.contains("Call did not connect"));
+    }
+
+    #[test]
+    fn test_rejects_bad_addresses() {
+        let mut bad_from = config();
+        bad_from.from = "not an address".to_string();
+        assert!(EmailSink::new(&bad_from).is_err());
+
+        let mut no_to = config();
+        no_to.to.clear();
+        assert!(EmailSink::new(&no_to).is_err());
+    }
+}
@@ -0,0 +1,347 @@
+//! Alert delivery
+//!
+//! Alerts go to one or more notification sinks (Pushover, JSON webhook,
+//! Slack-compatible webhook, ntfy, SMTP email, voip.ms SMS). Routing rules
+//! pick the sinks for each alert by severity and target; without rules every
+//! configured sink receives every alert.
+
+pub mod email;
+pub mod ntfy;
+pub mod pushover;
+pub mod routing;
+pub mod slack;
+pub mod sms;
+pub mod webhook;
+
+pub use email::{EmailSink, SmtpConfig, SmtpSecurity};
+pub use ntfy::{NtfyConfig, NtfySink};
+pub use pushover::PushoverSink;
+pub use routing::Route;
+pub use slack::SlackSink;
+pub use sms::{SmsConfig, SmsSink};
+pub use webhook::WebhookSink;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+
+use crate::config::Config;
+
+const MAX_RETRIES: u32 = 3;
+
+/// Timeout for a single HTTP delivery attempt
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Title shown by sinks that support one
+pub const ALERT_TITLE: &str = "PhoneCheck Alert";
+
+/// How urgent an alert is (ordered from least to most urgent)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Informational (e.g. recovery)
+    Info,
+    /// Degraded or needing attention but short of a failed check (e.g. a
+    /// borderline greeting, poor call quality, a reference awaiting
+    /// approval, or the checker itself failing)
+    Warning,
+    /// The PBX failed a check
+    Critical,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Severity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "info" => Ok(Severity::Info),
+            "warning" | "warn" => Ok(Severity::Warning),
+            "critical" | "crit" => Ok(Severity::Critical),
+            other => anyhow::bail!("Unknown severity '{}' (expected info, warning or critical)", other),
+        }
+    }
+}
+
+/// Source of `Alert::id`
+static NEXT_ALERT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// One alert to deliver
+#[derive(Debug, Clone)]
+pub struct Alert {
+    /// Unique per alert (copies share it), so a sink can tell the notifier's
+    /// retry from a new alert
+    pub id: u64,
+    pub severity: Severity,
+    /// Name of the target the alert is about
+    pub target: String,
+    pub message: String,
+    /// Pushover user or group key to use instead of the sink's default
+    /// (targets in a checks file can route alerts to their own recipients)
+    pub pushover_user_key: Option<String>,
+}
+
+impl Alert {
+    pub fn new(severity: Severity, target: &str, message: &str) -> Self {
+        Self {
+            id: NEXT_ALERT_ID.fetch_add(1, Ordering::Relaxed),
+            severity,
+            target: target.to_string(),
+            message: message.to_string(),
+            pushover_user_key: None,
+        }
+    }
+}
+
+/// A backend that delivers alerts
+#[async_trait]
+pub trait NotificationSink: Send + Sync {
+    /// Name used in routing rules and logs (`pushover`, `slack`, ...)
+    fn name(&self) -> &str;
+
+    /// Deliver one alert (a single attempt; the notifier retries)
+    async fn send(&self, alert: &Alert) -> Result<()>;
+}
+
+/// Sends alerts to the sinks chosen by the routing rules
+pub struct Notifier {
+    sinks: Vec<Box<dyn NotificationSink>>,
+    routes: Vec<Route>,
+}
+
+impl Notifier {
+    /// Build the sinks configured in `config`
+    pub fn new(config: &Config) -> Result<Self> {
+        let client = http_client();
+        let mut sinks: Vec<Box<dyn NotificationSink>> = vec![Box::new(PushoverSink::new(
+            client.clone(),
+            &config.pushover_api_url,
+            &config.pushover_api_token,
+            &config.pushover_user_key,
+        ))];
+
+        if let Some(ref url) = config.webhook_url {
+            sinks.push(Box::new(WebhookSink::new(client.clone(), url)));
+        }
+        if let Some(ref url) = config.slack_webhook_url {
+            sinks.push(Box::new(SlackSink::new(client.clone(), url)));
+        }
+        if let Some(ref ntfy) = config.ntfy {
+            sinks.push(Box::new(NtfySink::new(client.clone(), ntfy)));
+        }
+        if let Some(ref smtp) = config.smtp {
+            sinks.push(Box::new(EmailSink::new(smtp)?));
+        }
+        if let Some(ref sms) = config.sms {
+            sinks.push(Box::new(SmsSink::new(client, sms)));
+        }
+
+        Self::with_sinks(sinks, config.notify_routes.clone())
+    }
+
+    /// Use the given sinks and routing rules (an empty rule list sends every
+    /// alert to every sink)
+    pub fn with_sinks(sinks: Vec<Box<dyn NotificationSink>>, routes: Vec<Route>) -> Result<Self> {
+        let names: Vec<&str> = sinks.iter().map(|s| s.name()).collect();
+        for route in &routes {
+            for sink in route.sinks() {
+                if sink != "*" && !names.contains(&sink.as_str()) {
+                    anyhow::bail!(
+                        "NOTIFY_ROUTES uses sink '{}', which is not configured (configured: {})",
+                        sink,
+                        names.join(", ")
+                    );
+                }
+            }
+        }
+        Ok(Self { sinks, routes })
+    }
+
+    /// Names of the configured sinks
+    pub fn sink_names(&self) -> Vec<&str> {
+        self.sinks.iter().map(|s| s.name()).collect()
+    }
+
+    /// Sinks that should receive this alert
+    pub fn route(&self, alert: &Alert) -> Vec<&dyn NotificationSink> {
+        self.sinks
+            .iter()
+            .filter(|sink| {
+                self.routes.is_empty() || self.routes.iter().any(|r| r.matches(alert) && r.includes(sink.name()))
+            })
+            .map(|sink| sink.as_ref())
+            .collect()
+    }
+
+    /// Deliver an alert to every routed sink. Fails if any sink fails after
+    /// retries (the others are still attempted).
+    pub async fn send(&self, alert: &Alert) -> Result<()> {
+        info!("Sending {} alert: {}", alert.severity, alert.message);
+
+        let sinks = self.route(alert);
+        if sinks.is_empty() {
+            warn!("No notification route matches {} alert for '{}'", alert.severity, alert.target);
+            return Ok(());
+        }
+
+        let mut failed = Vec::new();
+        for sink in sinks {
+            if let Err(e) = deliver(sink, alert).await {
+                failed.push(format!("{}: {:#}", sink.name(), e));
+            }
+        }
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            anyhow::bail!("{}", failed.join("; "))
+        }
+    }
+}
+
+/// Send through one sink, retrying with exponential backoff
+async fn deliver(sink: &dyn NotificationSink, alert: &Alert) -> Result<()> {
+    let mut last_error = None;
+
+    for attempt in 0..MAX_RETRIES {
+        if attempt > 0 {
+            let backoff = Duration::from_secs(1 << attempt);
+            warn!("{} attempt {} failed, retrying in {:?}...", sink.name(), attempt, backoff);
+            sleep(backoff).await;
+        }
+
+        match sink.send(alert).await {
+            Ok(()) => {
+                info!("{} alert sent successfully", sink.name());
+                return Ok(());
+            }
+            Err(e) => {
+                last_error = Some(e);
+            }
+        }
+    }
+
+    let err = last_error.unwrap();
+    error!("Failed to send {} alert after {} attempts: {}", sink.name(), MAX_RETRIES, err);
+    Err(err)
+}
+
+/// HTTP client shared by the HTTP-based sinks
+pub fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(HTTP_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+}
+
+/// Fail on a non-2xx response, including the body in the error
+pub(crate) async fn check_status(response: reqwest::Response, sink: &str) -> Result<()> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    let body = response.text().await.unwrap_or_default();
+    anyhow::bail!("{} returned HTTP {}: {}", sink, status, body.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Records the alerts it receives
+    struct RecordingSink {
+        name: &'static str,
+        received: Mutex<Vec<String>>,
+    }
+
+    impl RecordingSink {
+        fn boxed(name: &'static str) -> Box<dyn NotificationSink> {
+            Box::new(Self { name, received: Mutex::new(Vec::new()) })
+        }
+    }
+
+    #[async_trait]
+    impl NotificationSink for RecordingSink {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        async fn send(&self, alert: &Alert) -> Result<()> {
+            self.received.lock().unwrap().push(alert.message.clone());
+            Ok(())
+        }
+    }
+
+    fn routed(notifier: &Notifier, alert: &Alert) -> Vec<String> {
+        notifier.route(alert).iter().map(|s| s.name().to_string()).collect()
+    }
+
+    #[test]
+    fn test_severity_order_and_parse() {
+        assert!(Severity::Info < Severity::Warning);
+        assert!(Severity::Warning < Severity::Critical);
+        assert_eq!("CRITICAL".parse::<Severity>().unwrap(), Severity::Critical);
+        assert_eq!("warn".parse::<Severity>().unwrap(), Severity::Warning);
+        assert!("urgent".parse::<Severity>().is_err());
+        assert_eq!(Severity::Info.to_string(), "info");
+    }
+
+    #[test]
+    fn test_without_routes_every_sink_receives_alerts() {
+        let notifier = Notifier::with_sinks(vec![RecordingSink::boxed("pushover"), RecordingSink::boxed("slack")], vec![]).unwrap();
+        let alert = Alert::new(Severity::Info, "main", "hello");
+        assert_eq!(routed(&notifier, &alert), vec!["pushover", "slack"]);
+    }
+
+    #[test]
+    fn test_routes_by_severity_and_target() {
+        let routes = Route::parse_list("pushover; sms severity=critical target=main; slack severity=warning").unwrap();
+        let sinks = vec![RecordingSink::boxed("pushover"), RecordingSink::boxed("slack"), RecordingSink::boxed("sms")];
+        let notifier = Notifier::with_sinks(sinks, routes).unwrap();
+
+        let critical_main = Alert::new(Severity::Critical, "main", "down");
+        assert_eq!(routed(&notifier, &critical_main), vec!["pushover", "slack", "sms"]);
+
+        let critical_support = Alert::new(Severity::Critical, "support", "down");
+        assert_eq!(routed(&notifier, &critical_support), vec!["pushover", "slack"]);
+
+        let info = Alert::new(Severity::Info, "main", "back up");
+        assert_eq!(routed(&notifier, &info), vec!["pushover"]);
+    }
+
+    #[test]
+    fn test_routes_must_name_configured_sinks() {
+        let routes = Route::parse_list("sms").unwrap();
+        let err = Notifier::with_sinks(vec![RecordingSink::boxed("pushover")], routes).err().unwrap();
+        assert!(err.to_string().contains("'sms'"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_send_with_no_matching_route_is_ok() {
+        let routes = Route::parse_list("pushover severity=critical").unwrap();
+        let notifier = Notifier::with_sinks(vec![RecordingSink::boxed("pushover")], routes).unwrap();
+        assert!(notifier.send(&Alert::new(Severity::Info, "main", "note")).await.is_ok());
+    }
+}
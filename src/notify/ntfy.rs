@@ -0,0 +1,92 @@
+/// ntfy push notifications
+///
+/// POSTs the message body to `<server>/<topic>` with the title, priority and
+/// tags in headers. Works with ntfy.sh or a self-hosted server; an access
+/// token is sent as a bearer token for protected topics.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+
+use super::{check_status, Alert, NotificationSink, Severity, ALERT_TITLE};
+
+/// Default ntfy server
+pub const NTFY_URL: &str = "https://ntfy.sh";
+
+/// ntfy server, topic and optional access token
+#[derive(Debug, Clone, PartialEq)]
+pub struct NtfyConfig {
+    pub url: String,
+    pub topic: String,
+    pub token: Option<String>,
+}
+
+pub struct NtfySink {
+    client: reqwest::Client,
+    url: String,
+    token: Option<String>,
+}
+
+impl NtfySink {
+    pub fn new(client: reqwest::Client, config: &NtfyConfig) -> Self {
+        Self {
+            client,
+            url: format!("{}/{}", config.url.trim_end_matches('/'), config.topic),
+            token: config.token.clone(),
+        }
+    }
+}
+
+/// ntfy priority (1-5) and tag for a severity
+fn priority_and_tag(severity: Severity) -> (&'static str, &'static str) {
+    match severity {
+        Severity::Info => ("2", "information_source"),
+        Severity::Warning => ("3", "warning"),
+        Severity::Critical => ("5", "rotating_light"),
+    }
+}
+
+#[async_trait]
+impl NotificationSink for NtfySink {
+    fn name(&self) -> &str {
+        "ntfy"
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        let (priority, tag) = priority_and_tag(alert.severity);
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header("Title", ALERT_TITLE)
+            .header("Priority", priority)
+            .header("Tags", tag)
+            .body(alert.message.clone());
+        if let Some(ref token) = self.token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request.send().await.context("Failed to send ntfy request")?;
+        check_status(response, "ntfy").await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topic_url() {
+        let config = NtfyConfig {
+            url: "https://ntfy.example.com/".to_string(),
+            topic: "phonecheck".to_string(),
+            token: None,
+        };
+        let sink = NtfySink::new(reqwest::Client::new(), &config);
+        assert_eq!(sink.url, "https://ntfy.example.com/phonecheck");
+    }
+
+    #[test]
+    fn test_priority_follows_severity() {
+        assert_eq!(priority_and_tag(Severity::Critical).0, "5");
+        assert_eq!(priority_and_tag(Severity::Info).0, "2");
+    }
+}
@@ -1,13 +1,16 @@
+/// Pushover push notifications
+///
+/// POSTs a form to the Pushover messages API. Critical alerts are sent with
+/// high priority, warnings with normal priority and info with low priority.
+
 use anyhow::{Context, Result};
+use async_trait::async_trait;
 use serde::Deserialize;
-use std::time::Duration;
-use tokio::time::sleep;
-use tracing::{error, info, warn};
 
-use crate::config::Config;
+use super::{Alert, NotificationSink, Severity, ALERT_TITLE};
 
-const MAX_RETRIES: u32 = 3;
-const PUSHOVER_API_URL: &str = "https://api.pushover.net/1/messages.json";
+/// Default Pushover messages endpoint
+pub const PUSHOVER_API_URL: &str = "https://api.pushover.net/1/messages.json";
 
 #[derive(Debug, Deserialize)]
 struct PushoverResponse {
@@ -16,61 +19,51 @@ struct PushoverResponse {
     errors: Option<Vec<String>>,
 }
 
-pub struct Notifier {
+pub struct PushoverSink {
     client: reqwest::Client,
-    user_key: String,
+    api_url: String,
     api_token: String,
+    user_key: String,
 }
 
-impl Notifier {
-    pub fn new(config: &Config) -> Self {
+impl PushoverSink {
+    pub fn new(client: reqwest::Client, api_url: &str, api_token: &str, user_key: &str) -> Self {
         Self {
-            client: reqwest::Client::new(),
-            user_key: config.pushover_user_key.clone(),
-            api_token: config.pushover_api_token.clone(),
+            client,
+            api_url: api_url.to_string(),
+            api_token: api_token.to_string(),
+            user_key: user_key.to_string(),
         }
     }
+}
 
-    pub async fn send_alert(&self, message: &str) -> Result<()> {
-        info!("Sending Pushover alert: {}", message);
-
-        let mut last_error = None;
-
-        for attempt in 0..MAX_RETRIES {
-            if attempt > 0 {
-                let backoff = Duration::from_secs(1 << attempt);
-                warn!("Pushover attempt {} failed, retrying in {:?}...", attempt, backoff);
-                sleep(backoff).await;
-            }
-
-            match self.try_send(message).await {
-                Ok(()) => {
-                    info!("Pushover alert sent successfully");
-                    return Ok(());
-                }
-                Err(e) => {
-                    last_error = Some(e);
-                }
-            }
-        }
+fn priority(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "-1",
+        Severity::Warning => "0",
+        Severity::Critical => "1", // High priority
+    }
+}
 
-        let err = last_error.unwrap();
-        error!("Failed to send Pushover alert after {} attempts: {}", MAX_RETRIES, err);
-        Err(err)
+#[async_trait]
+impl NotificationSink for PushoverSink {
+    fn name(&self) -> &str {
+        "pushover"
     }
 
-    async fn try_send(&self, message: &str) -> Result<()> {
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        let user_key = alert.pushover_user_key.as_deref().unwrap_or(&self.user_key);
         let params = [
             ("token", self.api_token.as_str()),
-            ("user", self.user_key.as_str()),
-            ("message", message),
-            ("title", "PhoneCheck Alert"),
-            ("priority", "1"), // High priority
+            ("user", user_key),
+            ("message", alert.message.as_str()),
+            ("title", ALERT_TITLE),
+            ("priority", priority(alert.severity)),
         ];
 
         let response = self
             .client
-            .post(PUSHOVER_API_URL)
+            .post(&self.api_url)
             .form(&params)
             .send()
             .await
@@ -112,4 +105,11 @@ mod tests {
         assert_eq!(response.status, 0);
         assert_eq!(response.errors, Some(vec!["user key is invalid".to_string()]));
     }
+
+    #[test]
+    fn test_priority_follows_severity() {
+        assert_eq!(priority(Severity::Critical), "1");
+        assert_eq!(priority(Severity::Warning), "0");
+        assert_eq!(priority(Severity::Info), "-1");
+    }
 }
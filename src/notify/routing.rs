@@ -0,0 +1,113 @@
+//! Alert routing rules
+//!
+//! `NOTIFY_ROUTES` holds `;`-separated rules. Each rule names one or more
+//! sinks (comma-separated, `*` for all), optionally followed by filters:
+//! ```text
+//! pushover; sms severity=critical target=main,support; slack severity=warning
+//! ```
+//! `severity=` is a minimum and `target=` a list of target names. An alert
+//! goes to every sink named by a rule it matches.
+
+use anyhow::{bail, Context, Result};
+
+use super::{Alert, Severity};
+
+/// One routing rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    sinks: Vec<String>,
+    min_severity: Severity,
+    targets: Option<Vec<String>>,
+}
+
+impl Route {
+    /// Parse a `;`-separated list of rules
+    pub fn parse_list(s: &str) -> Result<Vec<Route>> {
+        s.split(';')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+            .map(|rule| Route::parse(rule).with_context(|| format!("Invalid route '{}'", rule)))
+            .collect()
+    }
+
+    /// Parse a single rule, e.g. `sms,email severity=critical target=main`
+    pub fn parse(rule: &str) -> Result<Route> {
+        let mut words = rule.split_whitespace();
+        let sinks: Vec<String> = split_list(words.next().unwrap_or(""));
+        if sinks.is_empty() {
+            bail!("Route must name at least one sink");
+        }
+
+        let mut route = Route { sinks, min_severity: Severity::Info, targets: None };
+        for word in words {
+            match word.split_once('=') {
+                Some(("severity", value)) => route.min_severity = value.parse()?,
+                Some(("target", value)) => {
+                    let targets = split_list(value);
+                    if targets.is_empty() {
+                        bail!("target= needs at least one target name");
+                    }
+                    route.targets = Some(targets);
+                }
+                _ => bail!("Unknown filter '{}' (expected severity=<level> or target=<names>)", word),
+            }
+        }
+        Ok(route)
+    }
+
+    /// Sink names this rule sends to
+    pub fn sinks(&self) -> &[String] {
+        &self.sinks
+    }
+
+    /// Whether the rule sends to the named sink
+    pub fn includes(&self, sink: &str) -> bool {
+        self.sinks.iter().any(|s| s == "*" || s == sink)
+    }
+
+    /// Whether the alert passes this rule's filters
+    pub fn matches(&self, alert: &Alert) -> bool {
+        alert.severity >= self.min_severity
+            && self.targets.as_ref().is_none_or(|targets| targets.contains(&alert.target))
+    }
+}
+
+fn split_list(s: &str) -> Vec<String> {
+    s.split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rules() {
+        let routes = Route::parse_list(" pushover ; sms,email severity=critical target=main,support ;").unwrap();
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].sinks(), ["pushover"]);
+        assert_eq!(routes[0].min_severity, Severity::Info);
+        assert!(routes[0].targets.is_none());
+        assert_eq!(routes[1].sinks(), ["sms", "email"]);
+        assert_eq!(routes[1].min_severity, Severity::Critical);
+        assert_eq!(routes[1].targets.as_deref(), Some(&["main".to_string(), "support".to_string()][..]));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Route::parse("sms severity=urgent").is_err());
+        assert!(Route::parse("sms priority=1").is_err());
+        assert!(Route::parse("sms target=").is_err());
+        let err = Route::parse_list("pushover; sms when=always").unwrap_err();
+        assert!(format!("{:#}", err).contains("'sms when=always'"));
+    }
+
+    #[test]
+    fn test_matches() {
+        let route = Route::parse("* severity=warning target=main").unwrap();
+        assert!(route.includes("slack"));
+        assert!(route.matches(&Alert::new(Severity::Critical, "main", "")));
+        assert!(route.matches(&Alert::new(Severity::Warning, "main", "")));
+        assert!(!route.matches(&Alert::new(Severity::Info, "main", "")));
+        assert!(!route.matches(&Alert::new(Severity::Critical, "support", "")));
+    }
+}
@@ -0,0 +1,63 @@
+/// Slack-compatible incoming webhook
+///
+/// POSTs `{"text": ...}`, which Slack, Mattermost, Rocket.Chat and Discord's
+/// `/slack` endpoint all accept.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::json;
+
+use super::{check_status, Alert, NotificationSink, Severity, ALERT_TITLE};
+
+pub struct SlackSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl SlackSink {
+    pub fn new(client: reqwest::Client, url: &str) -> Self {
+        Self { client, url: url.to_string() }
+    }
+}
+
+/// Message text: an emoji for the severity, the title in bold, then the alert
+fn text(alert: &Alert) -> String {
+    let icon = match alert.severity {
+        Severity::Info => ":information_source:",
+        Severity::Warning => ":warning:",
+        Severity::Critical => ":rotating_light:",
+    };
+    format!("{} *{}* ({})\n{}", icon, ALERT_TITLE, alert.severity, alert.message)
+}
+
+#[async_trait]
+impl NotificationSink for SlackSink {
+    fn name(&self) -> &str {
+        "slack"
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        let response = self
+            .client
+            .post(&self.url)
+            .json(&json!({ "text": text(alert) }))
+            .send()
+            .await
+            .context("Failed to send Slack webhook request")?;
+        check_status(response, "Slack webhook").await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text() {
+        let alert = Alert::new(Severity::Critical, "main", "Call did not connect");
+        assert_eq!(
+            text(&alert),
+            ":rotating_light: *PhoneCheck Alert* (critical)\nCall did not connect"
+        );
+    }
+}
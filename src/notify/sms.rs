@@ -0,0 +1,172 @@
+/// voip.ms SMS (sendSMS API)
+///
+/// Sends each alert as a text message from an SMS-enabled DID on the voip.ms
+/// account to one or more numbers. See docs/research/voipms-api.md.
+///
+/// The API takes its parameters in the query string and answers
+/// `{"status": "success"}`; any other status is an error code such as
+/// `invalid_credentials` or `missing_did`. Messages are limited to 160
+/// characters, so longer alerts are truncated.
+///
+/// Each number is texted separately. When some fail, the error names only
+/// those, and the notifier's retry of the same alert goes only to them: the
+/// numbers that got the text don't get it twice.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use super::{Alert, NotificationSink};
+use crate::redact;
+
+/// Default voip.ms REST endpoint
+pub const VOIPMS_API_URL: &str = "https://voip.ms/api/v1/rest.php";
+
+/// Longest message sendSMS accepts
+pub const MAX_SMS_CHARS: usize = 160;
+
+/// voip.ms API credentials and SMS numbers
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmsConfig {
+    pub api_url: String,
+    /// Main account email
+    pub api_username: String,
+    /// API password (set in the voip.ms portal, not the SIP password)
+    pub api_password: String,
+    /// SMS-enabled DID to send from (10 digits)
+    pub did: String,
+    /// Numbers to text (10 digits each)
+    pub to: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SmsResponse {
+    status: String,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+pub struct SmsSink {
+    client: reqwest::Client,
+    config: SmsConfig,
+    /// Id of the last alert sent and the numbers it reached
+    delivered: Mutex<(u64, HashSet<String>)>,
+}
+
+impl SmsSink {
+    pub fn new(client: reqwest::Client, config: &SmsConfig) -> Self {
+        Self { client, config: config.clone(), delivered: Mutex::new((0, HashSet::new())) }
+    }
+
+    /// Whether `dst` already got alert `id` (forgetting the numbers of an
+    /// earlier alert)
+    fn already_sent(&self, id: u64, dst: &str) -> bool {
+        let mut delivered = self.delivered.lock().unwrap();
+        if delivered.0 != id {
+            *delivered = (id, HashSet::new());
+        }
+        delivered.1.contains(dst)
+    }
+
+    fn mark_sent(&self, id: u64, dst: String) {
+        let mut delivered = self.delivered.lock().unwrap();
+        if delivered.0 == id {
+            delivered.1.insert(dst);
+        }
+    }
+
+    async fn send_to(&self, dst: &str, message: &str) -> Result<()> {
+        let params = [
+            ("api_username", self.config.api_username.as_str()),
+            ("api_password", self.config.api_password.as_str()),
+            ("method", "sendSMS"),
+            ("did", self.config.did.as_str()),
+            ("dst", dst),
+            ("message", message),
+        ];
+
+        let response = self
+            .client
+            .get(&self.config.api_url)
+            .query(&params)
+            .send()
+            .await
+            .context("Failed to send voip.ms request")?;
+
+        let result: SmsResponse = response
+            .json()
+            .await
+            .context("Failed to parse voip.ms response")?;
+
+        if result.status == "success" {
+            Ok(())
+        } else {
+            match result.message {
+                Some(message) => anyhow::bail!("voip.ms API error: {} ({})", result.status, message),
+                None => anyhow::bail!("voip.ms API error: {}", result.status),
+            }
+        }
+    }
+}
+
+/// Only digits, as voip.ms expects NANPA numbers without punctuation
+fn digits(phone: &str) -> String {
+    phone.chars().filter(|c| c.is_ascii_digit()).collect()
+}
+
+/// Cut the message to the SMS limit on a character boundary
+fn truncate(message: &str) -> String {
+    message.chars().take(MAX_SMS_CHARS).collect()
+}
+
+#[async_trait]
+impl NotificationSink for SmsSink {
+    fn name(&self) -> &str {
+        "sms"
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        let message = truncate(&alert.message);
+        let mut failed = Vec::new();
+        for dst in self.config.to.iter().map(|dst| digits(dst)) {
+            if self.already_sent(alert.id, &dst) {
+                continue;
+            }
+            match self.send_to(&dst, &message).await {
+                Ok(()) => self.mark_sent(alert.id, dst),
+                Err(e) => failed.push(format!("{}: {:#}", redact::phone_number(&dst), e)),
+            }
+        }
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            anyhow::bail!("{}", failed.join("; "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_parsing() {
+        let ok: SmsResponse = serde_json::from_str(r#"{"status": "success", "sms": "23434"}"#).unwrap();
+        assert_eq!(ok.status, "success");
+
+        let err: SmsResponse = serde_json::from_str(r#"{"status": "invalid_credentials"}"#).unwrap();
+        assert_eq!(err.status, "invalid_credentials");
+        assert!(err.message.is_none());
+    }
+
+    #[test]
+    fn test_truncate_and_digits() {
+        let long = "é".repeat(200);
+        assert_eq!(truncate(&long).chars().count(), MAX_SMS_CHARS);
+        assert_eq!(truncate("short"), "short");
+        assert_eq!(digits("(909) 555-1234"), "9095551234");
+    }
+}
@@ -0,0 +1,81 @@
+/// Generic JSON webhook
+///
+/// POSTs each alert as a JSON object:
+/// ```json
+/// {"title": "PhoneCheck Alert", "severity": "critical", "target": "main",
+///  "message": "...", "timestamp": "2025-01-15T16:00:05Z"}
+/// ```
+/// Any 2xx response counts as delivered.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::Serialize;
+
+use super::{check_status, Alert, NotificationSink, ALERT_TITLE};
+
+#[derive(Debug, Serialize)]
+struct WebhookPayload<'a> {
+    title: &'a str,
+    severity: &'a str,
+    target: &'a str,
+    message: &'a str,
+    timestamp: String,
+}
+
+impl<'a> WebhookPayload<'a> {
+    fn new(alert: &'a Alert) -> Self {
+        Self {
+            title: ALERT_TITLE,
+            severity: alert.severity.as_str(),
+            target: &alert.target,
+            message: &alert.message,
+            timestamp: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        }
+    }
+}
+
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookSink {
+    pub fn new(client: reqwest::Client, url: &str) -> Self {
+        Self { client, url: url.to_string() }
+    }
+}
+
+#[async_trait]
+impl NotificationSink for WebhookSink {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        let response = self
+            .client
+            .post(&self.url)
+            .json(&WebhookPayload::new(alert))
+            .send()
+            .await
+            .context("Failed to send webhook request")?;
+        check_status(response, "Webhook").await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::Severity;
+
+    #[test]
+    fn test_payload_fields() {
+        let alert = Alert::new(Severity::Warning, "support", "Speech recognition failed");
+        let json = serde_json::to_value(WebhookPayload::new(&alert)).unwrap();
+        assert_eq!(json["title"], ALERT_TITLE);
+        assert_eq!(json["severity"], "warning");
+        assert_eq!(json["target"], "support");
+        assert_eq!(json["message"], "Speech recognition failed");
+        assert!(json["timestamp"].as_str().unwrap().ends_with('Z'));
+    }
+}
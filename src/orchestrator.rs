@@ -3,92 +3,357 @@
 //! Coordinates SIP calls, audio capture, speech recognition, and alerting.
 
 use anyhow::Result;
+use chrono::Utc;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio_util::sync::CancellationToken;
 use tracing::{error, info, warn};
 
+use crate::alerts::{format_duration, AlertAction, AlertTracker};
 use crate::config::Config;
 use crate::health::HealthMetrics;
-use crate::notify::Notifier;
+use crate::history::{AttemptRecord, CheckRecord, PacketCounts, ReferenceChange, ReferenceChangeKind};
+use crate::notify::{Alert, Notifier, Severity};
+use crate::reference::slot_name;
+use crate::rtp::stream::StreamSpan;
+use crate::rtp::tones::{failure_tone, ToneEvent};
 use crate::sip::{CallResult, SipClient};
-use crate::speech::{CheckResult, SpeechRecognizer};
+use crate::speech::{CheckResult, ReferenceUpdate, SpeechRecognizer};
+use crate::targets::CheckTarget;
 
-/// Run a single PBX health check
+/// Check each target in turn (calls share one SIP account, so they are not
+/// run in parallel). With several targets, saved audio gets the target name
+/// appended to the file stem.
+pub async fn run_checks(
+    targets: &[CheckTarget],
+    recognizer_mutex: &std::sync::Mutex<SpeechRecognizer>,
+    notifier: &Notifier,
+    alerts: &AlertTracker,
+    health_metrics: &HealthMetrics,
+    cancel_token: CancellationToken,
+    save_audio_path: Option<&str>,
+) {
+    for target in targets {
+        if cancel_token.is_cancelled() {
+            break;
+        }
+        let audio_path = save_audio_path.map(|path| {
+            if targets.len() > 1 {
+                target_audio_path(path, &target.name)
+            } else {
+                path.to_string()
+            }
+        });
+        run_check(
+            target,
+            recognizer_mutex,
+            notifier,
+            alerts,
+            health_metrics,
+            cancel_token.clone(),
+            audio_path.as_deref(),
+        )
+        .await;
+    }
+}
+
+/// Run a single PBX health check. A failed attempt is confirmed by
+/// re-dialing according to the target's confirmation policy before it counts
+/// as a failure.
 pub async fn run_check(
-    config: &Arc<Config>,
+    target: &CheckTarget,
     recognizer_mutex: &std::sync::Mutex<SpeechRecognizer>,
     notifier: &Notifier,
+    alerts: &AlertTracker,
     health_metrics: &HealthMetrics,
     cancel_token: CancellationToken,
     save_audio_path: Option<&str>,
 ) {
-    info!("Starting PBX health check...");
+    if target.is_default() {
+        info!("Starting PBX health check...");
+    } else {
+        info!("Starting PBX health check for target '{}'...", target.name);
+    }
+
+    let started = Utc::now();
+    let policy = ConfirmPolicy::from_config(&target.config);
+    let mut failed: Vec<(Severity, String)> = Vec::new();
+    let mut attempts = Vec::new();
 
-    let call_result = match perform_call(config, cancel_token).await {
-        Ok(res) => res,
-        Err(e) => {
-            handle_failure(health_metrics, notifier, &format!("PhoneCheck ERROR: {}", e)).await;
-            return;
+    let confirmed_failure = loop {
+        let mut record = AttemptRecord::default();
+        let outcome = attempt_check(
+            target,
+            recognizer_mutex,
+            health_metrics,
+            cancel_token.clone(),
+            save_audio_path,
+            &mut record,
+        )
+        .await;
+        health_metrics.record_attempt_for(&target.name, outcome.is_ok());
+        record.ok = outcome.is_ok();
+        match outcome {
+            Ok(()) => info!("Attempt {} of {} passed", attempts.len() + 1, policy.attempts),
+            Err(failure) => {
+                warn!("Attempt {} of {} failed: {}", attempts.len() + 1, policy.attempts, failure.1);
+                record.error = Some(failure.1.clone());
+                failed.push(failure);
+            }
         }
-    };
+        attempts.push(record);
 
-    if !validate_call_result(&call_result, health_metrics, notifier).await {
-        return;
-    }
+        if let Some(verdict) = policy.verdict(failed.len() as u32, attempts.len() as u32) {
+            break verdict;
+        }
 
-    if let Some(path) = save_audio_path {
-        save_audio(&call_result.audio_samples, path);
-    }
+        info!("Re-dialing in {}s to confirm...", policy.delay.as_secs());
+        tokio::select! {
+            _ = tokio::time::sleep(policy.delay) => {}
+            _ = cancel_token.cancelled() => {
+                info!("Check cancelled before it could be confirmed");
+                return;
+            }
+        }
+    };
 
-    let check_result = match process_audio(recognizer_mutex, &call_result.audio_samples) {
-        Ok(res) => res,
-        Err(e) => {
-            handle_failure(health_metrics, notifier, &format!("PhoneCheck ALERT: Speech recognition failed - {}", e)).await;
-            return;
+    let (message, alert) = if confirmed_failure {
+        // Alert with the most severe failure, preferring the latest
+        let (severity, mut message) = failed
+            .iter()
+            .max_by_key(|(severity, _)| *severity)
+            .cloned()
+            .unwrap_or((Severity::Critical, "PhoneCheck ALERT: Check failed".to_string()));
+        if attempts.len() > 1 {
+            message.push_str(&format!(" ({} of {} attempts failed)", failed.len(), attempts.len()));
         }
+        let alert = match handle_failure(target, health_metrics, notifier, alerts, severity, &message).await {
+            AlertAction::None => "suppressed",
+            action => action.as_str(),
+        };
+        (Some(message), alert)
+    } else {
+        (None, handle_success(target, health_metrics, notifier, alerts).await.as_str())
     };
 
-    report_result(check_result, health_metrics, notifier).await;
+    health_metrics.record_check(CheckRecord {
+        id: 0,
+        timestamp: started,
+        target: target.name.clone(),
+        ok: !confirmed_failure,
+        message,
+        alert: alert.to_string(),
+        attempts,
+    });
 }
 
-async fn perform_call(config: &Arc<Config>, cancel_token: CancellationToken) -> Result<CallResult> {
-    let sip_client = SipClient::new(Arc::clone(config)).await?;
-    let listen_duration = std::time::Duration::from_secs(config.listen_duration_secs);
-    let result = sip_client.make_test_call_cancellable(listen_duration, cancel_token.clone()).await?;
+/// How many attempts must fail before a check counts as failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfirmPolicy {
+    /// Most calls made per check (N)
+    pub attempts: u32,
+    /// Failed calls that confirm a failure (K)
+    pub failures: u32,
+    /// Pause before re-dialing
+    pub delay: Duration,
+}
 
-    // Retry once on timeout/unreachable (transient network issue, stale NAT mapping)
-    if !result.connected {
-        if let Some(ref err) = result.error {
-            if err.contains("timeout") || err.contains("No response") {
-                warn!("First attempt failed ({}), retrying with fresh connection...", err);
-                let sip_client = SipClient::new(Arc::clone(config)).await?;
-                return sip_client.make_test_call_cancellable(listen_duration, cancel_token).await;
-            }
+impl ConfirmPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            attempts: config.confirm_attempts.max(1),
+            failures: config.confirm_failures.clamp(1, config.confirm_attempts.max(1)),
+            delay: Duration::from_secs(config.confirm_delay_secs),
         }
     }
 
-    Ok(result)
+    /// Outcome once it is decided: `Some(true)` when `failures` of the
+    /// attempts failed, `Some(false)` when that can no longer happen or
+    /// nothing has failed yet (only a failure is re-dialed), `None` when
+    /// another attempt is needed
+    pub fn verdict(&self, failed: u32, made: u32) -> Option<bool> {
+        if failed >= self.failures {
+            Some(true)
+        } else if (failed == 0 && made > 0) || failed + self.attempts.saturating_sub(made) < self.failures {
+            Some(false)
+        } else {
+            None
+        }
+    }
 }
 
-async fn validate_call_result(
-    result: &CallResult,
+/// One call attempt: Ok if the PBX passed, or the failure's severity
+/// and alert message. Call details are filled into `record`.
+async fn attempt_check(
+    target: &CheckTarget,
+    recognizer_mutex: &std::sync::Mutex<SpeechRecognizer>,
     health_metrics: &HealthMetrics,
-    notifier: &Notifier,
-) -> bool {
+    cancel_token: CancellationToken,
+    save_audio_path: Option<&str>,
+    record: &mut AttemptRecord,
+) -> Result<(), (Severity, String)> {
+    let call_started = std::time::Instant::now();
+    let call_result = perform_call(&target.config, cancel_token).await;
+    record.call_ms = call_started.elapsed().as_millis() as u64;
+    let call_result = call_result.map_err(|e| (Severity::Critical, format!("PhoneCheck ERROR: {}", e)))?;
+
+    record.sip_status = call_result.sip_status;
+    record.early_media_status = call_result.early_media_status;
+    record.answer_ms = call_result.answer_ms;
+    record.audio_ms = crate::rtp::samples_to_duration_ms(call_result.audio_samples.len());
+    record.packets = PacketCounts {
+        received: call_result.packets.packets_received,
+        lost: call_result.packets.packets_lost,
+        dropped: call_result.packets.packets_dropped,
+        concealed: call_result.packets.frames_concealed,
+    };
+    if call_result.packets.frames_concealed > 0 {
+        info!(
+            "Concealed {} lost frames ({} fill)",
+            call_result.packets.frames_concealed, target.config.packet_loss_concealment
+        );
+    }
+    if call_result.call_stats.expected > 0 {
+        info!("Call quality: {}", call_result.call_stats);
+        record.call_stats = Some(call_result.call_stats.clone());
+    }
+    if let Some(quality) = call_result.quality {
+        info!("Call rating: {}", quality);
+        record.quality = Some(quality);
+        health_metrics.record_call_quality(&target.name, quality);
+    }
+
+    // Saved before validation so early media from unanswered calls is kept
+    if let Some(path) = save_audio_path {
+        if !call_result.audio_samples.is_empty() {
+            save_audio(&call_result.audio_samples, path);
+        }
+    }
+
+    log_tones(&call_result.tones);
+    if call_result.streams.len() > 1 {
+        log_streams(&call_result.stream_timeline);
+        record.streams = call_result.streams.clone();
+        record.stream_timeline = call_result.stream_timeline.clone();
+    }
+
+    validate_call_result(&call_result)?;
+
+    // Busy, reorder and SIT are unambiguous; report them without running
+    // speech recognition
+    if let Some(event) = failure_tone(&call_result.tones) {
+        warn!("Failure tone detected: {}", event.tone);
+        return Err((Severity::Critical, format!("PhoneCheck ALERT: {} in audio at {}", event.tone, tone_offset(event))));
+    }
+
+    if !call_result.captures.is_empty() {
+        check_ivr_captures(target, recognizer_mutex, health_metrics, &call_result.captures, record)?;
+    } else {
+        check_greeting(target, recognizer_mutex, health_metrics, &call_result.audio_samples, record)?;
+    }
+
+    // The greeting was right; the line may still be bad
+    check_call_quality(target, &call_result)
+}
+
+/// Match the answered audio against the greetings expected at this time
+fn check_greeting(
+    target: &CheckTarget,
+    recognizer_mutex: &std::sync::Mutex<SpeechRecognizer>,
+    health_metrics: &HealthMetrics,
+    samples: &[f32],
+    record: &mut AttemptRecord,
+) -> Result<(), (Severity, String)> {
+    // Any greeting expected at this time of day may answer
+    let references = target.greeting_references_at(Utc::now());
+    let mut check_result = process_audio(recognizer_mutex, samples, target, &references)
+        .map_err(|e| (Severity::Warning, format!("PhoneCheck ALERT: Speech recognition failed - {}", e)))?;
+    check_result.apply_phrase_policy(&target.config.phrase_policy());
+    record.similarity = check_result.similarity;
+    record.matched_reference = check_result.matched_reference.clone();
+    record.offset_ms = check_result.offset_ms;
+    record.cross_check = check_result.cross_check;
+    record.phrase_score = check_result.phrase.as_ref().map(|found| found.score);
+    record.transcript = Some(check_result.transcript.clone());
+    record_reference_update(health_metrics, target, &check_result);
+
+    report_result(target, &references[0].0, check_result)
+}
+
+/// A call rated below the target's MOS floor is degraded: a warning, since
+/// callers still get through
+fn check_call_quality(target: &CheckTarget, call_result: &CallResult) -> Result<(), (Severity, String)> {
+    let (Some(min_mos), Some(quality)) = (target.config.min_mos, call_result.quality) else {
+        return Ok(());
+    };
+    if quality.mos >= min_mos {
+        return Ok(());
+    }
+    warn!("Call quality below MOS {}: {}", min_mos, quality);
+    Err((
+        Severity::Warning,
+        format!(
+            "PhoneCheck DEGRADED: greeting OK but call quality is poor - {}, below MIN_MOS {} ({})",
+            quality, min_mos, call_result.call_stats
+        ),
+    ))
+}
+
+async fn perform_call(config: &Arc<Config>, cancel_token: CancellationToken) -> Result<CallResult> {
+    let sip_client = SipClient::new(Arc::clone(config)).await?;
+    let listen_duration = Duration::from_secs(config.listen_duration_secs);
+    sip_client.make_test_call_cancellable(listen_duration, cancel_token).await
+}
+
+fn validate_call_result(result: &CallResult) -> Result<(), (Severity, String)> {
     if !result.connected {
         let error_msg = result.error.as_deref().unwrap_or("Unknown error");
         error!("Call did not connect: {}", error_msg);
-        handle_failure(health_metrics, notifier, &format!("PhoneCheck ALERT: Call did not connect - {}", error_msg)).await;
-        return false;
+        let mut message = format!("PhoneCheck ALERT: Call did not connect - {}", error_msg);
+        if result.has_early_media() {
+            let early_ms = crate::rtp::samples_to_duration_ms(result.early_media_samples.len());
+            warn!("Early media received before failure: {} ms", early_ms);
+            message.push_str(&format!(" (after {} ms of early media)", early_ms));
+        }
+        if let Some(event) = failure_tone(&result.tones) {
+            message.push_str(&format!(" - {} in early media at {}", event.tone, tone_offset(event)));
+        }
+        return Err((Severity::Critical, message));
     }
 
     if !result.audio_received {
         warn!("Call connected but no audio received");
-        handle_failure(health_metrics, notifier, "PhoneCheck ALERT: Call connected but no audio received").await;
-        return false;
+        return Err((Severity::Critical, "PhoneCheck ALERT: Call connected but no audio received".to_string()));
+    }
+
+    Ok(())
+}
+
+fn log_tones(tones: &[ToneEvent]) {
+    for event in tones {
+        info!("Tone: {} at {} ms ({} ms)", event.tone, event.start_ms, event.duration_ms);
+    }
+}
+
+fn log_streams(timeline: &[StreamSpan]) {
+    for span in timeline {
+        info!("RTP stream {:08x} at {}-{} ms", span.ssrc, span.start_ms, span.end_ms);
     }
+}
 
-    true
+fn tone_offset(event: &ToneEvent) -> String {
+    format!("{:.1}s", event.start_ms as f64 / 1000.0)
+}
+
+/// Insert the target name before the extension: `check.wav` -> `check_main.wav`
+fn target_audio_path(path: &str, target: &str) -> String {
+    let path = std::path::Path::new(path);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("audio");
+    let file_name = match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("{}_{}.{}", stem, target, ext),
+        None => format!("{}_{}", stem, target),
+    };
+    path.with_file_name(file_name).to_string_lossy().into_owned()
 }
 
 fn save_audio(samples: &[f32], path: &str) {
@@ -98,57 +363,358 @@ fn save_audio(samples: &[f32], path: &str) {
     }
 }
 
+/// Check the audio against the accepted (name, path) references
 fn process_audio(
     recognizer_mutex: &std::sync::Mutex<SpeechRecognizer>,
     samples: &[f32],
+    target: &CheckTarget,
+    references: &[(String, String)],
 ) -> Result<CheckResult> {
+    let references: Vec<(&str, &str)> = references.iter().map(|(name, path)| (name.as_str(), path.as_str())).collect();
     let mut recognizer = recognizer_mutex.lock().map_err(|e| anyhow::anyhow!("Failed to lock recognizer: {}", e))?;
-    recognizer.check_audio(samples)
+    recognizer.check_references(samples, &references, &target.thresholds())
 }
 
-async fn report_result(
-    result: CheckResult,
+/// Match each IVR capture step against its own reference. The check fails
+/// on the first step that doesn't match.
+fn check_ivr_captures(
+    target: &CheckTarget,
+    recognizer_mutex: &std::sync::Mutex<SpeechRecognizer>,
     health_metrics: &HealthMetrics,
-    notifier: &Notifier,
-) {
+    captures: &[Vec<f32>],
+    record: &mut AttemptRecord,
+) -> Result<(), (Severity, String)> {
+    for (i, samples) in captures.iter().enumerate() {
+        let step = i + 1;
+        let reference = slot_name(Some(step));
+        let references = [(reference.clone(), target.reference_path(Some(step)))];
+        let result = process_audio(recognizer_mutex, samples, target, &references).map_err(|e| {
+            (
+                Severity::Warning,
+                format!("PhoneCheck ALERT: Speech recognition failed on IVR step {} - {}", step, e),
+            )
+        })?;
+
+        info!("IVR step {} transcribed: \"{}\"", step, result.transcript);
+        record.similarity = result.similarity;
+        record.transcript = Some(result.transcript.clone());
+        if let Some(similarity) = result.similarity {
+            info!("IVR step {} embedding similarity: {:.4}", step, similarity);
+        }
+        record_reference_update(health_metrics, target, &result);
+
+        if result.awaiting_approval {
+            return Err(awaiting_approval(target, Some(step), &reference, &result.transcript));
+        }
+        if result.in_warning_band {
+            return Err(near_miss(&format!("IVR step {}", step), &result));
+        }
+        if !result.phrase_found {
+            warn!(
+                "ALERT: IVR step {} did not match. Heard: \"{}\", similarity: {:?}",
+                step, result.transcript, result.similarity
+            );
+            return Err((
+                Severity::Critical,
+                format!(
+                    "PhoneCheck ALERT: IVR step {} greeting not detected. Heard: \"{}\"",
+                    step, result.transcript
+                ),
+            ));
+        }
+    }
+
+    info!("SUCCESS: All {} IVR steps matched - PBX is healthy", captures.len());
+    Ok(())
+}
+
+/// Count the reference the audio matched, and log a change the check made
+/// to a reference to the history
+fn record_reference_update(health_metrics: &HealthMetrics, target: &CheckTarget, result: &CheckResult) {
+    if let (Some(reference), Some(similarity)) = (&result.matched_reference, result.similarity) {
+        info!("Matched {} reference", reference);
+        health_metrics.record_reference_match(&target.name, reference, similarity);
+    }
+    let Some(update) = &result.reference_update else {
+        return;
+    };
+    let mut change = match update {
+        ReferenceUpdate::Proposed { reference } => {
+            info!("Saved candidate {} reference for approval", reference);
+            ReferenceChange::new(&target.name, reference, ReferenceChangeKind::Proposed, "check")
+        }
+        ReferenceUpdate::Updated { reference, similarity, anchor_similarity } => {
+            info!("Updated {} reference ({:.4} from its anchor)", reference, 1.0 - anchor_similarity);
+            let mut change = ReferenceChange::new(&target.name, reference, ReferenceChangeKind::Updated, "check");
+            change.similarity = Some(*similarity);
+            change.anchor_similarity = Some(*anchor_similarity);
+            change
+        }
+    };
+    change.transcript = Some(result.transcript.clone());
+    health_metrics.record_reference_change(change);
+}
+
+/// Warning for a reference with no approved version yet: the check can't
+/// pass until an operator approves the captured candidate of `reference`
+fn awaiting_approval(target: &CheckTarget, step: Option<usize>, reference: &str, transcript: &str) -> (Severity, String) {
+    let mut command = "phonecheck reference approve".to_string();
+    if !target.is_default() {
+        command.push_str(&format!(" --target {}", target.name));
+    }
+    if let Some(step) = step {
+        command.push_str(&format!(" --step {}", step));
+    } else if !target.references.is_empty() {
+        command.push_str(&format!(" --name {}", reference));
+    }
+    warn!("No approved {} reference; captured \"{}\" for approval", reference, transcript);
+    (
+        Severity::Warning,
+        format!(
+            "PhoneCheck ALERT: No approved {} reference yet. Heard: \"{}\" - run `{}` if this is the expected greeting",
+            reference, transcript, command
+        ),
+    )
+}
+
+/// Warning for audio that missed its threshold but scored within the warning
+/// band: probably the right greeting on a bad line, so not a full alert
+fn near_miss(what: &str, result: &CheckResult) -> (Severity, String) {
+    let similarity = result.similarity.unwrap_or_default();
+    let threshold = result.threshold.unwrap_or_default();
+    warn!(
+        "WARNING: {} scored {:.4}, below the threshold {:.2} but within the warning band. Heard: \"{}\"",
+        what, similarity, threshold, result.transcript
+    );
+    (
+        Severity::Warning,
+        format!(
+            "PhoneCheck WARNING: {} only partly matched (similarity {:.2}, threshold {:.2}). Heard: \"{}\"",
+            what, similarity, threshold, result.transcript
+        ),
+    )
+}
+
+/// Outcome of a greeting check; `reference` is the greeting a candidate is
+/// saved for when none is approved
+fn report_result(target: &CheckTarget, reference: &str, result: CheckResult) -> Result<(), (Severity, String)> {
     info!("Transcribed: \"{}\"", result.transcript);
     if let Some(similarity) = result.similarity {
         info!("Embedding similarity: {:.4}", similarity);
     }
 
-    if result.phrase_found {
+    if result.awaiting_approval {
+        Err(awaiting_approval(target, None, reference, &result.transcript))
+    } else if result.phrase_found {
         info!("SUCCESS: Expected phrase detected - PBX is healthy");
-        health_metrics.record_success();
+        Ok(())
+    } else if result.in_warning_band {
+        Err(near_miss("Greeting", &result))
     } else {
         warn!(
             "ALERT: Expected phrase NOT detected. Heard: \"{}\", similarity: {:?}",
             result.transcript,
             result.similarity
         );
-        handle_failure(
-            health_metrics,
-            notifier,
-            &format!(
+        Err((
+            Severity::Critical,
+            format!(
                 "PhoneCheck ALERT: Expected greeting not detected. Heard: \"{}\"",
                 result.transcript
             ),
-        )
-        .await;
+        ))
     }
 }
 
-async fn handle_failure(health_metrics: &HealthMetrics, notifier: &Notifier, message: &str) {
-    let was_healthy = health_metrics.status().last_check_ok;
-    health_metrics.record_failure();
+async fn handle_success(
+    target: &CheckTarget,
+    health_metrics: &HealthMetrics,
+    notifier: &Notifier,
+    alerts: &AlertTracker,
+) -> AlertAction {
+    health_metrics.record_success_for(&target.name);
 
-    if was_healthy {
-        // First failure after success — send alert
-        if let Err(e) = notifier.send_alert(message).await {
-            error!("Failed to send push notification: {}", e);
-            error!("Original alert: {}", message);
+    let action = alerts.record_success(&target.name, Utc::now());
+    if let AlertAction::Resolve { outage, failures } = action {
+        let message = format!(
+            "PhoneCheck RESOLVED: PBX is healthy again after {} ({} failed checks)",
+            format_duration(outage),
+            failures
+        );
+        send_alert(target, notifier, Severity::Info, &message).await;
+    }
+    action
+}
+
+async fn handle_failure(
+    target: &CheckTarget,
+    health_metrics: &HealthMetrics,
+    notifier: &Notifier,
+    alerts: &AlertTracker,
+    severity: Severity,
+    message: &str,
+) -> AlertAction {
+    health_metrics.record_failure_for(&target.name);
+
+    let action = alerts.record_failure(&target.name, Utc::now(), severity);
+    match action {
+        // First failure of an incident, or the first one this severe
+        AlertAction::Alert => send_alert(target, notifier, severity, message).await,
+        AlertAction::Remind { outage, failures } => {
+            let message = format!(
+                "PhoneCheck REMINDER: still failing after {} ({} failed checks). Latest: {}",
+                format_duration(outage),
+                failures,
+                message
+            );
+            send_alert(target, notifier, severity, &message).await;
         }
-    } else {
         // Consecutive failure — suppress to avoid alert spam
-        warn!("Consecutive failure (alert suppressed): {}", message);
+        _ => warn!("Consecutive failure (alert suppressed): {}", message),
+    }
+    action
+}
+
+async fn send_alert(target: &CheckTarget, notifier: &Notifier, severity: Severity, message: &str) {
+    // Name the target when several numbers are monitored
+    let message = if target.is_default() {
+        message.to_string()
+    } else {
+        format!("[{}] {}", target.name, message)
+    };
+
+    let alert = Alert {
+        pushover_user_key: Some(target.pushover_user_key.clone()),
+        ..Alert::new(severity, &target.name, &message)
+    };
+    if let Err(e) = notifier.send(&alert).await {
+        error!("Failed to send notification: {}", e);
+        error!("Original alert: {}", message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_audio_path() {
+        assert_eq!(target_audio_path("check.wav", "main"), "check_main.wav");
+        assert_eq!(target_audio_path("/tmp/out/check.wav", "support"), "/tmp/out/check_support.wav");
+        assert_eq!(target_audio_path("capture", "main"), "capture_main");
+    }
+
+    fn policy(attempts: u32, failures: u32) -> ConfirmPolicy {
+        ConfirmPolicy { attempts, failures, delay: Duration::from_secs(30) }
+    }
+
+    #[test]
+    fn test_awaiting_approval_names_the_command() {
+        let mut target = CheckTarget::from_config(Arc::new(test_config())).unwrap();
+        let (severity, message) = awaiting_approval(&target, None, "greeting", "thank you for calling");
+        assert_eq!(severity, Severity::Warning);
+        assert!(message.contains("No approved greeting reference"), "{}", message);
+        assert!(message.contains("`phonecheck reference approve`"), "{}", message);
+
+        target.name = "support".to_string();
+        let (_, message) = awaiting_approval(&target, Some(2), "step2", "press one");
+        assert!(message.contains("No approved step2 reference"), "{}", message);
+        assert!(message.contains("`phonecheck reference approve --target support --step 2`"), "{}", message);
+
+        target.references.push(crate::targets::GreetingReference {
+            name: "night".to_string(),
+            window: target.schedule.clone(),
+            dates: None,
+        });
+        let (_, message) = awaiting_approval(&target, None, "night", "we are closed");
+        assert!(message.contains("No approved night reference"), "{}", message);
+        assert!(message.contains("`phonecheck reference approve --target support --name night`"), "{}", message);
+    }
+
+    #[test]
+    fn test_warning_band_is_not_a_full_alert() {
+        let target = CheckTarget::from_config(Arc::new(test_config())).unwrap();
+        let heard = |similarity: f32, in_warning_band: bool| CheckResult {
+            transcript: "thank you for calling".to_string(),
+            phrase_found: false,
+            similarity: Some(similarity),
+            threshold: Some(0.75),
+            in_warning_band,
+            matched_reference: None,
+            offset_ms: None,
+            cross_check: None,
+            phrase: None,
+            awaiting_approval: false,
+            reference_update: None,
+        };
+
+        let (severity, message) = report_result(&target, "greeting", heard(0.7, true)).unwrap_err();
+        assert_eq!(severity, Severity::Warning);
+        assert!(message.contains("similarity 0.70, threshold 0.75"), "{}", message);
+
+        let (severity, message) = report_result(&target, "greeting", heard(0.4, false)).unwrap_err();
+        assert_eq!(severity, Severity::Critical);
+        assert!(message.contains("not detected"), "{}", message);
+    }
+
+    #[test]
+    fn test_poor_call_quality_is_degraded() {
+        use crate::rtp::emodel::CallQuality;
+        use crate::rtp::plc::Concealment;
+
+        let mut config = test_config();
+        let call = |loss: f32| CallResult { quality: Some(CallQuality::estimate(loss, 0.0, None, Concealment::Zero)), ..CallResult::success(vec![], true) };
+
+        // No floor configured: never degraded
+        let target = CheckTarget::from_config(Arc::new(config.clone())).unwrap();
+        assert!(check_call_quality(&target, &call(0.2)).is_ok());
+
+        config.min_mos = Some(3.5);
+        let target = CheckTarget::from_config(Arc::new(config)).unwrap();
+        assert!(check_call_quality(&target, &call(0.0)).is_ok());
+        assert!(check_call_quality(&target, &CallResult::success(vec![], true)).is_ok());
+        let (severity, message) = check_call_quality(&target, &call(0.12)).unwrap_err();
+        assert_eq!(severity, Severity::Warning);
+        assert!(message.contains("DEGRADED"), "{}", message);
+        assert!(message.contains("below MIN_MOS 3.5"), "{}", message);
+    }
+
+    fn test_config() -> Config {
+        let mut env = std::collections::HashMap::new();
+        env.insert("SIP_USERNAME", "testuser");
+        env.insert("SIP_PASSWORD", "testpass");
+        env.insert("SIP_SERVER", "sip.example.com");
+        env.insert("TARGET_PHONE", "5551234567");
+        env.insert("PUSHOVER_USER_KEY", "user123");
+        env.insert("PUSHOVER_API_TOKEN", "token456");
+        Config::from_map(&env).unwrap()
+    }
+
+    #[test]
+    fn test_single_attempt_decides_immediately() {
+        let p = policy(1, 1);
+        assert_eq!(p.verdict(0, 1), Some(false));
+        assert_eq!(p.verdict(1, 1), Some(true));
+    }
+
+    #[test]
+    fn test_failure_needs_confirmation() {
+        // Alert only if both calls fail
+        let p = policy(2, 2);
+        assert_eq!(p.verdict(0, 1), Some(false));
+        assert_eq!(p.verdict(1, 1), None);
+        assert_eq!(p.verdict(1, 2), Some(false));
+        assert_eq!(p.verdict(2, 2), Some(true));
+    }
+
+    #[test]
+    fn test_k_of_n_stops_once_decided() {
+        let p = policy(3, 2);
+        // A passing first call is not re-dialed
+        assert_eq!(p.verdict(0, 1), Some(false));
+        assert_eq!(p.verdict(1, 1), None);
+        assert_eq!(p.verdict(1, 2), None);
+        assert_eq!(p.verdict(2, 2), Some(true));
+        assert_eq!(p.verdict(1, 3), Some(false));
     }
 }
@@ -0,0 +1,506 @@
+//! Transcript phrase matching
+//!
+//! Whisper's transcript of the greeting is compared with the expected
+//! phrases after both are normalized the same way: lowercased, punctuation
+//! dropped, contractions expanded, numbers spelled out and filler words and
+//! stutters removed (see `docs/research/fuzzy-matching-speech.md`).
+//!
+//! A phrase is then located in the transcript by token-level edit distance:
+//! words may be missing (cost 1), extra words may interrupt the phrase (cost
+//! 0.5) and words may be misheard, costing little for the same Metaphone
+//! code ("four" for "for", "cubik" for "cubic") and their relative character
+//! edit distance otherwise. Words before and after the phrase are free. The score
+//! is `1 - cost / words in the phrase`.
+//!
+//! How the phrase score combines with the audio matcher's verdict is set by
+//! a [`MatchPolicy`].
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Default phrase score a transcript must reach
+pub const DEFAULT_PHRASE_THRESHOLD: f32 = 0.8;
+
+/// Cost of an extra transcript word inside the phrase
+const INSERTION_COST: f32 = 0.5;
+
+/// Cost of a misheard word with the same Metaphone code
+const PHONETIC_COST: f32 = 0.1;
+
+/// Words dropped from transcripts
+const FILLERS: &[&str] = &["um", "umm", "uh", "uhh", "er", "erm", "ah", "hmm", "mm", "mhm"];
+
+/// How the transcript and the audio matcher decide a check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchPolicy {
+    /// The audio matcher alone (the transcript is only recorded)
+    #[default]
+    Audio,
+    /// The transcript alone
+    Transcript,
+    /// Either the audio or the transcript matches
+    Either,
+    /// Both the audio and the transcript match
+    Both,
+    /// The weighted sum of both scores' margins over their thresholds
+    Weighted,
+}
+
+impl FromStr for MatchPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "audio" => Ok(MatchPolicy::Audio),
+            "transcript" => Ok(MatchPolicy::Transcript),
+            "either" => Ok(MatchPolicy::Either),
+            "both" => Ok(MatchPolicy::Both),
+            "weighted" => Ok(MatchPolicy::Weighted),
+            other => bail!(
+                "Unknown match policy '{}' (expected audio, transcript, either, both or weighted)",
+                other
+            ),
+        }
+    }
+}
+
+impl fmt::Display for MatchPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MatchPolicy::Audio => "audio",
+            MatchPolicy::Transcript => "transcript",
+            MatchPolicy::Either => "either",
+            MatchPolicy::Both => "both",
+            MatchPolicy::Weighted => "weighted",
+        })
+    }
+}
+
+/// A score and the threshold it has to reach
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Score {
+    pub value: f32,
+    pub threshold: f32,
+}
+
+impl Score {
+    pub fn passed(&self) -> bool {
+        self.value >= self.threshold
+    }
+
+    fn margin(&self) -> f32 {
+        self.value - self.threshold
+    }
+}
+
+impl MatchPolicy {
+    /// Combine the audio matcher's and the transcript's scores (none when
+    /// there is nothing to score, which never passes). `transcript_weight`
+    /// is the share of the transcript in a weighted decision.
+    pub fn decide(self, audio: Option<Score>, transcript: Option<Score>, transcript_weight: f32) -> bool {
+        let passed = |score: Option<Score>| score.is_some_and(|s| s.passed());
+        match self {
+            MatchPolicy::Audio => passed(audio),
+            MatchPolicy::Transcript => passed(transcript),
+            MatchPolicy::Either => passed(audio) || passed(transcript),
+            MatchPolicy::Both => passed(audio) && passed(transcript),
+            MatchPolicy::Weighted => match (audio, transcript) {
+                (Some(audio), Some(transcript)) => {
+                    transcript_weight * transcript.margin() + (1.0 - transcript_weight) * audio.margin() >= 0.0
+                }
+                _ => false,
+            },
+        }
+    }
+}
+
+/// Phrase matching settings of a target
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhrasePolicy<'a> {
+    pub policy: MatchPolicy,
+    /// Expected phrases (any of them may match)
+    pub phrases: Vec<&'a str>,
+    pub threshold: f32,
+    pub transcript_weight: f32,
+}
+
+/// The expected phrase closest to a transcript
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PhraseMatch {
+    pub phrase: String,
+    pub score: f32,
+}
+
+/// Score `transcript` against each of `phrases`, keeping the best (none
+/// if there are no phrases)
+pub fn best_match(transcript: &str, phrases: &[&str]) -> Option<PhraseMatch> {
+    let heard = normalize(transcript);
+    phrases
+        .iter()
+        .map(|&phrase| PhraseMatch {
+            phrase: phrase.to_string(),
+            score: phrase_score(&normalize(phrase), &heard),
+        })
+        .max_by(|a, b| a.score.total_cmp(&b.score))
+}
+
+/// Score of the best occurrence of `expected` words in `heard` words
+fn phrase_score(expected: &[String], heard: &[String]) -> f32 {
+    if expected.is_empty() {
+        return 0.0;
+    }
+    let codes = |words: &[String]| words.iter().map(|w| metaphone(w)).collect::<Vec<_>>();
+    let (expected_codes, heard_codes) = (codes(expected), codes(heard));
+
+    // cost[j]: cheapest alignment of the expected words so far ending at
+    // heard word j; row 0 is free so the phrase may start anywhere
+    let mut prev = vec![0.0f32; heard.len() + 1];
+    let mut cur = vec![0.0f32; heard.len() + 1];
+    for i in 1..=expected.len() {
+        cur[0] = i as f32;
+        for j in 1..=heard.len() {
+            let substitution = if expected[i - 1] == heard[j - 1] {
+                0.0
+            } else if !expected_codes[i - 1].is_empty() && expected_codes[i - 1] == heard_codes[j - 1] {
+                PHONETIC_COST
+            } else {
+                word_distance(&expected[i - 1], &heard[j - 1])
+            };
+            cur[j] = (prev[j - 1] + substitution)
+                .min(prev[j] + 1.0)
+                .min(cur[j - 1] + INSERTION_COST);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+
+    let cost = prev.iter().copied().fold(f32::INFINITY, f32::min);
+    (1.0 - cost / expected.len() as f32).max(0.0)
+}
+
+/// Character edit distance relative to the longer word; words that differ
+/// in more than a third of their letters count as different (1)
+fn word_distance(a: &str, b: &str) -> f32 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            cur[j + 1] = (prev[j] + usize::from(ca != cb)).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    let ratio = prev[b.len()] as f32 / a.len().max(b.len()).max(1) as f32;
+    if ratio <= 1.0 / 3.0 {
+        ratio
+    } else {
+        1.0
+    }
+}
+
+/// Lowercase words of `text` with punctuation dropped, contractions
+/// expanded, numbers spelled out and fillers and repeated words removed
+pub fn normalize(text: &str) -> Vec<String> {
+    let text = text.to_lowercase().replace(['\u{2019}', '\u{2018}'], "'").replace('&', " and ");
+    let cleaned: String = text
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '\'' { c } else { ' ' })
+        .collect();
+
+    let mut words: Vec<String> = Vec::new();
+    let mut previous = "";
+    for token in cleaned.split_whitespace() {
+        // Stutters ("the the"), but not repeated digits ("press 5 5 5")
+        if token == previous && !token.chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+        previous = token;
+        for word in expand_contraction(token.trim_matches('\'')) {
+            let word = word.replace('\'', "");
+            if word.is_empty() || FILLERS.contains(&word.as_str()) {
+                continue;
+            }
+            if word.chars().all(|c| c.is_ascii_digit()) {
+                words.extend(spell_number(&word));
+            } else {
+                words.push(word);
+            }
+        }
+    }
+    words
+}
+
+fn expand_contraction(word: &str) -> Vec<String> {
+    let irregular = match word {
+        "won't" => Some("will not"),
+        "can't" => Some("can not"),
+        "shan't" => Some("shall not"),
+        "let's" => Some("let us"),
+        _ => None,
+    };
+    if let Some(expanded) = irregular {
+        return expanded.split(' ').map(str::to_string).collect();
+    }
+    const SUFFIXES: &[(&str, &str)] = &[
+        ("n't", "not"),
+        ("'re", "are"),
+        ("'ll", "will"),
+        ("'ve", "have"),
+        ("'m", "am"),
+        ("'d", "would"),
+    ];
+    for (suffix, expansion) in SUFFIXES {
+        if let Some(stem) = word.strip_suffix(suffix).filter(|stem| !stem.is_empty()) {
+            return vec![stem.to_string(), expansion.to_string()];
+        }
+    }
+    if let Some(stem) = word.strip_suffix("'s") {
+        // "it's" is "it is"; otherwise a possessive ("cubic's" is "cubic")
+        const IS: &[&str] = &["it", "that", "what", "there", "here", "he", "she", "who", "where"];
+        if IS.contains(&stem) {
+            return vec![stem.to_string(), "is".to_string()];
+        }
+        return vec![stem.to_string()];
+    }
+    vec![word.to_string()]
+}
+
+const ONES: &[&str] = &[
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten", "eleven", "twelve",
+    "thirteen", "fourteen", "fifteen", "sixteen", "seventeen", "eighteen", "nineteen",
+];
+const TENS: &[&str] = &["", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety"];
+
+/// Words for a string of digits: numbers up to 9999 as spoken ("24" is
+/// "twenty four"), anything longer or with a leading zero (phone numbers,
+/// extensions) digit by digit
+fn spell_number(digits: &str) -> Vec<String> {
+    if digits.len() > 4 || (digits.len() > 1 && digits.starts_with('0')) {
+        return digits.bytes().map(|d| ONES[(d - b'0') as usize].to_string()).collect();
+    }
+    let n: usize = digits.parse().unwrap_or(0);
+    if n == 0 {
+        return vec![ONES[0].to_string()];
+    }
+    let mut words = Vec::new();
+    let push_below_hundred = |words: &mut Vec<String>, n: usize| match n {
+        0 => {}
+        1..=19 => words.push(ONES[n].to_string()),
+        _ => {
+            words.push(TENS[n / 10].to_string());
+            if !n.is_multiple_of(10) {
+                words.push(ONES[n % 10].to_string());
+            }
+        }
+    };
+    if n >= 1000 {
+        words.extend([ONES[n / 1000].to_string(), "thousand".to_string()]);
+    }
+    if n % 1000 >= 100 {
+        words.extend([ONES[n % 1000 / 100].to_string(), "hundred".to_string()]);
+    }
+    push_below_hundred(&mut words, n % 100);
+    words
+}
+
+/// Metaphone code of a word (Lawrence Philips' original rules)
+pub fn metaphone(word: &str) -> String {
+    let w: Vec<char> = word
+        .chars()
+        .filter(char::is_ascii_alphabetic)
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    let at = |i: usize| w.get(i).copied().unwrap_or('\0');
+    let is_vowel = |c: char| matches!(c, 'A' | 'E' | 'I' | 'O' | 'U');
+    let mut code = String::new();
+    if w.is_empty() {
+        return code;
+    }
+
+    // Silent or special initial letters
+    let start = match (w[0], at(1)) {
+        ('A', 'E') | ('G', 'N') | ('K', 'N') | ('P', 'N') | ('W', 'R') => 1,
+        ('X', _) => {
+            code.push('S');
+            1
+        }
+        ('W', 'H') => {
+            code.push('W');
+            2
+        }
+        _ => 0,
+    };
+
+    for i in start..w.len() {
+        let c = w[i];
+        let prev = if i > 0 { w[i - 1] } else { '\0' };
+        let (next, after) = (at(i + 1), at(i + 2));
+        let last = i + 1 == w.len();
+        if c == prev && c != 'C' {
+            continue;
+        }
+        match c {
+            'A' | 'E' | 'I' | 'O' | 'U' if i == 0 => code.push(c),
+            'B' if !(prev == 'M' && last) => code.push('B'),
+            'C' if next == 'I' && after == 'A' => code.push('X'),
+            'C' if next == 'H' => code.push(if prev == 'S' { 'K' } else { 'X' }),
+            // "SCE", "SCI" and "SCY" are just S
+            'C' if matches!(next, 'I' | 'E' | 'Y') && prev == 'S' => {}
+            'C' if matches!(next, 'I' | 'E' | 'Y') => code.push('S'),
+            'C' => code.push('K'),
+            'D' if next == 'G' && matches!(after, 'E' | 'Y' | 'I') => code.push('J'),
+            'D' => code.push('T'),
+            'G' if next == 'H' && !(i + 2 == w.len() || is_vowel(after)) => {}
+            'G' if next == 'N' && (i + 2 == w.len() || (after == 'E' && at(i + 3) == 'D' && i + 4 == w.len())) => {}
+            'G' if prev == 'D' && matches!(next, 'E' | 'I' | 'Y') => {}
+            'G' if matches!(next, 'E' | 'I' | 'Y') => code.push('J'),
+            'G' => code.push('K'),
+            'H' if is_vowel(next) && !matches!(prev, 'C' | 'S' | 'P' | 'T' | 'G') => code.push('H'),
+            'K' if prev != 'C' => code.push('K'),
+            'P' => code.push(if next == 'H' { 'F' } else { 'P' }),
+            'Q' => code.push('K'),
+            'S' if next == 'H' || (next == 'I' && matches!(after, 'O' | 'A')) => code.push('X'),
+            'S' => code.push('S'),
+            'T' if next == 'I' && matches!(after, 'O' | 'A') => code.push('X'),
+            'T' if next == 'H' => code.push('0'),
+            'T' if !(next == 'C' && after == 'H') => code.push('T'),
+            'V' => code.push('F'),
+            'W' | 'Y' if is_vowel(next) => code.push(c),
+            'X' => code.push_str("KS"),
+            'Z' => code.push('S'),
+            'F' | 'J' | 'L' | 'M' | 'N' | 'R' => code.push(c),
+            _ => {}
+        }
+    }
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(text: &str) -> Vec<String> {
+        text.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_metaphone() {
+        assert_eq!(metaphone("machinery"), "MXNR");
+        assert_eq!(metaphone("machinary"), "MXNR");
+        assert_eq!(metaphone("cubic"), metaphone("cubik"));
+        assert_eq!(metaphone("for"), metaphone("four"));
+        assert_eq!(metaphone("phone"), metaphone("fone"));
+        assert_eq!(metaphone("know"), metaphone("no"));
+        assert_eq!(metaphone("thank"), "0NK");
+        assert_eq!(metaphone("school"), "SKL");
+        assert_eq!(metaphone("xylophone"), "SLFN");
+        assert_eq!(metaphone("whistle"), "WSTL");
+        assert_eq!(metaphone("nation"), "NXN");
+        assert_eq!(metaphone("gnome"), "NM");
+        assert_eq!(metaphone("lamb"), "LM");
+        assert_eq!(metaphone("edge"), "EJ");
+        assert_eq!(metaphone(""), "");
+        assert_ne!(metaphone("calling"), metaphone("closed"));
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("Thank you for calling Cubic-Machinery!"), words("thank you for calling cubic machinery"));
+        assert_eq!(normalize("We’re open, um, 24 hours"), words("we are open twenty four hours"));
+        assert_eq!(normalize("You've reached the the office; it's closed"), words("you have reached the office it is closed"));
+        assert_eq!(normalize("We can't & won't"), words("we can not and will not"));
+        assert_eq!(normalize("Cubic's line: 5551234, ext 0042"), words("cubic line five five five one two three four ext zero zero four two"));
+        assert_eq!(normalize("Open 9 to 1105 since 1999"), words("open nine to one thousand one hundred five since one thousand nine hundred ninety nine"));
+        assert_eq!(normalize("0"), words("zero"));
+        assert_eq!(normalize("press 5 5 5 1 2 3 4"), words("press five five five one two three four"));
+        assert!(normalize(" ... ").is_empty());
+    }
+
+    #[test]
+    fn test_exact_phrase_in_a_longer_transcript() {
+        let found = best_match(
+            "Hello! Thank you for calling Cubic Machinery. Please hold.",
+            &["thank you for calling cubic machinery"],
+        )
+        .unwrap();
+        assert!((found.score - 1.0).abs() < 1e-6, "{:?}", found);
+    }
+
+    #[test]
+    fn test_tolerates_misheard_missing_and_extra_words() {
+        let phrase = ["thank you for calling cubic machinery"];
+        let misheard = best_match("thank you four calling cubik machinary", &phrase).unwrap();
+        assert!(misheard.score > 0.9, "{:?}", misheard);
+
+        // One of six words missing, or one extra word inside the phrase
+        let missing = best_match("thank you for calling machinery", &phrase).unwrap();
+        assert!((missing.score - (1.0 - 1.0 / 6.0)).abs() < 1e-5, "{:?}", missing);
+        let extra = best_match("thank you all for calling cubic machinery", &phrase).unwrap();
+        assert!((extra.score - (1.0 - 1.0 / 12.0)).abs() < 1e-5, "{:?}", extra);
+    }
+
+    #[test]
+    fn test_different_greeting_scores_low() {
+        let found = best_match(
+            "the number you have dialed is not in service",
+            &["thank you for calling cubic machinery"],
+        )
+        .unwrap();
+        assert!(found.score < 0.3, "{:?}", found);
+        assert_eq!(best_match("", &["thank you for calling"]).unwrap().score, 0.0);
+        assert_eq!(best_match("anything", &[]), None);
+    }
+
+    #[test]
+    fn test_best_of_several_phrases() {
+        let found = best_match(
+            "you have reached cubic machinery after hours",
+            &["thank you for calling cubic machinery", "you've reached cubic machinery"],
+        )
+        .unwrap();
+        assert_eq!(found.phrase, "you've reached cubic machinery");
+        assert!((found.score - 1.0).abs() < 1e-6, "{:?}", found);
+    }
+
+    #[test]
+    fn test_parse_policy() {
+        assert_eq!("audio".parse::<MatchPolicy>().unwrap(), MatchPolicy::Audio);
+        assert_eq!(" Either ".parse::<MatchPolicy>().unwrap(), MatchPolicy::Either);
+        assert_eq!("WEIGHTED".parse::<MatchPolicy>().unwrap(), MatchPolicy::Weighted);
+        assert_eq!(MatchPolicy::Both.to_string(), "both");
+        assert!("any".parse::<MatchPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_policies() {
+        let pass = Some(Score { value: 0.9, threshold: 0.8 });
+        let fail = Some(Score { value: 0.7, threshold: 0.8 });
+        let cases = [
+            (MatchPolicy::Audio, [true, true, false, false]),
+            (MatchPolicy::Transcript, [true, false, true, false]),
+            (MatchPolicy::Either, [true, true, true, false]),
+            (MatchPolicy::Both, [true, false, false, false]),
+        ];
+        for (policy, expected) in cases {
+            let decided = [(pass, pass), (pass, fail), (fail, pass), (fail, fail)]
+                .map(|(audio, transcript)| policy.decide(audio, transcript, 0.5));
+            assert_eq!(decided, expected, "{}", policy);
+        }
+        assert!(!MatchPolicy::Either.decide(None, None, 0.5));
+        assert!(MatchPolicy::Transcript.decide(None, pass, 0.5));
+    }
+
+    #[test]
+    fn test_weighted_policy() {
+        let audio = Some(Score { value: 0.70, threshold: 0.75 });
+        let transcript = Some(Score { value: 1.0, threshold: 0.8 });
+        // A clear transcript makes up for audio just below its threshold...
+        assert!(MatchPolicy::Weighted.decide(audio, transcript, 0.5));
+        // ...unless it has little weight
+        assert!(!MatchPolicy::Weighted.decide(audio, transcript, 0.1));
+        assert!(!MatchPolicy::Weighted.decide(None, transcript, 0.5));
+    }
+}
@@ -0,0 +1,759 @@
+//! Versioned reference store
+//!
+//! Each reference (a target's greeting, one of its named greetings such as
+//! `night`, or one of its IVR capture steps) keeps every enrolled version in
+//! its own directory, next to the audio clip it was computed from, its
+//! transcript, when it was created and which models produced it:
+//! ```text
+//! models/
+//!   reference_embedding.bin        active greeting reference (read by checks)
+//!   versions/
+//!     greeting/
+//!       active                     number of the active version
+//!       0001/embedding.bin
+//!       0001/audio.wav
+//!       0001/meta.json
+//!       pending/...                candidate awaiting approval
+//!     step1/...
+//! ```
+//! Activating a version copies its embedding file to the path checks read, so
+//! enrolling and rolling back need no change to the matching path.
+//!
+//! The active version is the anchor: version files are never rewritten, and
+//! a check may only replace the active copy with audio that stays within the
+//! drift limit of the anchor. A check that finds no reference at all saves
+//! its audio as a pending candidate, which is only used once approved.
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::embedding::AudioEmbedder;
+use crate::embedding_file::{self, EmbeddingFile, ModelHash, StoredEmbedding};
+use crate::model_manager::{named_reference_path_in, reference_path_in};
+
+/// Directory (inside a reference directory) holding the versions
+const VERSIONS_DIR: &str = "versions";
+
+/// Directory (next to the versions) holding a candidate awaiting approval
+const PENDING_DIR: &str = "pending";
+
+/// Default largest cosine distance (1 - similarity) from the anchor that a
+/// check may move the active reference
+pub const DEFAULT_MAX_DRIFT: f32 = 0.10;
+
+/// Name of the greeting (`None`) or an IVR capture step's reference, as used
+/// in the versions directory and the history
+pub fn slot_name(step: Option<usize>) -> String {
+    match step {
+        None => "greeting".to_string(),
+        Some(step) => format!("step{}", step),
+    }
+}
+
+/// Where a version came from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ReferenceSource {
+    /// Enrolled from a WAV file
+    Wav { path: String },
+    /// Enrolled from a live call to the target
+    Call,
+    /// Captured by a scheduled check that found no reference
+    Check,
+    /// The unversioned reference found when the store was first used
+    Imported,
+}
+
+impl std::fmt::Display for ReferenceSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReferenceSource::Wav { path } => write!(f, "wav {}", path),
+            ReferenceSource::Call => write!(f, "call"),
+            ReferenceSource::Check => write!(f, "check"),
+            ReferenceSource::Imported => write!(f, "imported"),
+        }
+    }
+}
+
+/// Models that produced an embedding (embeddings from different models are
+/// not comparable)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelIdentity {
+    pub embedder: String,
+    /// SHA-256 of the embedder model (hex; empty if unknown)
+    #[serde(default)]
+    pub embedder_sha256: String,
+    pub whisper: String,
+}
+
+/// Description of one version, stored as `meta.json`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReferenceMeta {
+    /// Version number (0 for a pending candidate)
+    pub version: u32,
+    pub created_at: DateTime<Utc>,
+    pub source: ReferenceSource,
+    pub transcript: Option<String>,
+    pub model: ModelIdentity,
+    pub dimension: usize,
+    /// Length of the saved audio clip (none for imported references)
+    pub audio_ms: Option<u64>,
+}
+
+/// A new version to enroll
+pub struct Enrollment<'a> {
+    pub embedding: &'a [f32],
+    /// 16 kHz mono audio the embedding was computed from
+    pub audio: &'a [f32],
+    pub transcript: Option<String>,
+    pub source: ReferenceSource,
+    /// Path of the embedder model
+    pub embedder: String,
+    /// Path of the Whisper model
+    pub whisper: String,
+    /// Hash of the embedder model, written into the embedding file and the
+    /// metadata
+    pub model_sha256: ModelHash,
+}
+
+/// The versions of one reference
+pub struct ReferenceStore {
+    /// Embedding file read by checks
+    active_path: PathBuf,
+    versions_dir: PathBuf,
+    slot: String,
+}
+
+impl ReferenceStore {
+    /// Store for the greeting (`None`) or an IVR capture step of the
+    /// reference set in `reference_dir`
+    pub fn new(reference_dir: &str, step: Option<usize>) -> Self {
+        Self::for_path(&reference_path_in(reference_dir, step))
+    }
+
+    /// Store for a named greeting (e.g. `night`) of the reference set in
+    /// `reference_dir`
+    pub fn named(reference_dir: &str, name: &str) -> Self {
+        Self::for_path(&named_reference_path_in(reference_dir, name))
+    }
+
+    /// Store whose active file is `active_path` (`reference_embedding.bin`
+    /// is the greeting, `reference_embedding_stepN.bin` step N and
+    /// `reference_embedding_<name>.bin` a named greeting)
+    pub fn for_path(active_path: &str) -> Self {
+        let path = Path::new(active_path);
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+        let slot = match stem.strip_prefix("reference_embedding") {
+            Some("") => slot_name(None),
+            Some(rest) => rest.trim_start_matches('_').to_string(),
+            None => stem.to_string(),
+        };
+        let dir = path.parent().unwrap_or(Path::new("."));
+        Self {
+            active_path: path.to_path_buf(),
+            versions_dir: dir.join(VERSIONS_DIR).join(&slot),
+            slot,
+        }
+    }
+
+    /// Embedding file read by checks
+    pub fn active_path(&self) -> &Path {
+        &self.active_path
+    }
+
+    /// `greeting`, `stepN` or the name of a named greeting
+    pub fn slot(&self) -> &str {
+        &self.slot
+    }
+
+    fn version_dir(&self, version: u32) -> PathBuf {
+        self.versions_dir.join(format!("{:04}", version))
+    }
+
+    /// Saved audio clip of a version
+    pub fn audio_path(&self, version: u32) -> PathBuf {
+        self.version_dir(version).join("audio.wav")
+    }
+
+    /// All versions, oldest first
+    pub fn versions(&self) -> Result<Vec<ReferenceMeta>> {
+        let entries = match std::fs::read_dir(&self.versions_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", self.versions_dir.display())),
+        };
+
+        let mut versions = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            if let Some(version) = entry.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) {
+                versions.push(self.get(version)?);
+            }
+        }
+        versions.sort_by_key(|meta| meta.version);
+        Ok(versions)
+    }
+
+    /// Description of one version
+    pub fn get(&self, version: u32) -> Result<ReferenceMeta> {
+        let path = self.version_dir(version).join("meta.json");
+        let json = std::fs::read_to_string(&path)
+            .with_context(|| format!("Reference version {} not found ({})", version, path.display()))?;
+        serde_json::from_str(&json).with_context(|| format!("Invalid reference metadata in {}", path.display()))
+    }
+
+    fn embedding_path(&self, version: u32) -> PathBuf {
+        self.version_dir(version).join("embedding.bin")
+    }
+
+    /// Embedding file of one version
+    pub fn stored(&self, version: u32) -> Result<StoredEmbedding> {
+        let path = self.embedding_path(version);
+        embedding_file::read(&path)?
+            .with_context(|| format!("Reference version {} has no embedding ({})", version, path.display()))
+    }
+
+    /// Embedding of one version
+    pub fn embedding(&self, version: u32) -> Result<Vec<f32>> {
+        Ok(self.stored(version)?.into_embedding())
+    }
+
+    /// Version currently copied to the active path
+    pub fn active_version(&self) -> Option<u32> {
+        std::fs::read_to_string(self.versions_dir.join("active"))
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+    }
+
+    /// Audio clip the active version was computed from, if it has one
+    pub fn active_audio_path(&self) -> Option<PathBuf> {
+        Some(self.audio_path(self.active_version()?)).filter(|path| path.exists())
+    }
+
+    /// Whether the active file still holds the active version's embedding
+    /// (false once a check has replaced it, or for an unversioned file)
+    pub fn active_is_versioned(&self) -> bool {
+        let Some(version) = self.active_version() else {
+            return false;
+        };
+        match (embedding_file::read(&self.active_path), self.stored(version)) {
+            (Ok(Some(active)), Ok(stored)) => active.embedding() == stored.embedding(),
+            _ => false,
+        }
+    }
+
+    /// Save a new version and make it active. An unversioned active file
+    /// found on first use is kept as version 1 so it can be rolled back to.
+    pub fn enroll(&self, enrollment: Enrollment<'_>) -> Result<ReferenceMeta> {
+        let version = self.next_version()?;
+        let meta = self.write_version(&self.version_dir(version), version, enrollment)?;
+        self.activate(version)?;
+        Ok(meta)
+    }
+
+    /// Number for a new version, keeping an unversioned active file first
+    fn next_version(&self) -> Result<u32> {
+        let mut versions = self.versions()?;
+        if versions.is_empty() {
+            if let Some(meta) = self.import_active()? {
+                versions.push(meta);
+            }
+        }
+        Ok(versions.last().map_or(1, |meta| meta.version + 1))
+    }
+
+    /// Write the audio, embedding and metadata of an enrollment into `dir`
+    fn write_version(&self, dir: &Path, version: u32, enrollment: Enrollment<'_>) -> Result<ReferenceMeta> {
+        let meta = ReferenceMeta {
+            version,
+            created_at: Utc::now(),
+            source: enrollment.source,
+            transcript: enrollment.transcript,
+            model: ModelIdentity {
+                embedder: enrollment.embedder,
+                embedder_sha256: embedding_file::hex(&enrollment.model_sha256),
+                whisper: enrollment.whisper,
+            },
+            dimension: enrollment.embedding.len(),
+            audio_ms: Some(crate::rtp::samples_to_duration_ms(enrollment.audio.len())),
+        };
+        std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        crate::rtp::save_wav(enrollment.audio, dir.join("audio.wav"))?;
+        embedding_file::write(
+            dir.join("embedding.bin"),
+            &EmbeddingFile::new(enrollment.embedding.to_vec(), enrollment.model_sha256),
+        )?;
+        write_meta(dir, &meta)?;
+        Ok(meta)
+    }
+
+    fn pending_dir(&self) -> PathBuf {
+        self.versions_dir.join(PENDING_DIR)
+    }
+
+    /// The candidate awaiting approval, if any
+    pub fn pending(&self) -> Result<Option<ReferenceMeta>> {
+        let path = self.pending_dir().join("meta.json");
+        let json = match std::fs::read_to_string(&path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        serde_json::from_str(&json)
+            .map(Some)
+            .with_context(|| format!("Invalid reference metadata in {}", path.display()))
+    }
+
+    /// Save a candidate for approval. A candidate already waiting is kept
+    /// (it is the one an operator may be reviewing); returns whether this
+    /// one was saved.
+    pub fn propose(&self, enrollment: Enrollment<'_>) -> Result<bool> {
+        if self.pending()?.is_some() {
+            return Ok(false);
+        }
+        self.write_version(&self.pending_dir(), 0, enrollment)?;
+        info!("Saved candidate reference {} for approval", self.pending_dir().display());
+        Ok(true)
+    }
+
+    /// Turn the pending candidate into a new version and make it active
+    pub fn approve(&self) -> Result<ReferenceMeta> {
+        let mut meta = self.pending()?.context("No candidate reference is awaiting approval")?;
+        // Validates the embedding before it becomes a version
+        embedding_file::read(self.pending_dir().join("embedding.bin"))?
+            .context("The candidate reference has no embedding")?;
+
+        meta.version = self.next_version()?;
+        let dir = self.version_dir(meta.version);
+        std::fs::rename(self.pending_dir(), &dir)
+            .with_context(|| format!("Failed to move the candidate to {}", dir.display()))?;
+        write_meta(&dir, &meta)?;
+        self.activate(meta.version)?;
+        Ok(meta)
+    }
+
+    /// Discard the pending candidate, so the next check proposes a new one
+    pub fn reject(&self) -> Result<ReferenceMeta> {
+        let meta = self.pending()?.context("No candidate reference is awaiting approval")?;
+        std::fs::remove_dir_all(self.pending_dir())
+            .with_context(|| format!("Failed to remove {}", self.pending_dir().display()))?;
+        Ok(meta)
+    }
+
+    /// The anchor that checks may not drift away from: the active version.
+    /// An unversioned active file is first kept as version 1.
+    pub fn anchor(&self) -> Result<Option<StoredEmbedding>> {
+        let version = match self.active_version() {
+            Some(version) => version,
+            None if self.versions()?.is_empty() => match self.import_active()? {
+                Some(meta) => meta.version,
+                None => return Ok(None),
+            },
+            None => bail!("No active version in {}", self.versions_dir.display()),
+        };
+        self.stored(version).map(Some)
+    }
+
+    /// Keep the active file (as is) as version 1, if there is one
+    fn import_active(&self) -> Result<Option<ReferenceMeta>> {
+        let Some(stored) = embedding_file::read(&self.active_path)? else {
+            return Ok(None);
+        };
+        let meta = ReferenceMeta {
+            version: 1,
+            created_at: std::fs::metadata(&self.active_path)
+                .and_then(|m| m.modified())
+                .map(DateTime::<Utc>::from)
+                .unwrap_or_else(|_| Utc::now()),
+            source: ReferenceSource::Imported,
+            transcript: None,
+            model: ModelIdentity {
+                embedder_sha256: stored.model_sha256().map(embedding_file::hex).unwrap_or_default(),
+                ..ModelIdentity::default()
+            },
+            dimension: stored.embedding().len(),
+            audio_ms: None,
+        };
+        std::fs::create_dir_all(self.version_dir(1))?;
+        std::fs::copy(&self.active_path, self.embedding_path(1))
+            .with_context(|| format!("Failed to copy {}", self.active_path.display()))?;
+        write_meta(&self.version_dir(1), &meta)?;
+        self.set_active(1)?;
+        info!("Kept existing reference {} as version 1", self.active_path.display());
+        Ok(Some(meta))
+    }
+
+    fn set_active(&self, version: u32) -> Result<()> {
+        let path = self.versions_dir.join("active");
+        std::fs::write(&path, format!("{}\n", version)).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Copy a version's embedding file to the active path
+    pub fn activate(&self, version: u32) -> Result<()> {
+        // Validates the file before it replaces the active one
+        self.stored(version)?;
+        if let Some(parent) = self.active_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::copy(self.embedding_path(version), &self.active_path)
+            .with_context(|| format!("Failed to write {}", self.active_path.display()))?;
+        self.set_active(version)?;
+        info!("Activated reference version {} ({})", version, self.active_path.display());
+        Ok(())
+    }
+
+    /// Activate `version`, or by default the newest version older than the
+    /// active one. Returns the version activated.
+    pub fn rollback(&self, version: Option<u32>) -> Result<u32> {
+        let version = match version {
+            Some(version) => version,
+            None => {
+                let active = self.active_version().context("No active reference version to roll back from")?;
+                match self.versions()?.iter().rev().find(|meta| meta.version < active) {
+                    Some(meta) => meta.version,
+                    None => bail!("Version {} is the oldest reference; nothing to roll back to", active),
+                }
+            }
+        };
+        self.activate(version)?;
+        Ok(version)
+    }
+
+    /// Cosine similarity between two versions' embeddings
+    pub fn compare(&self, a: u32, b: u32) -> Result<f32> {
+        let (a, b) = (self.stored(a)?, self.stored(b)?);
+        if let (Some(model_a), Some(model_b)) = (a.model_sha256(), b.model_sha256()) {
+            if model_a != model_b {
+                bail!(
+                    "Versions were computed by different models ({} and {})",
+                    embedding_file::short_hex(model_a),
+                    embedding_file::short_hex(model_b)
+                );
+            }
+        }
+        if a.embedding().len() != b.embedding().len() {
+            bail!("Embeddings have different dimensions ({} and {})", a.embedding().len(), b.embedding().len());
+        }
+        Ok(AudioEmbedder::cosine_similarity(a.embedding(), b.embedding()))
+    }
+}
+
+fn write_meta(dir: &Path, meta: &ReferenceMeta) -> Result<()> {
+    let path = dir.join("meta.json");
+    std::fs::write(&path, serde_json::to_string_pretty(meta)?).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// One line per version, marking the active one
+pub fn format_list(store: &ReferenceStore) -> Result<String> {
+    let versions = store.versions()?;
+    let pending = store.pending()?;
+    let mut out = String::new();
+    if versions.is_empty() {
+        if store.active_path().exists() {
+            writeln!(out, "No versions yet; {} is unversioned (enroll to start versioning)", store.active_path().display())?;
+        } else if pending.is_none() {
+            writeln!(out, "No reference at {}", store.active_path().display())?;
+        }
+    }
+
+    let active = store.active_version();
+    for meta in &versions {
+        writeln!(
+            out,
+            "{} {:>4}  {}  {:<10}  {}",
+            if Some(meta.version) == active { "*" } else { " " },
+            meta.version,
+            meta.created_at.format("%Y-%m-%d %H:%M"),
+            meta.source.to_string(),
+            meta.transcript.as_deref().map(|t| format!("\"{}\"", t)).unwrap_or_default()
+        )?;
+    }
+    if active.is_some() && !store.active_is_versioned() {
+        writeln!(out, "\nNote: {} has changed since it was activated", store.active_path().display())?;
+    }
+    if let Some(meta) = pending {
+        writeln!(
+            out,
+            "{}Candidate captured {} awaiting approval: {}",
+            if out.is_empty() { "" } else { "\n" },
+            meta.created_at.format("%Y-%m-%d %H:%M"),
+            meta.transcript.as_deref().map(|t| format!("\"{}\"", t)).unwrap_or_else(|| "-".to_string())
+        )?;
+        writeln!(out, "(`reference approve` to use it, `reference reject` to discard it)")?;
+    }
+    Ok(out)
+}
+
+/// Full description of one version
+pub fn format_meta(store: &ReferenceStore, meta: &ReferenceMeta) -> String {
+    let mut out = if meta.version == 0 {
+        "Version:    candidate (awaiting approval)\n".to_string()
+    } else {
+        let active = store.active_version() == Some(meta.version);
+        format!("Version:    {}{}\n", meta.version, if active { " (active)" } else { "" })
+    };
+    out.push_str(&format!("Created:    {}\n", meta.created_at.to_rfc3339()));
+    out.push_str(&format!("Source:     {}\n", meta.source));
+    out.push_str(&format!("Transcript: {}\n", meta.transcript.as_deref().unwrap_or("-")));
+    out.push_str(&format!("Embedder:   {}\n", if meta.model.embedder.is_empty() { "-" } else { &meta.model.embedder }));
+    if !meta.model.embedder_sha256.is_empty() {
+        out.push_str(&format!("SHA-256:    {}\n", meta.model.embedder_sha256));
+    }
+    out.push_str(&format!("Whisper:    {}\n", if meta.model.whisper.is_empty() { "-" } else { &meta.model.whisper }));
+    out.push_str(&format!("Dimension:  {}\n", meta.dimension));
+    match meta.audio_ms {
+        Some(ms) => {
+            let audio = if meta.version == 0 { store.pending_dir().join("audio.wav") } else { store.audio_path(meta.version) };
+            out.push_str(&format!("Audio:      {} ({} ms)\n", audio.display(), ms))
+        }
+        None => out.push_str("Audio:      -\n"),
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store(name: &str) -> (PathBuf, ReferenceStore) {
+        let dir = std::env::temp_dir().join(format!("phonecheck_refstore_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = ReferenceStore::new(dir.to_str().unwrap(), None);
+        (dir, store)
+    }
+
+    fn embedding(value: f32) -> Vec<f32> {
+        (0..768).map(|i| if i % 2 == 0 { value } else { 1.0 }).collect()
+    }
+
+    fn enrollment<'a>(embedding: &'a [f32], transcript: &str, source: ReferenceSource) -> Enrollment<'a> {
+        Enrollment {
+            embedding,
+            audio: &[0.0; 1600],
+            transcript: Some(transcript.to_string()),
+            source,
+            embedder: String::new(),
+            whisper: String::new(),
+            model_sha256: [7; 32],
+        }
+    }
+
+    fn enroll(store: &ReferenceStore, value: f32, transcript: &str) -> ReferenceMeta {
+        let embedding = embedding(value);
+        store
+            .enroll(enrollment(&embedding, transcript, ReferenceSource::Wav { path: "greeting.wav".to_string() }))
+            .unwrap()
+    }
+
+    fn active_embedding(store: &ReferenceStore) -> Option<Vec<f32>> {
+        embedding_file::read(store.active_path()).unwrap().map(StoredEmbedding::into_embedding)
+    }
+
+    #[test]
+    fn test_enroll_activates_new_versions() {
+        let (dir, store) = temp_store("enroll");
+        assert!(store.versions().unwrap().is_empty());
+        assert_eq!(store.active_version(), None);
+
+        let first = enroll(&store, 1.0, "thank you for calling");
+        let second = enroll(&store, -1.0, "thanks for calling");
+        assert_eq!((first.version, second.version), (1, 2));
+        assert_eq!(first.audio_ms, Some(100));
+        assert_eq!(store.active_version(), Some(2));
+        assert!(store.active_is_versioned());
+        assert_eq!(
+            active_embedding(&store),
+            Some(store.embedding(2).unwrap())
+        );
+        assert!(store.audio_path(1).exists());
+        assert_eq!(store.active_audio_path(), Some(store.audio_path(2)));
+
+        let versions = store.versions().unwrap();
+        assert_eq!(versions, vec![first, second]);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_rollback_to_previous_and_specific_version() {
+        let (dir, store) = temp_store("rollback");
+        enroll(&store, 1.0, "one");
+        enroll(&store, -1.0, "two");
+        enroll(&store, 0.5, "three");
+
+        assert_eq!(store.rollback(None).unwrap(), 2);
+        assert_eq!(store.rollback(None).unwrap(), 1);
+        assert!(store.rollback(None).is_err());
+        assert_eq!(store.rollback(Some(3)).unwrap(), 3);
+        assert!(store.rollback(Some(9)).is_err());
+        assert_eq!(store.active_version(), Some(3));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_existing_reference_is_imported_as_version_one() {
+        let (dir, store) = temp_store("import");
+        // Legacy headerless file
+        let legacy: Vec<u8> = [0.25f32; 768].iter().flat_map(|f| f.to_le_bytes()).collect();
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(store.active_path(), &legacy).unwrap();
+
+        let meta = enroll(&store, 1.0, "new greeting");
+        assert_eq!(meta.version, 2);
+        assert_eq!(meta.model.embedder_sha256, "07".repeat(32));
+        let imported = store.get(1).unwrap();
+        assert_eq!(imported.source, ReferenceSource::Imported);
+        assert_eq!(imported.audio_ms, None);
+        assert_eq!(imported.model.embedder_sha256, "");
+        assert_eq!(store.stored(1).unwrap(), StoredEmbedding::Legacy(vec![0.25; 768]));
+
+        // Rolled back byte for byte
+        assert_eq!(store.rollback(None).unwrap(), 1);
+        assert_eq!(std::fs::read(store.active_path()).unwrap(), legacy);
+        // An imported reference has no audio to align against
+        assert_eq!(store.active_audio_path(), None);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        let (dir, store) = temp_store("compare");
+        enroll(&store, 1.0, "one");
+        enroll(&store, -1.0, "two");
+
+        assert!((store.compare(1, 1).unwrap() - 1.0).abs() < 1e-6);
+        assert!(store.compare(1, 2).unwrap().abs() < 1e-6);
+        assert!(store.compare(1, 3).is_err());
+
+        // Embeddings from another model are not comparable
+        embedding_file::write(store.embedding_path(2), &EmbeddingFile::new(vec![1.0; 768], [8; 32])).unwrap();
+        let err = store.compare(1, 2).unwrap_err().to_string();
+        assert!(err.contains("different models"), "{}", err);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_list_marks_active_and_changed_reference() {
+        let (dir, store) = temp_store("list");
+        assert!(format_list(&store).unwrap().starts_with("No reference"));
+
+        enroll(&store, 1.0, "one");
+        enroll(&store, -1.0, "two");
+        store.rollback(None).unwrap();
+        let list = format_list(&store).unwrap();
+        let lines: Vec<&str> = list.lines().collect();
+        assert!(lines[0].starts_with("*    1"), "{}", list);
+        assert!(lines[1].starts_with("     2"), "{}", list);
+        assert!(lines[1].ends_with("\"two\""), "{}", list);
+        assert!(!list.contains("changed"));
+
+        // A check replaced the active file
+        embedding_file::write(store.active_path(), &EmbeddingFile::new(vec![0.1; 768], [7; 32])).unwrap();
+        assert!(format_list(&store).unwrap().contains("has changed since it was activated"));
+
+        let meta = store.get(1).unwrap();
+        let shown = format_meta(&store, &meta);
+        assert!(shown.starts_with("Version:    1 (active)\n"), "{}", shown);
+        assert!(shown.contains("Source:     wav greeting.wav"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_candidate_needs_approval() {
+        let (dir, store) = temp_store("approve");
+        assert!(store.approve().is_err());
+
+        let first = embedding(1.0);
+        assert!(store.propose(enrollment(&first, "thank you for calling", ReferenceSource::Check)).unwrap());
+        // Nothing is active until the candidate is approved
+        assert!(!store.active_path().exists());
+        assert_eq!(store.anchor().unwrap(), None);
+        assert!(format_list(&store).unwrap().starts_with("Candidate captured"));
+        assert!(format_meta(&store, &store.pending().unwrap().unwrap()).contains("pending/audio.wav"));
+
+        // A later check does not replace the candidate under review
+        let second = embedding(-1.0);
+        assert!(!store.propose(enrollment(&second, "not in service", ReferenceSource::Check)).unwrap());
+
+        let meta = store.approve().unwrap();
+        assert_eq!(meta.version, 1);
+        assert_eq!(meta.source, ReferenceSource::Check);
+        assert_eq!(meta.transcript.as_deref(), Some("thank you for calling"));
+        assert_eq!(store.get(1).unwrap(), meta);
+        assert_eq!(store.pending().unwrap(), None);
+        assert_eq!(active_embedding(&store), Some(first.clone()));
+        assert_eq!(store.anchor().unwrap().unwrap().embedding(), &first[..]);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_rejected_candidate_is_discarded() {
+        let (dir, store) = temp_store("reject");
+        let bad = embedding(-1.0);
+        store.propose(enrollment(&bad, "the number you have dialed", ReferenceSource::Check)).unwrap();
+        assert_eq!(store.reject().unwrap().transcript.as_deref(), Some("the number you have dialed"));
+        assert_eq!(store.pending().unwrap(), None);
+        assert!(store.reject().is_err());
+        assert!(store.versions().unwrap().is_empty());
+
+        // The next check may propose again
+        let good = embedding(1.0);
+        assert!(store.propose(enrollment(&good, "thank you for calling", ReferenceSource::Check)).unwrap());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_anchor_is_the_active_version() {
+        let (dir, store) = temp_store("anchor");
+        // An unversioned reference becomes version 1, the anchor
+        std::fs::create_dir_all(&dir).unwrap();
+        embedding_file::write(store.active_path(), &EmbeddingFile::new(embedding(0.5), [7; 32])).unwrap();
+        assert_eq!(store.anchor().unwrap().unwrap().embedding(), &embedding(0.5)[..]);
+        assert_eq!(store.active_version(), Some(1));
+        assert_eq!(store.get(1).unwrap().source, ReferenceSource::Imported);
+
+        // Replacing the active copy leaves the anchor alone
+        embedding_file::write(store.active_path(), &EmbeddingFile::new(embedding(0.6), [7; 32])).unwrap();
+        assert_eq!(store.anchor().unwrap().unwrap().embedding(), &embedding(0.5)[..]);
+
+        // Enrolling moves it
+        enroll(&store, 1.0, "new greeting");
+        assert_eq!(store.anchor().unwrap().unwrap().embedding(), &embedding(1.0)[..]);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_store_for_path_matches_step() {
+        for step in [None, Some(1), Some(12)] {
+            let store = ReferenceStore::new("./models/support", step);
+            let by_path = ReferenceStore::for_path(store.active_path().to_str().unwrap());
+            assert_eq!(by_path.slot(), slot_name(step));
+            assert_eq!(by_path.versions_dir, store.versions_dir);
+        }
+        assert_eq!(ReferenceStore::named("./models/main", "night").slot(), "night");
+        assert_eq!(ReferenceStore::for_path("/tmp/custom.bin").slot(), "custom");
+    }
+
+    #[test]
+    fn test_step_references_are_separate() {
+        let (dir, greeting) = temp_store("steps");
+        let step = ReferenceStore::new(dir.to_str().unwrap(), Some(1));
+        enroll(&greeting, 1.0, "greeting");
+
+        assert!(step.versions().unwrap().is_empty());
+        assert!(step.active_path().ends_with("reference_embedding_step1.bin"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
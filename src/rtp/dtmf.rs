@@ -0,0 +1,202 @@
+/// RFC 4733 telephone-event (DTMF) encoding
+///
+/// DTMF digits are sent as named events rather than audio tones. Each event
+/// is carried in a series of RTP packets that share the event's start
+/// timestamp, with a growing duration field. The first packet has the marker
+/// bit set, and the last one is sent three times with the End bit set so a
+/// single lost packet doesn't leave the digit "held down".
+///
+/// Payload format (RFC 4733 Section 2.3):
+/// ```text
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |     event     |E|R| volume    |          duration             |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+
+use std::time::Duration;
+
+/// Dynamic payload type we offer for telephone-event in SDP
+pub const TELEPHONE_EVENT_PT: u8 = 101;
+
+/// How long each digit is held (RFC 4733 recommends at least 40ms)
+pub const DIGIT_DURATION: Duration = Duration::from_millis(100);
+
+/// Silence between consecutive digits
+pub const INTER_DIGIT_GAP: Duration = Duration::from_millis(100);
+
+/// Packetization interval for event updates
+pub const PACKET_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Number of times the final (End) packet is sent
+const END_PACKET_REPEATS: usize = 3;
+
+/// Event volume in -dBm0 (10 is a typical level for DTMF)
+const EVENT_VOLUME: u8 = 10;
+
+/// RTP clock rate for telephone-event (matches the 8kHz audio clock)
+const CLOCK_RATE: u32 = 8000;
+
+/// Map a DTMF digit to its RFC 4733 event code
+pub fn digit_to_event(digit: char) -> Option<u8> {
+    match digit.to_ascii_uppercase() {
+        d @ '0'..='9' => Some(d as u8 - b'0'),
+        '*' => Some(10),
+        '#' => Some(11),
+        d @ 'A'..='D' => Some(d as u8 - b'A' + 12),
+        _ => None,
+    }
+}
+
+/// Check that every character is a valid DTMF digit
+pub fn is_valid_digits(digits: &str) -> bool {
+    !digits.is_empty() && digits.chars().all(|c| digit_to_event(c).is_some())
+}
+
+/// Encode the 4-byte telephone-event payload
+pub fn encode_event(event: u8, end: bool, duration: u16) -> [u8; 4] {
+    let mut payload = [0u8; 4];
+    payload[0] = event;
+    payload[1] = (if end { 0x80 } else { 0x00 }) | (EVENT_VOLUME & 0x3F);
+    payload[2..4].copy_from_slice(&duration.to_be_bytes());
+    payload
+}
+
+/// One RTP packet of a DTMF event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventPacket {
+    /// Marker bit (set on the first packet of the event)
+    pub marker: bool,
+    /// telephone-event payload
+    pub payload: [u8; 4],
+}
+
+/// Build the packet sequence for one digit held for `duration`
+///
+/// All packets share the event start timestamp; the caller assigns sequence
+/// numbers and sends them `PACKET_INTERVAL` apart (End repeats back to back).
+pub fn event_packets(event: u8, duration: Duration) -> Vec<EventPacket> {
+    let step = duration_to_units(PACKET_INTERVAL);
+    let total = duration_to_units(duration).max(step);
+
+    let mut packets = Vec::new();
+    let mut elapsed = step;
+    while elapsed < total {
+        packets.push(EventPacket {
+            marker: packets.is_empty(),
+            payload: encode_event(event, false, elapsed as u16),
+        });
+        elapsed += step;
+    }
+
+    let final_duration = total.min(u16::MAX as u32) as u16;
+    for _ in 0..END_PACKET_REPEATS {
+        packets.push(EventPacket {
+            marker: packets.is_empty(),
+            payload: encode_event(event, true, final_duration),
+        });
+    }
+
+    packets
+}
+
+/// Convert a duration to RTP timestamp units at 8kHz
+pub fn duration_to_units(duration: Duration) -> u32 {
+    (duration.as_millis() as u64 * CLOCK_RATE as u64 / 1000) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digit_to_event() {
+        assert_eq!(digit_to_event('0'), Some(0));
+        assert_eq!(digit_to_event('9'), Some(9));
+        assert_eq!(digit_to_event('*'), Some(10));
+        assert_eq!(digit_to_event('#'), Some(11));
+        assert_eq!(digit_to_event('A'), Some(12));
+        assert_eq!(digit_to_event('d'), Some(15));
+        assert_eq!(digit_to_event('E'), None);
+        assert_eq!(digit_to_event(' '), None);
+    }
+
+    #[test]
+    fn test_is_valid_digits() {
+        assert!(is_valid_digits("2"));
+        assert!(is_valid_digits("123#"));
+        assert!(!is_valid_digits(""));
+        assert!(!is_valid_digits("12x"));
+    }
+
+    #[test]
+    fn test_encode_event() {
+        // Digit 5, not ended, duration 800
+        assert_eq!(encode_event(5, false, 800), [5, 10, 0x03, 0x20]);
+        // End bit is the top bit of the second byte
+        assert_eq!(encode_event(11, true, 160), [11, 0x80 | 10, 0x00, 0xA0]);
+    }
+
+    #[test]
+    fn test_event_packets_sequence() {
+        let packets = event_packets(2, Duration::from_millis(100));
+
+        // 100ms at 20ms intervals: updates at 160..640, then 3 End packets at 800
+        assert_eq!(packets.len(), 4 + END_PACKET_REPEATS);
+        assert!(packets[0].marker);
+        assert!(packets[1..].iter().all(|p| !p.marker));
+
+        let durations: Vec<u16> = packets
+            .iter()
+            .map(|p| u16::from_be_bytes([p.payload[2], p.payload[3]]))
+            .collect();
+        assert_eq!(durations, vec![160, 320, 480, 640, 800, 800, 800]);
+
+        let ends: Vec<bool> = packets.iter().map(|p| p.payload[1] & 0x80 != 0).collect();
+        assert_eq!(ends, vec![false, false, false, false, true, true, true]);
+    }
+
+    #[test]
+    fn test_short_event_still_ends() {
+        // Shorter than one packet interval: only End packets, first one marked
+        let packets = event_packets(1, Duration::from_millis(5));
+        assert_eq!(packets.len(), END_PACKET_REPEATS);
+        assert!(packets[0].marker);
+        assert!(packets.iter().all(|p| p.payload[1] & 0x80 != 0));
+    }
+
+    #[test]
+    fn test_duration_to_units() {
+        assert_eq!(duration_to_units(Duration::from_millis(20)), 160);
+        assert_eq!(duration_to_units(Duration::from_secs(1)), 8000);
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        /// Every event ends with exactly END_PACKET_REPEATS End packets
+        #[test]
+        fn events_always_end(event in 0u8..16, ms in 0u64..2000) {
+            let packets = event_packets(event, Duration::from_millis(ms));
+            let ends = packets.iter().filter(|p| p.payload[1] & 0x80 != 0).count();
+            prop_assert_eq!(ends, END_PACKET_REPEATS);
+            prop_assert!(packets.iter().all(|p| p.payload[0] == event));
+        }
+
+        /// Durations never decrease within an event
+        #[test]
+        fn durations_monotonic(ms in 0u64..2000) {
+            let packets = event_packets(3, Duration::from_millis(ms));
+            let durations: Vec<u16> = packets
+                .iter()
+                .map(|p| u16::from_be_bytes([p.payload[2], p.payload[3]]))
+                .collect();
+            prop_assert!(durations.windows(2).all(|w| w[0] <= w[1]));
+        }
+    }
+}
@@ -0,0 +1,197 @@
+/// ITU-T G.107 E-model: transmission rating (R) and MOS estimate of a call
+///
+/// The E-model rates a connection by starting from the best achievable R
+/// and subtracting impairments:
+///
+/// ```text
+/// R = R0 - Is - Id - Ie,eff + A
+/// ```
+///
+/// With the G.107 default values for everything we can't measure (loudness
+/// ratings, noise, sidetone, echo loss), `R0 - Is` is 93.2, and the
+/// talker/listener echo parts of `Id` are negligible, leaving:
+///
+/// - `Id`: the delay impairment `Idd` of the one-way mouth-to-ear delay
+///   `Ta`, estimated as half the RTCP round-trip time plus the
+///   packetization time plus a jitter buffer of twice the measured jitter.
+///   Without an RTT measurement the network delay is taken as zero.
+/// - `Ie,eff`: the codec's equipment impairment `Ie` raised by packet loss
+///   `Ppl` according to its loss robustness `Bpl` (random loss, BurstR = 1).
+///   G.711 has `Ie = 0`, and `Bpl` depends on the concealment (ITU-T G.113
+///   Appendix I): 4.3 when gaps are filled with silence, where every percent
+///   of loss costs several R points, and 25.1 with G.711 Appendix I
+///   waveform substitution.
+///
+/// The advantage factor `A` is 0 (a wired call). R converts to an estimated
+/// MOS (conversational quality, 1-4.5) with the G.107 Annex B formula.
+use serde::{Deserialize, Serialize};
+
+use super::jitter::JitterBufferStats;
+use super::plc::Concealment;
+use super::rtcp::CallStats;
+
+/// `R0 - Is` with the G.107 default parameters
+const R_DEFAULT: f64 = 93.2;
+
+/// Equipment impairment factor of G.711
+const G711_IE: f64 = 0.0;
+
+/// Packet-loss robustness of G.711 without concealment (G.113 Appendix I)
+const G711_BPL: f64 = 4.3;
+
+/// Packet-loss robustness of G.711 with Appendix I concealment (G.113
+/// Appendix I)
+const G711_PLC_BPL: f64 = 25.1;
+
+/// Audio carried in each RTP packet
+const PACKETIZATION_MS: f64 = 20.0;
+
+/// R-factor and MOS estimate of one call
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CallQuality {
+    /// Transmission rating, 0-100 (93.2 is a perfect G.711 call)
+    pub r_factor: f32,
+    /// Estimated mean opinion score, 1-4.5
+    pub mos: f32,
+}
+
+impl std::fmt::Display for CallQuality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MOS {:.2} (R {:.0})", self.mos, self.r_factor)
+    }
+}
+
+impl CallQuality {
+    /// Rate a G.711 call from its packet loss share, jitter and round-trip
+    /// time, with losses filled in by `concealment`
+    pub fn estimate(loss: f32, jitter_ms: f32, rtt_ms: Option<f32>, concealment: Concealment) -> Self {
+        let one_way_ms = rtt_ms.unwrap_or(0.0) as f64 / 2.0 + PACKETIZATION_MS + 2.0 * jitter_ms as f64;
+        let ie_eff = effective_equipment_impairment(loss as f64 * 100.0, concealment);
+        let r = R_DEFAULT - delay_impairment(one_way_ms) - ie_eff;
+        let r = r.clamp(0.0, 100.0);
+        Self { r_factor: r as f32, mos: r_to_mos(r) as f32 }
+    }
+
+    /// Rate a call from what the receiver measured. Loss counts packets the
+    /// network lost plus those the jitter buffer discarded (too late or
+    /// duplicated), as both are gaps to the listener. None if no RTP arrived.
+    pub fn from_stats(packets: &JitterBufferStats, call: &CallStats, concealment: Concealment) -> Option<Self> {
+        if packets.packets_received == 0 {
+            return None;
+        }
+        let network_lost = (call.lost.max(0) as u64).max(packets.packets_lost);
+        let expected = call.expected.max(packets.packets_received + packets.packets_lost);
+        let loss = ((network_lost + packets.packets_dropped) as f32 / expected as f32).min(1.0);
+        Some(Self::estimate(loss, call.jitter_ms, call.rtt_ms, concealment))
+    }
+}
+
+/// `Idd` of G.107 Section 7.4 for a one-way delay in ms
+fn delay_impairment(ta_ms: f64) -> f64 {
+    if ta_ms <= 100.0 {
+        return 0.0;
+    }
+    let x = (ta_ms / 100.0).log2();
+    25.0 * ((1.0 + x.powi(6)).powf(1.0 / 6.0) - 3.0 * (1.0 + (x / 3.0).powi(6)).powf(1.0 / 6.0) + 2.0)
+}
+
+/// `Ie,eff` of G.107 Section 7.5 for a loss percentage
+fn effective_equipment_impairment(ppl: f64, concealment: Concealment) -> f64 {
+    let bpl = match concealment {
+        Concealment::Zero => G711_BPL,
+        Concealment::Waveform => G711_PLC_BPL,
+    };
+    G711_IE + (95.0 - G711_IE) * ppl / (ppl + bpl)
+}
+
+/// MOS from R (G.107 Annex B). The cubic dips below 1 for R under ~6.5,
+/// where it is held at 1.
+fn r_to_mos(r: f64) -> f64 {
+    if r <= 0.0 {
+        1.0
+    } else if r >= 100.0 {
+        4.5
+    } else {
+        (1.0 + 0.035 * r + r * (r - 60.0) * (100.0 - r) * 7e-6).max(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_call_rates_best() {
+        let quality = CallQuality::estimate(0.0, 0.0, Some(20.0), Concealment::Zero);
+        assert!((quality.r_factor - 93.2).abs() < 0.01);
+        assert!((quality.mos - 4.41).abs() < 0.01, "{}", quality);
+        assert_eq!(quality.to_string(), "MOS 4.41 (R 93)");
+    }
+
+    #[test]
+    fn test_loss_degrades_quickly_without_concealment() {
+        // 1% random loss costs ~18 R points with Bpl 4.3
+        let one = CallQuality::estimate(0.01, 0.0, None, Concealment::Zero);
+        assert!((one.r_factor - (93.2 - 95.0 / 5.3)).abs() < 0.01, "{}", one);
+        let five = CallQuality::estimate(0.05, 0.0, None, Concealment::Zero);
+        let twelve = CallQuality::estimate(0.12, 0.0, None, Concealment::Zero);
+        assert!(one.mos > five.mos && five.mos > twelve.mos);
+        assert!(twelve.mos < 2.0, "{}", twelve);
+        assert_eq!(CallQuality::estimate(1.0, 0.0, None, Concealment::Zero).mos, 1.0);
+    }
+
+    #[test]
+    fn test_concealment_softens_loss() {
+        // With Appendix I concealment 1% loss costs under 4 R points, 5%
+        // about 16
+        let one = CallQuality::estimate(0.01, 0.0, None, Concealment::Waveform);
+        assert!((one.r_factor - (93.2 - 95.0 / 26.1)).abs() < 0.01, "{}", one);
+        let five = CallQuality::estimate(0.05, 0.0, None, Concealment::Waveform);
+        assert!(five.mos > 3.8, "{}", five);
+        assert!(five.mos > CallQuality::estimate(0.05, 0.0, None, Concealment::Zero).mos + 1.0);
+        let clean = CallQuality::estimate(0.0, 0.0, None, Concealment::Waveform);
+        assert_eq!(clean, CallQuality::estimate(0.0, 0.0, None, Concealment::Zero));
+    }
+
+    #[test]
+    fn test_delay_impairment() {
+        assert_eq!(delay_impairment(100.0), 0.0);
+        // About 3 at 200 ms, 15 at 300 ms and 24 at 400 ms
+        assert!((delay_impairment(200.0) - 3.0).abs() < 0.5, "{}", delay_impairment(200.0));
+        assert!((delay_impairment(300.0) - 14.7).abs() < 0.5, "{}", delay_impairment(300.0));
+        assert!((delay_impairment(400.0) - 24.1).abs() < 0.5, "{}", delay_impairment(400.0));
+
+        // Jitter and RTT push the one-way delay past 100 ms
+        let slow = CallQuality::estimate(0.0, 40.0, Some(300.0), Concealment::Zero);
+        assert!(slow.r_factor < 90.0, "{}", slow);
+    }
+
+    #[test]
+    fn test_r_to_mos_bounds() {
+        assert_eq!(r_to_mos(-5.0), 1.0);
+        assert_eq!(r_to_mos(120.0), 4.5);
+        assert!((r_to_mos(50.0) - 2.58).abs() < 0.01);
+        let mut previous = 1.0;
+        for r in 1..100 {
+            let mos = r_to_mos(r as f64);
+            assert!(mos >= previous, "MOS falls at R {}", r);
+            previous = mos;
+        }
+    }
+
+    #[test]
+    fn test_from_stats() {
+        assert_eq!(CallQuality::from_stats(&JitterBufferStats::default(), &CallStats::default(), Concealment::Zero), None);
+
+        // RTCP saw 10 of 100 lost; the jitter buffer discarded 2 more late
+        let packets = JitterBufferStats { packets_received: 92, packets_dropped: 2, ..Default::default() };
+        let call = CallStats { expected: 100, lost: 10, ..Default::default() };
+        let quality = CallQuality::from_stats(&packets, &call, Concealment::Zero).unwrap();
+        assert_eq!(quality, CallQuality::estimate(0.12, 0.0, None, Concealment::Zero));
+
+        // Without sequence statistics the jitter buffer's counts are used
+        let packets = JitterBufferStats { packets_received: 90, packets_lost: 10, ..Default::default() };
+        let quality = CallQuality::from_stats(&packets, &CallStats::default(), Concealment::Zero).unwrap();
+        assert_eq!(quality, CallQuality::estimate(0.1, 0.0, None, Concealment::Zero));
+    }
+}
@@ -163,6 +163,83 @@ impl G711Decoder {
     }
 }
 
+/// G.711 encoder (16-bit linear PCM to u-law or A-law), used to send audio
+/// such as the test PBX's announcements
+pub struct G711Encoder {
+    codec: G711Codec,
+}
+
+/// u-law encoding bias and clipping level (Sun reference implementation)
+const ULAW_BIAS: i32 = 0x84;
+const ULAW_CLIP: i32 = 32635;
+
+/// Upper bounds of the A-law segments (13-bit magnitudes)
+const ALAW_SEGMENT_END: [i32; 8] = [0x1F, 0x3F, 0x7F, 0xFF, 0x1FF, 0x3FF, 0x7FF, 0xFFF];
+
+impl G711Encoder {
+    pub fn new(codec: G711Codec) -> Self {
+        Self { codec }
+    }
+
+    /// RTP payload type of the codec (RFC 3551)
+    pub fn payload_type(&self) -> u8 {
+        match self.codec {
+            G711Codec::ULaw => 0,
+            G711Codec::ALaw => 8,
+        }
+    }
+
+    /// Encode 16-bit PCM samples to G.711 bytes
+    pub fn encode(&self, samples: &[i16]) -> Vec<u8> {
+        samples.iter().map(|&s| self.encode_sample(s)).collect()
+    }
+
+    /// Encode a single sample
+    #[inline]
+    pub fn encode_sample(&self, sample: i16) -> u8 {
+        match self.codec {
+            G711Codec::ULaw => linear_to_ulaw(sample),
+            G711Codec::ALaw => linear_to_alaw(sample),
+        }
+    }
+}
+
+fn linear_to_ulaw(sample: i16) -> u8 {
+    let mut pcm = sample as i32;
+    let sign = if pcm < 0 {
+        pcm = -pcm;
+        0x80
+    } else {
+        0
+    };
+    pcm = pcm.min(ULAW_CLIP) + ULAW_BIAS;
+
+    let mut exponent = 7;
+    let mut mask = 0x4000;
+    while pcm & mask == 0 && exponent > 0 {
+        exponent -= 1;
+        mask >>= 1;
+    }
+    let mantissa = (pcm >> (exponent + 3)) & 0x0F;
+    !((sign | (exponent << 4) | mantissa) as u8)
+}
+
+fn linear_to_alaw(sample: i16) -> u8 {
+    let mut pcm = (sample as i32) >> 3;
+    let mask = if pcm >= 0 {
+        0xD5
+    } else {
+        pcm = -pcm - 1;
+        0x55
+    };
+
+    let Some(segment) = ALAW_SEGMENT_END.iter().position(|&end| pcm <= end) else {
+        return 0x7F ^ mask;
+    };
+    let quantized = if segment < 2 { pcm >> 1 } else { pcm >> segment };
+    (((segment as i32) << 4 | (quantized & 0x0F)) as u8) ^ mask
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -284,6 +361,35 @@ mod tests {
         ));
         assert!(G711Decoder::from_payload_type(96).is_none());
     }
+
+    #[test]
+    fn test_encode_silence() {
+        assert_eq!(G711Encoder::new(G711Codec::ULaw).encode_sample(0), 0xFF);
+        assert_eq!(G711Encoder::new(G711Codec::ALaw).encode_sample(0), 0xD5);
+    }
+
+    #[test]
+    fn test_encode_round_trips_table_values() {
+        // Every decoded level encodes back to a byte with the same level
+        for codec in [G711Codec::ULaw, G711Codec::ALaw] {
+            let decoder = G711Decoder::new(codec);
+            let encoder = G711Encoder::new(codec);
+            for byte in 0..=255u8 {
+                let level = decoder.decode_sample(byte);
+                assert_eq!(decoder.decode_sample(encoder.encode_sample(level)), level, "{:?} byte {}", codec, byte);
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_clips_extremes() {
+        for codec in [G711Codec::ULaw, G711Codec::ALaw] {
+            let decoder = G711Decoder::new(codec);
+            let encoder = G711Encoder::new(codec);
+            assert!(decoder.decode_sample(encoder.encode_sample(i16::MAX)) > 30000);
+            assert!(decoder.decode_sample(encoder.encode_sample(i16::MIN)) < -30000);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -3,15 +3,95 @@
 ///
 /// The jitter buffer collects incoming RTP packets and outputs them in sequence order,
 /// with configurable delay to absorb network jitter.
+///
+/// Two modes:
+/// - Fixed: a missing packet is waited for until `max_gap` packets after it
+///   have arrived.
+/// - Adaptive (in the style of PJSIP's jitter buffer): the interarrival
+///   jitter is estimated from arrival times and RTP timestamps (RFC 3550
+///   Section 6.4.1), and the target depth follows it between a minimum and
+///   a maximum. A missing packet is given up on once the packets after it
+///   have waited the target depth's worth of time. The depth grows as soon
+///   as the jitter calls for it, or when a packet given up on arrives after
+///   all, and shrinks at the start of a talkspurt (marker bit), where the
+///   change falls into silence, or one frame at a time after a calm stretch.
+///   A jump in timestamps that arrival times don't follow (a sender that
+///   rebased its clock) restarts the estimate instead of counting as jitter.
 
 use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Instant;
 use tracing::{debug, trace, warn};
 
+/// Clock rate of G.711 RTP timestamps
+const CLOCK_RATE: f64 = 8000.0;
+
+/// Timestamp units per packet until the stream shows otherwise (20ms)
+const DEFAULT_FRAME_UNITS: u32 = 160;
+
+/// Longest packet accepted as a frame size (100ms)
+const MAX_FRAME_UNITS: u32 = 800;
+
+/// Jitter multiples the adaptive mode waits for a missing packet
+const JITTER_MULTIPLE: f64 = 3.0;
+
+/// A change in transit time above this (1s) is a timestamp jump, not jitter
+const MAX_TRANSIT_STEP: f64 = CLOCK_RATE;
+
+/// Packets with the target deeper than needed before it shrinks by a frame
+/// outside a talkspurt start (5s at 20ms)
+const SHRINK_AFTER: u32 = 250;
+
+/// Default bounds of the adaptive target depth (40ms to 300ms at 20ms)
+pub const ADAPTIVE_MIN_DEPTH: u16 = 2;
+pub const ADAPTIVE_MAX_DEPTH: u16 = 15;
+
+/// How the buffer decides how long to wait for a missing packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JitterMode {
+    /// `target_depth` and `max_gap` as configured
+    #[default]
+    Fixed,
+    /// The target depth follows the measured jitter within these bounds,
+    /// starting from `target_depth`
+    Adaptive { min_depth: u16, max_depth: u16 },
+}
+
+impl JitterMode {
+    /// Adaptive mode with the default bounds
+    pub fn adaptive() -> Self {
+        JitterMode::Adaptive { min_depth: ADAPTIVE_MIN_DEPTH, max_depth: ADAPTIVE_MAX_DEPTH }
+    }
+}
+
+impl FromStr for JitterMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "fixed" => Ok(JitterMode::Fixed),
+            "adaptive" => Ok(JitterMode::adaptive()),
+            other => anyhow::bail!("Unknown jitter buffer mode '{}' (expected fixed or adaptive)", other),
+        }
+    }
+}
+
+impl fmt::Display for JitterMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            JitterMode::Fixed => "fixed",
+            JitterMode::Adaptive { .. } => "adaptive",
+        })
+    }
+}
+
 /// Configuration for the jitter buffer
 #[derive(Debug, Clone)]
 pub struct JitterBufferConfig {
     /// Target buffer depth in packets (default: 3)
     /// Higher values = more jitter tolerance but higher latency
+    /// In adaptive mode this is only the starting depth.
     pub target_depth: u16,
     /// Maximum buffer size in packets before dropping old packets (default: 50)
     /// When exceeded, OLDEST packets are dropped first (FIFO eviction).
@@ -21,7 +101,10 @@ pub struct JitterBufferConfig {
     /// Maximum sequence number gap before considering packets lost (default: 10)
     /// If the gap exceeds this, the buffer skips ahead to the next available packet.
     /// At 50 packets/sec, gap of 10 = 200ms of missing audio triggers skip.
+    /// Fixed mode only.
     pub max_gap: u16,
+    /// Fixed or adaptive (default: fixed)
+    pub mode: JitterMode,
 }
 
 impl Default for JitterBufferConfig {
@@ -30,6 +113,7 @@ impl Default for JitterBufferConfig {
             target_depth: 3,
             max_size: 50,
             max_gap: 10,
+            mode: JitterMode::Fixed,
         }
     }
 }
@@ -39,17 +123,138 @@ impl Default for JitterBufferConfig {
 pub struct BufferedPacket {
     pub sequence: u16,
     pub timestamp: u32,
+    /// RTP marker bit: the first packet of a talkspurt
+    pub marker: bool,
     pub payload: Vec<u8>,
 }
 
+/// Adaptive mode state: the jitter estimate and the target depth it sets
+#[derive(Debug)]
+struct Adaptation {
+    min_depth: u16,
+    max_depth: u16,
+    /// Current target depth in packets
+    target: u16,
+    /// Interarrival jitter in timestamp units
+    jitter: f64,
+    /// Sequence number, timestamp and arrival of the previous packet
+    previous: Option<(u16, u32, Instant)>,
+    /// Timestamp units per packet
+    frame_units: u32,
+    /// Packets since the target became deeper than the jitter needs
+    calm_packets: u32,
+    /// The packets last given up on (first sequence number, count)
+    last_skip: Option<(u16, u16)>,
+}
+
+impl Adaptation {
+    fn new(min_depth: u16, max_depth: u16, initial: u16) -> Self {
+        Self {
+            min_depth,
+            max_depth,
+            target: initial.clamp(min_depth, max_depth),
+            jitter: 0.0,
+            previous: None,
+            frame_units: DEFAULT_FRAME_UNITS,
+            calm_packets: 0,
+            last_skip: None,
+        }
+    }
+
+    /// The adaptation of a buffer in `config`'s mode (none when fixed)
+    fn for_config(config: &JitterBufferConfig) -> Option<Self> {
+        match config.mode {
+            JitterMode::Fixed => None,
+            JitterMode::Adaptive { min_depth, max_depth } => Some(Self::new(min_depth, max_depth, config.target_depth)),
+        }
+    }
+
+    /// Update the jitter estimate with a packet's arrival, then the target
+    fn on_arrival(&mut self, packet: &BufferedPacket, arrival: Instant) {
+        if let Some((sequence, timestamp, previous_arrival)) = self.previous {
+            let units = packet.timestamp.wrapping_sub(timestamp) as i32;
+            if packet.sequence.wrapping_sub(sequence) == 1 && units > 0 && units as u32 <= MAX_FRAME_UNITS {
+                self.frame_units = units as u32;
+            }
+
+            let elapsed = arrival.saturating_duration_since(previous_arrival).as_secs_f64() * CLOCK_RATE;
+            let transit_change = elapsed - units as f64;
+            if packet.marker || transit_change.abs() > MAX_TRANSIT_STEP {
+                trace!("New timing reference at seq={} (marker={})", packet.sequence, packet.marker);
+            } else {
+                self.jitter += (transit_change.abs() - self.jitter) / 16.0;
+            }
+        }
+        self.previous = Some((packet.sequence, packet.timestamp, arrival));
+        self.adjust(packet.marker);
+    }
+
+    /// Depth that covers the current jitter
+    fn desired(&self) -> u16 {
+        let frames = (JITTER_MULTIPLE * self.jitter / self.frame_units as f64).ceil() as u16 + 1;
+        frames.clamp(self.min_depth, self.max_depth)
+    }
+
+    fn adjust(&mut self, talkspurt: bool) {
+        let desired = self.desired();
+        if desired >= self.target {
+            self.resize(desired);
+            self.calm_packets = 0;
+            return;
+        }
+
+        self.calm_packets += 1;
+        if talkspurt {
+            self.resize(desired);
+            self.calm_packets = 0;
+        } else if self.calm_packets >= SHRINK_AFTER {
+            self.resize(self.target - 1);
+            self.calm_packets = 0;
+        }
+    }
+
+    /// A late packet: if it is one we gave up on, wait a packet longer
+    fn on_late(&mut self, sequence: u16) {
+        if let Some((first, count)) = self.last_skip {
+            if sequence.wrapping_sub(first) < count {
+                self.resize((self.target + 1).min(self.max_depth));
+                self.calm_packets = 0;
+            }
+        }
+    }
+
+    fn resize(&mut self, target: u16) {
+        if target != self.target {
+            debug!(
+                "Jitter buffer target depth {} -> {} (jitter {:.1} ms)",
+                self.target,
+                target,
+                self.jitter / CLOCK_RATE * 1000.0
+            );
+            self.target = target;
+        }
+    }
+
+    /// Whether a missing packet has been waited for long enough, given
+    /// when the first packet after it arrived
+    fn expired(&self, first_after: Instant, now: Instant) -> bool {
+        let waited = now.saturating_duration_since(first_after).as_secs_f64() * CLOCK_RATE + self.frame_units as f64;
+        waited >= self.target as f64 * self.frame_units as f64
+    }
+}
+
 /// Jitter buffer state
 #[derive(Debug)]
 pub struct JitterBuffer {
     config: JitterBufferConfig,
-    /// Packets indexed by sequence number
-    packets: BTreeMap<u16, BufferedPacket>,
+    /// Packets indexed by sequence number, with their arrival time
+    packets: BTreeMap<u16, (BufferedPacket, Instant)>,
     /// Next expected sequence number for output
     next_seq: Option<u16>,
+    /// Arrival time of the latest packet
+    now: Option<Instant>,
+    /// Jitter estimate and target depth in adaptive mode
+    adaptation: Option<Adaptation>,
     /// Number of packets received
     packets_received: u64,
     /// Number of packets output in order
@@ -63,9 +268,11 @@ pub struct JitterBuffer {
 impl JitterBuffer {
     pub fn new(config: JitterBufferConfig) -> Self {
         Self {
+            adaptation: Adaptation::for_config(&config),
             config,
             packets: BTreeMap::new(),
             next_seq: None,
+            now: None,
             packets_received: 0,
             packets_output: 0,
             packets_dropped: 0,
@@ -73,10 +280,17 @@ impl JitterBuffer {
         }
     }
 
-    /// Insert a packet into the buffer
+    /// Insert a packet that just arrived
     /// Returns true if packet was accepted, false if dropped (late/duplicate)
     pub fn insert(&mut self, packet: BufferedPacket) -> bool {
+        self.insert_at(packet, Instant::now())
+    }
+
+    /// Insert a packet that arrived at `arrival` (arrival times drive the
+    /// adaptive mode's jitter estimate and how long it waits for a gap)
+    pub fn insert_at(&mut self, packet: BufferedPacket, arrival: Instant) -> bool {
         self.packets_received += 1;
+        self.now = Some(self.now.map_or(arrival, |now| now.max(arrival)));
 
         let seq = packet.sequence;
 
@@ -92,6 +306,9 @@ impl JitterBuffer {
         if self.is_before(seq, next_seq) {
             trace!("Dropping late packet: seq={} (expected >= {})", seq, next_seq);
             self.packets_dropped += 1;
+            if let Some(ref mut adaptation) = self.adaptation {
+                adaptation.on_late(seq);
+            }
             return false;
         }
 
@@ -102,8 +319,14 @@ impl JitterBuffer {
             return false;
         }
 
+        // Only packets taken in feed the jitter estimate: a duplicate says
+        // nothing about the network's timing
+        if let Some(ref mut adaptation) = self.adaptation {
+            adaptation.on_arrival(&packet, arrival);
+        }
+
         // Insert the packet
-        self.packets.insert(seq, packet);
+        self.packets.insert(seq, (packet, arrival));
         trace!("Buffered packet: seq={}, buffer_size={}", seq, self.packets.len());
 
         // Trim buffer if too large
@@ -124,12 +347,12 @@ impl JitterBuffer {
         let next_seq = self.next_seq?;
 
         // Wait until we have target_depth packets before starting output
-        if self.packets_output == 0 && self.packets.len() < self.config.target_depth as usize {
+        if self.packets_output == 0 && self.packets.len() < self.target_depth() as usize {
             return None;
         }
 
         // Try to get the next expected packet
-        if let Some(packet) = self.packets.remove(&next_seq) {
+        if let Some((packet, _)) = self.packets.remove(&next_seq) {
             self.next_seq = Some(next_seq.wrapping_add(1));
             self.packets_output += 1;
             return Some(packet);
@@ -137,21 +360,25 @@ impl JitterBuffer {
 
         // Next packet is missing - check if we should skip it
         // Only skip if we have packets beyond the gap
-        let gap = self.gap_to_next_available();
-        if gap > self.config.max_gap {
-            // Too big a gap, advance to the next available packet
-            if let Some((&available_seq, _)) = self.packets.iter().next() {
-                self.packets_lost += (available_seq.wrapping_sub(next_seq)) as u64;
+        if self.gap_expired() {
+            // Waited long enough, advance to the next available packet
+            if let Some(available_seq) = self.first_available() {
+                let skipped = available_seq.wrapping_sub(next_seq);
+                self.packets_lost += skipped as u64;
                 debug!(
                     "Skipping {} missing packets, jumping from {} to {}",
-                    available_seq.wrapping_sub(next_seq),
+                    skipped,
                     next_seq,
                     available_seq
                 );
+                if let Some(ref mut adaptation) = self.adaptation {
+                    adaptation.last_skip = Some((next_seq, skipped));
+                }
                 self.next_seq = Some(available_seq);
-                return self.packets.remove(&available_seq).inspect(|_p| {
+                return self.packets.remove(&available_seq).map(|(packet, _)| {
                     self.next_seq = Some(available_seq.wrapping_add(1));
                     self.packets_output += 1;
+                    packet
                 });
             }
         }
@@ -168,17 +395,16 @@ impl JitterBuffer {
 
         // During initial buffering, wait for target_depth
         if self.packets_output == 0 {
-            return self.packets.len() >= self.config.target_depth as usize;
+            return self.packets.len() >= self.target_depth() as usize;
         }
 
         // After initial buffering, return true if next packet is available
-        // or if there's a gap larger than max_gap
+        // or if the gap before the next available one has been waited out
         if self.packets.contains_key(&next_seq) {
             return true;
         }
 
-        // Check if gap is too large
-        self.gap_to_next_available() > self.config.max_gap
+        self.gap_expired()
     }
 
     /// Get all remaining packets in order (for flushing)
@@ -188,7 +414,7 @@ impl JitterBuffer {
         // Skip to first available packet if needed
         if let Some(next_seq) = self.next_seq {
             if !self.packets.contains_key(&next_seq) {
-                if let Some((&first_available, _)) = self.packets.iter().next() {
+                if let Some(first_available) = self.first_available() {
                     self.next_seq = Some(first_available);
                 }
             }
@@ -200,13 +426,21 @@ impl JitterBuffer {
         }
 
         // Also get any remaining packets that might be out of order
-        let remaining: Vec<_> = self.packets.values().cloned().collect();
+        let remaining: Vec<_> = self.packets.values().map(|(packet, _)| packet.clone()).collect();
         self.packets.clear();
         result.extend(remaining);
 
         result
     }
 
+    /// Start over for a new RTP stream: forget the sequence numbers and the
+    /// jitter learned so far, keeping the packet counts. Drain first.
+    pub fn restart(&mut self) {
+        self.packets.clear();
+        self.next_seq = None;
+        self.adaptation = Adaptation::for_config(&self.config);
+    }
+
     /// Get buffer statistics
     pub fn stats(&self) -> JitterBufferStats {
         JitterBufferStats {
@@ -215,16 +449,51 @@ impl JitterBuffer {
             packets_dropped: self.packets_dropped,
             packets_lost: self.packets_lost,
             current_depth: self.packets.len() as u16,
+            target_depth: self.target_depth(),
+            jitter_ms: self.adaptation.as_ref().map_or(0.0, |a| (a.jitter / CLOCK_RATE * 1000.0) as f32),
+            frames_concealed: 0,
+        }
+    }
+
+    /// Packets to buffer before output starts (adapted in adaptive mode)
+    fn target_depth(&self) -> u16 {
+        self.adaptation.as_ref().map_or(self.config.target_depth, |a| a.target)
+    }
+
+    /// Whether to give up on the missing next packet: in fixed mode once the
+    /// gap exceeds max_gap, in adaptive mode once the first packet after it
+    /// has waited the target depth
+    fn gap_expired(&self) -> bool {
+        match self.adaptation {
+            None => self.gap_to_next_available() > self.config.max_gap,
+            Some(ref adaptation) => {
+                let first_after = self.first_available().and_then(|seq| self.packets.get(&seq));
+                match (first_after, self.now) {
+                    (Some((_, arrival)), Some(now)) => adaptation.expired(*arrival, now),
+                    _ => false,
+                }
+            }
         }
     }
 
+    /// First buffered packet in sequence order from next_seq (across
+    /// wraparound)
+    fn first_available(&self) -> Option<u16> {
+        let next_seq = self.next_seq?;
+        self.packets
+            .range(next_seq..)
+            .next()
+            .or_else(|| self.packets.iter().next())
+            .map(|(&seq, _)| seq)
+    }
+
     /// Calculate gap between current sequence and next available packet
     fn gap_to_next_available(&self) -> u16 {
         let Some(next_seq) = self.next_seq else {
             return 0;
         };
 
-        if let Some((&first_available, _)) = self.packets.iter().next() {
+        if let Some(first_available) = self.first_available() {
             first_available.wrapping_sub(next_seq)
         } else {
             0
@@ -248,6 +517,12 @@ pub struct JitterBufferStats {
     pub packets_dropped: u64,
     pub packets_lost: u64,
     pub current_depth: u16,
+    /// Packets buffered before output starts (adapted in adaptive mode)
+    pub target_depth: u16,
+    /// Interarrival jitter estimate (adaptive mode only)
+    pub jitter_ms: f32,
+    /// Lost frames the receiver filled in (not counted by the buffer itself)
+    pub frames_concealed: u64,
 }
 
 #[cfg(test)]
@@ -258,6 +533,7 @@ mod tests {
         BufferedPacket {
             sequence: seq,
             timestamp: seq as u32 * 160, // Typical G.711 timestamp increment
+            marker: false,
             payload: vec![0u8; 160],
         }
     }
@@ -268,6 +544,7 @@ mod tests {
             target_depth: 2,
             max_size: 10,
             max_gap: 5,
+            ..Default::default()
         });
 
         // Insert packets 0, 1, 2, 3 in order
@@ -290,6 +567,7 @@ mod tests {
             target_depth: 2,
             max_size: 10,
             max_gap: 5,
+            ..Default::default()
         });
 
         // Insert packets out of order: 0, 2, 1, 3
@@ -311,6 +589,7 @@ mod tests {
             target_depth: 2,
             max_size: 10,
             max_gap: 5,
+            ..Default::default()
         });
 
         // Insert 0, 1, 2
@@ -334,6 +613,7 @@ mod tests {
             target_depth: 2,
             max_size: 10,
             max_gap: 5,
+            ..Default::default()
         });
 
         // Insert same packet twice
@@ -350,6 +630,7 @@ mod tests {
             target_depth: 2,
             max_size: 10,
             max_gap: 2, // Gap of 3 will exceed this
+            ..Default::default()
         });
 
         // Insert packets with gap: 0, 1, 5, 6 (missing 2, 3, 4)
@@ -375,6 +656,7 @@ mod tests {
             target_depth: 2,
             max_size: 3,
             max_gap: 5,
+            ..Default::default()
         });
 
         // Insert more packets than max_size
@@ -394,6 +676,7 @@ mod tests {
             target_depth: 2,
             max_size: 10,
             max_gap: 5,
+            ..Default::default()
         });
 
         // Insert packets around u16::MAX
@@ -415,6 +698,7 @@ mod tests {
             target_depth: 2,
             max_size: 10,
             max_gap: 5,
+            ..Default::default()
         });
 
         buffer.insert(make_packet(0));
@@ -428,12 +712,29 @@ mod tests {
         assert_eq!(packets[2].sequence, 3);
     }
 
+    #[test]
+    fn test_restart_accepts_earlier_sequence() {
+        let mut buffer = JitterBuffer::new(JitterBufferConfig { target_depth: 1, ..Default::default() });
+        for seq in 1000..1003 {
+            buffer.insert(make_packet(seq));
+        }
+        assert_eq!(buffer.drain().len(), 3);
+
+        // A new stream restarting far behind the old one
+        assert!(!buffer.insert(make_packet(10)));
+        buffer.restart();
+        assert!(buffer.insert(make_packet(10)));
+        assert_eq!(buffer.pop().unwrap().sequence, 10);
+        assert_eq!(buffer.stats().packets_received, 5);
+    }
+
     #[test]
     fn test_initial_buffering() {
         let mut buffer = JitterBuffer::new(JitterBufferConfig {
             target_depth: 3,
             max_size: 10,
             max_gap: 5,
+            ..Default::default()
         });
 
         // Insert only 2 packets - shouldn't output yet (target_depth=3)
@@ -468,6 +769,118 @@ mod tests {
         assert_eq!(stats.packets_output, 2);
         assert_eq!(stats.current_depth, 1); // One packet remaining
     }
+
+    /// Adaptive buffer starting at the minimum depth of 2
+    fn adaptive() -> JitterBuffer {
+        JitterBuffer::new(JitterBufferConfig { target_depth: 2, mode: JitterMode::adaptive(), ..Default::default() })
+    }
+
+    /// Insert a 20ms packet arriving `ms` after `base`, then pop everything
+    /// ready
+    fn arrive(buffer: &mut JitterBuffer, base: Instant, seq: u16, ts: u32, ms: u64, marker: bool) -> Vec<u16> {
+        let packet = BufferedPacket { sequence: seq, timestamp: ts, marker, payload: vec![0u8; 160] };
+        buffer.insert_at(packet, base + std::time::Duration::from_millis(ms));
+        std::iter::from_fn(|| buffer.pop()).map(|p| p.sequence).collect()
+    }
+
+    #[test]
+    fn test_parse_jitter_mode() {
+        assert_eq!("fixed".parse::<JitterMode>().unwrap(), JitterMode::Fixed);
+        assert_eq!(
+            " Adaptive ".parse::<JitterMode>().unwrap(),
+            JitterMode::Adaptive { min_depth: ADAPTIVE_MIN_DEPTH, max_depth: ADAPTIVE_MAX_DEPTH }
+        );
+        assert!("elastic".parse::<JitterMode>().is_err());
+        assert_eq!(JitterMode::adaptive().to_string(), "adaptive");
+        assert_eq!(JitterBufferConfig::default().mode, JitterMode::Fixed);
+    }
+
+    #[test]
+    fn test_adaptive_waits_target_depth_for_missing_packet() {
+        let mut buffer = adaptive();
+        let base = Instant::now();
+        let mut output = Vec::new();
+        for seq in [0u16, 1, 2, 4] {
+            output.extend(arrive(&mut buffer, base, seq, seq as u32 * 160, seq as u64 * 20, false));
+        }
+        // 3 is missing and 4 has only just arrived
+        assert_eq!(output, vec![0, 1, 2]);
+        assert!(!buffer.has_ready());
+
+        // 20ms later two frames have waited: give up on 3
+        output.extend(arrive(&mut buffer, base, 5, 800, 100, false));
+        assert_eq!(output, vec![0, 1, 2, 4, 5]);
+        assert_eq!(buffer.stats().packets_lost, 1);
+    }
+
+    #[test]
+    fn test_adaptive_reordered_pair_is_not_lost() {
+        let mut buffer = adaptive();
+        let base = Instant::now();
+        let mut output = Vec::new();
+        for (seq, ms) in [(0u16, 0u64), (1, 20), (3, 60), (2, 65), (4, 80)] {
+            output.extend(arrive(&mut buffer, base, seq, seq as u32 * 160, ms, false));
+        }
+        assert_eq!(output, vec![0, 1, 2, 3, 4]);
+        assert_eq!(buffer.stats().packets_lost, 0);
+    }
+
+    #[test]
+    fn test_adaptive_depth_follows_jitter() {
+        let mut buffer = adaptive();
+        let base = Instant::now();
+        // Every other packet is 30ms late
+        for seq in 0..50u16 {
+            let ms = seq as u64 * 20 + if seq % 2 == 1 { 30 } else { 0 };
+            arrive(&mut buffer, base, seq, seq as u32 * 160, ms, false);
+        }
+        let stats = buffer.stats();
+        assert!(stats.jitter_ms > 20.0, "{:?}", stats);
+        assert!(stats.target_depth >= 5, "{:?}", stats);
+        assert!(stats.target_depth <= ADAPTIVE_MAX_DEPTH);
+        assert_eq!(stats.packets_lost, 0);
+
+        // Steady again: the depth stays until the next talkspurt starts
+        for seq in 50..100u16 {
+            arrive(&mut buffer, base, seq, seq as u32 * 160, seq as u64 * 20, false);
+        }
+        assert!(buffer.stats().target_depth >= 5);
+        // After a second of silence
+        arrive(&mut buffer, base, 100, 100 * 160 + 8000, 100 * 20 + 1000, true);
+        assert_eq!(buffer.stats().target_depth, ADAPTIVE_MIN_DEPTH);
+    }
+
+    #[test]
+    fn test_adaptive_late_arrival_deepens() {
+        let mut buffer = adaptive();
+        let base = Instant::now();
+        for seq in [0u16, 1, 2, 4, 5] {
+            arrive(&mut buffer, base, seq, seq as u32 * 160, seq as u64 * 20, false);
+        }
+        assert_eq!(buffer.stats().packets_lost, 1);
+
+        // The packet given up on shows up after all
+        arrive(&mut buffer, base, 3, 480, 105, false);
+        let stats = buffer.stats();
+        assert_eq!(stats.packets_dropped, 1);
+        assert_eq!(stats.target_depth, 3);
+    }
+
+    #[test]
+    fn test_adaptive_timestamp_jump_is_not_jitter() {
+        let mut buffer = adaptive();
+        let base = Instant::now();
+        let mut output = Vec::new();
+        for seq in 0..20u16 {
+            // The sender rebases its timestamps at packet 10
+            let ts = seq as u32 * 160 + if seq >= 10 { 4_000_000 } else { 0 };
+            output.extend(arrive(&mut buffer, base, seq, ts, seq as u64 * 20, false));
+        }
+        assert_eq!(output, (0..20).collect::<Vec<_>>());
+        let stats = buffer.stats();
+        assert!(stats.jitter_ms < 0.01, "{:?}", stats);
+        assert_eq!(stats.target_depth, ADAPTIVE_MIN_DEPTH);
+    }
 }
 
 #[cfg(test)]
@@ -483,6 +896,7 @@ mod proptests {
                 target_depth: 1,
                 max_size: 100,
                 max_gap: 50,
+                ..Default::default()
             });
 
             // Insert in order
@@ -490,6 +904,7 @@ mod proptests {
                 buffer.insert(BufferedPacket {
                     sequence: start.wrapping_add(i as u16),
                     timestamp: 0,
+                    marker: false,
                     payload: vec![],
                 });
             }
@@ -511,12 +926,14 @@ mod proptests {
                 target_depth: 2,
                 max_size: 100,
                 max_gap: 50,
+                ..Default::default()
             });
 
             for i in 0..count {
                 buffer.insert(BufferedPacket {
                     sequence: start.wrapping_add(i as u16),
                     timestamp: 0,
+                    marker: false,
                     payload: vec![],
                 });
             }
@@ -537,6 +954,7 @@ mod proptests {
                 target_depth: 2,
                 max_size: 100,
                 max_gap: 50,
+                ..Default::default()
             });
 
             // Insert and pop some packets
@@ -544,6 +962,7 @@ mod proptests {
                 buffer.insert(BufferedPacket {
                     sequence: start.wrapping_add(i as u16),
                     timestamp: 0,
+                    marker: false,
                     payload: vec![],
                 });
             }
@@ -560,6 +979,7 @@ mod proptests {
             let accepted = buffer.insert(BufferedPacket {
                 sequence: late_seq,
                 timestamp: 0,
+                marker: false,
                 payload: vec![],
             });
 
@@ -574,6 +994,7 @@ mod proptests {
                 target_depth: 2,
                 max_size: 100,
                 max_gap: 50,
+                ..Default::default()
             });
 
             // Insert around wraparound point
@@ -582,6 +1003,7 @@ mod proptests {
                 buffer.insert(BufferedPacket {
                     sequence: start.wrapping_add(i),
                     timestamp: 0,
+                    marker: false,
                     payload: vec![],
                 });
             }
@@ -806,6 +1228,214 @@ mod state_machine {
         println!("States explored (constrained): {}", checker.unique_state_count());
         checker.assert_properties();
     }
+
+    /// What happens to the next packet of an adaptive-mode stream
+    #[derive(Clone, Debug, Hash, PartialEq, Eq)]
+    enum StreamAction {
+        /// Arrives 20ms after the previous one was sent
+        Send,
+        /// Held up in the network until released
+        Hold,
+        /// The oldest held packet arrives
+        Release,
+        /// Never arrives
+        Lose,
+        /// Arrives with the marker bit after a second of silence
+        Talkspurt,
+        /// Arrives on time with timestamps rebased by 10s
+        Rebase,
+        /// The last packet that arrived arrives again
+        Duplicate,
+    }
+
+    /// A packet as it reaches the buffer
+    #[derive(Clone, Debug, Hash, PartialEq, Eq)]
+    struct Arrival {
+        seq: u16,
+        ts: u32,
+        at_ms: u64,
+        marker: bool,
+    }
+
+    /// The sender's progress and what has reached the buffer, in order
+    #[derive(Clone, Debug, Hash, PartialEq, Eq)]
+    struct StreamState {
+        arrivals: Vec<Arrival>,
+        next_seq: u16,
+        next_ts: u32,
+        clock_ms: u64,
+        held: Vec<(u16, u32)>,
+        ops: u8,
+        /// Whether any packet was lost or held (loss may then be declared)
+        disturbed: bool,
+        /// Whether arrival times ever deviated from the timestamps
+        delayed: bool,
+    }
+
+    /// What the real adaptive buffer does with the arrivals: packets
+    /// output while streaming, then drained
+    struct Replay {
+        stats: JitterBufferStats,
+        streamed: Vec<u16>,
+        drained: Vec<u16>,
+    }
+
+    fn replay(state: &StreamState) -> Replay {
+        let mut buffer = JitterBuffer::new(JitterBufferConfig {
+            target_depth: ADAPTIVE_MIN_DEPTH,
+            max_size: 4,
+            max_gap: 2,
+            mode: JitterMode::adaptive(),
+        });
+        let base = Instant::now();
+        let mut streamed = Vec::new();
+        for arrival in &state.arrivals {
+            let packet = BufferedPacket {
+                sequence: arrival.seq,
+                timestamp: arrival.ts,
+                marker: arrival.marker,
+                payload: vec![],
+            };
+            buffer.insert_at(packet, base + std::time::Duration::from_millis(arrival.at_ms));
+            while let Some(packet) = buffer.pop() {
+                streamed.push(packet.sequence);
+            }
+        }
+        let stats = buffer.stats();
+        let drained = buffer.drain().iter().map(|p| p.sequence).collect();
+        Replay { stats, streamed, drained }
+    }
+
+    struct AdaptiveModel {
+        max_ops: u8,
+    }
+
+    impl Model for AdaptiveModel {
+        type State = StreamState;
+        type Action = StreamAction;
+
+        fn init_states(&self) -> Vec<Self::State> {
+            vec![StreamState {
+                arrivals: Vec::new(),
+                next_seq: 0,
+                next_ts: 0,
+                clock_ms: 0,
+                held: Vec::new(),
+                ops: 0,
+                disturbed: false,
+                delayed: false,
+            }]
+        }
+
+        fn actions(&self, state: &Self::State, actions: &mut Vec<Self::Action>) {
+            if state.ops >= self.max_ops {
+                return;
+            }
+            actions.extend([
+                StreamAction::Send,
+                StreamAction::Hold,
+                StreamAction::Lose,
+                StreamAction::Talkspurt,
+                StreamAction::Rebase,
+            ]);
+            if !state.held.is_empty() {
+                actions.push(StreamAction::Release);
+            }
+            if !state.arrivals.is_empty() {
+                actions.push(StreamAction::Duplicate);
+            }
+        }
+
+        fn next_state(&self, state: &Self::State, action: Self::Action) -> Option<Self::State> {
+            let mut next = state.clone();
+            next.ops += 1;
+            let send = |next: &mut StreamState, marker: bool| {
+                next.arrivals.push(Arrival { seq: next.next_seq, ts: next.next_ts, at_ms: next.clock_ms, marker });
+            };
+
+            match action {
+                StreamAction::Send => send(&mut next, false),
+                StreamAction::Hold => {
+                    next.held.push((next.next_seq, next.next_ts));
+                    next.disturbed = true;
+                    next.delayed = true;
+                }
+                StreamAction::Release => {
+                    let (seq, ts) = next.held.remove(0);
+                    let at_ms = next.clock_ms;
+                    next.arrivals.push(Arrival { seq, ts, at_ms, marker: false });
+                    return Some(next);
+                }
+                StreamAction::Lose => next.disturbed = true,
+                StreamAction::Talkspurt => {
+                    next.clock_ms += 1000;
+                    next.next_ts = next.next_ts.wrapping_add(8000);
+                    send(&mut next, true);
+                }
+                StreamAction::Rebase => {
+                    next.next_ts = next.next_ts.wrapping_add(80_000);
+                    send(&mut next, false);
+                }
+                StreamAction::Duplicate => {
+                    let mut again = next.arrivals.last().unwrap().clone();
+                    again.at_ms = next.clock_ms;
+                    next.arrivals.push(again);
+                    return Some(next);
+                }
+            }
+
+            next.next_seq = next.next_seq.wrapping_add(1);
+            next.next_ts = next.next_ts.wrapping_add(160);
+            next.clock_ms += 20;
+            Some(next)
+        }
+
+        fn properties(&self) -> Vec<Property<Self>> {
+            vec![
+                // Safety: the target depth stays within the adaptive bounds
+                Property::always("target_depth_bounded", |_: &Self, state: &StreamState| {
+                    let depth = replay(state).stats.target_depth;
+                    (ADAPTIVE_MIN_DEPTH..=ADAPTIVE_MAX_DEPTH).contains(&depth)
+                }),
+
+                // Safety: output (streamed, then drained) is in sequence
+                // order without repeats
+                Property::always("output_ordered", |_: &Self, state: &StreamState| {
+                    let replay = replay(state);
+                    let output: Vec<u16> = replay.streamed.iter().chain(&replay.drained).copied().collect();
+                    output.windows(2).all(|w| w[0] < w[1])
+                }),
+
+                // Safety: every packet that arrived is output, dropped or
+                // still buffered, and draining leaves nothing behind
+                Property::always("packet_accounting", |_: &Self, state: &StreamState| {
+                    let replay = replay(state);
+                    let stats = &replay.stats;
+                    stats.packets_received == stats.packets_output + stats.packets_dropped + stats.current_depth as u64
+                        && replay.drained.len() == stats.current_depth as usize
+                }),
+
+                // Safety: nothing is given up on unless a packet really was
+                // lost or held back
+                Property::always("no_spurious_loss", |_: &Self, state: &StreamState| {
+                    state.disturbed || replay(state).stats.packets_lost == 0
+                }),
+
+                // Safety: talkspurts and rebased timestamps are not jitter
+                Property::always("jumps_are_not_jitter", |_: &Self, state: &StreamState| {
+                    state.delayed || replay(state).stats.jitter_ms < 0.01
+                }),
+            ]
+        }
+    }
+
+    #[test]
+    fn test_adaptive_jitter_buffer_model() {
+        let model = AdaptiveModel { max_ops: 5 };
+        let checker = model.checker().threads(1).spawn_bfs().join();
+        println!("States explored (adaptive): {}", checker.unique_state_count());
+        checker.assert_properties();
+    }
 }
 
 /// Kani formal verification proofs
@@ -906,11 +1536,13 @@ mod kani_proofs {
             target_depth: 3,
             max_size: 10,
             max_gap: 5,
+            ..Default::default()
         });
 
         let packet = BufferedPacket {
             sequence: seq,
             timestamp: 0,
+            marker: false,
             payload: vec![],
         };
 
@@ -929,6 +1561,7 @@ mod kani_proofs {
             target_depth: 0,
             max_size,
             max_gap: 100,
+            ..Default::default()
         });
 
         // Insert more packets than max_size
@@ -936,6 +1569,7 @@ mod kani_proofs {
             let packet = BufferedPacket {
                 sequence: i,
                 timestamp: 0,
+                marker: false,
                 payload: vec![],
             };
             buffer.insert(packet);
@@ -960,6 +1594,7 @@ mod kani_proofs {
         buffer.insert(BufferedPacket {
             sequence: 0,
             timestamp: 0,
+            marker: false,
             payload: vec![],
         });
         let _ = buffer.pop();
@@ -974,6 +1609,7 @@ mod kani_proofs {
             target_depth: 0,
             max_size: 5,
             max_gap: 100,
+            ..Default::default()
         });
 
         // Insert some packets
@@ -981,6 +1617,7 @@ mod kani_proofs {
             buffer.insert(BufferedPacket {
                 sequence: i,
                 timestamp: 0,
+                marker: false,
                 payload: vec![],
             });
         }
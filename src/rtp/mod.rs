@@ -1,7 +1,13 @@
+pub mod dtmf;
+pub mod emodel;
 pub mod g711;
 pub mod jitter;
+pub mod plc;
 pub mod receiver;
 pub mod resample;
+pub mod rtcp;
+pub mod stream;
+pub mod tones;
 
 pub use receiver::RtpReceiver;
 
@@ -45,6 +51,29 @@ pub fn save_wav<P: AsRef<Path>>(samples: &[f32], path: P) -> Result<()> {
     Ok(())
 }
 
+/// Load a WAV file of any rate and sample format as mono f32 samples
+/// (first channel), returning them with the file's sample rate
+pub fn load_wav<P: AsRef<Path>>(path: P) -> Result<(Vec<f32>, u32)> {
+    let mut reader = hound::WavReader::open(path.as_ref())
+        .with_context(|| format!("Failed to open WAV file: {:?}", path.as_ref()))?;
+    let spec = reader.spec();
+    let channels = spec.channels.max(1) as usize;
+
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().step_by(channels).collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample.clamp(1, 32) - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .step_by(channels)
+                .map(|s| s.map(|s| s as f32 / scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+
+    Ok((samples, spec.sample_rate))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -77,5 +106,43 @@ mod tests {
             assert_eq!(back_ms, ms, "Round trip failed for {}ms", ms);
         }
     }
+
+    #[test]
+    fn test_load_wav_round_trip() {
+        let path = std::env::temp_dir().join(format!("phonecheck_load_wav_{}.wav", std::process::id()));
+        let samples: Vec<f32> = (0..1600).map(|i| (i as f32 / 1600.0) - 0.5).collect();
+        save_wav(&samples, &path).unwrap();
+
+        let (loaded, rate) = load_wav(&path).unwrap();
+        assert_eq!(rate, WHISPER_SAMPLE_RATE);
+        assert_eq!(loaded.len(), samples.len());
+        assert!(loaded.iter().zip(&samples).all(|(a, b)| (a - b).abs() < 0.001));
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_load_wav_takes_first_channel() {
+        let path = std::env::temp_dir().join(format!("phonecheck_load_wav_stereo_{}.wav", std::process::id()));
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for _ in 0..100 {
+            writer.write_sample(16384i16).unwrap();
+            writer.write_sample(-16384i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let (loaded, rate) = load_wav(&path).unwrap();
+        assert_eq!(rate, 44100);
+        assert_eq!(loaded.len(), 100);
+        assert!(loaded.iter().all(|&s| (s - 0.5).abs() < 0.001));
+
+        let _ = std::fs::remove_file(&path);
+    }
 }
 
@@ -0,0 +1,309 @@
+/// Packet loss concealment (PLC) for decoded G.711 audio
+///
+/// When packets go missing, the receiver fills their place so the capture
+/// keeps its real timing: a greeting with 5% loss must not come out 5%
+/// shorter and time-compressed for the recognizers. Two strategies:
+///
+/// - Zero fill: the missing frames are silence.
+/// - Waveform substitution (ITU-T G.711 Appendix I): the pitch period of
+///   the last 20ms before the loss is found by autocorrelation and the last
+///   period is repeated. After 10ms the repeated segment grows to two, then
+///   three periods (a single period repeated for long sounds buzzy), and the
+///   output is attenuated by 20% per 10ms so that losses longer than 60ms
+///   fade to silence. The first received frame after a loss is cross-faded
+///   with the continuation of the concealment, over a quarter period plus
+///   4ms for every further 10ms lost (at most 10ms).
+///
+/// Appendix I delays the output by 3.75ms to also smooth the start of a
+/// loss; we decode as packets arrive and skip that part.
+use anyhow::{bail, Result};
+use std::fmt;
+use std::str::FromStr;
+
+/// Shortest and longest pitch period searched (200Hz down to 66Hz at 8kHz)
+const PITCH_MIN: usize = 40;
+const PITCH_MAX: usize = 120;
+
+/// Samples correlated to find the pitch (20ms)
+const CORR_LEN: usize = 160;
+
+/// Audio kept from before a loss: three of the longest periods plus the
+/// quarter period cross-faded when switching segments
+const HISTORY_LEN: usize = 3 * PITCH_MAX + PITCH_MAX / 4;
+
+/// 10ms at 8kHz: the step of segment growth, attenuation and cross-fades
+const STEP: usize = 80;
+
+/// Cross-fade added for every 10ms of loss after the first (4ms)
+const RECOVERY_STEP: usize = 32;
+
+/// Attenuation per 10ms once a loss is longer than 10ms
+const ATTENUATION_PER_STEP: f32 = 0.2;
+
+/// How missing audio is filled in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Concealment {
+    /// Silence
+    Zero,
+    /// Pitch-synchronous repetition of the audio before the loss
+    #[default]
+    Waveform,
+}
+
+impl FromStr for Concealment {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "zero" => Ok(Concealment::Zero),
+            "waveform" => Ok(Concealment::Waveform),
+            other => bail!("Unknown packet loss concealment '{}' (expected zero or waveform)", other),
+        }
+    }
+}
+
+impl fmt::Display for Concealment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Concealment::Zero => "zero",
+            Concealment::Waveform => "waveform",
+        })
+    }
+}
+
+/// A loss being concealed by waveform substitution
+#[derive(Debug)]
+struct Erasure {
+    /// The audio before the loss (HISTORY_LEN samples)
+    history: Vec<i16>,
+    pitch: usize,
+    /// Samples concealed so far
+    position: usize,
+}
+
+impl Erasure {
+    /// Concealment sample at `position` samples into the loss
+    fn sample(&self, position: usize) -> f32 {
+        let quarter = self.pitch / 4;
+        let periods = segment_periods(position);
+        let mut value = self.source(periods, position);
+
+        // Cross-fade from the shorter segment just after it grows
+        let since_growth = position % STEP;
+        if periods > 1 && since_growth < quarter {
+            let weight = (since_growth + 1) as f32 / (quarter + 1) as f32;
+            value = weight * value + (1.0 - weight) * self.source(periods - 1, position);
+        }
+
+        value * gain(position)
+    }
+
+    /// Sample of a segment of the last `periods` pitch periods, repeated
+    fn source(&self, periods: usize, position: usize) -> f32 {
+        let length = periods * self.pitch;
+        self.history[self.history.len() - length + position % length] as f32
+    }
+}
+
+/// Pitch periods in the repeated segment `position` samples into a loss
+fn segment_periods(position: usize) -> usize {
+    (position / STEP + 1).min(3)
+}
+
+/// Output level `position` samples into a loss
+fn gain(position: usize) -> f32 {
+    if position < STEP {
+        return 1.0;
+    }
+    (1.0 - ATTENUATION_PER_STEP * (position - STEP) as f32 / STEP as f32).max(0.0)
+}
+
+/// Fills lost frames and smooths the return of audio after them
+#[derive(Debug)]
+pub struct Concealer {
+    strategy: Concealment,
+    /// The most recent output samples, at most HISTORY_LEN
+    history: Vec<i16>,
+    erasure: Option<Erasure>,
+    frames_concealed: u64,
+}
+
+impl Concealer {
+    pub fn new(strategy: Concealment) -> Self {
+        Self { strategy, history: Vec::with_capacity(2 * HISTORY_LEN), erasure: None, frames_concealed: 0 }
+    }
+
+    /// Frames filled in so far
+    pub fn frames_concealed(&self) -> u64 {
+        self.frames_concealed
+    }
+
+    /// Append `samples` of concealment for `frames` lost frames to `out`
+    pub fn conceal(&mut self, frames: u64, samples: usize, out: &mut Vec<i16>) {
+        self.frames_concealed += frames;
+        let start = out.len();
+
+        if self.strategy == Concealment::Waveform && self.erasure.is_none() && self.history.len() == HISTORY_LEN {
+            let pitch = find_pitch(&self.history);
+            self.erasure = Some(Erasure { history: self.history.clone(), pitch, position: 0 });
+        }
+        match self.erasure {
+            Some(ref mut erasure) => {
+                for _ in 0..samples {
+                    out.push(erasure.sample(erasure.position) as i16);
+                    erasure.position += 1;
+                }
+            }
+            None => out.resize(start + samples, 0),
+        }
+
+        self.remember(&out[start..]);
+    }
+
+    /// Append a received frame to `out`, cross-faded with the concealment
+    /// if it ends a loss
+    pub fn good_frame(&mut self, frame: &[i16], out: &mut Vec<i16>) {
+        let start = out.len();
+        out.extend_from_slice(frame);
+
+        if let Some(erasure) = self.erasure.take() {
+            let extra_steps = erasure.position.saturating_sub(1) / STEP;
+            let overlap = (erasure.pitch / 4 + extra_steps * RECOVERY_STEP).min(STEP).min(frame.len());
+            for i in 0..overlap {
+                let weight = (i + 1) as f32 / (overlap + 1) as f32;
+                let concealed = erasure.sample(erasure.position + i);
+                out[start + i] = (weight * frame[i] as f32 + (1.0 - weight) * concealed) as i16;
+            }
+        }
+
+        self.remember(&out[start..]);
+    }
+
+    /// Keep the last HISTORY_LEN samples of output
+    fn remember(&mut self, samples: &[i16]) {
+        self.history.extend_from_slice(samples);
+        if self.history.len() > HISTORY_LEN {
+            self.history.drain(..self.history.len() - HISTORY_LEN);
+        }
+    }
+}
+
+/// Pitch period of the end of `history`: the lag whose segment correlates
+/// best with the last CORR_LEN samples
+fn find_pitch(history: &[i16]) -> usize {
+    let end = history.len();
+    let target = &history[end - CORR_LEN..];
+    let mut best = (PITCH_MIN, f64::MIN);
+
+    for lag in PITCH_MIN..=PITCH_MAX {
+        let candidate = &history[end - CORR_LEN - lag..end - lag];
+        let (mut correlation, mut energy) = (0.0f64, 0.0f64);
+        for (&t, &c) in target.iter().zip(candidate) {
+            correlation += t as f64 * c as f64;
+            energy += c as f64 * c as f64;
+        }
+        if energy > 0.0 {
+            let score = correlation / energy.sqrt();
+            if score > best.1 {
+                best = (lag, score);
+            }
+        }
+    }
+
+    best.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 100Hz tone (80-sample period)
+    fn tone(samples: usize) -> Vec<i16> {
+        (0..samples)
+            .map(|i| ((2.0 * std::f64::consts::PI * i as f64 / 80.0).sin() * 8000.0) as i16)
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_concealment() {
+        assert_eq!("zero".parse::<Concealment>().unwrap(), Concealment::Zero);
+        assert_eq!(" Waveform ".parse::<Concealment>().unwrap(), Concealment::Waveform);
+        assert!("repeat".parse::<Concealment>().is_err());
+        assert_eq!(Concealment::default().to_string(), "waveform");
+    }
+
+    #[test]
+    fn test_find_pitch() {
+        assert_eq!(find_pitch(&tone(HISTORY_LEN)), 80);
+    }
+
+    #[test]
+    fn test_zero_fill() {
+        let mut concealer = Concealer::new(Concealment::Zero);
+        let mut out = Vec::new();
+        concealer.good_frame(&tone(480), &mut out);
+        concealer.conceal(2, 320, &mut out);
+        assert_eq!(out.len(), 800);
+        assert!(out[480..].iter().all(|&s| s == 0));
+        assert_eq!(concealer.frames_concealed(), 2);
+    }
+
+    #[test]
+    fn test_waveform_continues_the_tone() {
+        let signal = tone(800);
+        let mut concealer = Concealer::new(Concealment::Waveform);
+        let mut out = Vec::new();
+        concealer.good_frame(&signal[..480], &mut out);
+        concealer.conceal(1, 160, &mut out);
+
+        // The first 10ms repeat the last period
+        for (concealed, original) in out[480..560].iter().zip(&signal[480..560]) {
+            assert!((*concealed as i32 - *original as i32).abs() <= 1);
+        }
+        // The next 10ms are attenuated but still in phase
+        for (concealed, original) in out[560..640].iter().zip(&signal[560..640]) {
+            assert!((*concealed as i32 - *original as i32).abs() <= 8000 / 5 + 1);
+        }
+    }
+
+    #[test]
+    fn test_long_loss_fades_to_silence() {
+        let mut concealer = Concealer::new(Concealment::Waveform);
+        let mut out = Vec::new();
+        concealer.good_frame(&tone(480), &mut out);
+        concealer.conceal(4, 640, &mut out);
+
+        let peak = |samples: &[i16]| samples.iter().map(|s| s.unsigned_abs()).max().unwrap();
+        assert!(peak(&out[480..560]) > 7000);
+        assert!(peak(&out[480 + 400..480 + 480]) < 8000 / 5 + 1);
+        assert!(out[480 + 480..].iter().all(|&s| s == 0));
+    }
+
+    #[test]
+    fn test_recovery_cross_fades() {
+        let signal = tone(1120);
+        let mut concealer = Concealer::new(Concealment::Waveform);
+        let mut out = Vec::new();
+        concealer.good_frame(&signal[..480], &mut out);
+        concealer.conceal(1, 160, &mut out);
+        // Audio resumes with a jump in level
+        let loud: Vec<i16> = signal[640..800].iter().map(|&s| s.saturating_mul(3)).collect();
+        concealer.good_frame(&loud, &mut out);
+
+        // A quarter period plus 4ms is blended with the concealment, the
+        // rest is the received frame untouched
+        let overlap = 80 / 4 + RECOVERY_STEP;
+        assert_eq!(&out[640 + overlap..], &loud[overlap..]);
+        assert!(out[640..640 + overlap].iter().zip(&loud).any(|(a, b)| a != b));
+    }
+
+    #[test]
+    fn test_loss_before_enough_history_is_silence() {
+        let mut concealer = Concealer::new(Concealment::Waveform);
+        let mut out = Vec::new();
+        concealer.good_frame(&tone(160), &mut out);
+        concealer.conceal(1, 160, &mut out);
+        assert!(out[160..].iter().all(|&s| s == 0));
+        assert_eq!(concealer.frames_concealed(), 1);
+    }
+}
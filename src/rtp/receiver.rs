@@ -1,29 +1,81 @@
 use anyhow::{Context, Result};
 use rand::Rng;
-use std::time::Duration;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
 use tokio::net::UdpSocket;
 use tokio::time::timeout;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, info, trace, warn};
 
+use super::dtmf;
 use super::g711::{G711Codec, G711Decoder};
-use super::jitter::{BufferedPacket, JitterBuffer, JitterBufferConfig};
+use super::jitter::{BufferedPacket, JitterBuffer, JitterBufferConfig, JitterBufferStats, JitterMode};
+use super::plc::{Concealer, Concealment};
 use super::resample::resample_to_16k;
+use super::rtcp::{self, CallStats, RtcpSession, SenderInfo};
+use super::stream::{RtpStream, StreamChange, StreamSpan, StreamTracker};
+use super::tones::{ToneDetector, ToneEvent};
 
 /// RTP packet header (simplified)
 #[derive(Debug)]
 struct RtpHeader {
+    marker: bool,
     payload_type: u8,
     sequence: u16,
     timestamp: u32,
     ssrc: u32,
 }
 
+/// SSRC used for everything we send (keepalives and DTMF events)
+const LOCAL_SSRC: u32 = 1;
+
+/// First sequence number of our outgoing stream
+const INITIAL_TX_SEQUENCE: u16 = 100;
+
+/// Longest run of missing packets that is concealed (5s at 20ms). A
+/// longer jump in sequence numbers is a restarted stream, not loss.
+const MAX_CONCEALED_FRAMES: u16 = 250;
+
+/// Ports tried for an even RTP port with a free odd port above it
+const BIND_PAIR_ATTEMPTS: usize = 10;
+
+/// The last packet decoded, to size the gap before the next one
+#[derive(Debug, Clone, Copy)]
+struct DecodedFrame {
+    sequence: u16,
+    timestamp: u32,
+    samples: usize,
+}
+
 pub struct RtpReceiver {
     socket: UdpSocket,
     decoder: Option<G711Decoder>,
     samples: Vec<i16>,
     jitter_buffer: JitterBuffer,
+    /// Fills the place of packets lost between decoded ones
+    concealer: Concealer,
+    last_frame: Option<DecodedFrame>,
+    /// Samples decoded so far including concealment, at 8kHz (survives
+    /// take_samples_f32)
+    decoded_samples: u64,
+    /// The RTP streams (SSRCs) heard, and which one is being received
+    streams: StreamTracker,
+    /// Packets of a stream on probation, decoded if it takes over
+    probation: Vec<BufferedPacket>,
+    /// Next outgoing sequence number (shared by keepalives and DTMF)
+    tx_sequence: u16,
+    /// Next outgoing RTP timestamp (8kHz clock)
+    tx_timestamp: u32,
+    /// Tone detection over everything decoded (survives take_samples_f32)
+    tone_detector: ToneDetector,
+    /// Socket on the odd port above the RTP port, if one could be bound
+    rtcp_socket: Option<UdpSocket>,
+    /// Where to send our reports, and whether that is the muxed RTP port
+    rtcp_target: Option<(SocketAddr, bool)>,
+    rtcp: RtcpSession,
+    /// RTP packets and payload octets sent, for our sender reports
+    tx_packets: u32,
+    tx_octets: u32,
 }
 
 impl RtpReceiver {
@@ -36,12 +88,29 @@ impl RtpReceiver {
 
         debug!("RTP receiver bound to port {}", port);
 
-        Ok(Self {
-            socket,
-            decoder: None,
-            samples: Vec::new(),
-            jitter_buffer: JitterBuffer::new(JitterBufferConfig::default()),
-        })
+        Ok(Self::from_socket(socket))
+    }
+
+    /// Bind an even RTP port with RTCP on the odd port above it (RFC 3550
+    /// Section 11). If no pair is free, RTCP shares the RTP port, which the
+    /// SDP offer's `a=rtcp` tells the far end.
+    pub async fn bind_pair() -> Result<Self> {
+        for _ in 0..BIND_PAIR_ATTEMPTS {
+            let socket = UdpSocket::bind("0.0.0.0:0").await.context("Failed to bind RTP socket")?;
+            let port = socket.local_addr()?.port();
+            if port % 2 != 0 || port == u16::MAX {
+                continue;
+            }
+            if let Ok(rtcp_socket) = UdpSocket::bind(("0.0.0.0", port + 1)).await {
+                debug!("RTP receiver bound to port {} (RTCP {})", port, port + 1);
+                let mut receiver = Self::from_socket(socket);
+                receiver.rtcp_socket = Some(rtcp_socket);
+                return Ok(receiver);
+            }
+        }
+
+        warn!("No free RTP/RTCP port pair, RTCP shares the RTP port");
+        Self::bind(0).await
     }
 
     /// Create from an already-bound socket (avoids port race conditions)
@@ -51,13 +120,42 @@ impl RtpReceiver {
             decoder: None,
             samples: Vec::new(),
             jitter_buffer: JitterBuffer::new(JitterBufferConfig::default()),
+            concealer: Concealer::new(Concealment::default()),
+            last_frame: None,
+            decoded_samples: 0,
+            streams: StreamTracker::new(),
+            probation: Vec::new(),
+            tx_sequence: INITIAL_TX_SEQUENCE,
+            tx_timestamp: INITIAL_TX_SEQUENCE as u32 * 160,
+            tone_detector: ToneDetector::new(),
+            rtcp_socket: None,
+            rtcp_target: None,
+            rtcp: RtcpSession::new(LOCAL_SSRC),
+            tx_packets: 0,
+            tx_octets: 0,
         }
     }
 
+    /// Fixed or adaptive jitter buffering (fixed by default). Set before
+    /// any audio arrives: the buffer is replaced.
+    pub fn set_jitter_mode(&mut self, mode: JitterMode) {
+        self.jitter_buffer = JitterBuffer::new(JitterBufferConfig { mode, ..Default::default() });
+    }
+
+    /// How lost packets are filled in (waveform substitution by default)
+    pub fn set_concealment(&mut self, strategy: Concealment) {
+        self.concealer = Concealer::new(strategy);
+    }
+
     pub fn local_port(&self) -> Result<u16> {
         Ok(self.socket.local_addr()?.port())
     }
 
+    /// Port of the separate RTCP socket, if `bind_pair` found a pair
+    pub fn rtcp_port(&self) -> Option<u16> {
+        self.rtcp_socket.as_ref().and_then(|socket| socket.local_addr().ok()).map(|addr| addr.port())
+    }
+
     /// Discover public address/port for this socket using STUN
     pub async fn discover_public_address(&self, stun_server: &str) -> Result<std::net::SocketAddr> {
         crate::stun::discover_public_address_tokio(&self.socket, stun_server).await
@@ -123,6 +221,47 @@ impl RtpReceiver {
         }
     }
 
+    /// Send RTCP reports to `target`. With `mux` they go out of the RTP
+    /// socket, otherwise out of the RTCP socket (the RTP socket if none was
+    /// bound, as the SDP offer said).
+    pub fn set_rtcp_target(&mut self, target: SocketAddr, mux: bool) {
+        debug!("Sending RTCP to {}{}", target, if mux { " (rtcp-mux)" } else { "" });
+        self.rtcp_target = Some((target, mux));
+    }
+
+    /// Send a compound RTCP packet to the RTCP target, if there is one
+    async fn send_rtcp(&self, packet: &[u8]) {
+        let socket = match self.rtcp_target {
+            Some((_, true)) => Some(&self.socket),
+            Some((_, false)) => Some(self.rtcp_socket.as_ref().unwrap_or(&self.socket)),
+            None => None,
+        };
+        if let (Some(socket), Some((target, _))) = (socket, self.rtcp_target) {
+            if let Err(e) = socket.send_to(packet, target).await {
+                debug!("RTCP send to {} failed: {}", target, e);
+            }
+        }
+    }
+
+    /// Send our next sender or receiver report
+    async fn send_rtcp_report(&mut self) {
+        let now = Instant::now();
+        let sender = (self.tx_packets > 0).then(|| SenderInfo {
+            ntp_timestamp: self.rtcp.ntp_at(now),
+            rtp_timestamp: self.tx_timestamp,
+            packet_count: self.tx_packets,
+            octet_count: self.tx_octets,
+        });
+        let report = self.rtcp.report(sender, now);
+        trace!("Sending RTCP report ({} bytes)", report.len());
+        self.send_rtcp(&report).await;
+    }
+
+    /// Tell the far end we are leaving (RTCP BYE), before hanging up
+    pub async fn send_rtcp_bye(&self) {
+        self.send_rtcp(&self.rtcp.bye()).await;
+    }
+
     /// Send empty RTP packets to punch through NAT
     pub async fn punch_nat(&self, remote_addr: std::net::SocketAddr) -> Result<()> {
         info!("Sending NAT hole-punch packets to {}", remote_addr);
@@ -175,20 +314,14 @@ impl RtpReceiver {
         keepalive_target: Option<std::net::SocketAddr>,
     ) -> Result<bool> {
         let mut buf = [0u8; 2048];
+        let mut rtcp_buf = [0u8; 2048];
         let deadline = tokio::time::Instant::now() + duration;
         let mut cancelled = false;
         let mut packet_count: u32 = 0;
         let mut first_packet_logged = false;
-        let mut keepalive_seq: u16 = 100;
         let mut last_keepalive = tokio::time::Instant::now();
         let keepalive_interval = Duration::from_millis(20);
 
-        // Build a reusable keepalive packet (minimal RTP header)
-        let mut keepalive_pkt = [0u8; 12];
-        keepalive_pkt[0] = 0x80; // V=2
-        keepalive_pkt[1] = 0x00; // PT=0 (PCMU)
-        keepalive_pkt[8..12].copy_from_slice(&[0x00, 0x00, 0x00, 0x01]); // SSRC
-
         loop {
             if cancel_token.is_cancelled() {
                 debug!("RTP receive cancelled by shutdown signal");
@@ -204,18 +337,24 @@ impl RtpReceiver {
             // Send keepalive if interval elapsed
             if let Some(target) = keepalive_target {
                 if last_keepalive.elapsed() >= keepalive_interval {
-                    keepalive_pkt[2..4].copy_from_slice(&keepalive_seq.to_be_bytes());
-                    let ts = (keepalive_seq as u32) * 160;
-                    keepalive_pkt[4..8].copy_from_slice(&ts.to_be_bytes());
+                    // Minimal RTP header, PT=0 (PCMU), no payload
+                    let keepalive_pkt = self.next_tx_header(0, false, self.tx_timestamp);
+                    self.tx_timestamp = self.tx_timestamp.wrapping_add(160);
                     let _ = self.socket.send_to(&keepalive_pkt, target).await;
-                    keepalive_seq = keepalive_seq.wrapping_add(1);
                     last_keepalive = tokio::time::Instant::now();
                 }
             }
 
+            if self.rtcp_target.is_some() && self.rtcp.report_due(Instant::now()) {
+                self.send_rtcp_report().await;
+            }
+
             tokio::select! {
                 result = timeout(remaining.min(Duration::from_millis(20)), self.socket.recv_from(&mut buf)) => {
                     match result {
+                        Ok(Ok((len, _))) if rtcp::is_rtcp(&buf[..len]) => {
+                            self.rtcp.on_rtcp(&buf[..len], Instant::now());
+                        }
                         Ok(Ok((len, addr))) => {
                             packet_count += 1;
                             if !first_packet_logged {
@@ -223,7 +362,7 @@ impl RtpReceiver {
                                 first_packet_logged = true;
                             }
                             if len >= 12 {
-                                self.process_packet(&buf[..len]);
+                                self.process_packet(&buf[..len], addr);
                             }
                         }
                         Ok(Err(e)) => {
@@ -232,6 +371,12 @@ impl RtpReceiver {
                         Err(_) => {}
                     }
                 }
+                result = recv_rtcp(self.rtcp_socket.as_ref(), &mut rtcp_buf) => {
+                    match result {
+                        Ok((len, _)) => self.rtcp.on_rtcp(&rtcp_buf[..len], Instant::now()),
+                        Err(e) => debug!("RTCP receive error: {}", e),
+                    }
+                }
                 _ = cancel_token.cancelled() => {
                     debug!("RTP receive cancelled by shutdown signal");
                     cancelled = true;
@@ -242,10 +387,71 @@ impl RtpReceiver {
 
         info!("RTP receive done: {} packets received, {} i16 samples decoded", packet_count, self.samples.len());
         self.flush_jitter_buffer();
+        let buffer = self.jitter_buffer.stats();
+        debug!("Jitter buffer target depth {}, jitter {:.1} ms", buffer.target_depth, buffer.jitter_ms);
         Ok(!cancelled)
     }
 
-    fn process_packet(&mut self, data: &[u8]) {
+    /// Send DTMF digits as RFC 4733 telephone-events
+    ///
+    /// Each digit is held for `dtmf::DIGIT_DURATION` followed by
+    /// `dtmf::INTER_DIGIT_GAP`. Incoming audio is buffered by the socket
+    /// meanwhile and picked up by the next receive call.
+    pub async fn send_dtmf(
+        &mut self,
+        digits: &str,
+        target: std::net::SocketAddr,
+        payload_type: u8,
+    ) -> Result<()> {
+        if !dtmf::is_valid_digits(digits) {
+            anyhow::bail!("Invalid DTMF digits: {:?}", digits);
+        }
+
+        info!("Sending DTMF \"{}\" to {} (PT {})", digits, target, payload_type);
+
+        for event in digits.chars().filter_map(dtmf::digit_to_event) {
+            let event_timestamp = self.tx_timestamp;
+            let packets = dtmf::event_packets(event, dtmf::DIGIT_DURATION);
+
+            for event_packet in &packets {
+                let header = self.next_tx_header(payload_type, event_packet.marker, event_timestamp);
+                let mut packet = header.to_vec();
+                packet.extend_from_slice(&event_packet.payload);
+                self.tx_octets = self.tx_octets.wrapping_add(event_packet.payload.len() as u32);
+                self.socket
+                    .send_to(&packet, target)
+                    .await
+                    .context("Failed to send DTMF packet")?;
+
+                // Updates are paced at the packet interval; End repeats go back to back
+                let is_end = event_packet.payload[1] & 0x80 != 0;
+                if !is_end {
+                    tokio::time::sleep(dtmf::PACKET_INTERVAL).await;
+                }
+            }
+
+            self.tx_timestamp = event_timestamp
+                .wrapping_add(dtmf::duration_to_units(dtmf::DIGIT_DURATION + dtmf::INTER_DIGIT_GAP));
+            tokio::time::sleep(dtmf::INTER_DIGIT_GAP).await;
+        }
+
+        Ok(())
+    }
+
+    /// Build the next outgoing RTP header and advance the sequence number
+    fn next_tx_header(&mut self, payload_type: u8, marker: bool, timestamp: u32) -> [u8; 12] {
+        let mut header = [0u8; 12];
+        header[0] = 0x80; // V=2, P=0, X=0, CC=0
+        header[1] = (if marker { 0x80 } else { 0x00 }) | (payload_type & 0x7F);
+        header[2..4].copy_from_slice(&self.tx_sequence.to_be_bytes());
+        header[4..8].copy_from_slice(&timestamp.to_be_bytes());
+        header[8..12].copy_from_slice(&LOCAL_SSRC.to_be_bytes());
+        self.tx_sequence = self.tx_sequence.wrapping_add(1);
+        self.tx_packets = self.tx_packets.wrapping_add(1);
+        header
+    }
+
+    fn process_packet(&mut self, data: &[u8], source: SocketAddr) {
         if data.len() < 12 {
             return;
         }
@@ -256,46 +462,172 @@ impl RtpReceiver {
         }
 
         let header = self.parse_header(data);
-        if self.decoder.is_none() {
-            self.decoder = G711Decoder::from_payload_type(header.payload_type);
-            if self.decoder.is_none() {
+        // Events reuse one timestamp across packets, so only audio feeds the
+        // jitter estimate
+        let audio_timestamp = G711Decoder::from_payload_type(header.payload_type).map(|_| header.timestamp);
+        self.rtcp.on_rtp(header.ssrc, header.sequence, audio_timestamp, Instant::now());
+
+        let Some(decoder) = G711Decoder::from_payload_type(header.payload_type) else {
+            if self.decoder.is_some() {
+                // telephone-event or comfort noise from the far end, not audio
+                trace!("Ignoring non-audio RTP payload type {}", header.payload_type);
+            } else {
                 warn!("Unsupported RTP payload type: {}", header.payload_type);
-                return;
             }
-        }
+            return;
+        };
 
         let payload_start = self.calculate_payload_offset(data);
         if payload_start >= data.len() {
             return;
         }
 
-        self.jitter_buffer.insert(BufferedPacket {
+        let packet = BufferedPacket {
             sequence: header.sequence,
             timestamp: header.timestamp,
+            marker: header.marker,
             payload: data[payload_start..].to_vec(),
-        });
+        };
+        match self.streams.classify(header.ssrc, header.sequence) {
+            StreamChange::Same => {}
+            StreamChange::Straggler => {
+                trace!("Ignoring seq={} from previous RTP stream {:08x}", header.sequence, header.ssrc);
+                return;
+            }
+            StreamChange::Probation(sequential) => {
+                trace!("Holding seq={} from RTP stream {:08x} on probation", header.sequence, header.ssrc);
+                if sequential == 1 {
+                    self.probation.clear();
+                }
+                self.probation.push(packet);
+                return;
+            }
+            StreamChange::New | StreamChange::Resumed => {
+                self.switch_stream(&header, source);
+                for held in std::mem::take(&mut self.probation) {
+                    self.streams.record(held.sequence);
+                    self.jitter_buffer.insert(held);
+                }
+            }
+        }
+        self.streams.record(header.sequence);
+        if self.decoder.is_none() {
+            self.decoder = Some(decoder);
+        }
+
+        self.jitter_buffer.insert(packet);
 
         self.process_buffered_packets();
     }
 
+    /// Finish the current stream and start over for the one `header` came
+    /// from: its sequence numbers, timestamps and codec owe nothing to the
+    /// previous stream's
+    fn switch_stream(&mut self, header: &RtpHeader, source: SocketAddr) {
+        if let Some(previous) = self.streams.current() {
+            info!(
+                "RTP stream switched from SSRC {:08x} to {:08x} (from {}) at {} ms",
+                previous,
+                header.ssrc,
+                source,
+                self.decoded_ms()
+            );
+            self.flush_jitter_buffer();
+            self.jitter_buffer.restart();
+            self.last_frame = None;
+            self.decoder = None;
+        }
+        let first_sequence = self.probation.first().map_or(header.sequence, |packet| packet.sequence);
+        self.streams.switch(header.ssrc, first_sequence, source, self.decoded_ms());
+    }
+
     fn process_buffered_packets(&mut self) {
+        let decoded_from = self.samples.len();
         while let Some(packet) = self.jitter_buffer.pop() {
-            if let Some(ref decoder) = self.decoder {
-                decoder.decode_into(&packet.payload, &mut self.samples);
-            }
+            self.decode_packet(&packet);
         }
+        self.tone_detector.feed(&self.samples[decoded_from..]);
     }
 
     fn flush_jitter_buffer(&mut self) {
+        let decoded_from = self.samples.len();
         for packet in self.jitter_buffer.drain() {
-            if let Some(ref decoder) = self.decoder {
-                decoder.decode_into(&packet.payload, &mut self.samples);
+            self.decode_packet(&packet);
+        }
+        self.tone_detector.feed(&self.samples[decoded_from..]);
+    }
+
+    /// Decode a packet from the jitter buffer onto the samples, concealing
+    /// the packets missing before it
+    fn decode_packet(&mut self, packet: &BufferedPacket) {
+        let Some(ref decoder) = self.decoder else {
+            return;
+        };
+        let mut frame = Vec::with_capacity(packet.payload.len());
+        decoder.decode_into(&packet.payload, &mut frame);
+        let decoded_from = self.samples.len();
+
+        if let Some(last) = self.last_frame {
+            let missing = packet.sequence.wrapping_sub(last.sequence).wrapping_sub(1);
+            if missing > 0 && missing <= MAX_CONCEALED_FRAMES {
+                let samples = gap_samples(&last, packet.timestamp, missing);
+                trace!("Concealing {} lost packets ({} samples) before seq={}", missing, samples, packet.sequence);
+                self.concealer.conceal(missing as u64, samples, &mut self.samples);
+            } else if missing > 0 {
+                debug!("RTP sequence jumped from {} to {}, not concealing", last.sequence, packet.sequence);
             }
         }
+
+        self.concealer.good_frame(&frame, &mut self.samples);
+        self.decoded_samples += (self.samples.len() - decoded_from) as u64;
+        self.last_frame = Some(DecodedFrame {
+            sequence: packet.sequence,
+            timestamp: packet.timestamp,
+            samples: frame.len(),
+        });
+    }
+
+    /// Packet counts of everything received so far, and the lost frames
+    /// concealed
+    pub fn packet_stats(&self) -> JitterBufferStats {
+        JitterBufferStats {
+            frames_concealed: self.concealer.frames_concealed(),
+            ..self.jitter_buffer.stats()
+        }
+    }
+
+    /// Loss, jitter, reordering and round-trip time of the call so far,
+    /// from RTP reception and the far end's RTCP
+    pub fn call_stats(&self) -> CallStats {
+        self.rtcp.stats()
+    }
+
+    /// Tones detected in all audio received so far (DTMF, ringback, busy,
+    /// reorder, SIT), timed from the first decoded sample
+    pub fn tone_timeline(&self) -> Vec<ToneEvent> {
+        self.tone_detector.timeline()
+    }
+
+    /// The RTP streams (SSRCs) heard so far, with their source addresses
+    /// and where in the audio each started
+    pub fn streams(&self) -> &[RtpStream] {
+        self.streams.streams()
+    }
+
+    /// Which stream each stretch of the audio came from, timed from the
+    /// first decoded sample
+    pub fn stream_timeline(&self) -> Vec<StreamSpan> {
+        self.streams.timeline(self.decoded_ms())
+    }
+
+    /// Audio decoded so far, in ms
+    fn decoded_ms(&self) -> u64 {
+        self.decoded_samples * 1000 / 8000
     }
 
     fn parse_header(&self, data: &[u8]) -> RtpHeader {
         RtpHeader {
+            marker: data[1] & 0x80 != 0,
             payload_type: data[1] & 0x7F,
             sequence: u16::from_be_bytes([data[2], data[3]]),
             timestamp: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
@@ -323,6 +655,37 @@ impl RtpReceiver {
         let f32_samples: Vec<f32> = self.samples.iter().map(|&s| s as f32 / 32768.0).collect();
         resample_to_16k(&f32_samples)
     }
+
+    /// Take the samples accumulated so far as f32 (resampled to 16kHz),
+    /// leaving the buffer empty for the next segment
+    pub fn take_samples_f32(&mut self) -> Vec<f32> {
+        let samples = self.get_samples_f32();
+        self.samples.clear();
+        samples
+    }
+}
+
+/// Samples missing between `last` and a packet `missing` packets after it.
+/// The timestamp delta says how much audio went missing; if it is not
+/// plausible for that many packets (a far end that restarts timestamps, or
+/// sends no audio during silence), the missing packets are taken to be the
+/// size of the last one.
+fn gap_samples(last: &DecodedFrame, timestamp: u32, missing: u16) -> usize {
+    let by_sequence = missing as usize * last.samples;
+    let by_timestamp = (timestamp.wrapping_sub(last.timestamp) as usize).saturating_sub(last.samples);
+    if by_timestamp > 0 && by_timestamp <= 2 * by_sequence {
+        by_timestamp
+    } else {
+        by_sequence
+    }
+}
+
+/// Receive on the RTCP socket, or never if there is none
+async fn recv_rtcp(socket: Option<&UdpSocket>, buf: &mut [u8]) -> std::io::Result<(usize, SocketAddr)> {
+    match socket {
+        Some(socket) => socket.recv_from(buf).await,
+        None => std::future::pending().await,
+    }
 }
 
 /// Parse RTP header from raw bytes (public for testing)
@@ -361,6 +724,227 @@ mod tests {
         assert!(!result.unwrap());
     }
 
+    #[tokio::test]
+    async fn test_send_dtmf_packets() {
+        let mut receiver = RtpReceiver::bind(0).await.unwrap();
+        let far_end = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let target = far_end.local_addr().unwrap();
+
+        receiver.send_dtmf("2", target, 101).await.unwrap();
+
+        let mut buf = [0u8; 64];
+        let mut packets = Vec::new();
+        while let Ok(Ok((len, _))) =
+            timeout(Duration::from_millis(100), far_end.recv_from(&mut buf)).await
+        {
+            packets.push(buf[..len].to_vec());
+        }
+
+        assert_eq!(packets.len(), dtmf::event_packets(2, dtmf::DIGIT_DURATION).len());
+        // Marker on the first packet only, PT 101 throughout
+        assert_eq!(packets[0][1], 0x80 | 101);
+        assert!(packets[1..].iter().all(|p| p[1] == 101));
+
+        let (_, first_seq, first_ts, ssrc, offset) = parse_rtp_header(&packets[0]).unwrap();
+        assert_eq!(ssrc, LOCAL_SSRC);
+        for (i, packet) in packets.iter().enumerate() {
+            let (_, seq, ts, _, _) = parse_rtp_header(packet).unwrap();
+            assert_eq!(seq, first_seq.wrapping_add(i as u16));
+            assert_eq!(ts, first_ts, "all packets of an event share its timestamp");
+            assert_eq!(packet[offset], 2, "event code for digit 2");
+        }
+        assert!(packets.last().unwrap()[offset + 1] & 0x80 != 0, "last packet has End bit");
+    }
+
+    #[tokio::test]
+    async fn test_send_dtmf_rejects_invalid_digits() {
+        let mut receiver = RtpReceiver::bind(0).await.unwrap();
+        let target = "127.0.0.1:9".parse().unwrap();
+        assert!(receiver.send_dtmf("1x", target, 101).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_take_samples_drains_buffer() {
+        let mut receiver = RtpReceiver::bind(0).await.unwrap();
+        // One 20ms PCMU packet of silence (0xFF)
+        let mut packet = vec![0x80, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0, 0, 2];
+        packet.extend_from_slice(&[0xFF; 160]);
+        receiver.process_packet(&packet, FAR_END);
+        receiver.flush_jitter_buffer();
+
+        assert!(!receiver.take_samples_f32().is_empty());
+        assert!(receiver.take_samples_f32().is_empty());
+    }
+
+    /// Where the test packets come from
+    const FAR_END: SocketAddr = SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 1)), 4000);
+
+    /// A 20ms PCMU packet of silence
+    fn silence_packet(sequence: u16, timestamp: u32) -> Vec<u8> {
+        let mut packet = vec![0x80, 0x00, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+        packet[2..4].copy_from_slice(&sequence.to_be_bytes());
+        packet[4..8].copy_from_slice(&timestamp.to_be_bytes());
+        packet.extend_from_slice(&[0xFF; 160]);
+        packet
+    }
+
+    #[tokio::test]
+    async fn test_lost_packets_are_concealed() {
+        let mut receiver = RtpReceiver::bind(0).await.unwrap();
+        receiver.set_concealment(Concealment::Zero);
+        for seq in (0..10u16).filter(|seq| ![4, 5].contains(seq)) {
+            receiver.process_packet(&silence_packet(seq, seq as u32 * 160), FAR_END);
+        }
+        receiver.flush_jitter_buffer();
+
+        // The two lost packets keep their place in the audio
+        assert_eq!(receiver.samples.len(), 10 * 160);
+        assert_eq!(receiver.packet_stats().frames_concealed, 2);
+    }
+
+    #[tokio::test]
+    async fn test_sequence_jump_is_not_concealed() {
+        let mut receiver = RtpReceiver::bind(0).await.unwrap();
+        for (seq, ts) in [(0u16, 0u32), (1, 160), (2, 320), (5000, 800_000), (5001, 800_160)] {
+            receiver.process_packet(&silence_packet(seq, ts), FAR_END);
+        }
+        receiver.flush_jitter_buffer();
+
+        assert_eq!(receiver.samples.len(), 5 * 160);
+        assert_eq!(receiver.packet_stats().frames_concealed, 0);
+    }
+
+    #[tokio::test]
+    async fn test_stream_switch_restarts_sequence() {
+        let mut receiver = RtpReceiver::bind(0).await.unwrap();
+        for seq in 30000..30050u16 {
+            receiver.process_packet(&silence_packet(seq, seq as u32 * 160), FAR_END);
+        }
+
+        // Transferred to a media server elsewhere: new SSRC, sequence
+        // numbers behind the old ones, PCMA
+        let media_server = SocketAddr::from(([192, 0, 2, 9], 5000));
+        for seq in 100..150u16 {
+            let mut packet = silence_packet(seq, seq as u32 * 160);
+            packet[1] = 8;
+            packet[8..12].copy_from_slice(&0xBEEFu32.to_be_bytes());
+            receiver.process_packet(&packet, media_server);
+        }
+        // A straggler from the first stream is not audio any more
+        receiver.process_packet(&silence_packet(30049, 30049 * 160), FAR_END);
+        receiver.flush_jitter_buffer();
+
+        assert_eq!(receiver.samples.len(), 100 * 160);
+        assert_eq!(receiver.packet_stats().frames_concealed, 0);
+        let streams = receiver.streams();
+        assert_eq!(streams.len(), 2);
+        assert_eq!((streams[0].ssrc, streams[0].source, streams[0].packets), (2, FAR_END, 50));
+        assert_eq!((streams[1].ssrc, streams[1].source, streams[1].start_ms), (0xBEEF, media_server, 1000));
+        assert_eq!(
+            receiver.stream_timeline(),
+            vec![
+                StreamSpan { ssrc: 2, start_ms: 0, end_ms: 1000 },
+                StreamSpan { ssrc: 0xBEEF, start_ms: 1000, end_ms: 2000 },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stray_ssrc_interleaved_does_not_switch() {
+        let mut receiver = RtpReceiver::bind(0).await.unwrap();
+        let stray = SocketAddr::from(([192, 0, 2, 66], 6000));
+        for seq in 1000..1050u16 {
+            receiver.process_packet(&silence_packet(seq, seq as u32 * 160), FAR_END);
+            // A packet from another SSRC after every one of ours, never two
+            // in sequence
+            let mut packet = silence_packet(seq.wrapping_mul(7), seq as u32 * 320);
+            packet[8..12].copy_from_slice(&0xBAD0u32.to_be_bytes());
+            receiver.process_packet(&packet, stray);
+        }
+        receiver.flush_jitter_buffer();
+
+        assert_eq!(receiver.samples.len(), 50 * 160);
+        assert_eq!(receiver.packet_stats().frames_concealed, 0);
+        assert_eq!(receiver.streams().len(), 1);
+        assert_eq!(receiver.stream_timeline(), vec![StreamSpan { ssrc: 2, start_ms: 0, end_ms: 1000 }]);
+    }
+
+    #[test]
+    fn test_gap_samples() {
+        let last = DecodedFrame { sequence: 9, timestamp: 1440, samples: 160 };
+        // Two 20ms packets missing
+        assert_eq!(gap_samples(&last, 1440 + 3 * 160, 2), 320);
+        // Two 30ms packets missing (the timestamps tell)
+        assert_eq!(gap_samples(&last, 1440 + 160 + 2 * 240, 2), 480);
+        // Timestamps that make no sense for two packets fall back to the
+        // size of the last packet
+        assert_eq!(gap_samples(&last, 1440, 2), 320);
+        assert_eq!(gap_samples(&last, 1440 + 100_000, 2), 320);
+    }
+
+    #[tokio::test]
+    async fn test_tone_timeline_survives_take_samples() {
+        use crate::rtp::tones::Tone;
+
+        let mut receiver = RtpReceiver::bind(0).await.unwrap();
+        // Two cycles of 480+620Hz busy tone (500ms on, 500ms off) as PCMU
+        // packets
+        let pcm: Vec<i16> = (0..16000)
+            .map(|i| {
+                if (i / 4000) % 2 == 1 {
+                    return 0;
+                }
+                let t = i as f64 / 8000.0;
+                let v = (2.0 * std::f64::consts::PI * 480.0 * t).sin()
+                    + (2.0 * std::f64::consts::PI * 620.0 * t).sin();
+                (v * 8000.0) as i16
+            })
+            .collect();
+        // No encoder in the crate: pick the nearest u-law code for each sample
+        let ulaw = G711Decoder::new(G711Codec::ULaw);
+        let encode = |s: i16| {
+            (0..=255u8)
+                .min_by_key(|&b| (ulaw.decode_sample(b) as i32 - s as i32).abs())
+                .unwrap()
+        };
+        for (seq, frame) in pcm.chunks(160).enumerate() {
+            let mut packet = vec![0x80, 0x00, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+            packet[2..4].copy_from_slice(&(seq as u16).to_be_bytes());
+            packet.extend(frame.iter().map(|&s| encode(s)));
+            receiver.process_packet(&packet, FAR_END);
+        }
+        receiver.flush_jitter_buffer();
+        receiver.take_samples_f32();
+
+        let tones: Vec<Tone> = receiver.tone_timeline().iter().map(|e| e.tone).collect();
+        assert_eq!(tones, vec![Tone::Busy; 2]);
+    }
+
+    #[tokio::test]
+    async fn test_bind_pair_and_muxed_rtcp() {
+        let mut receiver = RtpReceiver::bind_pair().await.unwrap();
+        let port = receiver.local_port().unwrap();
+        if let Some(rtcp_port) = receiver.rtcp_port() {
+            assert_eq!(port % 2, 0);
+            assert_eq!(rtcp_port, port + 1);
+        }
+
+        // An SR with CNAME arriving on the RTP port counts as RTCP, not audio
+        let far_end = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let report = rtcp::to_bytes(&[
+            rtcp::RtcpPacket::SenderReport { ssrc: 7, sender: SenderInfo::default(), reports: vec![] },
+            rtcp::RtcpPacket::SourceDescription(vec![(7, Some("pbx".to_string()))]),
+        ]);
+        far_end.send_to(&report, ("127.0.0.1", port)).await.unwrap();
+        receiver.receive_for_cancellable(Duration::from_millis(100), CancellationToken::new()).await.unwrap();
+
+        let stats = receiver.call_stats();
+        assert_eq!(stats.sender_reports, 1);
+        assert_eq!(stats.remote_cname.as_deref(), Some("pbx"));
+        assert_eq!(stats.expected, 0);
+        assert_eq!(receiver.packet_stats().packets_received, 0);
+    }
+
     #[test]
     fn test_parse_rtp_header_valid() {
         let packet = [
@@ -4,17 +4,26 @@ use tracing::warn;
 
 /// High-quality FFT-based resampling from 8kHz to 16kHz using Rubato
 pub fn resample_8k_to_16k_fft(samples: &[f32]) -> Result<Vec<f32>> {
+    resample_fft(samples, 8000, 16000)
+}
+
+/// High-quality FFT-based resampling between arbitrary rates using Rubato
+pub fn resample_fft(samples: &[f32], from_rate: u32, to_rate: u32) -> Result<Vec<f32>> {
     if samples.is_empty() {
         return Ok(Vec::new());
     }
+    if from_rate == to_rate {
+        return Ok(samples.to_vec());
+    }
+
+    let ratio = to_rate as f64 / from_rate as f64;
 
-    // Create resampler: 8000 Hz -> 16000 Hz (ratio = 2.0)
     // chunk_size should be a reasonable size for processing
     let chunk_size = 1024;
-    let mut resampler = FftFixedIn::<f32>::new(8000, 16000, chunk_size, 2, 1)
+    let mut resampler = FftFixedIn::<f32>::new(from_rate as usize, to_rate as usize, chunk_size, 2, 1)
         .context("Failed to create resampler")?;
 
-    let mut output = Vec::with_capacity(samples.len() * 2);
+    let mut output = Vec::with_capacity((samples.len() as f64 * ratio) as usize + 1);
 
     // Process in chunks
     let mut pos = 0;
@@ -38,7 +47,7 @@ pub fn resample_8k_to_16k_fft(samples: &[f32]) -> Result<Vec<f32>> {
 
             if !resampled.is_empty() && !resampled[0].is_empty() {
                 // Only take the proportion of samples we actually need
-                let expected_output = (chunk.len() as f64 * 2.0).ceil() as usize;
+                let expected_output = (chunk.len() as f64 * ratio).ceil() as usize;
                 let take = expected_output.min(resampled[0].len());
                 output.extend_from_slice(&resampled[0][..take]);
             }
@@ -92,3 +101,32 @@ pub fn resample_to_16k(samples: &[f32]) -> Vec<f32> {
         }
     }
 }
+
+/// Linear interpolation resampling between arbitrary rates
+pub fn resample_linear(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if samples.is_empty() || from_rate == to_rate {
+        return samples.to_vec();
+    }
+
+    let step = from_rate as f64 / to_rate as f64;
+    let len = (samples.len() as f64 / step) as usize;
+    (0..len)
+        .map(|i| {
+            let pos = i as f64 * step;
+            let index = pos as usize;
+            let next = samples.get(index + 1).copied().unwrap_or(samples[index]);
+            samples[index] + (next - samples[index]) * (pos - index as f64) as f32
+        })
+        .collect()
+}
+
+/// Resample between arbitrary rates, falling back to linear interpolation
+pub fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    match resample_fft(samples, from_rate, to_rate) {
+        Ok(resampled) => resampled,
+        Err(e) => {
+            warn!("FFT resampling failed, falling back to linear: {}", e);
+            resample_linear(samples, from_rate, to_rate)
+        }
+    }
+}
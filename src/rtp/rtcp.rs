@@ -0,0 +1,862 @@
+/// RTCP sender/receiver reports and call-quality statistics (RFC 3550)
+///
+/// Every RTP session has a control channel: on the next (odd) port, or on
+/// the RTP port itself when rtcp-mux (RFC 5761) is negotiated. The far end
+/// sends Sender Reports (SR) with its NTP wall clock and packet counts,
+/// SDES with its CNAME, and BYE when a source leaves. We answer with our own
+/// reports, each carrying a reception report block per source we hear:
+/// fraction and cumulative loss, extended highest sequence number,
+/// interarrival jitter, and LSR/DLSR so the far end can measure round-trip
+/// time. When we have sent RTP (keepalives count) our report is an SR, so
+/// the far end's report blocks about us give us the round-trip time too.
+///
+/// Reception statistics follow the reference algorithms in RFC 3550
+/// Appendix A.1 (sequence tracking), A.3 (loss) and A.8 (jitter), minus the
+/// probation period: the stream is trusted from its first packet, since a
+/// call's media starts as soon as we listen.
+///
+/// Compound packet layout (RFC 3550 Section 6.1):
+/// ```text
+///  SR or RR  | SDES (CNAME) | [BYE]
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |V=2|P|    RC   |   PT=SR=200   |             length            |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                         SSRC of sender                        |
+/// +=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+
+/// |   sender info (SR only): NTP timestamp, RTP timestamp,        |
+/// |   sender's packet count, sender's octet count                 |
+/// +=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+
+/// |   report blocks (24 bytes each)                               |
+/// +=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+=+
+/// ```
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Sender Report
+pub const PT_SR: u8 = 200;
+/// Receiver Report
+pub const PT_RR: u8 = 201;
+/// Source Description
+pub const PT_SDES: u8 = 202;
+/// Goodbye
+pub const PT_BYE: u8 = 203;
+
+/// SDES item type of the canonical name
+const SDES_CNAME: u8 = 1;
+
+/// CNAME we describe ourselves with
+const LOCAL_CNAME: &str = "phonecheck";
+
+/// Nominal interval between our reports. RFC 3550 scales this with session
+/// bandwidth; a two-party audio call always lands on the 5 s minimum.
+pub const REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// RTP clock rate of the G.711 payloads we receive
+const CLOCK_RATE: f64 = 8000.0;
+
+/// A sequence number this far ahead of the highest seen is a jump, not loss
+const MAX_DROPOUT: u16 = 3000;
+
+/// A sequence number up to this far behind the highest seen is a late packet
+const MAX_MISORDER: u16 = 100;
+
+/// Seconds between the NTP epoch (1900) and the Unix epoch (1970)
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+/// One reception report block: what a receiver heard of one source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ReportBlock {
+    pub ssrc: u32,
+    /// Fraction of packets lost since the previous report, in 1/256
+    pub fraction_lost: u8,
+    /// Packets lost over the whole call (24-bit signed; duplicates make it
+    /// negative)
+    pub cumulative_lost: i32,
+    /// Cycle count in the high 16 bits, highest sequence number in the low
+    pub highest_sequence: u32,
+    /// Interarrival jitter in RTP timestamp units
+    pub jitter: u32,
+    /// Middle 32 bits of the NTP timestamp of the last SR from this source
+    pub last_sr: u32,
+    /// Time since that SR arrived, in 1/65536 s
+    pub delay_since_last_sr: u32,
+}
+
+/// The sender info section of an SR
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SenderInfo {
+    pub ntp_timestamp: u64,
+    pub rtp_timestamp: u32,
+    pub packet_count: u32,
+    pub octet_count: u32,
+}
+
+/// One packet of a compound RTCP packet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RtcpPacket {
+    SenderReport { ssrc: u32, sender: SenderInfo, reports: Vec<ReportBlock> },
+    ReceiverReport { ssrc: u32, reports: Vec<ReportBlock> },
+    /// The CNAME of each described source, if it had one
+    SourceDescription(Vec<(u32, Option<String>)>),
+    Bye { ssrcs: Vec<u32>, reason: Option<String> },
+    /// APP, feedback and extended reports: skipped
+    Other(u8),
+}
+
+impl RtcpPacket {
+    /// Append the wire form of this packet to `out`
+    pub fn write(&self, out: &mut Vec<u8>) {
+        let start = out.len();
+        let (count, packet_type) = match self {
+            Self::SenderReport { reports, .. } => (reports.len(), PT_SR),
+            Self::ReceiverReport { reports, .. } => (reports.len(), PT_RR),
+            Self::SourceDescription(chunks) => (chunks.len(), PT_SDES),
+            Self::Bye { ssrcs, .. } => (ssrcs.len(), PT_BYE),
+            Self::Other(packet_type) => (0, *packet_type),
+        };
+        out.extend_from_slice(&[0x80 | (count.min(31) as u8), packet_type, 0, 0]);
+
+        match self {
+            Self::SenderReport { ssrc, sender, reports } => {
+                out.extend_from_slice(&ssrc.to_be_bytes());
+                out.extend_from_slice(&sender.ntp_timestamp.to_be_bytes());
+                out.extend_from_slice(&sender.rtp_timestamp.to_be_bytes());
+                out.extend_from_slice(&sender.packet_count.to_be_bytes());
+                out.extend_from_slice(&sender.octet_count.to_be_bytes());
+                reports.iter().for_each(|r| write_report_block(r, out));
+            }
+            Self::ReceiverReport { ssrc, reports } => {
+                out.extend_from_slice(&ssrc.to_be_bytes());
+                reports.iter().for_each(|r| write_report_block(r, out));
+            }
+            Self::SourceDescription(chunks) => {
+                for (ssrc, cname) in chunks {
+                    let chunk_start = out.len();
+                    out.extend_from_slice(&ssrc.to_be_bytes());
+                    if let Some(cname) = cname {
+                        let cname = &cname.as_bytes()[..cname.len().min(255)];
+                        out.extend_from_slice(&[SDES_CNAME, cname.len() as u8]);
+                        out.extend_from_slice(cname);
+                    }
+                    // Item list ends with a null octet, padded to 32 bits
+                    out.push(0);
+                    out.resize(chunk_start + (out.len() - chunk_start).next_multiple_of(4), 0);
+                }
+            }
+            Self::Bye { ssrcs, reason } => {
+                ssrcs.iter().for_each(|s| out.extend_from_slice(&s.to_be_bytes()));
+                if let Some(reason) = reason {
+                    let reason = &reason.as_bytes()[..reason.len().min(255)];
+                    out.push(reason.len() as u8);
+                    out.extend_from_slice(reason);
+                    out.resize(start + (out.len() - start).next_multiple_of(4), 0);
+                }
+            }
+            Self::Other(_) => {}
+        }
+
+        let words = ((out.len() - start) / 4 - 1) as u16;
+        out[start + 2..start + 4].copy_from_slice(&words.to_be_bytes());
+    }
+}
+
+fn write_report_block(block: &ReportBlock, out: &mut Vec<u8>) {
+    out.extend_from_slice(&block.ssrc.to_be_bytes());
+    let lost = (block.cumulative_lost.clamp(-0x80_0000, 0x7F_FFFF) as u32) & 0xFF_FFFF;
+    out.extend_from_slice(&(((block.fraction_lost as u32) << 24) | lost).to_be_bytes());
+    out.extend_from_slice(&block.highest_sequence.to_be_bytes());
+    out.extend_from_slice(&block.jitter.to_be_bytes());
+    out.extend_from_slice(&block.last_sr.to_be_bytes());
+    out.extend_from_slice(&block.delay_since_last_sr.to_be_bytes());
+}
+
+/// Serialize a compound packet
+pub fn to_bytes(packets: &[RtcpPacket]) -> Vec<u8> {
+    let mut out = Vec::new();
+    packets.iter().for_each(|p| p.write(&mut out));
+    out
+}
+
+/// Whether a datagram on a muxed RTP port is RTCP rather than RTP
+/// (RFC 5761 Section 4: RTCP packet types 192-223 would be RTP payload
+/// types 64-95 with the marker bit set, which are never assigned)
+pub fn is_rtcp(data: &[u8]) -> bool {
+    data.len() >= 8 && (data[0] >> 6) == 2 && (192..=223).contains(&data[1])
+}
+
+fn be_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+fn parse_report_blocks(data: &[u8], count: usize) -> Vec<ReportBlock> {
+    data.chunks_exact(24)
+        .take(count)
+        .map(|b| {
+            let loss = be_u32(b, 4);
+            // Sign-extend the 24-bit cumulative loss
+            let cumulative_lost = ((loss << 8) as i32) >> 8;
+            ReportBlock {
+                ssrc: be_u32(b, 0),
+                fraction_lost: (loss >> 24) as u8,
+                cumulative_lost,
+                highest_sequence: be_u32(b, 8),
+                jitter: be_u32(b, 12),
+                last_sr: be_u32(b, 16),
+                delay_since_last_sr: be_u32(b, 20),
+            }
+        })
+        .collect()
+}
+
+fn parse_sdes(data: &[u8], count: usize) -> Vec<(u32, Option<String>)> {
+    let mut chunks = Vec::new();
+    let mut at = 0;
+    while chunks.len() < count && at + 4 <= data.len() {
+        let ssrc = be_u32(data, at);
+        at += 4;
+        let mut cname = None;
+        while at < data.len() && data[at] != 0 {
+            let item = data[at];
+            let len = *data.get(at + 1).unwrap_or(&0) as usize;
+            let value = data.get(at + 2..at + 2 + len);
+            if item == SDES_CNAME {
+                cname = value.map(|v| String::from_utf8_lossy(v).into_owned());
+            }
+            at += 2 + len;
+        }
+        // Skip the terminating null and padding to the next 32-bit boundary
+        at = (at + 4) & !3;
+        chunks.push((ssrc, cname));
+    }
+    chunks
+}
+
+/// Parse a compound RTCP packet. Parsing stops at the first malformed
+/// packet; everything before it is returned.
+pub fn parse_compound(data: &[u8]) -> Vec<RtcpPacket> {
+    let mut packets = Vec::new();
+    let mut at = 0;
+    while at + 4 <= data.len() {
+        let header = &data[at..];
+        if header[0] >> 6 != 2 {
+            break;
+        }
+        let length = (u16::from_be_bytes([header[2], header[3]]) as usize + 1) * 4;
+        if length > header.len() {
+            break;
+        }
+        let mut body = &header[4..length];
+        // Only the last packet of a compound may be padded
+        if header[0] & 0x20 != 0 {
+            let padding = *body.last().unwrap_or(&0) as usize;
+            body = &body[..body.len().saturating_sub(padding)];
+        }
+        let count = (header[0] & 0x1F) as usize;
+
+        let packet = match header[1] {
+            PT_SR if body.len() >= 24 => RtcpPacket::SenderReport {
+                ssrc: be_u32(body, 0),
+                sender: SenderInfo {
+                    ntp_timestamp: (be_u32(body, 4) as u64) << 32 | be_u32(body, 8) as u64,
+                    rtp_timestamp: be_u32(body, 12),
+                    packet_count: be_u32(body, 16),
+                    octet_count: be_u32(body, 20),
+                },
+                reports: parse_report_blocks(&body[24..], count),
+            },
+            PT_RR if body.len() >= 4 => RtcpPacket::ReceiverReport {
+                ssrc: be_u32(body, 0),
+                reports: parse_report_blocks(&body[4..], count),
+            },
+            PT_SDES => RtcpPacket::SourceDescription(parse_sdes(body, count)),
+            PT_BYE => {
+                let ssrc_bytes = (count * 4).min(body.len() & !3);
+                let ssrcs = (0..ssrc_bytes).step_by(4).map(|i| be_u32(body, i)).collect();
+                let reason = body.get(ssrc_bytes).and_then(|&len| {
+                    body.get(ssrc_bytes + 1..ssrc_bytes + 1 + len as usize)
+                        .map(|r| String::from_utf8_lossy(r).into_owned())
+                });
+                RtcpPacket::Bye { ssrcs, reason }
+            }
+            packet_type => RtcpPacket::Other(packet_type),
+        };
+        packets.push(packet);
+        at += length;
+    }
+    packets
+}
+
+/// The current wall clock as a 64-bit NTP timestamp
+pub fn ntp_now() -> u64 {
+    let since_unix = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    ((since_unix.as_secs() + NTP_UNIX_OFFSET) << 32) + duration_to_ntp(since_unix.subsec_nanos() as u64)
+}
+
+/// Fractional NTP units (1/2^32 s) in a number of nanoseconds
+fn duration_to_ntp(nanos: u64) -> u64 {
+    (((nanos as u128) << 32) / 1_000_000_000) as u64
+}
+
+/// The middle 32 bits of an NTP timestamp, as used by LSR and DLSR
+/// (16.16 fixed point seconds)
+pub fn compact_ntp(ntp: u64) -> u32 {
+    (ntp >> 16) as u32
+}
+
+fn duration_to_compact(duration: Duration) -> u32 {
+    (duration.as_secs_f64() * 65536.0) as u32
+}
+
+/// Reception statistics of one source (RFC 3550 Appendix A.1, A.3, A.8)
+#[derive(Debug, Clone)]
+struct ReceptionStats {
+    ssrc: u32,
+    base_seq: u16,
+    max_seq: u16,
+    /// Sequence number wraparounds, shifted left 16 bits
+    cycles: u32,
+    /// A large jump is only believed when the next packet follows it
+    bad_seq: Option<u16>,
+    /// Packets expected before the last restart of sequence tracking
+    expected_before_restart: u64,
+    received: u64,
+    /// Packets older than the highest sequence seen: late or duplicated
+    reordered: u64,
+    expected_prior: u64,
+    received_prior: u64,
+    /// Relative transit time of the previous packet, in RTP units
+    transit: Option<i32>,
+    /// Interarrival jitter in RTP units
+    jitter: f64,
+}
+
+impl ReceptionStats {
+    /// Statistics of a source whose first packet had sequence `seq`
+    fn new(ssrc: u32, seq: u16) -> Self {
+        Self {
+            ssrc,
+            base_seq: seq,
+            max_seq: seq,
+            cycles: 0,
+            bad_seq: None,
+            expected_before_restart: 0,
+            received: 1,
+            reordered: 0,
+            expected_prior: 0,
+            received_prior: 0,
+            transit: None,
+            jitter: 0.0,
+        }
+    }
+
+    /// Restart sequence tracking after the source jumped to `seq`, keeping
+    /// the counts from before the jump
+    fn restart(&mut self, seq: u16) {
+        self.expected_before_restart = self.expected();
+        self.base_seq = seq;
+        self.max_seq = seq;
+        self.cycles = 0;
+        self.bad_seq = None;
+        self.transit = None;
+    }
+
+    /// Account for one packet. Returns false if it was not counted (the
+    /// first packet after a large jump).
+    fn update_seq(&mut self, seq: u16) -> bool {
+        let delta = seq.wrapping_sub(self.max_seq);
+        if delta < MAX_DROPOUT {
+            if seq < self.max_seq {
+                self.cycles += 1 << 16;
+            }
+            self.max_seq = seq;
+        } else if delta <= u16::MAX - MAX_MISORDER {
+            if self.bad_seq == Some(seq) {
+                self.restart(seq);
+            } else {
+                self.bad_seq = Some(seq.wrapping_add(1));
+                return false;
+            }
+        } else {
+            self.reordered += 1;
+        }
+        self.received += 1;
+        true
+    }
+
+    /// Update the jitter estimate with a packet's RTP timestamp and its
+    /// arrival time in RTP units
+    fn update_jitter(&mut self, timestamp: u32, arrival: u32) {
+        let transit = arrival.wrapping_sub(timestamp) as i32;
+        if let Some(previous) = self.transit {
+            let d = transit.wrapping_sub(previous).unsigned_abs() as f64;
+            self.jitter += (d - self.jitter) / 16.0;
+        }
+        self.transit = Some(transit);
+    }
+
+    fn extended_max(&self) -> u32 {
+        self.cycles | self.max_seq as u32
+    }
+
+    fn expected(&self) -> u64 {
+        self.expected_before_restart + (self.extended_max() as u64 + 1).saturating_sub(self.base_seq as u64)
+    }
+
+    fn lost(&self) -> i64 {
+        self.expected() as i64 - self.received as i64
+    }
+
+    /// Fraction lost since the previous call, in 1/256 (RFC 3550 A.3)
+    fn take_fraction_lost(&mut self) -> u8 {
+        let expected = self.expected();
+        let expected_interval = expected.saturating_sub(self.expected_prior);
+        let received_interval = self.received.saturating_sub(self.received_prior);
+        self.expected_prior = expected;
+        self.received_prior = self.received;
+        let lost_interval = expected_interval as i64 - received_interval as i64;
+        if expected_interval == 0 || lost_interval <= 0 {
+            0
+        } else {
+            ((lost_interval << 8) / expected_interval as i64).min(255) as u8
+        }
+    }
+}
+
+/// Call-quality statistics from RTP reception and RTCP
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CallStats {
+    /// Packets expected from the highest sequence numbers seen
+    pub expected: u64,
+    /// Packets never received (negative when duplicates outnumber losses)
+    pub lost: i64,
+    /// Packets that arrived after a later one, including duplicates
+    pub reordered: u64,
+    /// Interarrival jitter at the end of the call
+    pub jitter_ms: f32,
+    /// Round-trip time from the far end's last report about us
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rtt_ms: Option<f32>,
+    /// Sender reports received from the far end
+    pub sender_reports: u32,
+    /// CNAME the far end described itself with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_cname: Option<String>,
+    /// Whether the far end sent an RTCP BYE
+    #[serde(default)]
+    pub bye: bool,
+}
+
+impl CallStats {
+    /// Share of expected packets that never arrived
+    pub fn loss_fraction(&self) -> f32 {
+        if self.expected == 0 {
+            0.0
+        } else {
+            self.lost.max(0) as f32 / self.expected as f32
+        }
+    }
+}
+
+impl std::fmt::Display for CallStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.1}% loss ({} of {}), {} reordered, jitter {:.1} ms",
+            self.loss_fraction() * 100.0,
+            self.lost.max(0),
+            self.expected,
+            self.reordered,
+            self.jitter_ms
+        )?;
+        match self.rtt_ms {
+            Some(rtt) => write!(f, ", RTT {:.0} ms", rtt),
+            None => write!(f, ", RTT unknown"),
+        }
+    }
+}
+
+/// The RTCP side of one call: reception statistics of every source heard
+/// and what the far end's reports told us
+#[derive(Debug)]
+pub struct RtcpSession {
+    local_ssrc: u32,
+    sources: Vec<ReceptionStats>,
+    /// Wall clock reading at `epoch`, for NTP timestamps
+    epoch: Instant,
+    epoch_ntp: u64,
+    /// Compact NTP timestamp and arrival of the far end's last SR, per source
+    last_sr: Vec<(u32, u32, Instant)>,
+    rtt: Option<Duration>,
+    sender_reports: u32,
+    remote_cname: Option<String>,
+    bye: bool,
+    next_report: Instant,
+}
+
+impl RtcpSession {
+    pub fn new(local_ssrc: u32) -> Self {
+        let now = Instant::now();
+        Self {
+            local_ssrc,
+            sources: Vec::new(),
+            epoch: now,
+            epoch_ntp: ntp_now(),
+            last_sr: Vec::new(),
+            rtt: None,
+            sender_reports: 0,
+            remote_cname: None,
+            bye: false,
+            // RFC 3550 6.2: the first report goes out after half an interval
+            next_report: now + REPORT_INTERVAL / 2,
+        }
+    }
+
+    /// NTP timestamp of an instant during the session
+    pub fn ntp_at(&self, at: Instant) -> u64 {
+        let elapsed = at.saturating_duration_since(self.epoch);
+        self.epoch_ntp
+            .wrapping_add(elapsed.as_secs() << 32)
+            .wrapping_add(duration_to_ntp(elapsed.subsec_nanos() as u64))
+    }
+
+    /// Account for a received RTP packet. `timestamp` is given for audio
+    /// only: telephone-events reuse one timestamp across packets and would
+    /// read as jitter.
+    pub fn on_rtp(&mut self, ssrc: u32, sequence: u16, timestamp: Option<u32>, arrival: Instant) {
+        let source = match self.sources.iter().position(|s| s.ssrc == ssrc) {
+            Some(i) => {
+                if !self.sources[i].update_seq(sequence) {
+                    return;
+                }
+                &mut self.sources[i]
+            }
+            None => {
+                self.sources.push(ReceptionStats::new(ssrc, sequence));
+                self.sources.last_mut().unwrap()
+            }
+        };
+        let arrival_units = (arrival.saturating_duration_since(self.epoch).as_secs_f64() * CLOCK_RATE) as u64 as u32;
+        if let Some(timestamp) = timestamp {
+            source.update_jitter(timestamp, arrival_units);
+        }
+    }
+
+    /// Take in a compound RTCP packet from the far end
+    pub fn on_rtcp(&mut self, data: &[u8], arrival: Instant) {
+        for packet in parse_compound(data) {
+            match packet {
+                RtcpPacket::SenderReport { ssrc, sender, reports } => {
+                    self.sender_reports += 1;
+                    self.last_sr.retain(|(s, _, _)| *s != ssrc);
+                    self.last_sr.push((ssrc, compact_ntp(sender.ntp_timestamp), arrival));
+                    self.take_round_trip(&reports, arrival);
+                }
+                RtcpPacket::ReceiverReport { reports, .. } => self.take_round_trip(&reports, arrival),
+                RtcpPacket::SourceDescription(chunks) => {
+                    if let Some(cname) = chunks.into_iter().find_map(|(_, cname)| cname) {
+                        self.remote_cname = Some(cname);
+                    }
+                }
+                RtcpPacket::Bye { .. } => self.bye = true,
+                RtcpPacket::Other(_) => {}
+            }
+        }
+    }
+
+    /// RTT = arrival - LSR - DLSR, from a report block about our stream
+    /// (RFC 3550 6.4.1)
+    fn take_round_trip(&mut self, reports: &[ReportBlock], arrival: Instant) {
+        let Some(block) = reports.iter().find(|r| r.ssrc == self.local_ssrc && r.last_sr != 0) else {
+            return;
+        };
+        let rtt = compact_ntp(self.ntp_at(arrival))
+            .wrapping_sub(block.last_sr)
+            .wrapping_sub(block.delay_since_last_sr);
+        // A wrapped (negative) result means clocks or fields are off
+        if rtt < 0x8000_0000 {
+            self.rtt = Some(Duration::from_secs_f64(rtt as f64 / 65536.0));
+        }
+    }
+
+    /// Whether the next report is due
+    pub fn report_due(&self, now: Instant) -> bool {
+        now >= self.next_report
+    }
+
+    /// Build our next compound report and schedule the one after it. An SR
+    /// when `sender` is given (we have sent RTP), otherwise an RR; always
+    /// followed by our CNAME.
+    pub fn report(&mut self, sender: Option<SenderInfo>, now: Instant) -> Vec<u8> {
+        let reports: Vec<ReportBlock> = self
+            .sources
+            .iter_mut()
+            .map(|source| {
+                let (last_sr, delay_since_last_sr) = self
+                    .last_sr
+                    .iter()
+                    .find(|(ssrc, _, _)| *ssrc == source.ssrc)
+                    .map(|&(_, lsr, arrived)| (lsr, duration_to_compact(now.saturating_duration_since(arrived))))
+                    .unwrap_or((0, 0));
+                ReportBlock {
+                    ssrc: source.ssrc,
+                    fraction_lost: source.take_fraction_lost(),
+                    cumulative_lost: source.lost().clamp(i32::MIN as i64, i32::MAX as i64) as i32,
+                    highest_sequence: source.extended_max(),
+                    jitter: source.jitter as u32,
+                    last_sr,
+                    delay_since_last_sr,
+                }
+            })
+            .take(31)
+            .collect();
+
+        let report = match sender {
+            Some(sender) => RtcpPacket::SenderReport { ssrc: self.local_ssrc, sender, reports },
+            None => RtcpPacket::ReceiverReport { ssrc: self.local_ssrc, reports },
+        };
+        let sdes = RtcpPacket::SourceDescription(vec![(self.local_ssrc, Some(LOCAL_CNAME.to_string()))]);
+
+        // Randomized to 0.5-1.5 intervals so endpoints don't synchronize
+        let factor = rand::thread_rng().gen_range(0.5..1.5);
+        self.next_report = now + REPORT_INTERVAL.mul_f64(factor);
+        to_bytes(&[report, sdes])
+    }
+
+    /// Our BYE, sent when we hang up
+    pub fn bye(&self) -> Vec<u8> {
+        to_bytes(&[
+            RtcpPacket::ReceiverReport { ssrc: self.local_ssrc, reports: Vec::new() },
+            RtcpPacket::Bye { ssrcs: vec![self.local_ssrc], reason: None },
+        ])
+    }
+
+    /// Statistics of everything received so far, summed over sources
+    pub fn stats(&self) -> CallStats {
+        CallStats {
+            expected: self.sources.iter().map(|s| s.expected()).sum(),
+            lost: self.sources.iter().map(|s| s.lost()).sum(),
+            reordered: self.sources.iter().map(|s| s.reordered).sum(),
+            jitter_ms: self
+                .sources
+                .iter()
+                .map(|s| (s.jitter * 1000.0 / CLOCK_RATE) as f32)
+                .fold(0.0, f32::max),
+            rtt_ms: self.rtt.map(|rtt| rtt.as_secs_f32() * 1000.0),
+            sender_reports: self.sender_reports,
+            remote_cname: self.remote_cname.clone(),
+            bye: self.bye,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(session: &mut RtcpSession, ssrc: u32, sequences: impl IntoIterator<Item = u16>) {
+        let start = session.epoch;
+        for seq in sequences {
+            let at = start + Duration::from_millis(20 * seq as u64);
+            session.on_rtp(ssrc, seq, Some(seq as u32 * 160), at);
+        }
+    }
+
+    #[test]
+    fn test_report_round_trip() {
+        let block = ReportBlock {
+            ssrc: 0x1234_5678,
+            fraction_lost: 30,
+            cumulative_lost: -3,
+            highest_sequence: 0x0001_0005,
+            jitter: 42,
+            last_sr: 0xAABB_CCDD,
+            delay_since_last_sr: 65536,
+        };
+        let packets = vec![
+            RtcpPacket::SenderReport {
+                ssrc: 7,
+                sender: SenderInfo { ntp_timestamp: 0x0102_0304_0506_0708, rtp_timestamp: 160, packet_count: 50, octet_count: 8000 },
+                reports: vec![block],
+            },
+            RtcpPacket::SourceDescription(vec![(7, Some("pbx@example".to_string())), (8, None)]),
+            RtcpPacket::Bye { ssrcs: vec![7], reason: Some("hangup".to_string()) },
+        ];
+        let bytes = to_bytes(&packets);
+        assert_eq!(bytes.len() % 4, 0);
+        assert!(is_rtcp(&bytes));
+        assert_eq!(parse_compound(&bytes), packets);
+    }
+
+    #[test]
+    fn test_is_rtcp_demux() {
+        // PCMU and telephone-event RTP, with and without marker
+        assert!(!is_rtcp(&[0x80, 0x00, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1]));
+        assert!(!is_rtcp(&[0x80, 0x80 | 101, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1]));
+        assert!(is_rtcp(&[0x80, PT_RR, 0, 1, 0, 0, 0, 1]));
+        assert!(!is_rtcp(&[0x80, PT_RR]));
+    }
+
+    #[test]
+    fn test_parse_stops_at_truncated_packet() {
+        let mut bytes = to_bytes(&[RtcpPacket::ReceiverReport { ssrc: 1, reports: vec![] }]);
+        bytes.extend_from_slice(&[0x81, PT_RR, 0, 7, 0, 0]);
+        assert_eq!(parse_compound(&bytes), vec![RtcpPacket::ReceiverReport { ssrc: 1, reports: vec![] }]);
+        assert!(parse_compound(&[0x00, 0x01]).is_empty());
+    }
+
+    #[test]
+    fn test_loss_and_reordering() {
+        let mut session = RtcpSession::new(1);
+        // 0..50 with 10-14 lost and 20/21 swapped
+        let mut sequences: Vec<u16> = (0..50).filter(|s| !(10..15).contains(s)).collect();
+        let at = sequences.iter().position(|&s| s == 20).unwrap();
+        sequences.swap(at, at + 1);
+        feed(&mut session, 0xBEEF, sequences);
+
+        let stats = session.stats();
+        assert_eq!(stats.expected, 50);
+        assert_eq!(stats.lost, 5);
+        assert_eq!(stats.reordered, 1);
+        assert!((stats.loss_fraction() - 0.1).abs() < 1e-6);
+        assert!(stats.to_string().starts_with("10.0% loss (5 of 50), 1 reordered"));
+    }
+
+    #[test]
+    fn test_sequence_wraparound() {
+        let mut session = RtcpSession::new(1);
+        let start = session.epoch;
+        for i in 0..20u16 {
+            let seq = 65530u16.wrapping_add(i);
+            session.on_rtp(9, seq, Some(i as u32 * 160), start + Duration::from_millis(20 * i as u64));
+        }
+        let stats = session.stats();
+        assert_eq!(stats.expected, 20);
+        assert_eq!(stats.lost, 0);
+        let report = session.report(None, start);
+        let RtcpPacket::ReceiverReport { reports, .. } = &parse_compound(&report)[0] else { panic!() };
+        assert_eq!(reports[0].highest_sequence, 1 << 16 | 13);
+    }
+
+    #[test]
+    fn test_jitter_from_uneven_arrivals() {
+        let mut session = RtcpSession::new(1);
+        let start = session.epoch;
+        // Packets sent every 20 ms, arriving alternately 0 and 10 ms late
+        for i in 0..200u32 {
+            let late = if i % 2 == 0 { 0 } else { 10 };
+            let at = start + Duration::from_millis(20 * i as u64 + late);
+            session.on_rtp(3, i as u16, Some(i * 160), at);
+        }
+        let jitter = session.stats().jitter_ms;
+        assert!((jitter - 10.0).abs() < 1.0, "jitter {}", jitter);
+
+        // Evenly paced packets have none
+        let mut steady = RtcpSession::new(1);
+        feed(&mut steady, 3, 0..200);
+        assert!(steady.stats().jitter_ms < 0.2);
+    }
+
+    #[test]
+    fn test_large_jump_restarts_after_two_packets() {
+        let mut session = RtcpSession::new(1);
+        feed(&mut session, 5, 0..10);
+        // The source restarts at 20000: one stray packet is ignored, a
+        // second in sequence is believed
+        feed(&mut session, 5, 20000..20010);
+        let stats = session.stats();
+        assert_eq!(stats.lost, 0);
+        assert_eq!(stats.expected, 19);
+    }
+
+    #[test]
+    fn test_receiver_report_blocks() {
+        let mut session = RtcpSession::new(1);
+        feed(&mut session, 0xBEEF, (0..100).filter(|s| s % 4 != 0));
+        let start = session.epoch;
+
+        // The far end's SR gives us LSR/DLSR to report back
+        let sr = to_bytes(&[RtcpPacket::SenderReport {
+            ssrc: 0xBEEF,
+            sender: SenderInfo { ntp_timestamp: 0x0000_1234_5678_0000, ..Default::default() },
+            reports: vec![],
+        }]);
+        session.on_rtcp(&sr, start);
+
+        let bytes = session.report(None, start + Duration::from_millis(500));
+        let packets = parse_compound(&bytes);
+        let RtcpPacket::ReceiverReport { ssrc, reports } = &packets[0] else { panic!("expected RR: {:?}", packets) };
+        assert_eq!(*ssrc, 1);
+        assert_eq!(reports.len(), 1);
+        let block = reports[0];
+        assert_eq!(block.ssrc, 0xBEEF);
+        assert_eq!(block.cumulative_lost, 24);
+        assert_eq!(block.fraction_lost, (24 * 256 / 99) as u8);
+        assert_eq!(block.highest_sequence, 99);
+        assert_eq!(block.last_sr, 0x1234_5678);
+        assert_eq!(block.delay_since_last_sr, 32768);
+        assert_eq!(packets[1], RtcpPacket::SourceDescription(vec![(1, Some(LOCAL_CNAME.to_string()))]));
+
+        // Nothing lost since the last report
+        let bytes = session.report(None, start + Duration::from_secs(1));
+        let RtcpPacket::ReceiverReport { reports, .. } = &parse_compound(&bytes)[0] else { panic!() };
+        assert_eq!(reports[0].fraction_lost, 0);
+        assert_eq!(reports[0].cumulative_lost, 24);
+    }
+
+    #[test]
+    fn test_round_trip_time_from_report_about_us() {
+        let mut session = RtcpSession::new(1);
+        let start = session.epoch;
+
+        // We send an SR at start; the far end holds it 30 ms and its report
+        // reaches us 100 ms after we sent ours
+        let our_sr = compact_ntp(session.ntp_at(start));
+        let rr = to_bytes(&[RtcpPacket::ReceiverReport {
+            ssrc: 0xBEEF,
+            reports: vec![ReportBlock {
+                ssrc: 1,
+                last_sr: our_sr,
+                delay_since_last_sr: duration_to_compact(Duration::from_millis(30)),
+                ..Default::default()
+            }],
+        }]);
+        session.on_rtcp(&rr, start + Duration::from_millis(100));
+        let rtt = session.stats().rtt_ms.unwrap();
+        assert!((rtt - 70.0).abs() < 1.0, "rtt {}", rtt);
+
+        // Blocks about other sources and without an LSR are ignored
+        let mut other = RtcpSession::new(1);
+        let rr = to_bytes(&[RtcpPacket::ReceiverReport {
+            ssrc: 0xBEEF,
+            reports: vec![ReportBlock { ssrc: 2, last_sr: our_sr, ..Default::default() }, ReportBlock { ssrc: 1, ..Default::default() }],
+        }]);
+        other.on_rtcp(&rr, start);
+        assert_eq!(other.stats().rtt_ms, None);
+    }
+
+    #[test]
+    fn test_sdes_and_bye() {
+        let mut session = RtcpSession::new(1);
+        let start = session.epoch;
+        session.on_rtcp(&to_bytes(&[RtcpPacket::SourceDescription(vec![(9, Some("media@pbx".to_string()))])]), start);
+        assert_eq!(session.stats().remote_cname.as_deref(), Some("media@pbx"));
+        assert!(!session.stats().bye);
+        let bye = session.bye();
+        session.on_rtcp(&bye, start);
+        assert!(session.stats().bye);
+    }
+
+    #[test]
+    fn test_report_schedule() {
+        let mut session = RtcpSession::new(1);
+        let start = session.epoch;
+        assert!(!session.report_due(start));
+        assert!(session.report_due(start + REPORT_INTERVAL / 2));
+        session.report(Some(SenderInfo::default()), start);
+        assert!(!session.report_due(start + REPORT_INTERVAL / 2 - Duration::from_millis(1)));
+        assert!(session.report_due(start + REPORT_INTERVAL * 3 / 2));
+    }
+}
@@ -0,0 +1,265 @@
+/// RTP streams of a call, by SSRC
+///
+/// A PBX that moves the call between media servers (an auto-attendant
+/// handing over to a voicemail server, say) starts a new RTP stream: a new
+/// SSRC, fresh random sequence numbers and timestamps, often from another
+/// address. The receiver then has to start over rather than hold the new
+/// packets against the old stream's sequence numbers.
+///
+/// The tracker keeps each stream's SSRC, source address and where in the
+/// decoded audio it started, and a timeline of which stream each stretch of
+/// the audio came from. Offsets are relative to the first decoded sample,
+/// like the tone timeline. A stream can return (the call is transferred
+/// back); packets of a previous stream that are not newer than the last one
+/// it sent are stragglers from before the switch and are ignored.
+///
+/// As in RFC 3550 A.1, a new or returning SSRC is on probation until
+/// `MIN_SEQUENTIAL` packets in sequence have come from it, so a lone stray
+/// packet (or a burst of garbage) cannot take the call over. The first
+/// stream of the call has nothing to take over from and starts at once.
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+
+/// One RTP stream (SSRC) heard in a call
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RtpStream {
+    pub ssrc: u32,
+    /// Address its first packet came from
+    pub source: SocketAddr,
+    /// Where in the audio it was first heard
+    pub start_ms: u64,
+    /// Packets taken from it
+    pub packets: u64,
+    /// Highest sequence number seen, to tell stragglers from a return
+    #[serde(skip)]
+    last_sequence: u16,
+}
+
+/// A stretch of the audio that came from one stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StreamSpan {
+    pub ssrc: u32,
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+/// Packets in sequence a new or returning SSRC must send before it takes
+/// over the call (RFC 3550 A.1)
+pub const MIN_SEQUENTIAL: u32 = 2;
+
+/// How a packet relates to the stream being received
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamChange {
+    /// From the current stream
+    Same,
+    /// The first packet of the call, or of a new stream
+    New,
+    /// From a previous stream that is sending again
+    Resumed,
+    /// From a previous stream, no newer than the last packet it sent
+    Straggler,
+    /// From a stream on probation, with how many packets in sequence it
+    /// has sent so far (1 when the probation started over)
+    Probation(u32),
+}
+
+/// A new or returning SSRC that has not yet sent `MIN_SEQUENTIAL` packets in
+/// sequence
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    ssrc: u32,
+    last_sequence: u16,
+    sequential: u32,
+}
+
+/// Which stream is being received and which ones were before
+#[derive(Debug, Default)]
+pub struct StreamTracker {
+    streams: Vec<RtpStream>,
+    /// Index into `streams` of each span's stream and the span's start
+    spans: Vec<(usize, u64)>,
+    candidate: Option<Candidate>,
+}
+
+impl StreamTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// SSRC of the stream being received
+    pub fn current(&self) -> Option<u32> {
+        self.spans.last().map(|&(index, _)| self.streams[index].ssrc)
+    }
+
+    /// How a packet from `ssrc` with `sequence` relates to the current
+    /// stream, counting it towards the probation of a new or returning SSRC.
+    /// `New` or `Resumed` means the SSRC should take over now.
+    pub fn classify(&mut self, ssrc: u32, sequence: u16) -> StreamChange {
+        let current = self.current();
+        if current == Some(ssrc) {
+            return StreamChange::Same;
+        }
+        let known = self.streams.iter().find(|stream| stream.ssrc == ssrc);
+        if known.is_some_and(|stream| !is_after(sequence, stream.last_sequence)) {
+            return StreamChange::Straggler;
+        }
+        let settled = if known.is_some() { StreamChange::Resumed } else { StreamChange::New };
+        if current.is_none() {
+            return settled;
+        }
+
+        let sequential = match self.candidate {
+            Some(candidate) if candidate.ssrc == ssrc && sequence == candidate.last_sequence.wrapping_add(1) => {
+                candidate.sequential + 1
+            }
+            _ => 1,
+        };
+        if sequential >= MIN_SEQUENTIAL {
+            self.candidate = None;
+            settled
+        } else {
+            self.candidate = Some(Candidate { ssrc, last_sequence: sequence, sequential });
+            StreamChange::Probation(sequential)
+        }
+    }
+
+    /// Make `ssrc` the current stream from `offset_ms` into the audio
+    pub fn switch(&mut self, ssrc: u32, sequence: u16, source: SocketAddr, offset_ms: u64) {
+        let index = match self.streams.iter().position(|stream| stream.ssrc == ssrc) {
+            Some(index) => index,
+            None => {
+                self.streams.push(RtpStream { ssrc, source, start_ms: offset_ms, packets: 0, last_sequence: sequence });
+                self.streams.len() - 1
+            }
+        };
+        self.streams[index].last_sequence = sequence;
+        self.candidate = None;
+        // A switch before any of the previous stream's audio was decoded
+        // replaces its span
+        if self.spans.last().is_some_and(|&(_, start)| start == offset_ms) {
+            self.spans.pop();
+        }
+        self.spans.push((index, offset_ms));
+    }
+
+    /// Count a packet of the current stream
+    pub fn record(&mut self, sequence: u16) {
+        if let Some(&(index, _)) = self.spans.last() {
+            let stream = &mut self.streams[index];
+            stream.packets += 1;
+            if is_after(sequence, stream.last_sequence) {
+                stream.last_sequence = sequence;
+            }
+        }
+    }
+
+    /// Every stream heard, in the order they started
+    pub fn streams(&self) -> &[RtpStream] {
+        &self.streams
+    }
+
+    /// Which stream each stretch of the audio came from, the last one
+    /// running to `end_ms`
+    pub fn timeline(&self, end_ms: u64) -> Vec<StreamSpan> {
+        self.spans
+            .iter()
+            .enumerate()
+            .map(|(i, &(index, start_ms))| StreamSpan {
+                ssrc: self.streams[index].ssrc,
+                start_ms,
+                end_ms: self.spans.get(i + 1).map_or(end_ms, |&(_, next)| next),
+            })
+            .collect()
+    }
+}
+
+/// Whether `a` is after `b` in sequence number order (with wraparound)
+fn is_after(a: u16, b: u16) -> bool {
+    let diff = a.wrapping_sub(b);
+    diff > 0 && diff < 0x8000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([10, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn test_first_packet_starts_a_stream() {
+        let mut tracker = StreamTracker::new();
+        assert_eq!(tracker.current(), None);
+        assert_eq!(tracker.classify(7, 100), StreamChange::New);
+
+        tracker.switch(7, 100, addr(4000), 0);
+        tracker.record(100);
+        assert_eq!(tracker.classify(7, 101), StreamChange::Same);
+        assert_eq!(tracker.current(), Some(7));
+        assert_eq!(tracker.streams()[0].packets, 1);
+        assert_eq!(tracker.timeline(500), vec![StreamSpan { ssrc: 7, start_ms: 0, end_ms: 500 }]);
+    }
+
+    #[test]
+    fn test_transfer_and_return() {
+        let mut tracker = StreamTracker::new();
+        tracker.switch(7, 100, addr(4000), 0);
+        for seq in 100..150 {
+            tracker.record(seq);
+        }
+
+        // Transferred to a voicemail server with a restarted sequence
+        assert_eq!(tracker.classify(9, 3), StreamChange::Probation(1));
+        assert_eq!(tracker.classify(9, 4), StreamChange::New);
+        tracker.switch(9, 3, addr(5000), 1000);
+        tracker.record(3);
+        tracker.record(4);
+
+        // A packet the first server sent before the transfer, arriving late
+        assert_eq!(tracker.classify(7, 149), StreamChange::Straggler);
+        // The first server sending again
+        assert_eq!(tracker.classify(7, 150), StreamChange::Probation(1));
+        assert_eq!(tracker.classify(7, 151), StreamChange::Resumed);
+        tracker.switch(7, 150, addr(4000), 3000);
+
+        let streams = tracker.streams();
+        assert_eq!(streams.len(), 2);
+        assert_eq!((streams[1].ssrc, streams[1].source, streams[1].start_ms), (9, addr(5000), 1000));
+        assert_eq!(
+            tracker.timeline(4000),
+            vec![
+                StreamSpan { ssrc: 7, start_ms: 0, end_ms: 1000 },
+                StreamSpan { ssrc: 9, start_ms: 1000, end_ms: 3000 },
+                StreamSpan { ssrc: 7, start_ms: 3000, end_ms: 4000 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_probation_needs_packets_in_sequence() {
+        let mut tracker = StreamTracker::new();
+        tracker.switch(7, 100, addr(4000), 0);
+
+        // Stray packets out of sequence keep starting the probation over,
+        // whichever stream sends in between
+        assert_eq!(tracker.classify(9, 40), StreamChange::Probation(1));
+        assert_eq!(tracker.classify(7, 101), StreamChange::Same);
+        assert_eq!(tracker.classify(9, 900), StreamChange::Probation(1));
+        assert_eq!(tracker.classify(11, 901), StreamChange::Probation(1));
+        assert_eq!(tracker.classify(9, 901), StreamChange::Probation(1));
+        assert_eq!(tracker.current(), Some(7));
+
+        assert_eq!(tracker.classify(7, 102), StreamChange::Same);
+        assert_eq!(tracker.classify(9, 902), StreamChange::New);
+    }
+
+    #[test]
+    fn test_switch_before_any_audio_replaces_span() {
+        let mut tracker = StreamTracker::new();
+        tracker.switch(7, 100, addr(4000), 0);
+        tracker.switch(9, 5, addr(5000), 0);
+        assert_eq!(tracker.timeline(200), vec![StreamSpan { ssrc: 9, start_ms: 0, end_ms: 200 }]);
+        assert_eq!(tracker.streams().len(), 2);
+    }
+}
@@ -0,0 +1,666 @@
+/// In-band tone detection (DTMF and call-progress tones)
+///
+/// Runs Goertzel filters over the decoded 8kHz PCM to find DTMF digits,
+/// dial tone, ringback, busy, reorder (fast busy) and the SIT tri-tone
+/// that precedes carrier intercept announcements. Detection is streaming:
+/// samples are fed as packets are decoded, and the timeline can be read at
+/// any point.
+///
+/// Two block sizes are used:
+/// - DTMF: 205 samples (~25.6ms), the classic size that resolves the DTMF
+///   rows/columns while still catching 40ms digits.
+/// - Call progress: 400 samples (50ms). At this size the 40Hz spacing of
+///   440/480Hz falls exactly on a filter null, and the SIT frequency pairs
+///   (913.8/985.2, 1370.6/1428.5) are separable.
+///
+/// Tones are North American (ANSI T1.401 / Telcordia SR-2275).
+
+use std::fmt;
+
+/// Sample rate of the decoded G.711 audio
+const SAMPLE_RATE: f64 = 8000.0;
+
+/// Block size for DTMF analysis
+const DTMF_BLOCK: usize = 205;
+
+/// Block size for call-progress analysis
+const PROGRESS_BLOCK: usize = 400;
+
+/// Blocks quieter than this RMS (about -50 dBFS) are silence
+const SILENCE_RMS: f64 = 100.0;
+
+/// Minimum fraction of block energy a dual tone must carry
+const DUAL_TONE_FRACTION: f64 = 0.7;
+
+/// Minimum fraction of block energy each half of a dual tone must carry
+const DUAL_TONE_COMPONENT: f64 = 0.2;
+
+/// Minimum fraction of block energy a single SIT segment must carry
+const SINGLE_TONE_FRACTION: f64 = 0.7;
+
+/// DTMF digits need at least this many consecutive blocks (~51ms)
+const DTMF_MIN_BLOCKS: usize = 2;
+
+const DTMF_ROWS: [f64; 4] = [697.0, 770.0, 852.0, 941.0];
+const DTMF_COLS: [f64; 4] = [1209.0, 1336.0, 1477.0, 1633.0];
+const DTMF_KEYS: [[char; 4]; 4] = [
+    ['1', '2', '3', 'A'],
+    ['4', '5', '6', 'B'],
+    ['7', '8', '9', 'C'],
+    ['*', '0', '#', 'D'],
+];
+
+/// SIT segment frequencies (low/high variants for the first two segments)
+const SIT_FIRST: [f64; 2] = [913.8, 985.2];
+const SIT_SECOND: [f64; 2] = [1370.6, 1428.5];
+const SIT_THIRD: f64 = 1776.7;
+
+/// SIT segments are 274ms (short) or 380ms (long)
+const SIT_LONG_MS: u64 = 325;
+const SIT_MIN_MS: u64 = 200;
+const SIT_MAX_MS: u64 = 500;
+
+/// Blocks of anything else allowed between SIT segments
+const SIT_MAX_GAP_BLOCKS: usize = 2;
+
+/// Busy is 500ms on/off, reorder 250ms on/off: the range each on and off
+/// period of the 480+620Hz pair must fall in
+const BUSY_CADENCE_MS: (u64, u64) = (375, 650);
+const REORDER_CADENCE_MS: (u64, u64) = (150, 350);
+
+/// On/off cycles at the right cadence before busy or reorder is reported
+const CADENCE_MIN_CYCLES: usize = 2;
+
+/// Dial tone and ringback must last at least this long
+const STEADY_TONE_MIN_MS: u64 = 500;
+
+/// Special Information Tone category (from the first two segments)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SitKind {
+    /// IC: number changed or disconnected
+    Intercept,
+    /// VC: vacant code / number not in service
+    VacantCode,
+    /// RO: reorder, call could not be completed
+    Reorder,
+    /// NC: no circuit available
+    NoCircuit,
+    /// IO: ineffective other
+    IneffectiveOther,
+    /// Segment pattern not in the standard table
+    Unknown,
+}
+
+impl SitKind {
+    /// Classify from (high frequency variant, long duration) of the first
+    /// two segments
+    fn from_segments(first: (bool, bool), second: (bool, bool)) -> Self {
+        match (first, second) {
+            ((false, false), (false, false)) => SitKind::Intercept,
+            ((true, true), (false, false)) => SitKind::VacantCode,
+            ((false, false), (true, true)) | ((true, false), (false, true)) => SitKind::Reorder,
+            ((true, true), (true, true)) | ((false, true), (false, true)) => SitKind::NoCircuit,
+            ((false, true), (false, false)) => SitKind::IneffectiveOther,
+            _ => SitKind::Unknown,
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            SitKind::Intercept => "intercept",
+            SitKind::VacantCode => "vacant code",
+            SitKind::Reorder => "reorder",
+            SitKind::NoCircuit => "no circuit",
+            SitKind::IneffectiveOther => "ineffective other",
+            SitKind::Unknown => "unknown",
+        }
+    }
+}
+
+/// A detected tone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tone {
+    Dtmf(char),
+    Dial,
+    Ringback,
+    Busy,
+    Reorder,
+    Sit(SitKind),
+}
+
+impl Tone {
+    /// Tones that mean the call reached a network failure, not the greeting
+    pub fn is_failure(&self) -> bool {
+        matches!(self, Tone::Busy | Tone::Reorder | Tone::Sit(_))
+    }
+}
+
+impl fmt::Display for Tone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tone::Dtmf(digit) => write!(f, "DTMF {}", digit),
+            Tone::Dial => write!(f, "dial tone"),
+            Tone::Ringback => write!(f, "ringback"),
+            Tone::Busy => write!(f, "busy tone"),
+            Tone::Reorder => write!(f, "reorder tone"),
+            Tone::Sit(kind) => write!(f, "SIT tone: {}", kind.description()),
+        }
+    }
+}
+
+/// A tone in the call's timeline (times relative to the first decoded sample)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ToneEvent {
+    pub tone: Tone,
+    pub start_ms: u64,
+    pub duration_ms: u64,
+}
+
+/// Most significant failure tone in a timeline: SIT, then busy, then reorder
+pub fn failure_tone(timeline: &[ToneEvent]) -> Option<&ToneEvent> {
+    let rank = |t: &Tone| match t {
+        Tone::Sit(_) => 0,
+        Tone::Busy => 1,
+        _ => 2,
+    };
+    timeline
+        .iter()
+        .filter(|e| e.tone.is_failure())
+        .min_by_key(|e| (rank(&e.tone), e.start_ms))
+}
+
+/// Call-progress classification of one block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProgressBlock {
+    Silence,
+    Dial,
+    Ringback,
+    /// 480+620Hz, shared by busy and reorder (told apart by cadence)
+    BusyPair,
+    /// SIT first segment; true for the 985.2Hz variant
+    SitFirst(bool),
+    /// SIT second segment; true for the 1428.5Hz variant
+    SitSecond(bool),
+    SitThird,
+    Other,
+}
+
+/// Streaming tone detector over 8kHz PCM
+#[derive(Debug, Clone, Default)]
+pub struct ToneDetector {
+    dtmf_pending: Vec<i16>,
+    dtmf_blocks: Vec<Option<char>>,
+    progress_pending: Vec<i16>,
+    progress_blocks: Vec<ProgressBlock>,
+}
+
+impl ToneDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed decoded 8kHz samples
+    pub fn feed(&mut self, samples: &[i16]) {
+        self.dtmf_pending.extend_from_slice(samples);
+        let mut consumed = 0;
+        while self.dtmf_pending.len() - consumed >= DTMF_BLOCK {
+            let block = &self.dtmf_pending[consumed..consumed + DTMF_BLOCK];
+            self.dtmf_blocks.push(classify_dtmf(block));
+            consumed += DTMF_BLOCK;
+        }
+        self.dtmf_pending.drain(..consumed);
+
+        self.progress_pending.extend_from_slice(samples);
+        let mut consumed = 0;
+        while self.progress_pending.len() - consumed >= PROGRESS_BLOCK {
+            let block = &self.progress_pending[consumed..consumed + PROGRESS_BLOCK];
+            self.progress_blocks.push(classify_progress(block));
+            consumed += PROGRESS_BLOCK;
+        }
+        self.progress_pending.drain(..consumed);
+    }
+
+    /// Tones detected so far, ordered by start time
+    pub fn timeline(&self) -> Vec<ToneEvent> {
+        let mut events = dtmf_events(&self.dtmf_blocks);
+        events.extend(progress_events(&self.progress_blocks));
+        events.sort_by_key(|e| (e.start_ms, e.duration_ms));
+        events
+    }
+}
+
+/// Detect tones in a complete buffer of 8kHz PCM
+pub fn detect_tones(samples: &[i16]) -> Vec<ToneEvent> {
+    let mut detector = ToneDetector::new();
+    detector.feed(samples);
+    detector.timeline()
+}
+
+/// Goertzel power at `freq`, normalized to the fraction of block energy
+/// (a pure sine at `freq` gives ~1.0)
+fn tone_fraction(block: &[i16], energy: f64, freq: f64) -> f64 {
+    let coeff = 2.0 * (2.0 * std::f64::consts::PI * freq / SAMPLE_RATE).cos();
+    let (mut s1, mut s2) = (0.0f64, 0.0f64);
+    for &x in block {
+        let s0 = x as f64 + coeff * s1 - s2;
+        s2 = s1;
+        s1 = s0;
+    }
+    let power = s1 * s1 + s2 * s2 - coeff * s1 * s2;
+    2.0 * power / (block.len() as f64 * energy)
+}
+
+fn block_energy(block: &[i16]) -> f64 {
+    block.iter().map(|&x| (x as f64) * (x as f64)).sum()
+}
+
+fn is_silent(block: &[i16], energy: f64) -> bool {
+    (energy / block.len() as f64).sqrt() < SILENCE_RMS
+}
+
+fn is_dual_tone(a: f64, b: f64) -> bool {
+    a >= DUAL_TONE_COMPONENT && b >= DUAL_TONE_COMPONENT && a + b >= DUAL_TONE_FRACTION
+}
+
+fn classify_dtmf(block: &[i16]) -> Option<char> {
+    let energy = block_energy(block);
+    if is_silent(block, energy) {
+        return None;
+    }
+
+    let strongest = |freqs: &[f64; 4]| {
+        let powers: Vec<f64> = freqs.iter().map(|&f| tone_fraction(block, energy, f)).collect();
+        let (best, &power) = powers
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))?;
+        // The other tones of the group must be well below the winner
+        let clean = powers.iter().enumerate().all(|(i, &p)| i == best || p < power / 4.0);
+        clean.then_some((best, power))
+    };
+
+    let (row, row_power) = strongest(&DTMF_ROWS)?;
+    let (col, col_power) = strongest(&DTMF_COLS)?;
+    is_dual_tone(row_power, col_power).then_some(DTMF_KEYS[row][col])
+}
+
+fn classify_progress(block: &[i16]) -> ProgressBlock {
+    let energy = block_energy(block);
+    if is_silent(block, energy) {
+        return ProgressBlock::Silence;
+    }
+    let p = |f: f64| tone_fraction(block, energy, f);
+
+    let (p350, p440, p480, p620) = (p(350.0), p(440.0), p(480.0), p(620.0));
+    if is_dual_tone(p350, p440) {
+        return ProgressBlock::Dial;
+    }
+    if is_dual_tone(p440, p480) {
+        return ProgressBlock::Ringback;
+    }
+    if is_dual_tone(p480, p620) {
+        return ProgressBlock::BusyPair;
+    }
+
+    let single = |freqs: [f64; 2]| {
+        let (lo, hi) = (p(freqs[0]), p(freqs[1]));
+        (lo.max(hi) >= SINGLE_TONE_FRACTION).then_some(hi > lo)
+    };
+    if let Some(high) = single(SIT_FIRST) {
+        return ProgressBlock::SitFirst(high);
+    }
+    if let Some(high) = single(SIT_SECOND) {
+        return ProgressBlock::SitSecond(high);
+    }
+    if p(SIT_THIRD) >= SINGLE_TONE_FRACTION {
+        return ProgressBlock::SitThird;
+    }
+
+    ProgressBlock::Other
+}
+
+fn block_ms(blocks: usize, block_size: usize) -> u64 {
+    (blocks * block_size) as u64 * 1000 / SAMPLE_RATE as u64
+}
+
+/// Collapse consecutive equal blocks into (value, first block, length) runs
+fn runs<T: Copy + PartialEq>(blocks: &[T]) -> Vec<(T, usize, usize)> {
+    let mut runs: Vec<(T, usize, usize)> = Vec::new();
+    for (i, &block) in blocks.iter().enumerate() {
+        match runs.last_mut() {
+            Some((value, _, len)) if *value == block => *len += 1,
+            _ => runs.push((block, i, 1)),
+        }
+    }
+    runs
+}
+
+fn dtmf_events(blocks: &[Option<char>]) -> Vec<ToneEvent> {
+    runs(blocks)
+        .into_iter()
+        .filter_map(|(digit, start, len)| {
+            let digit = digit?;
+            (len >= DTMF_MIN_BLOCKS).then(|| ToneEvent {
+                tone: Tone::Dtmf(digit),
+                start_ms: block_ms(start, DTMF_BLOCK),
+                duration_ms: block_ms(len, DTMF_BLOCK),
+            })
+        })
+        .collect()
+}
+
+fn progress_events(blocks: &[ProgressBlock]) -> Vec<ToneEvent> {
+    let runs = runs(blocks);
+    let mut events = Vec::new();
+
+    for (i, &(block, start, len)) in runs.iter().enumerate() {
+        let start_ms = block_ms(start, PROGRESS_BLOCK);
+        let duration_ms = block_ms(len, PROGRESS_BLOCK);
+        let detected = match block {
+            ProgressBlock::Dial if duration_ms >= STEADY_TONE_MIN_MS => Some((Tone::Dial, duration_ms)),
+            ProgressBlock::Ringback if duration_ms >= STEADY_TONE_MIN_MS => Some((Tone::Ringback, duration_ms)),
+            // A SIT event spans all three segments
+            ProgressBlock::SitFirst(_) => {
+                sit_from(&runs, i).map(|(kind, end_ms)| (Tone::Sit(kind), end_ms - start_ms))
+            }
+            _ => None,
+        };
+        if let Some((tone, duration_ms)) = detected {
+            events.push(ToneEvent { tone, start_ms, duration_ms });
+        }
+    }
+
+    events.extend(cadence_events(&runs, blocks.len()));
+    events
+}
+
+/// Busy and reorder tones: trains of 480+620Hz bursts whose on and off
+/// periods match one cadence for at least `CADENCE_MIN_CYCLES` cycles, one
+/// event per burst. A single burst, or a steady tone, is neither.
+fn cadence_events(runs: &[(ProgressBlock, usize, usize)], total_blocks: usize) -> Vec<ToneEvent> {
+    let bursts: Vec<(usize, usize)> = runs
+        .iter()
+        .filter(|&&(block, _, _)| block == ProgressBlock::BusyPair)
+        .map(|&(_, start, len)| (start, len))
+        .collect();
+
+    let mut events = Vec::new();
+    let mut train: Vec<ToneEvent> = Vec::new();
+    let mut finish = |train: &mut Vec<ToneEvent>| {
+        if train.len() >= CADENCE_MIN_CYCLES {
+            events.append(train);
+        }
+        train.clear();
+    };
+
+    for (i, &(start, len)) in bursts.iter().enumerate() {
+        let on_ms = block_ms(len, PROGRESS_BLOCK);
+        // Up to the next burst, or to the end of the audio after the last
+        let off_end = bursts.get(i + 1).map_or(total_blocks, |&(next, _)| next);
+        let off_ms = block_ms(off_end - start - len, PROGRESS_BLOCK);
+
+        // The off period of a train's last cycle only has to be long enough
+        let cycle = [(Tone::Busy, BUSY_CADENCE_MS), (Tone::Reorder, REORDER_CADENCE_MS)]
+            .into_iter()
+            .find(|&(_, (min, max))| (min..=max).contains(&on_ms) && off_ms >= min)
+            .map(|(tone, (_, max))| (tone, off_ms <= max));
+
+        match cycle {
+            Some((tone, continues)) => {
+                if train.last().is_some_and(|event| event.tone != tone) {
+                    finish(&mut train);
+                }
+                train.push(ToneEvent { tone, start_ms: block_ms(start, PROGRESS_BLOCK), duration_ms: on_ms });
+                if !continues {
+                    finish(&mut train);
+                }
+            }
+            None => finish(&mut train),
+        }
+    }
+    finish(&mut train);
+
+    events
+}
+
+/// Match a SIT tri-tone starting at run `first`. Returns the kind and the
+/// end time of the third segment.
+fn sit_from(runs: &[(ProgressBlock, usize, usize)], first: usize) -> Option<(SitKind, u64)> {
+    let segment_ms = |len: usize| block_ms(len, PROGRESS_BLOCK);
+    let plausible = |len: usize| (SIT_MIN_MS..=SIT_MAX_MS).contains(&segment_ms(len));
+
+    // Next run matching `want` within the allowed gap
+    let next = |from: usize, want: &dyn Fn(ProgressBlock) -> bool| {
+        let mut gap = 0;
+        for (i, &(block, _, len)) in runs.iter().enumerate().skip(from + 1) {
+            if want(block) {
+                return Some(i);
+            }
+            gap += len;
+            if gap > SIT_MAX_GAP_BLOCKS {
+                return None;
+            }
+        }
+        None
+    };
+
+    let (ProgressBlock::SitFirst(first_high), _, first_len) = runs[first] else {
+        return None;
+    };
+    let second = next(first, &|b| matches!(b, ProgressBlock::SitSecond(_)))?;
+    let (ProgressBlock::SitSecond(second_high), _, second_len) = runs[second] else {
+        return None;
+    };
+    let third = next(second, &|b| b == ProgressBlock::SitThird)?;
+    let (_, third_start, third_len) = runs[third];
+
+    if !plausible(first_len) || !plausible(second_len) || !plausible(third_len) {
+        return None;
+    }
+
+    let kind = SitKind::from_segments(
+        (first_high, segment_ms(first_len) >= SIT_LONG_MS),
+        (second_high, segment_ms(second_len) >= SIT_LONG_MS),
+    );
+    Some((kind, block_ms(third_start + third_len, PROGRESS_BLOCK)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sum of sines at -10 dBFS each
+    fn tone(freqs: &[f64], ms: u64) -> Vec<i16> {
+        let n = (ms * 8) as usize;
+        (0..n)
+            .map(|i| {
+                let t = i as f64 / SAMPLE_RATE;
+                let v: f64 = freqs
+                    .iter()
+                    .map(|f| (2.0 * std::f64::consts::PI * f * t).sin() * 10000.0)
+                    .sum();
+                v as i16
+            })
+            .collect()
+    }
+
+    fn silence(ms: u64) -> Vec<i16> {
+        vec![0; (ms * 8) as usize]
+    }
+
+    fn cadence(freqs: &[f64], on_ms: u64, off_ms: u64, cycles: usize) -> Vec<i16> {
+        let mut samples = Vec::new();
+        for _ in 0..cycles {
+            samples.extend(tone(freqs, on_ms));
+            samples.extend(silence(off_ms));
+        }
+        samples
+    }
+
+    fn sit(first: f64, first_ms: u64, second: f64, second_ms: u64) -> Vec<i16> {
+        let mut samples = silence(200);
+        samples.extend(tone(&[first], first_ms));
+        samples.extend(tone(&[second], second_ms));
+        samples.extend(tone(&[SIT_THIRD], 380));
+        samples.extend(silence(500));
+        samples
+    }
+
+    fn tones_of(timeline: &[ToneEvent]) -> Vec<Tone> {
+        timeline.iter().map(|e| e.tone).collect()
+    }
+
+    #[test]
+    fn test_detects_dtmf_digits() {
+        let mut samples = silence(100);
+        for digit in ['1', '5', '9', '#'] {
+            let (r, c) = DTMF_KEYS
+                .iter()
+                .enumerate()
+                .find_map(|(r, row)| row.iter().position(|&k| k == digit).map(|c| (r, c)))
+                .unwrap();
+            samples.extend(tone(&[DTMF_ROWS[r], DTMF_COLS[c]], 100));
+            samples.extend(silence(100));
+        }
+
+        let timeline = detect_tones(&samples);
+        assert_eq!(
+            tones_of(&timeline),
+            vec![Tone::Dtmf('1'), Tone::Dtmf('5'), Tone::Dtmf('9'), Tone::Dtmf('#')]
+        );
+        // Roughly 200ms apart
+        assert!((150..=250).contains(&(timeline[1].start_ms - timeline[0].start_ms)));
+    }
+
+    #[test]
+    fn test_short_dtmf_blip_ignored() {
+        let mut samples = silence(100);
+        samples.extend(tone(&[770.0, 1336.0], 20));
+        samples.extend(silence(100));
+        assert!(detect_tones(&samples).is_empty());
+    }
+
+    #[test]
+    fn test_detects_ringback() {
+        let timeline = detect_tones(&cadence(&[440.0, 480.0], 2000, 4000, 2));
+        assert_eq!(tones_of(&timeline), vec![Tone::Ringback, Tone::Ringback]);
+        assert!((1900..=2050).contains(&timeline[0].duration_ms));
+        assert!((5900..=6100).contains(&timeline[1].start_ms));
+    }
+
+    #[test]
+    fn test_detects_dial_tone() {
+        let timeline = detect_tones(&tone(&[350.0, 440.0], 1000));
+        assert_eq!(tones_of(&timeline), vec![Tone::Dial]);
+    }
+
+    #[test]
+    fn test_busy_vs_reorder_cadence() {
+        let busy = detect_tones(&cadence(&[480.0, 620.0], 500, 500, 3));
+        assert_eq!(tones_of(&busy), vec![Tone::Busy; 3]);
+
+        let reorder = detect_tones(&cadence(&[480.0, 620.0], 250, 250, 4));
+        assert_eq!(tones_of(&reorder), vec![Tone::Reorder; 4]);
+    }
+
+    #[test]
+    fn test_busy_pair_needs_two_cycles_at_cadence() {
+        // A single burst
+        assert!(detect_tones(&cadence(&[480.0, 620.0], 500, 500, 1)).is_empty());
+        // A steady tone
+        assert!(detect_tones(&tone(&[480.0, 620.0], 3000)).is_empty());
+        // Busy-length bursts with reorder-length gaps
+        assert!(detect_tones(&cadence(&[480.0, 620.0], 500, 250, 4)).is_empty());
+        // Bursts too far apart to be a cadence
+        assert!(detect_tones(&cadence(&[480.0, 620.0], 500, 1500, 3)).is_empty());
+
+        // Two cycles are enough, the last off period running to the end
+        let mut samples = cadence(&[480.0, 620.0], 250, 250, 1);
+        samples.extend(tone(&[480.0, 620.0], 250));
+        samples.extend(silence(2000));
+        assert_eq!(tones_of(&detect_tones(&samples)), vec![Tone::Reorder; 2]);
+    }
+
+    #[test]
+    fn test_sit_kinds() {
+        let cases = [
+            (sit(913.8, 274, 1370.6, 274), SitKind::Intercept),
+            (sit(985.2, 380, 1370.6, 274), SitKind::VacantCode),
+            (sit(913.8, 274, 1428.5, 380), SitKind::Reorder),
+            (sit(985.2, 380, 1428.5, 380), SitKind::NoCircuit),
+            (sit(913.8, 380, 1370.6, 274), SitKind::IneffectiveOther),
+        ];
+        for (samples, kind) in cases {
+            let timeline = detect_tones(&samples);
+            assert_eq!(tones_of(&timeline), vec![Tone::Sit(kind)], "expected {:?}", kind);
+            assert!((150..=250).contains(&timeline[0].start_ms));
+        }
+    }
+
+    #[test]
+    fn test_incomplete_sit_ignored() {
+        // First two segments only (no 1776.7Hz)
+        let mut samples = silence(200);
+        samples.extend(tone(&[913.8], 274));
+        samples.extend(tone(&[1370.6], 274));
+        samples.extend(silence(500));
+        assert!(detect_tones(&samples).is_empty());
+    }
+
+    #[test]
+    fn test_silence_and_noise_have_no_tones() {
+        assert!(detect_tones(&silence(2000)).is_empty());
+
+        // Broadband noise (LCG) is not a tone
+        let mut state: u32 = 12345;
+        let noise: Vec<i16> = (0..16000)
+            .map(|_| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                ((state >> 16) as i16) / 4
+            })
+            .collect();
+        assert!(detect_tones(&noise).is_empty());
+    }
+
+    #[test]
+    fn test_streaming_matches_batch() {
+        let mut samples = cadence(&[480.0, 620.0], 500, 500, 2);
+        samples.extend(sit(985.2, 380, 1370.6, 274));
+
+        let mut detector = ToneDetector::new();
+        for chunk in samples.chunks(160) {
+            detector.feed(chunk);
+        }
+        assert_eq!(detector.timeline(), detect_tones(&samples));
+    }
+
+    #[test]
+    fn test_failure_tone_prefers_sit() {
+        let timeline = vec![
+            ToneEvent { tone: Tone::Ringback, start_ms: 0, duration_ms: 2000 },
+            ToneEvent { tone: Tone::Reorder, start_ms: 2000, duration_ms: 250 },
+            ToneEvent { tone: Tone::Sit(SitKind::VacantCode), start_ms: 5000, duration_ms: 1000 },
+        ];
+        assert_eq!(failure_tone(&timeline).unwrap().tone, Tone::Sit(SitKind::VacantCode));
+        assert!(failure_tone(&timeline[..1]).is_none());
+        assert_eq!(Tone::Sit(SitKind::Intercept).to_string(), "SIT tone: intercept");
+        assert_eq!(Tone::Busy.to_string(), "busy tone");
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        /// Arbitrary audio never panics and yields an ordered timeline
+        #[test]
+        fn timeline_ordered(samples in prop::collection::vec(any::<i16>(), 0..8000)) {
+            let timeline = detect_tones(&samples);
+            prop_assert!(timeline.windows(2).all(|w| w[0].start_ms <= w[1].start_ms));
+            let total_ms = samples.len() as u64 / 8;
+            prop_assert!(timeline.iter().all(|e| e.start_ms + e.duration_ms <= total_ms + 1));
+        }
+    }
+}
@@ -0,0 +1,681 @@
+//! Check schedules
+//!
+//! A schedule is one or more cron expressions evaluated in an IANA time zone,
+//! minus a set of excluded dates (holidays, closures).
+//!
+//! Cron expressions use the usual five fields, and several expressions can be
+//! combined with `;`:
+//! ```text
+//! minute hour day-of-month month day-of-week
+//! */15 8 * * MON-FRI; 0 9-17 * * MON-FRI
+//! ```
+//! Fields accept `*`, numbers, ranges (`9-17`), lists (`1,15`) and steps
+//! (`*/15`, `8-18/2`). Months and weekdays also accept names (`JAN`, `MON`),
+//! and Sunday is `0` or `7`. As in classic cron, when both day-of-month and
+//! day-of-week are restricted, a day matching either one runs.
+//!
+//! Excluded dates come from an iCalendar (`.ics`) file or a plain list with
+//! one `YYYY-MM-DD`, `YYYY-MM-DD..YYYY-MM-DD` (inclusive) or `MM-DD` (every
+//! year) per line.
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Datelike, Duration, LocalResult, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+use std::collections::BTreeSet;
+use std::fmt;
+use tracing::warn;
+
+/// Default schedule: the top of every hour from 8am to 5pm, every day
+pub const DEFAULT_CRON: &str = "0 8-17 * * *";
+
+/// Default time zone for schedules
+pub const DEFAULT_TIMEZONE: &str = "America/Los_Angeles";
+
+/// How far ahead to search for the next run (covers leap days)
+const SEARCH_DAYS: i64 = 366 * 8;
+
+/// Longest range accepted in a date-list file
+const MAX_RANGE_DAYS: i64 = 366;
+
+const MONTH_NAMES: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+const WEEKDAY_NAMES: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+/// A single five-field cron expression, stored as bitmasks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronExpr {
+    minutes: u64,
+    hours: u32,
+    days_of_month: u32,
+    months: u16,
+    days_of_week: u8,
+    /// Whether day-of-month / day-of-week were given as something other than `*`
+    dom_restricted: bool,
+    dow_restricted: bool,
+}
+
+impl CronExpr {
+    /// Parse an expression such as `*/15 8-17 * * MON-FRI`
+    pub fn parse(expr: &str) -> Result<Self> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        if fields.len() != 5 {
+            bail!(
+                "Cron expression '{}' must have 5 fields (minute hour day-of-month month day-of-week)",
+                expr
+            );
+        }
+
+        let minutes = parse_field(fields[0], 0, 59, &[])
+            .with_context(|| format!("Invalid minute field in '{}'", expr))?;
+        let hours = parse_field(fields[1], 0, 23, &[])
+            .with_context(|| format!("Invalid hour field in '{}'", expr))?;
+        let days_of_month = parse_field(fields[2], 1, 31, &[])
+            .with_context(|| format!("Invalid day-of-month field in '{}'", expr))?;
+        let months = parse_field(fields[3], 1, 12, &MONTH_NAMES)
+            .with_context(|| format!("Invalid month field in '{}'", expr))?;
+        // 7 is an alias for Sunday
+        let mut days_of_week = parse_field(fields[4], 0, 7, &WEEKDAY_NAMES)
+            .with_context(|| format!("Invalid day-of-week field in '{}'", expr))?;
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & 0x7F;
+        }
+
+        Ok(Self {
+            minutes,
+            hours: hours as u32,
+            days_of_month: days_of_month as u32,
+            months: months as u16,
+            days_of_week: days_of_week as u8,
+            dom_restricted: !fields[2].starts_with('*'),
+            dow_restricted: !fields[4].starts_with('*'),
+        })
+    }
+
+    /// Whether the expression runs on this date
+    pub fn matches_date(&self, date: NaiveDate) -> bool {
+        if self.months & (1 << date.month()) == 0 {
+            return false;
+        }
+        let dom = self.days_of_month & (1 << date.day()) != 0;
+        let dow = self.days_of_week & (1 << date.weekday().num_days_from_sunday()) != 0;
+        match (self.dom_restricted, self.dow_restricted) {
+            (true, true) => dom || dow,
+            _ => dom && dow,
+        }
+    }
+
+    /// Whether the expression runs at this hour and minute
+    pub fn matches_time(&self, hour: u32, minute: u32) -> bool {
+        hour < 24
+            && minute < 60
+            && self.hours & (1 << hour) != 0
+            && self.minutes & (1 << minute) != 0
+    }
+}
+
+/// Parse one cron field into a bitmask of allowed values
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64> {
+    let mut mask = 0u64;
+
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .with_context(|| format!("Invalid step '{}'", step))?;
+                if step == 0 {
+                    bail!("Step cannot be zero");
+                }
+                (range, step)
+            }
+            None => (item, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (parse_value(a, min, names)?, parse_value(b, min, names)?)
+        } else {
+            let start = parse_value(range, min, names)?;
+            // `a/n` means from a to the end of the range
+            (start, if step > 1 { max } else { start })
+        };
+
+        if start < min || end > max || start > end {
+            bail!("'{}' is outside {}-{}", item, min, max);
+        }
+
+        let mut value = start as u64;
+        while value <= end as u64 {
+            mask |= 1 << value;
+            value += step as u64;
+        }
+    }
+
+    Ok(mask)
+}
+
+/// Parse a number or a name (names index from `min`)
+fn parse_value(s: &str, min: u32, names: &[&str]) -> Result<u32> {
+    if let Ok(n) = s.parse::<u32>() {
+        return Ok(n);
+    }
+    names
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(s))
+        .map(|i| i as u32 + min)
+        .with_context(|| format!("Invalid value '{}'", s))
+}
+
+/// Dates on which no checks run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExcludedDates {
+    dates: BTreeSet<NaiveDate>,
+    /// (month, day) pairs excluded every year
+    annual: BTreeSet<(u32, u32)>,
+}
+
+impl ExcludedDates {
+    /// Load excluded dates from an iCalendar or date-list file
+    pub fn load(path: &str) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read excluded dates file '{}'", path))?;
+        Self::parse(&contents).with_context(|| format!("Invalid excluded dates file '{}'", path))
+    }
+
+    /// Parse iCalendar data (detected by `BEGIN:VCALENDAR`) or a date list
+    pub fn parse(contents: &str) -> Result<Self> {
+        if contents.trim_start().starts_with("BEGIN:VCALENDAR") {
+            parse_ics(contents)
+        } else {
+            parse_date_list(contents)
+        }
+    }
+
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.dates.contains(&date) || self.annual.contains(&(date.month(), date.day()))
+    }
+
+    /// Number of entries (dates plus yearly dates)
+    pub fn len(&self) -> usize {
+        self.dates.len() + self.annual.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn parse_date_list(contents: &str) -> Result<ExcludedDates> {
+    let mut excluded = ExcludedDates::default();
+
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let context = || format!("line {}: '{}'", i + 1, line);
+
+        if let Some((start, end)) = line.split_once("..") {
+            let start = parse_iso_date(start.trim()).with_context(context)?;
+            let end = parse_iso_date(end.trim()).with_context(context)?;
+            if end < start || (end - start).num_days() > MAX_RANGE_DAYS {
+                bail!("{}: range must be forward and at most {} days", context(), MAX_RANGE_DAYS);
+            }
+            excluded.dates.extend(start.iter_days().take_while(|d| *d <= end));
+        } else if line.len() == 5 {
+            // MM-DD, checked against a leap year so 02-29 is accepted
+            let date = NaiveDate::parse_from_str(&format!("2024-{}", line), "%Y-%m-%d")
+                .with_context(context)?;
+            excluded.annual.insert((date.month(), date.day()));
+        } else {
+            excluded.dates.insert(parse_iso_date(line).with_context(context)?);
+        }
+    }
+
+    Ok(excluded)
+}
+
+fn parse_iso_date(s: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").with_context(|| format!("Invalid date '{}' (expected YYYY-MM-DD)", s))
+}
+
+/// Extract event dates from iCalendar data
+///
+/// All-day events exclude every day from DTSTART up to (not including)
+/// DTEND. Timed events exclude the days they touch; times and TZID are not
+/// converted. `RRULE:FREQ=YEARLY` repeats the event every year; other
+/// recurrence rules only exclude the first occurrence.
+fn parse_ics(contents: &str) -> Result<ExcludedDates> {
+    let mut excluded = ExcludedDates::default();
+
+    // Unfold continuation lines (RFC 5545 Section 3.1)
+    let mut lines: Vec<String> = Vec::new();
+    for raw in contents.lines() {
+        match raw.strip_prefix([' ', '\t']) {
+            Some(rest) if !lines.is_empty() => lines.last_mut().unwrap().push_str(rest),
+            _ => lines.push(raw.to_string()),
+        }
+    }
+
+    let mut in_event = false;
+    let mut start: Option<(NaiveDate, bool)> = None;
+    let mut end: Option<(NaiveDate, bool)> = None;
+    let mut yearly = false;
+    let mut summary = String::new();
+
+    for line in &lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let property = name.split(';').next().unwrap_or("").to_ascii_uppercase();
+        let value = value.trim();
+
+        match property.as_str() {
+            "BEGIN" if value.eq_ignore_ascii_case("VEVENT") => {
+                in_event = true;
+                start = None;
+                end = None;
+                yearly = false;
+                summary.clear();
+            }
+            "END" if value.eq_ignore_ascii_case("VEVENT") && in_event => {
+                in_event = false;
+                let (first, _) = start.with_context(|| format!("Event '{}' has no DTSTART", summary))?;
+                let last = match end {
+                    // Exclusive end (all-day events, or timed events ending at midnight)
+                    Some((date, true)) if date > first => date.pred_opt().unwrap_or(date),
+                    Some((date, _)) if date >= first => date,
+                    _ => first,
+                };
+                if (last - first).num_days() > MAX_RANGE_DAYS {
+                    bail!("Event '{}' spans more than {} days", summary, MAX_RANGE_DAYS);
+                }
+                for date in first.iter_days().take_while(|d| *d <= last) {
+                    if yearly {
+                        excluded.annual.insert((date.month(), date.day()));
+                    } else {
+                        excluded.dates.insert(date);
+                    }
+                }
+            }
+            "DTSTART" if in_event => start = Some(parse_ics_date(value)?),
+            "DTEND" if in_event => end = Some(parse_ics_date(value)?),
+            "SUMMARY" if in_event => summary = value.to_string(),
+            "RRULE" if in_event => {
+                let rule = value.to_ascii_uppercase();
+                let parts: Vec<&str> = rule.split(';').collect();
+                if parts.contains(&"FREQ=YEARLY") && parts.iter().all(|p| *p == "FREQ=YEARLY" || *p == "INTERVAL=1") {
+                    yearly = true;
+                } else {
+                    warn!("Unsupported RRULE '{}' - excluding the first occurrence only", value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(excluded)
+}
+
+/// Parse an iCalendar DATE or DATE-TIME value; the flag is true when the
+/// value marks the start of its day (so it is exclusive as an end)
+fn parse_ics_date(value: &str) -> Result<(NaiveDate, bool)> {
+    let date = value
+        .get(..8)
+        .and_then(|d| NaiveDate::parse_from_str(d, "%Y%m%d").ok())
+        .with_context(|| format!("Invalid iCalendar date '{}'", value))?;
+    let time = value.get(8..).unwrap_or("").trim_end_matches('Z');
+    Ok((date, time.is_empty() || time == "T000000"))
+}
+
+/// When checks run: cron expressions in a time zone, minus excluded dates
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    expressions: Vec<CronExpr>,
+    source: String,
+    timezone: Tz,
+    excluded: ExcludedDates,
+}
+
+impl Schedule {
+    /// Build a schedule from `;`-separated cron expressions and an IANA time zone
+    pub fn new(cron: &str, timezone: &str) -> Result<Self> {
+        let timezone: Tz = timezone
+            .trim()
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Unknown time zone '{}' (expected an IANA name like America/New_York)", timezone))?;
+
+        let epoch = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let mut expressions = Vec::new();
+        for source in cron.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let expr = CronExpr::parse(source)?;
+            // Reject expressions that can never fire (e.g. February 30th)
+            if !epoch.iter_days().take(SEARCH_DAYS as usize).any(|d| expr.matches_date(d)) {
+                bail!("Cron expression '{}' never runs", source);
+            }
+            expressions.push(expr);
+        }
+        if expressions.is_empty() {
+            bail!("Schedule is empty");
+        }
+
+        Ok(Self {
+            expressions,
+            source: cron.trim().to_string(),
+            timezone,
+            excluded: ExcludedDates::default(),
+        })
+    }
+
+    /// The default schedule: hourly from 8am to 5pm Pacific, every day
+    pub fn business_hours() -> Self {
+        Self::new(DEFAULT_CRON, DEFAULT_TIMEZONE).expect("default schedule is valid")
+    }
+
+    /// Skip these dates
+    pub fn with_excluded_dates(mut self, excluded: ExcludedDates) -> Self {
+        self.excluded = excluded;
+        self
+    }
+
+    /// The cron expressions as written
+    pub fn cron(&self) -> &str {
+        &self.source
+    }
+
+    pub fn timezone(&self) -> Tz {
+        self.timezone
+    }
+
+    pub fn excluded_dates(&self) -> &ExcludedDates {
+        &self.excluded
+    }
+
+    /// Whether a check runs on this local date
+    pub fn runs_on(&self, date: NaiveDate) -> bool {
+        !self.excluded.contains(date) && self.expressions.iter().any(|e| e.matches_date(date))
+    }
+
+    /// Whether a check is scheduled in the minute containing this local time
+    pub fn matches_local(&self, local: NaiveDateTime) -> bool {
+        !self.excluded.contains(local.date())
+            && self
+                .expressions
+                .iter()
+                .any(|e| e.matches_date(local.date()) && e.matches_time(local.hour(), local.minute()))
+    }
+
+    /// The next scheduled local minute strictly after the minute of `local`
+    pub fn next_local_after(&self, local: NaiveDateTime) -> Option<NaiveDateTime> {
+        let start = local.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+
+        for date in start.date().iter_days().take(SEARCH_DAYS as usize) {
+            if !self.runs_on(date) {
+                continue;
+            }
+            let first_day = date == start.date();
+            let from_hour = if first_day { start.hour() } else { 0 };
+            for hour in from_hour..24 {
+                let from_minute = if first_day && hour == start.hour() { start.minute() } else { 0 };
+                for minute in from_minute..60 {
+                    let due = self
+                        .expressions
+                        .iter()
+                        .any(|e| e.matches_date(date) && e.matches_time(hour, minute));
+                    if due {
+                        return date.and_hms_opt(hour, minute, 0);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// The next scheduled instant after `now`
+    ///
+    /// Local times skipped by a DST change never run; times repeated by a DST
+    /// change run once, at their first occurrence after `now`.
+    pub fn next_after(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut local = now.with_timezone(&self.timezone).naive_local();
+
+        for _ in 0..SEARCH_DAYS {
+            let candidate = self.next_local_after(local)?;
+            let instants = match self.timezone.from_local_datetime(&candidate) {
+                LocalResult::Single(t) => vec![t],
+                LocalResult::Ambiguous(a, b) => vec![a, b],
+                LocalResult::None => vec![],
+            };
+            if let Some(t) = instants.into_iter().find(|t| t.with_timezone(&Utc) > now) {
+                return Some(t.with_timezone(&Utc));
+            }
+            local = candidate;
+        }
+
+        None
+    }
+
+    /// Local wall-clock time of an instant in this schedule's time zone
+    pub fn local_time(&self, instant: DateTime<Utc>) -> NaiveDateTime {
+        instant.with_timezone(&self.timezone).naive_local()
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.source, self.timezone.name())?;
+        if !self.excluded.is_empty() {
+            write!(f, ", {} excluded dates", self.excluded.len())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        date(y, m, d).and_hms_opt(h, min, 0).unwrap()
+    }
+
+    #[test]
+    fn test_parse_fields() {
+        let expr = CronExpr::parse("*/15 8-17 * * MON-FRI").unwrap();
+        assert!(expr.matches_time(8, 0));
+        assert!(expr.matches_time(8, 45));
+        assert!(!expr.matches_time(8, 10));
+        assert!(!expr.matches_time(18, 0));
+
+        // 2024-01-15 is a Monday, 2024-01-13 a Saturday
+        assert!(expr.matches_date(date(2024, 1, 15)));
+        assert!(!expr.matches_date(date(2024, 1, 13)));
+    }
+
+    #[test]
+    fn test_parse_lists_names_and_sunday_alias() {
+        let expr = CronExpr::parse("0,30 9 1 jan,Jul 7").unwrap();
+        assert!(expr.matches_time(9, 30));
+        // Day-of-month and day-of-week both restricted: either matches
+        assert!(expr.matches_date(date(2024, 1, 1))); // Monday the 1st
+        assert!(expr.matches_date(date(2024, 1, 7))); // Sunday
+        assert!(!expr.matches_date(date(2024, 1, 8)));
+        assert!(!expr.matches_date(date(2024, 2, 4))); // Sunday in February
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_expressions() {
+        assert!(CronExpr::parse("* * * *").is_err());
+        assert!(CronExpr::parse("60 * * * *").is_err());
+        assert!(CronExpr::parse("* 24 * * *").is_err());
+        assert!(CronExpr::parse("* * 0 * *").is_err());
+        assert!(CronExpr::parse("* * * 13 *").is_err());
+        assert!(CronExpr::parse("* * * * 8").is_err());
+        assert!(CronExpr::parse("*/0 * * * *").is_err());
+        assert!(CronExpr::parse("5-1 * * * *").is_err());
+        assert!(CronExpr::parse("* * * * FUNDAY").is_err());
+    }
+
+    #[test]
+    fn test_schedule_rejects_bad_timezone_and_impossible_dates() {
+        assert!(Schedule::new(DEFAULT_CRON, "America/New_York").is_ok());
+        assert!(Schedule::new(DEFAULT_CRON, "Eastern").is_err());
+        assert!(Schedule::new("", DEFAULT_TIMEZONE).is_err());
+        assert!(Schedule::new("0 9 30 2 *", DEFAULT_TIMEZONE).is_err());
+        // Leap day is fine
+        assert!(Schedule::new("0 9 29 2 *", DEFAULT_TIMEZONE).is_ok());
+    }
+
+    #[test]
+    fn test_next_local_after_weekdays_every_15_minutes_at_open() {
+        let schedule =
+            Schedule::new("*/15 8 * * MON-FRI; 0 9-17 * * MON-FRI", "America/New_York").unwrap();
+
+        // Friday 17:00 -> Monday 8:00
+        assert_eq!(schedule.next_local_after(at(2024, 1, 12, 17, 0)), Some(at(2024, 1, 15, 8, 0)));
+        // Monday 8:00 -> 8:15 -> ... -> 8:45 -> 9:00
+        assert_eq!(schedule.next_local_after(at(2024, 1, 15, 8, 0)), Some(at(2024, 1, 15, 8, 15)));
+        assert_eq!(schedule.next_local_after(at(2024, 1, 15, 8, 45)), Some(at(2024, 1, 15, 9, 0)));
+        // Seconds within the current minute don't re-run it
+        let mid_minute = at(2024, 1, 15, 8, 15).with_second(30).unwrap();
+        assert_eq!(schedule.next_local_after(mid_minute), Some(at(2024, 1, 15, 8, 30)));
+    }
+
+    #[test]
+    fn test_excluded_dates_are_skipped() {
+        let excluded = ExcludedDates::parse("2024-01-15\n12-25\n").unwrap();
+        let schedule = Schedule::new("0 9 * * MON-FRI", DEFAULT_TIMEZONE)
+            .unwrap()
+            .with_excluded_dates(excluded);
+
+        assert!(!schedule.runs_on(date(2024, 1, 15)));
+        assert!(!schedule.matches_local(at(2024, 1, 15, 9, 0)));
+        assert_eq!(schedule.next_local_after(at(2024, 1, 12, 10, 0)), Some(at(2024, 1, 16, 9, 0)));
+        // Yearly dates apply to every year
+        assert!(!schedule.runs_on(date(2025, 12, 25)));
+    }
+
+    #[test]
+    fn test_next_after_uses_timezone() {
+        let schedule = Schedule::new("0 8 * * *", "America/New_York").unwrap();
+        // 2024-01-15 12:00 UTC is 7:00 EST
+        let now = Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap();
+        assert_eq!(schedule.next_after(now), Some(Utc.with_ymd_and_hms(2024, 1, 15, 13, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn test_next_after_dst_transitions() {
+        // 2:30 doesn't exist on 2024-03-10 in Los Angeles: skipped to the next day
+        let schedule = Schedule::new("30 2 * * *", DEFAULT_TIMEZONE).unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 3, 10, 9, 0, 0).unwrap(); // 1:00 PST
+        let next = schedule.next_after(now).unwrap();
+        assert_eq!(schedule.local_time(next), at(2024, 3, 11, 2, 30));
+
+        // 1:30 happens twice on 2024-11-03: runs at the first one
+        let schedule = Schedule::new("30 1 * * *", DEFAULT_TIMEZONE).unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 11, 3, 7, 0, 0).unwrap(); // 0:00 PDT
+        assert_eq!(schedule.next_after(now), Some(Utc.with_ymd_and_hms(2024, 11, 3, 8, 30, 0).unwrap()));
+    }
+
+    #[test]
+    fn test_parse_date_list() {
+        let excluded = ExcludedDates::parse(
+            "# Office closures\n2024-07-04\n\n2024-12-24..2024-12-26  # Christmas\n01-01\n",
+        )
+        .unwrap();
+        assert_eq!(excluded.len(), 5);
+        assert!(excluded.contains(date(2024, 12, 25)));
+        assert!(excluded.contains(date(2030, 1, 1)));
+        assert!(!excluded.contains(date(2025, 7, 4)));
+
+        assert!(ExcludedDates::parse("2024-13-01").is_err());
+        assert!(ExcludedDates::parse("2024-12-26..2024-12-24").is_err());
+        assert!(ExcludedDates::parse("2024-01-01..2026-01-01").is_err());
+    }
+
+    #[test]
+    fn test_parse_ics() {
+        let ics = "BEGIN:VCALENDAR\r\n\
+                   VERSION:2.0\r\n\
+                   BEGIN:VEVENT\r\n\
+                   SUMMARY:Thanksgiving\r\n\
+                   DTSTART;VALUE=DATE:20241128\r\n\
+                   DTEND;VALUE=DATE:20241130\r\n\
+                   END:VEVENT\r\n\
+                   BEGIN:VEVENT\r\n\
+                   SUMMARY:Independence\r\n \
+                   Day\r\n\
+                   DTSTART;VALUE=DATE:20240704\r\n\
+                   RRULE:FREQ=YEARLY\r\n\
+                   END:VEVENT\r\n\
+                   BEGIN:VEVENT\r\n\
+                   SUMMARY:Offsite\r\n\
+                   DTSTART;TZID=America/New_York:20240315T090000\r\n\
+                   DTEND;TZID=America/New_York:20240315T170000\r\n\
+                   END:VEVENT\r\n\
+                   END:VCALENDAR\r\n";
+        let excluded = ExcludedDates::parse(ics).unwrap();
+
+        // All-day DTEND is exclusive
+        assert!(excluded.contains(date(2024, 11, 28)));
+        assert!(excluded.contains(date(2024, 11, 29)));
+        assert!(!excluded.contains(date(2024, 11, 30)));
+        // Yearly recurrence
+        assert!(excluded.contains(date(2027, 7, 4)));
+        // Timed event excludes its day
+        assert!(excluded.contains(date(2024, 3, 15)));
+        assert_eq!(excluded.len(), 4);
+    }
+
+    #[test]
+    fn test_display() {
+        let schedule = Schedule::business_hours();
+        assert_eq!(schedule.to_string(), "0 8-17 * * * (America/Los_Angeles)");
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        /// Parsing arbitrary input never panics
+        #[test]
+        fn cron_parse_never_panics(s in ".{0,40}") {
+            let _ = CronExpr::parse(&s);
+        }
+
+        /// Parsing arbitrary date lists never panics
+        #[test]
+        fn excluded_dates_parse_never_panics(s in ".{0,80}") {
+            let _ = ExcludedDates::parse(&s);
+        }
+
+        /// The next run is always later, scheduled, and not excluded
+        #[test]
+        fn next_run_is_scheduled(
+            minute_step in 1u32..60,
+            start_hour in 0u32..24,
+            day_offset in 0i64..2000,
+            hour in 0u32..24,
+            minute in 0u32..60
+        ) {
+            let cron = format!("*/{} {}-23 * * MON-FRI", minute_step, start_hour);
+            let schedule = Schedule::new(&cron, DEFAULT_TIMEZONE).unwrap();
+            let local = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+                + Duration::days(day_offset);
+
+            let next = schedule.next_local_after(local).unwrap();
+            prop_assert!(next > local);
+            prop_assert!(schedule.matches_local(next));
+            prop_assert!((next - local).num_days() <= 3);
+        }
+    }
+}
@@ -1,7 +1,9 @@
-//! Business hours scheduler
-//! Runs checks hourly between 8am and 5pm Pacific time, 7 days a week
+//! Check scheduler
+//! Runs checks on a [`Schedule`]: cron expressions in an IANA time zone,
+//! minus excluded dates. The default is hourly from 8am to 5pm Pacific time,
+//! 7 days a week.
 
-use chrono::{TimeZone, Timelike};
+use chrono::{DateTime, TimeZone, Timelike, Utc};
 use chrono_tz::America::Los_Angeles;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -12,6 +14,8 @@ use tokio::time::sleep;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, info, warn};
 
+use crate::schedule::Schedule;
+
 pub const BUSINESS_START_HOUR: u32 = 8; // 8 AM
 pub const BUSINESS_END_HOUR: u32 = 17; // 5 PM (17:00)
 
@@ -30,40 +34,27 @@ pub fn is_business_hours_at(hour: u32, _minute: u32, _second: u32) -> bool {
     (BUSINESS_START_HOUR..BUSINESS_END_HOUR).contains(&hour)
 }
 
-/// Calculate duration until next check should run
+/// Calculate duration until the next scheduled check
 /// Returns None if we should run immediately, Some(duration) if we need to wait
-pub fn time_until_next_check() -> Option<Duration> {
-    let now = Los_Angeles.from_utc_datetime(&chrono::Utc::now().naive_utc());
-    time_until_next_check_at(now.hour(), now.minute(), now.second())
+pub fn time_until_next_check(schedule: &Schedule) -> Option<Duration> {
+    time_until_next_check_at(schedule, Utc::now())
 }
 
-/// Testable version: calculate wait time from given hour/minute/second
-pub fn time_until_next_check_at(hour: u32, minute: u32, second: u32) -> Option<Duration> {
-    // If within business hours, check at the top of the next hour
-    if (BUSINESS_START_HOUR..BUSINESS_END_HOUR).contains(&hour) {
-        // If it's within the first 5 seconds of the hour, run now
-        if minute == 0 && second < 5 {
-            return None;
-        }
-
-        // Otherwise wait until next hour
-        let seconds_until_next_hour = (60 - minute) * 60 - second;
-        return Some(Duration::from_secs(seconds_until_next_hour as u64));
-    }
-
-    // Before business hours today
-    if hour < BUSINESS_START_HOUR {
-        let hours_until = BUSINESS_START_HOUR - hour;
-        let seconds_until = hours_until * 3600 - minute * 60 - second;
-        return Some(Duration::from_secs(seconds_until as u64));
-    }
-
-    // After business hours - wait until 8am tomorrow
-    let hours_until_midnight = 24 - hour;
-    let hours_after_midnight = BUSINESS_START_HOUR;
-    let total_hours = hours_until_midnight + hours_after_midnight;
-    let seconds_until = total_hours * 3600 - minute * 60 - second;
-    Some(Duration::from_secs(seconds_until as u64))
+/// Testable version: calculate wait time from a given instant
+pub fn time_until_next_check_at(schedule: &Schedule, now: DateTime<Utc>) -> Option<Duration> {
+    // If it's within the first 5 seconds of a scheduled minute, run now
+    let local = schedule.local_time(now);
+    if schedule.matches_local(local) && local.second() < 5 {
+        return None;
+    }
+
+    // Otherwise wait until the next scheduled minute (re-check in a day if
+    // every upcoming date is excluded)
+    let wait = schedule
+        .next_after(now)
+        .and_then(|next| (next - now).to_std().ok())
+        .unwrap_or(Duration::from_secs(24 * 3600));
+    Some(wait)
 }
 
 /// Format duration for logging
@@ -105,6 +96,18 @@ pub fn is_business_hours_with_tolerance_at(hour: u32, minute: u32, second: u32)
     false
 }
 
+/// Check whether a scheduled check is due now
+pub fn is_check_due(schedule: &Schedule) -> bool {
+    is_check_due_at(schedule, Utc::now())
+}
+
+/// Testable version: whether `now` falls in a scheduled minute
+/// If we wake up within the tolerance window after the intended time, still run the check
+pub fn is_check_due_at(schedule: &Schedule, now: DateTime<Utc>) -> bool {
+    let local = schedule.local_time(now);
+    schedule.matches_local(local) && (local.second() as u64) < SCHEDULE_TOLERANCE_SECS
+}
+
 /// Create a shutdown signal receiver
 /// Returns a watch receiver that will be notified when SIGINT/SIGTERM is received
 pub fn shutdown_signal() -> watch::Receiver<bool> {
@@ -199,6 +202,18 @@ pub async fn run_scheduler_with_shutdown<F, Fut>(
     run_scheduler_with_shutdown_and_guard(check_fn, shutdown_rx, &is_check_running).await;
 }
 
+/// Run the scheduler loop, waking for the next check of any of the given
+/// schedules (one per target). The check function decides which targets are due.
+pub async fn run_scheduler_for<F, Fut>(schedules: &[Schedule], mut check_fn: F)
+where
+    F: FnMut(CancellationToken) -> Fut,
+    Fut: std::future::Future<Output = ()>,
+{
+    let mut shutdown_rx = shutdown_signal();
+    let is_check_running = Arc::new(AtomicBool::new(false));
+    run_scheduler_loop(schedules, &mut check_fn, &mut shutdown_rx, &is_check_running).await;
+}
+
 /// Timeout for graceful shutdown of in-flight calls
 const GRACEFUL_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
 
@@ -211,7 +226,20 @@ async fn run_scheduler_with_shutdown_and_guard<F, Fut>(
     F: FnMut(CancellationToken) -> Fut,
     Fut: std::future::Future<Output = ()>,
 {
-    info!("Scheduler started (Pacific time, 8am-5pm daily)");
+    run_scheduler_loop(&[Schedule::business_hours()], check_fn, shutdown_rx, is_check_running).await;
+}
+
+async fn run_scheduler_loop<F, Fut>(
+    schedules: &[Schedule],
+    check_fn: &mut F,
+    shutdown_rx: &mut watch::Receiver<bool>,
+    is_check_running: &Arc<AtomicBool>,
+) where
+    F: FnMut(CancellationToken) -> Fut,
+    Fut: std::future::Future<Output = ()>,
+{
+    let described: Vec<String> = schedules.iter().map(|s| s.to_string()).collect();
+    info!("Scheduler started ({})", described.join("; "));
 
     loop {
         // Check for shutdown before starting
@@ -221,7 +249,14 @@ async fn run_scheduler_with_shutdown_and_guard<F, Fut>(
         }
 
         // Calculate wait time and track if we intend to run
-        let should_run = match time_until_next_check() {
+        // The soonest schedule wins (None, meaning run now, sorts first)
+        let now = Utc::now();
+        let next_wait = schedules
+            .iter()
+            .map(|schedule| time_until_next_check_at(schedule, now))
+            .min()
+            .flatten();
+        let should_run = match next_wait {
             Some(wait_duration) => {
                 info!("Next check in {}", format_duration(wait_duration));
 
@@ -249,7 +284,7 @@ async fn run_scheduler_with_shutdown_and_guard<F, Fut>(
 
         // Run the check if we intended to and we're still in valid window
         // Use tolerance-aware check to handle clock drift
-        if should_run && is_business_hours_with_tolerance() {
+        if should_run && schedules.iter().any(is_check_due) {
             // Try to acquire the check guard - prevents concurrent checks
             if let Some(_guard) = CheckGuard::try_acquire(is_check_running) {
                 // Create a cancellation token for this check
@@ -393,59 +428,74 @@ mod tests {
 
     // === time_until_next_check_at tests ===
 
+    /// Monday 2024-01-15 (no DST transition) at the given Pacific time
+    fn pacific(hour: u32, minute: u32, second: u32) -> DateTime<Utc> {
+        Los_Angeles
+            .with_ymd_and_hms(2024, 1, 15, hour, minute, second)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn business() -> Schedule {
+        Schedule::business_hours()
+    }
+
     #[test]
     fn test_time_until_next_check_at_top_of_hour() {
         // At exactly 8:00:00, should run immediately
-        assert_eq!(time_until_next_check_at(8, 0, 0), None);
-        assert_eq!(time_until_next_check_at(8, 0, 4), None); // Within 5 second grace
-        assert_eq!(time_until_next_check_at(10, 0, 2), None);
+        assert_eq!(time_until_next_check_at(&business(), pacific(8, 0, 0)), None);
+        assert_eq!(time_until_next_check_at(&business(), pacific(8, 0, 4)), None); // Within 5 second grace
+        assert_eq!(time_until_next_check_at(&business(), pacific(10, 0, 2)), None);
     }
 
     #[test]
     fn test_time_until_next_check_at_during_business_hours() {
         // 8:00:05 - just past grace period, wait until 9:00
-        let result = time_until_next_check_at(8, 0, 5);
+        let result = time_until_next_check_at(&business(), pacific(8, 0, 5));
         assert!(result.is_some());
         // Should wait ~3595 seconds (59:55)
         assert_eq!(result.unwrap().as_secs(), 59 * 60 + 55);
 
         // 8:30:00 - wait until 9:00 (30 minutes)
-        let result = time_until_next_check_at(8, 30, 0);
+        let result = time_until_next_check_at(&business(), pacific(8, 30, 0));
         assert_eq!(result.unwrap().as_secs(), 30 * 60);
 
         // 16:45:30 - wait until 17:00 (14:30), but 17:00 is end of business hours
         // Actually it should wait until next hour within business hours
-        let result = time_until_next_check_at(16, 45, 30);
+        let result = time_until_next_check_at(&business(), pacific(16, 45, 30));
         assert_eq!(result.unwrap().as_secs(), 14 * 60 + 30);
     }
 
     #[test]
     fn test_time_until_next_check_at_before_business_hours() {
         // 6:00:00 - wait until 8:00 (2 hours)
-        let result = time_until_next_check_at(6, 0, 0);
+        let result = time_until_next_check_at(&business(), pacific(6, 0, 0));
         assert_eq!(result.unwrap().as_secs(), 2 * 3600);
 
         // 7:30:00 - wait until 8:00 (30 minutes)
-        let result = time_until_next_check_at(7, 30, 0);
+        let result = time_until_next_check_at(&business(), pacific(7, 30, 0));
         assert_eq!(result.unwrap().as_secs(), 30 * 60);
 
         // 0:00:00 - wait until 8:00 (8 hours)
-        let result = time_until_next_check_at(0, 0, 0);
+        let result = time_until_next_check_at(&business(), pacific(0, 0, 0));
         assert_eq!(result.unwrap().as_secs(), 8 * 3600);
     }
 
     #[test]
     fn test_time_until_next_check_at_after_business_hours() {
-        // 17:00:00 - wait until 8:00 tomorrow (15 hours)
-        let result = time_until_next_check_at(17, 0, 0);
-        assert_eq!(result.unwrap().as_secs(), 15 * 3600);
+        // 17:00:00 - the 5pm check runs now
+        assert_eq!(time_until_next_check_at(&business(), pacific(17, 0, 0)), None);
+
+        // 17:00:05 - wait until 8:00 tomorrow (15 hours)
+        let result = time_until_next_check_at(&business(), pacific(17, 0, 5));
+        assert_eq!(result.unwrap().as_secs(), 15 * 3600 - 5);
 
         // 18:30:00 - wait until 8:00 tomorrow (13.5 hours)
-        let result = time_until_next_check_at(18, 30, 0);
+        let result = time_until_next_check_at(&business(), pacific(18, 30, 0));
         assert_eq!(result.unwrap().as_secs(), 13 * 3600 + 30 * 60);
 
         // 23:59:59 - wait until 8:00 tomorrow (~8 hours)
-        let result = time_until_next_check_at(23, 59, 59);
+        let result = time_until_next_check_at(&business(), pacific(23, 59, 59));
         assert_eq!(result.unwrap().as_secs(), 8 * 3600 + 1);
     }
 
@@ -459,13 +509,57 @@ mod tests {
 
     #[test]
     fn test_time_until_next_check_reasonable() {
-        let result = time_until_next_check();
+        let result = time_until_next_check(&Schedule::business_hours());
         if let Some(duration) = result {
             // Should never be more than ~24 hours
             assert!(duration.as_secs() <= 24 * 3600);
         }
     }
 
+    // === Schedule tests ===
+
+    #[test]
+    fn test_weekday_schedule_skips_weekend() {
+        // Eastern time, closed weekends, every 15 minutes in the first hour
+        let schedule =
+            Schedule::new("*/15 8 * * MON-FRI; 0 9-17 * * MON-FRI", "America/New_York").unwrap();
+
+        // Friday 2024-01-12 17:30 EST -> Monday 8:00 EST (62.5 hours)
+        let friday = chrono_tz::America::New_York
+            .with_ymd_and_hms(2024, 1, 12, 17, 30, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        let wait = time_until_next_check_at(&schedule, friday).unwrap();
+        assert_eq!(wait.as_secs(), 62 * 3600 + 30 * 60);
+
+        // Monday 8:15:10 EST is due (within tolerance), 8:20 is not
+        let monday = |m, s| {
+            chrono_tz::America::New_York
+                .with_ymd_and_hms(2024, 1, 15, 8, m, s)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        assert!(is_check_due_at(&schedule, monday(15, 10)));
+        assert!(!is_check_due_at(&schedule, monday(15, 30)));
+        assert!(!is_check_due_at(&schedule, monday(20, 0)));
+        assert_eq!(time_until_next_check_at(&schedule, monday(15, 2)), None);
+        assert_eq!(
+            time_until_next_check_at(&schedule, monday(20, 0)).unwrap().as_secs(),
+            10 * 60
+        );
+    }
+
+    #[test]
+    fn test_excluded_date_skips_whole_day() {
+        let excluded = crate::schedule::ExcludedDates::parse("2024-01-15").unwrap();
+        let schedule = Schedule::business_hours().with_excluded_dates(excluded);
+
+        // Monday 2024-01-15 is excluded: 8:00 doesn't run, next is Tuesday 8:00
+        assert!(!is_check_due_at(&schedule, pacific(8, 0, 0)));
+        let wait = time_until_next_check_at(&schedule, pacific(8, 0, 0)).unwrap();
+        assert_eq!(wait.as_secs(), 24 * 3600);
+    }
+
     // === Graceful shutdown tests ===
 
     #[tokio::test]
@@ -681,7 +775,7 @@ mod tests {
     #[test]
     fn test_time_until_next_check_during_dst_transition() {
         // Verify that time calculations don't panic during DST transitions
-        use chrono::{NaiveDate, NaiveTime, NaiveDateTime, TimeZone};
+        use chrono::{NaiveDate, NaiveTime, NaiveDateTime};
 
         // Test around spring forward (March 10, 2024)
         for hour in 0..24 {
@@ -690,9 +784,9 @@ mod tests {
                     NaiveDate::from_ymd_opt(2024, 3, 10).unwrap(),
                     NaiveTime::from_hms_opt(hour, minute, 0).unwrap(),
                 );
-                let la = Los_Angeles.from_utc_datetime(&dt);
-                // Should not panic
-                let _ = time_until_next_check_at(la.hour(), la.minute(), la.second());
+                // Should not panic, and still wait at most a day
+                let wait = time_until_next_check_at(&Schedule::business_hours(), dt.and_utc());
+                assert!(wait.is_none_or(|w| w.as_secs() <= 24 * 3600));
             }
         }
 
@@ -703,9 +797,9 @@ mod tests {
                     NaiveDate::from_ymd_opt(2024, 11, 3).unwrap(),
                     NaiveTime::from_hms_opt(hour, minute, 0).unwrap(),
                 );
-                let la = Los_Angeles.from_utc_datetime(&dt);
-                // Should not panic
-                let _ = time_until_next_check_at(la.hour(), la.minute(), la.second());
+                // Should not panic, and still wait at most a day
+                let wait = time_until_next_check_at(&Schedule::business_hours(), dt.and_utc());
+                assert!(wait.is_none_or(|w| w.as_secs() <= 24 * 3600));
             }
         }
     }
@@ -735,16 +829,33 @@ mod proptests {
 
         /// time_until_next_check always returns duration < 24 hours
         #[test]
-        fn wait_time_bounded(hour in 0u32..24u32, minute in 0u32..60u32, second in 0u32..60u32) {
-            if let Some(duration) = time_until_next_check_at(hour, minute, second) {
+        fn wait_time_bounded(secs in 0i64..(2 * 365 * 86_400)) {
+            let now = DateTime::from_timestamp(1_704_067_200 + secs, 0).unwrap(); // from 2024-01-01
+            if let Some(duration) = time_until_next_check_at(&Schedule::business_hours(), now) {
                 prop_assert!(duration.as_secs() <= 24 * 3600);
             }
         }
 
         /// During business hours at top of hour, returns None (run immediately)
         #[test]
-        fn top_of_hour_runs_immediately(hour in 8u32..17u32, second in 0u32..5u32) {
-            prop_assert_eq!(time_until_next_check_at(hour, 0, second), None);
+        fn top_of_hour_runs_immediately(day in 1u32..29u32, hour in 8u32..18u32, second in 0u32..5u32) {
+            let now = Los_Angeles.with_ymd_and_hms(2024, 2, day, hour, 0, second).unwrap().with_timezone(&Utc);
+            prop_assert_eq!(time_until_next_check_at(&Schedule::business_hours(), now), None);
+            prop_assert!(is_check_due_at(&Schedule::business_hours(), now));
+        }
+
+        /// Waking at the computed time always lands on a due check
+        #[test]
+        fn wake_time_is_due(
+            minute_step in 1u32..60u32,
+            secs in 0i64..(365 * 86_400)
+        ) {
+            let schedule = Schedule::new(&format!("*/{} 8-17 * * MON-FRI", minute_step), "America/New_York").unwrap();
+            let now = DateTime::from_timestamp(1_704_067_200 + secs, 0).unwrap();
+            if let Some(wait) = time_until_next_check_at(&schedule, now) {
+                let wake = now + chrono::Duration::from_std(wait).unwrap();
+                prop_assert!(is_check_due_at(&schedule, wake));
+            }
         }
 
         /// format_duration never panics
@@ -778,14 +889,11 @@ mod kani_proofs {
 
     #[kani::proof]
     fn time_until_bounded() {
-        let hour: u32 = kani::any();
-        kani::assume(hour < 24);
-        let minute: u32 = kani::any();
-        kani::assume(minute < 60);
-        let second: u32 = kani::any();
-        kani::assume(second < 60);
+        let offset: i64 = kani::any();
+        kani::assume((0..86_400).contains(&offset));
+        let now = DateTime::from_timestamp(1_704_067_200 + offset, 0).unwrap();
 
-        if let Some(duration) = time_until_next_check_at(hour, minute, second) {
+        if let Some(duration) = time_until_next_check_at(&Schedule::business_hours(), now) {
             // Must be less than 24 hours
             kani::assert(duration.as_secs() <= 24 * 3600, "wait time must be <= 24h");
         }
@@ -10,12 +10,20 @@ use tracing::{info, warn};
 
 use super::digest::{extract_authenticate_header, DigestChallenge, DigestResponse};
 use super::messages::{
-    build_ack, build_bye, build_invite, build_invite_with_auth, build_register,
-    build_register_with_auth, extract_rtp_address, extract_to_tag, extract_via_branch,
-    generate_call_id, generate_tag, parse_status_code,
+    build_ack, build_bye, build_cancel, build_invite, build_invite_with_auth, build_register,
+    build_register_with_auth, extract_rtcp_address, extract_rtp_address, extract_telephone_event_payload_type,
+    extract_to_tag, extract_via_branch, generate_call_id, generate_tag, parse_status_code,
 };
-use super::transport::SipTransport;
+use super::transport::{InviteResponse, SipTransport};
 use crate::config::Config;
+use crate::ivr::{IvrScript, IvrStep};
+use crate::rtp::dtmf::TELEPHONE_EVENT_PT;
+use crate::rtp::emodel::CallQuality;
+use crate::rtp::jitter::JitterBufferStats;
+use crate::rtp::plc::Concealment;
+use crate::rtp::rtcp::CallStats;
+use crate::rtp::stream::{RtpStream, StreamSpan};
+use crate::rtp::tones::ToneEvent;
 use crate::rtp::RtpReceiver;
 
 /// SIP client for making outbound calls
@@ -34,7 +42,31 @@ pub struct CallResult {
     pub audio_received: bool,
     pub audio_samples: Vec<f32>,
     pub error: Option<String>,
+    /// Final SIP status of the INVITE (None if no final response arrived)
     pub sip_status: Option<u16>,
+    /// Provisional status (usually 183) that carried early media SDP
+    pub early_media_status: Option<u16>,
+    /// Audio received before the call was answered (carrier announcements,
+    /// ringback). Empty when no early media was offered.
+    pub early_media_samples: Vec<f32>,
+    /// Audio of each IVR capture step, in script order. Empty when no IVR
+    /// script is configured.
+    pub captures: Vec<Vec<f32>>,
+    /// In-band tones (DTMF, ringback, busy, reorder, SIT) heard in the
+    /// received audio, including early media, in time order
+    pub tones: Vec<ToneEvent>,
+    /// RTP streams (SSRCs) heard, with their source addresses
+    pub streams: Vec<RtpStream>,
+    /// Which stream each stretch of the received audio came from
+    pub stream_timeline: Vec<StreamSpan>,
+    /// Time from sending the INVITE to the 200 OK (None if not answered)
+    pub answer_ms: Option<u64>,
+    /// RTP packet counts over the whole call, including early media
+    pub packets: JitterBufferStats,
+    /// Loss, jitter, reordering and round-trip time from RTP and RTCP
+    pub call_stats: CallStats,
+    /// E-model rating of the call (None if no RTP arrived)
+    pub quality: Option<CallQuality>,
 }
 
 impl CallResult {
@@ -45,6 +77,7 @@ impl CallResult {
             audio_samples,
             error: None,
             sip_status: Some(200),
+            ..Default::default()
         }
     }
 
@@ -55,6 +88,71 @@ impl CallResult {
     pub fn failed_with_status(status: u16, error: String) -> Self {
         Self { connected: false, sip_status: Some(status), error: Some(error), ..Default::default() }
     }
+
+    /// Attach early media captured before the final response. For calls that
+    /// never connected, the early media is also the audio to analyze.
+    pub fn with_early_media(mut self, status: u16, samples: Vec<f32>, audio_received: bool) -> Self {
+        if !self.connected {
+            self.audio_samples = samples.clone();
+            self.audio_received = audio_received;
+        }
+        self.early_media_status = Some(status);
+        self.early_media_samples = samples;
+        self
+    }
+
+    /// Attach the tone timeline detected in the received audio
+    pub fn with_tones(mut self, tones: Vec<ToneEvent>) -> Self {
+        self.tones = tones;
+        self
+    }
+
+    /// Attach the RTP streams heard and the timeline of which one each
+    /// stretch of the audio came from
+    pub fn with_streams(mut self, streams: Vec<RtpStream>, stream_timeline: Vec<StreamSpan>) -> Self {
+        self.streams = streams;
+        self.stream_timeline = stream_timeline;
+        self
+    }
+
+    /// Attach the RTP packet counts of the call
+    pub fn with_packet_stats(mut self, packets: JitterBufferStats) -> Self {
+        self.packets = packets;
+        self
+    }
+
+    /// Attach the call-quality statistics of the call, and rate it from
+    /// them, the packet counts attached before and how losses were concealed
+    pub fn with_call_stats(mut self, call_stats: CallStats, concealment: Concealment) -> Self {
+        self.quality = CallQuality::from_stats(&self.packets, &call_stats, concealment);
+        self.call_stats = call_stats;
+        self
+    }
+
+    /// Whether any early media audio was captured
+    pub fn has_early_media(&self) -> bool {
+        !self.early_media_samples.is_empty()
+    }
+
+    /// Result of an answered call that ran an IVR script. The captures are
+    /// concatenated into `audio_samples` for saving; audio counts as received
+    /// only if every capture step heard at least `min_audio_ms`.
+    pub fn success_with_captures(captures: Vec<Vec<f32>>, min_audio_ms: u64) -> Self {
+        let audio_received = !captures.is_empty()
+            && captures
+                .iter()
+                .all(|c| crate::rtp::samples_to_duration_ms(c.len()) >= min_audio_ms);
+        let audio_samples = captures.concat();
+        Self { captures, ..Self::success(audio_samples, audio_received) }
+    }
+}
+
+/// What happened while listening to early media
+struct EarlyMediaOutcome {
+    /// Final response, if one arrived before the wait ended
+    final_response: Option<String>,
+    /// Early media audio (16kHz f32)
+    samples: Vec<f32>,
 }
 
 /// Classify SIP error codes for better error handling and reporting
@@ -126,7 +224,7 @@ impl SipClient {
         listen_duration: Duration,
         cancel_token: CancellationToken,
     ) -> Result<CallResult> {
-        let rtp_receiver = RtpReceiver::bind(0).await?;
+        let rtp_receiver = RtpReceiver::bind_pair().await?;
         // Create transport once — REGISTER and INVITE must use the same source
         // port so the SIP server's NAT pinhole / IP authorization applies to both.
         let transport = SipTransport::new(self.server_addr).await?;
@@ -147,7 +245,10 @@ impl SipClient {
         cancel_token: CancellationToken,
         transport: SipTransport,
     ) -> Result<CallResult> {
+        rtp_receiver.set_jitter_mode(self.config.jitter_buffer);
+        rtp_receiver.set_concealment(self.config.packet_loss_concealment);
         let rtp_port = rtp_receiver.local_port()?;
+        let rtcp_port = rtp_receiver.rtcp_port();
         let local_addr = transport.local_addr()?;
         let call_id = generate_call_id(&local_addr.ip().to_string());
         let from_tag = generate_tag();
@@ -185,31 +286,101 @@ impl SipClient {
             }
         };
 
-        let invite = build_invite(&self.target_uri, &self.from_uri, &self.display_name, &call_id, &from_tag, cseq, local_addr, rtp_port, external_rtp_addr);
+        let mut invite = build_invite(&self.target_uri, &self.from_uri, &self.display_name, &call_id, &from_tag, cseq, local_addr, rtp_port, rtcp_port, external_rtp_addr);
 
-        let mut response = match transport.send_invite_await_final(&invite).await {
+        // The ring timeout bounds the whole INVITE transaction, including an
+        // authenticated retry and any early media
+        let ring_timeout = Duration::from_secs(self.config.ring_timeout_secs);
+        let invite_sent = tokio::time::Instant::now();
+        let ring_deadline = invite_sent + ring_timeout;
+
+        let mut invite_response = match transport.send_invite_await_early_or_final(&invite, ring_timeout, &cancel_token).await {
             Ok(r) => r,
             Err(e) => return Ok(CallResult::failed(format!("No response from server: {}", e))),
         };
 
-        let mut status_code = parse_status_code(&response).unwrap_or(0);
-
-        if status_code == 401 || status_code == 407 {
-            let res = self.handle_auth(&transport, &response, &call_id, &from_tag, &mut cseq, local_addr, rtp_port, external_rtp_addr).await?;
-            match res {
-                Ok(r) => {
-                    response = r;
-                    status_code = parse_status_code(&response).unwrap_or(0);
+        if let InviteResponse::Final(ref response) = invite_response {
+            let status_code = parse_status_code(response).unwrap_or(0);
+            if status_code == 401 || status_code == 407 {
+                let ring_remaining = ring_deadline.saturating_duration_since(tokio::time::Instant::now());
+                let res = self.handle_auth(&transport, response, &call_id, &from_tag, &mut cseq, local_addr, rtp_port, rtcp_port, external_rtp_addr, ring_remaining, &cancel_token).await?;
+                match res {
+                    Ok((auth_invite, r)) => {
+                        invite = auth_invite;
+                        invite_response = r;
+                    }
+                    Err(call_res) => return Ok(call_res),
                 }
-                Err(call_res) => return Ok(call_res),
             }
         }
 
+        // Early media: carriers play announcements ("the number you have dialed
+        // is not in service") in a 183 and may never answer, so start listening
+        // before the final response and keep whatever we hear.
+        let mut early_media: Option<(u16, Vec<f32>)> = None;
+        let response = match invite_response {
+            InviteResponse::Final(response) => response,
+            InviteResponse::Abandoned { proceeding: true } => {
+                let status = self.cancel_invite(&transport, &invite, &call_id, &from_tag, cseq, local_addr, cancel_token.is_cancelled()).await?;
+                return Ok(self.unanswered_result(status, cancel_token.is_cancelled()));
+            }
+            InviteResponse::Abandoned { proceeding: false } => {
+                // Nothing to CANCEL: the server never acknowledged the INVITE
+                warn!("No provisional response to the INVITE before Timer B, giving up without CANCEL");
+                return Ok(self.unanswered_result(None, cancel_token.is_cancelled()));
+            }
+            InviteResponse::AbandonedFinal(response) => {
+                let status = self.complete_abandoned(&transport, &response, &call_id, &from_tag, cseq, local_addr, cancel_token.is_cancelled()).await?;
+                return Ok(self.unanswered_result(Some(status), cancel_token.is_cancelled()));
+            }
+            InviteResponse::EarlyMedia { code, response: provisional } => {
+                info!("Early media offered in {} response, listening before answer", code);
+                let ring_remaining = ring_deadline.saturating_duration_since(tokio::time::Instant::now());
+                let outcome = self.receive_early_media(&transport, &mut rtp_receiver, &provisional, ring_remaining, &cancel_token).await?;
+                let early_ms = crate::rtp::samples_to_duration_ms(outcome.samples.len());
+                let early_received = early_ms >= self.config.min_audio_duration_ms;
+                info!("Early media capture complete: {} ms", early_ms);
+
+                match outcome.final_response {
+                    Some(response) => {
+                        early_media = Some((code, outcome.samples));
+                        response
+                    }
+                    None => {
+                        let status = self.cancel_invite(&transport, &invite, &call_id, &from_tag, cseq, local_addr, cancel_token.is_cancelled()).await?;
+                        return Ok(self
+                            .unanswered_result(status, cancel_token.is_cancelled())
+                            .with_early_media(code, outcome.samples, early_received)
+                            .with_tones(rtp_receiver.tone_timeline())
+                            .with_streams(rtp_receiver.streams().to_vec(), rtp_receiver.stream_timeline())
+                            .with_packet_stats(rtp_receiver.packet_stats())
+                            .with_call_stats(rtp_receiver.call_stats(), self.config.packet_loss_concealment));
+                    }
+                }
+            }
+        };
+
+        let status_code = parse_status_code(&response).unwrap_or(0);
+
         if status_code != 200 {
+            self.acknowledge_final(&transport, &response, &call_id, &from_tag, cseq, local_addr).await?;
             let category = SipErrorCategory::from_status(status_code);
-            return Ok(CallResult::failed_with_status(status_code, format!("{}: {}", status_code, category.description())));
+            let result = CallResult::failed_with_status(status_code, format!("{}: {}", status_code, category.description()));
+            return Ok(match early_media {
+                Some((code, samples)) => {
+                    let received = crate::rtp::samples_to_duration_ms(samples.len()) >= self.config.min_audio_duration_ms;
+                    result
+                        .with_early_media(code, samples, received)
+                        .with_tones(rtp_receiver.tone_timeline())
+                        .with_streams(rtp_receiver.streams().to_vec(), rtp_receiver.stream_timeline())
+                        .with_packet_stats(rtp_receiver.packet_stats())
+                        .with_call_stats(rtp_receiver.call_stats(), self.config.packet_loss_concealment)
+                }
+                None => result,
+            });
         }
 
+        let answer_ms = invite_sent.elapsed().as_millis() as u64;
         let to_tag = extract_to_tag(&response);
         let via_branch = extract_via_branch(&response).unwrap_or_else(|| "z9hG4bKunknown".to_string());
         let ack = build_ack(&self.target_uri, &self.from_uri, &self.display_name, &self.target_uri, to_tag.as_deref(), &call_id, &from_tag, cseq, local_addr, &via_branch);
@@ -222,6 +393,37 @@ impl SipClient {
         } else {
             warn!("No media address found in SDP!");
         }
+        if let Some((addr, mux)) = extract_rtcp_address(&response) {
+            rtp_receiver.set_rtcp_target(addr, mux);
+        }
+
+        if let Some(ref script) = self.config.ivr_script {
+            let te_payload_type = extract_telephone_event_payload_type(&response).unwrap_or_else(|| {
+                warn!("SDP answer has no telephone-event, sending DTMF as PT {}", TELEPHONE_EVENT_PT);
+                TELEPHONE_EVENT_PT
+            });
+            info!("Call connected, running IVR script: {}", script);
+            let outcome = self.run_ivr_script(&mut rtp_receiver, script, remote_rtp_addr, te_payload_type, &cancel_token).await;
+            let completed_normally = matches!(outcome, Ok((true, _)));
+            rtp_receiver.send_rtcp_bye().await;
+            self.terminate_call(&transport, &call_id, &from_tag, to_tag.as_deref(), cseq + 1, local_addr, completed_normally).await;
+
+            let (_, captures) = outcome?;
+            let mut result = CallResult::success_with_captures(captures, self.config.min_audio_duration_ms)
+                .with_tones(rtp_receiver.tone_timeline())
+                .with_streams(rtp_receiver.streams().to_vec(), rtp_receiver.stream_timeline())
+                .with_packet_stats(rtp_receiver.packet_stats())
+                .with_call_stats(rtp_receiver.call_stats(), self.config.packet_loss_concealment);
+            if let Some((code, samples)) = early_media {
+                let audio_received = result.audio_received;
+                result = result.with_early_media(code, samples, audio_received);
+            }
+            if !completed_normally {
+                result.error = Some("Call cancelled".to_string());
+            }
+            result.answer_ms = Some(answer_ms);
+            return Ok(result);
+        }
 
         info!("Call connected, listening for audio (local RTP port {})...", rtp_port);
         let completed_normally = if let Some(addr) = remote_rtp_addr {
@@ -232,16 +434,211 @@ impl SipClient {
         let audio_samples = rtp_receiver.get_samples_f32();
         let audio_received = crate::rtp::samples_to_duration_ms(audio_samples.len()) >= self.config.min_audio_duration_ms;
         info!("Audio capture complete: {} samples ({} ms), audio_received={}", audio_samples.len(), crate::rtp::samples_to_duration_ms(audio_samples.len()), audio_received);
+        rtp_receiver.send_rtcp_bye().await;
 
         self.terminate_call(&transport, &call_id, &from_tag, to_tag.as_deref(), cseq + 1, local_addr, completed_normally).await;
 
-        if completed_normally {
-            Ok(CallResult::success(audio_samples, audio_received))
-        } else {
-            let mut result = CallResult::success(audio_samples, audio_received);
+        let mut result = CallResult::success(audio_samples, audio_received)
+            .with_tones(rtp_receiver.tone_timeline())
+            .with_streams(rtp_receiver.streams().to_vec(), rtp_receiver.stream_timeline())
+            .with_packet_stats(rtp_receiver.packet_stats())
+            .with_call_stats(rtp_receiver.call_stats(), self.config.packet_loss_concealment);
+        if let Some((code, samples)) = early_media {
+            result = result.with_early_media(code, samples, audio_received);
+        }
+        if !completed_normally {
             result.error = Some("Call cancelled".to_string());
-            Ok(result)
         }
+        result.answer_ms = Some(answer_ms);
+        Ok(result)
+    }
+
+    /// Walk an IVR menu per the configured script
+    ///
+    /// Audio heard during wait steps is discarded; each capture step's audio
+    /// is returned in script order. Returns false if cancelled part way.
+    async fn run_ivr_script(
+        &self,
+        rtp_receiver: &mut RtpReceiver,
+        script: &IvrScript,
+        remote_rtp_addr: Option<SocketAddr>,
+        te_payload_type: u8,
+        cancel_token: &CancellationToken,
+    ) -> Result<(bool, Vec<Vec<f32>>)> {
+        // Drop early media and anything heard before the first step
+        rtp_receiver.take_samples_f32();
+
+        let mut captures = Vec::new();
+        for (i, step) in script.steps.iter().enumerate() {
+            if cancel_token.is_cancelled() {
+                return Ok((false, captures));
+            }
+            info!("IVR step {}/{}: {}", i + 1, script.steps.len(), step);
+
+            match step {
+                IvrStep::Wait(duration) | IvrStep::Capture(duration) => {
+                    let completed = match remote_rtp_addr {
+                        Some(addr) => rtp_receiver.receive_for_with_keepalive(*duration, cancel_token.clone(), addr).await?,
+                        None => rtp_receiver.receive_for_cancellable(*duration, cancel_token.clone()).await?,
+                    };
+                    let samples = rtp_receiver.take_samples_f32();
+                    if let IvrStep::Capture(_) = step {
+                        info!("IVR capture {} complete: {} ms", captures.len() + 1, crate::rtp::samples_to_duration_ms(samples.len()));
+                        captures.push(samples);
+                    }
+                    if !completed {
+                        return Ok((false, captures));
+                    }
+                }
+                IvrStep::SendDigits(digits) => {
+                    let addr = remote_rtp_addr.context("Cannot send DTMF: no media address in SDP answer")?;
+                    rtp_receiver.send_dtmf(digits, addr, te_payload_type).await?;
+                }
+            }
+        }
+
+        Ok((true, captures))
+    }
+
+    /// Receive early media until the INVITE gets a final response, `wait`
+    /// expires, or the call is cancelled. The early audio is taken out of
+    /// `rtp_receiver`, so after an answer it holds only the answered audio,
+    /// while the jitter buffer and stream state carry on.
+    async fn receive_early_media(
+        &self,
+        transport: &SipTransport,
+        rtp_receiver: &mut RtpReceiver,
+        provisional: &str,
+        wait: Duration,
+        cancel_token: &CancellationToken,
+    ) -> Result<EarlyMediaOutcome> {
+        let remote_rtp_addr = extract_rtp_address(provisional);
+        if let Some(addr) = remote_rtp_addr {
+            info!("Remote early media address from SDP: {}", addr);
+            let _ = rtp_receiver.punch_nat(addr).await;
+        }
+        if let Some((addr, mux)) = extract_rtcp_address(provisional) {
+            rtp_receiver.set_rtcp_target(addr, mux);
+        }
+
+        // Stopped as soon as the final response arrives
+        let rtp_token = cancel_token.child_token();
+
+        let receive = async {
+            match remote_rtp_addr {
+                Some(addr) => rtp_receiver.receive_for_with_keepalive(wait, rtp_token.clone(), addr).await,
+                None => rtp_receiver.receive_for_cancellable(wait, rtp_token.clone()).await,
+            }
+        };
+        let signalling = async {
+            let result = tokio::select! {
+                r = transport.await_final_response(wait) => r,
+                _ = cancel_token.cancelled() => Ok(None),
+            };
+            rtp_token.cancel();
+            result
+        };
+
+        let (received, final_response) = tokio::join!(receive, signalling);
+        received?;
+
+        Ok(EarlyMediaOutcome {
+            final_response: final_response?,
+            samples: rtp_receiver.take_samples_f32(),
+        })
+    }
+
+    /// CANCEL an INVITE that has not been answered (RFC 3261 Section 9.1)
+    ///
+    /// BYE is only valid inside a dialog, so an unanswered INVITE must be
+    /// cancelled or the PBX keeps ringing the target after we give up. The
+    /// server answers the CANCEL with 200 OK and the INVITE with 487, which
+    /// we ACK. If the callee answered before the CANCEL arrived, the call is
+    /// confirmed with ACK and hung up with BYE.
+    ///
+    /// Returns the INVITE's final status, if one arrived.
+    async fn cancel_invite(
+        &self,
+        transport: &SipTransport,
+        invite: &str,
+        call_id: &str,
+        from_tag: &str,
+        cseq: u32,
+        local_addr: SocketAddr,
+        shutting_down: bool,
+    ) -> Result<Option<u16>> {
+        let via_branch = extract_via_branch(invite).unwrap_or_else(|| "z9hG4bKunknown".to_string());
+        let cancel = build_cancel(&self.target_uri, &self.from_uri, &self.display_name, &self.target_uri, call_id, from_tag, cseq, local_addr, &via_branch);
+
+        info!("Sending CANCEL for unanswered INVITE");
+        let wait = if shutting_down { Duration::from_secs(2) } else { Duration::from_secs(5) };
+        let response = match transport.send_cancel(&cancel, wait).await? {
+            Some(r) => r,
+            None => {
+                warn!("INVITE did not complete after CANCEL");
+                return Ok(None);
+            }
+        };
+
+        self.complete_abandoned(transport, &response, call_id, from_tag, cseq, local_addr, shutting_down).await.map(Some)
+    }
+
+    /// Finish an INVITE given up on with its final response: ACK it, and
+    /// hang up if it was answered anyway. Returns the status code.
+    async fn complete_abandoned(
+        &self,
+        transport: &SipTransport,
+        response: &str,
+        call_id: &str,
+        from_tag: &str,
+        cseq: u32,
+        local_addr: SocketAddr,
+        shutting_down: bool,
+    ) -> Result<u16> {
+        let status_code = parse_status_code(response).unwrap_or(0);
+        if (200..300).contains(&status_code) {
+            warn!("Call was answered after it was given up on, hanging up");
+            let to_tag = extract_to_tag(response);
+            let via_branch = extract_via_branch(response).unwrap_or_else(|| "z9hG4bKunknown".to_string());
+            let ack = build_ack(&self.target_uri, &self.from_uri, &self.display_name, &self.target_uri, to_tag.as_deref(), call_id, from_tag, cseq, local_addr, &via_branch);
+            transport.send(&ack).await?;
+            self.terminate_call(transport, call_id, from_tag, to_tag.as_deref(), cseq + 1, local_addr, !shutting_down).await;
+        } else {
+            info!("INVITE ended ({})", status_code);
+            self.acknowledge_final(transport, response, call_id, from_tag, cseq, local_addr).await?;
+        }
+
+        Ok(status_code)
+    }
+
+    /// Result for a call given up on before it was answered
+    fn unanswered_result(&self, status: Option<u16>, cancelled: bool) -> CallResult {
+        let error = if cancelled {
+            "Call cancelled before answer".to_string()
+        } else {
+            format!("Call not answered within {}s", self.config.ring_timeout_secs)
+        };
+        match status {
+            Some(code) => CallResult::failed_with_status(code, error),
+            None => CallResult::failed(error),
+        }
+    }
+
+    /// ACK a non-2xx final response (RFC 3261 17.1.1.3) so the server stops
+    /// retransmitting it
+    async fn acknowledge_final(
+        &self,
+        transport: &SipTransport,
+        response: &str,
+        call_id: &str,
+        from_tag: &str,
+        cseq: u32,
+        local_addr: SocketAddr,
+    ) -> Result<()> {
+        let via_branch = extract_via_branch(response).unwrap_or_else(|| "z9hG4bKunknown".to_string());
+        let to_tag = extract_to_tag(response);
+        let ack = build_ack(&self.target_uri, &self.from_uri, &self.display_name, &self.target_uri, to_tag.as_deref(), call_id, from_tag, cseq, local_addr, &via_branch);
+        transport.send(&ack).await
     }
 
     async fn handle_auth(
@@ -253,8 +650,11 @@ impl SipClient {
         cseq: &mut u32,
         local_addr: SocketAddr,
         rtp_port: u16,
-        external_rtp_addr: Option<SocketAddr>
-    ) -> Result<std::result::Result<String, CallResult>> {
+        rtcp_port: Option<u16>,
+        external_rtp_addr: Option<SocketAddr>,
+        ring_timeout: Duration,
+        cancel_token: &CancellationToken,
+    ) -> Result<std::result::Result<(String, InviteResponse), CallResult>> {
         let status_code = parse_status_code(response).unwrap_or(0);
         if self.config.sip_password.is_empty() {
             return Ok(Err(CallResult::failed_with_status(status_code, "No SIP_PASSWORD".to_string())));
@@ -270,17 +670,14 @@ impl SipClient {
             None => return Ok(Err(CallResult::failed_with_status(status_code, "Bad challenge".to_string()))),
         };
 
-        let via_branch = extract_via_branch(response).unwrap_or_else(|| "z9hG4bKunknown".to_string());
-        let to_tag = extract_to_tag(response);
-        let ack = build_ack(&self.target_uri, &self.from_uri, &self.display_name, &self.target_uri, to_tag.as_deref(), call_id, from_tag, *cseq, local_addr, &via_branch);
-        transport.send(&ack).await?;
+        self.acknowledge_final(transport, response, call_id, from_tag, *cseq, local_addr).await?;
 
         let digest = DigestResponse::compute(&challenge, &self.config.sip_username, &self.config.sip_password, "INVITE", &self.target_uri);
         *cseq += 1;
-        let auth_invite = build_invite_with_auth(&self.target_uri, &self.from_uri, &self.display_name, call_id, from_tag, *cseq, local_addr, rtp_port, external_rtp_addr, &digest.to_header());
+        let auth_invite = build_invite_with_auth(&self.target_uri, &self.from_uri, &self.display_name, call_id, from_tag, *cseq, local_addr, rtp_port, rtcp_port, external_rtp_addr, &digest.to_header());
 
-        match transport.send_invite_await_final(&auth_invite).await {
-            Ok(r) => Ok(Ok(r)),
+        match transport.send_invite_await_early_or_final(&auth_invite, ring_timeout, cancel_token).await {
+            Ok(r) => Ok(Ok((auth_invite, r))),
             Err(e) => Ok(Err(CallResult::failed(format!("No response after auth: {}", e)))),
         }
     }
@@ -388,4 +785,50 @@ mod tests {
         assert_eq!(SipErrorCategory::from_status(404), SipErrorCategory::NotFound);
         assert_eq!(SipErrorCategory::from_status(486), SipErrorCategory::Busy);
     }
+
+    #[test]
+    fn test_early_media_becomes_audio_when_not_connected() {
+        let result = CallResult::failed_with_status(404, "404: Number not found".to_string())
+            .with_early_media(183, vec![0.1; 1600], true);
+
+        assert!(!result.connected);
+        assert!(result.has_early_media());
+        assert!(result.audio_received);
+        assert_eq!(result.audio_samples.len(), 1600);
+        assert_eq!(result.sip_status, Some(404));
+        assert_eq!(result.early_media_status, Some(183));
+    }
+
+    #[test]
+    fn test_early_media_keeps_answered_audio() {
+        let result = CallResult::success(vec![0.2; 3200], true)
+            .with_early_media(183, vec![0.1; 1600], false);
+
+        assert!(result.connected);
+        assert!(result.audio_received);
+        assert_eq!(result.audio_samples.len(), 3200);
+        assert_eq!(result.early_media_samples.len(), 1600);
+        assert_eq!(result.sip_status, Some(200));
+    }
+
+    #[test]
+    fn test_success_with_captures() {
+        // 1000ms and 600ms at 16kHz
+        let result = CallResult::success_with_captures(vec![vec![0.1; 16000], vec![0.2; 9600]], 500);
+        assert!(result.connected);
+        assert!(result.audio_received);
+        assert_eq!(result.captures.len(), 2);
+        assert_eq!(result.audio_samples.len(), 25600);
+
+        // One silent step fails the whole script
+        let result = CallResult::success_with_captures(vec![vec![0.1; 16000], Vec::new()], 500);
+        assert!(!result.audio_received);
+    }
+
+    #[test]
+    fn test_no_early_media_by_default() {
+        let result = CallResult::failed("No response from server".to_string());
+        assert!(!result.has_early_media());
+        assert_eq!(result.early_media_status, None);
+    }
 }
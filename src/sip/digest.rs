@@ -161,6 +161,67 @@ impl DigestResponse {
 
         format!("Digest {}", parts.join(", "))
     }
+
+    /// Parse an Authorization header value (server side)
+    pub fn parse(header_value: &str) -> Option<Self> {
+        let params_str = header_value.strip_prefix("Digest ").unwrap_or(header_value);
+        let params = parse_params(params_str);
+
+        let algorithm = match params.get("algorithm").map(|s| s.to_uppercase()).as_deref() {
+            Some("MD5") | None => DigestAlgorithm::Md5,
+            Some("MD5-SESS") => DigestAlgorithm::Md5Sess,
+            Some(_) => return None,
+        };
+
+        Some(DigestResponse {
+            username: params.get("username")?.clone(),
+            realm: params.get("realm")?.clone(),
+            nonce: params.get("nonce")?.clone(),
+            uri: params.get("uri")?.clone(),
+            response: params.get("response")?.clone(),
+            algorithm,
+            qop: params.get("qop").cloned(),
+            cnonce: params.get("cnonce").cloned(),
+            nc: params.get("nc").cloned(),
+            opaque: params.get("opaque").cloned(),
+        })
+    }
+
+    /// Check the response hash against the password (server side)
+    pub fn verify(&self, password: &str, method: &str) -> bool {
+        let challenge = DigestChallenge {
+            realm: self.realm.clone(),
+            nonce: self.nonce.clone(),
+            algorithm: self.algorithm,
+            qop: self.qop.clone(),
+            opaque: self.opaque.clone(),
+            stale: false,
+        };
+        let expected = compute_response(
+            &challenge,
+            &self.username,
+            password,
+            method,
+            &self.uri,
+            self.cnonce.as_deref(),
+            self.nc.as_deref(),
+        );
+        expected == self.response
+    }
+}
+
+/// Find and extract the Authorization or Proxy-Authorization header from a
+/// SIP request
+pub fn extract_authorization_header(request: &str) -> Option<String> {
+    for line in request.lines() {
+        let lower = line.to_lowercase();
+        if lower.starts_with("authorization:") || lower.starts_with("proxy-authorization:") {
+            if let Some(colon_pos) = line.find(':') {
+                return Some(line[colon_pos + 1..].trim().to_string());
+            }
+        }
+    }
+    None
 }
 
 /// Compute the digest response hash per RFC 2617
@@ -428,6 +489,28 @@ mod tests {
         assert!(header.contains("response=\"abc123\""));
     }
 
+    #[test]
+    fn test_parse_and_verify_round_trip() {
+        for header in [r#"Digest realm="pbx", nonce="n1""#, r#"Digest realm="pbx", nonce="n1", qop="auth""#] {
+            let challenge = DigestChallenge::parse(header).unwrap();
+            let computed = DigestResponse::compute(&challenge, "alice", "secret", "INVITE", "sip:100@pbx");
+
+            let parsed = DigestResponse::parse(&computed.to_header()).unwrap();
+            assert_eq!(parsed.username, "alice");
+            assert_eq!(parsed.uri, "sip:100@pbx");
+            assert!(parsed.verify("secret", "INVITE"));
+            assert!(!parsed.verify("wrong", "INVITE"));
+            assert!(!parsed.verify("secret", "REGISTER"));
+        }
+    }
+
+    #[test]
+    fn test_extract_authorization_header() {
+        let request = "INVITE sip:100@pbx SIP/2.0\r\nAuthorization: Digest username=\"a\"\r\n\r\n";
+        assert_eq!(extract_authorization_header(request), Some("Digest username=\"a\"".to_string()));
+        assert_eq!(extract_authorization_header("INVITE sip:100@pbx SIP/2.0\r\n\r\n"), None);
+    }
+
     #[test]
     fn test_to_header_with_qop() {
         let response = DigestResponse {
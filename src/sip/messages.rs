@@ -4,6 +4,8 @@
 use rand::Rng;
 use std::net::SocketAddr;
 
+use crate::rtp::dtmf::TELEPHONE_EVENT_PT;
+
 /// Generate a random Call-ID
 pub fn generate_call_id(local_host: &str) -> String {
     let random: u64 = rand::thread_rng().gen();
@@ -26,7 +28,8 @@ pub fn generate_branch() -> String {
 /// Build SIP INVITE request
 ///
 /// If `external_addr` is provided (from STUN), use it for Contact header and SDP.
-/// Otherwise, use `local_addr`.
+/// Otherwise, use `local_addr`. `rtcp_port` is the separate RTCP socket's
+/// port, if one was bound.
 pub fn build_invite(
     target_uri: &str,
     from_uri: &str,
@@ -36,6 +39,7 @@ pub fn build_invite(
     cseq: u32,
     local_addr: SocketAddr,
     rtp_port: u16,
+    rtcp_port: Option<u16>,
     external_rtp_addr: Option<SocketAddr>,
 ) -> String {
     build_invite_internal(
@@ -47,6 +51,7 @@ pub fn build_invite(
         cseq,
         local_addr,
         rtp_port,
+        rtcp_port,
         external_rtp_addr,
         None,
     )
@@ -62,6 +67,7 @@ pub fn build_invite_with_auth(
     cseq: u32,
     local_addr: SocketAddr,
     rtp_port: u16,
+    rtcp_port: Option<u16>,
     external_rtp_addr: Option<SocketAddr>,
     authorization: &str,
 ) -> String {
@@ -74,6 +80,7 @@ pub fn build_invite_with_auth(
         cseq,
         local_addr,
         rtp_port,
+        rtcp_port,
         external_rtp_addr,
         Some(authorization),
     )
@@ -89,6 +96,7 @@ fn build_invite_internal(
     cseq: u32,
     local_addr: SocketAddr,
     rtp_port: u16,
+    rtcp_port: Option<u16>,
     external_rtp_addr: Option<SocketAddr>,
     authorization: Option<&str>,
 ) -> String {
@@ -108,8 +116,17 @@ fn build_invite_internal(
         None => local_ip.to_string(),
     };
 
+    // RTCP goes to the separate socket when there is one and the SDP port is
+    // ours. Without one (no free port pair), or behind a NAT mapping known
+    // only for RTP, the far end must not assume the port above: it sends
+    // RTCP to the RTP port, where it is told apart as with rtcp-mux.
+    let sdp_rtcp_port = match rtcp_port {
+        Some(port) if sdp_rtp_port == rtp_port => port,
+        _ => sdp_rtp_port,
+    };
+
     // SDP body for audio session
-    let sdp = build_sdp(&sdp_ip, sdp_rtp_port);
+    let sdp = build_sdp(&sdp_ip, sdp_rtp_port, sdp_rtcp_port);
     let content_length = sdp.len();
 
     // Build Authorization header if present
@@ -152,8 +169,10 @@ fn build_invite_internal(
 }
 
 /// Build SDP body for audio session
-/// We offer G.711 u-law (PCMU) and A-law (PCMA)
-fn build_sdp(local_ip: &str, rtp_port: u16) -> String {
+/// We offer G.711 u-law (PCMU) and A-law (PCMA), plus RFC 4733
+/// telephone-event so IVR scripts can send DTMF digits. RTCP is offered
+/// muxed, with the RFC 3605 `a=rtcp` port for a far end that won't mux.
+fn build_sdp(local_ip: &str, rtp_port: u16, rtcp_port: u16) -> String {
     let session_id: u64 = rand::thread_rng().gen();
     let session_version: u64 = rand::thread_rng().gen();
 
@@ -163,12 +182,24 @@ fn build_sdp(local_ip: &str, rtp_port: u16) -> String {
          s=Phone Check Session\r\n\
          c=IN IP4 {}\r\n\
          t=0 0\r\n\
-         m=audio {} RTP/AVP 0 8\r\n\
+         m=audio {} RTP/AVP 0 8 {}\r\n\
          a=rtpmap:0 PCMU/8000\r\n\
          a=rtpmap:8 PCMA/8000\r\n\
+         a=rtpmap:{} telephone-event/8000\r\n\
+         a=fmtp:{} 0-16\r\n\
          a=ptime:20\r\n\
-         a=recvonly\r\n",
-        session_id, session_version, local_ip, local_ip, rtp_port
+         a=rtcp:{}\r\n\
+         a=rtcp-mux\r\n\
+         a=sendrecv\r\n",
+        session_id,
+        session_version,
+        local_ip,
+        local_ip,
+        rtp_port,
+        TELEPHONE_EVENT_PT,
+        TELEPHONE_EVENT_PT,
+        TELEPHONE_EVENT_PT,
+        rtcp_port
     )
 }
 
@@ -260,6 +291,48 @@ pub fn build_bye(
     )
 }
 
+/// Build CANCEL request for a pending INVITE (RFC 3261 Section 9.1)
+///
+/// Request-URI, Call-ID, From, To and CSeq number must match the INVITE,
+/// and the Via branch must be the INVITE's so the server matches it to the
+/// same transaction. The To header carries no tag since no dialog exists.
+pub fn build_cancel(
+    target_uri: &str,
+    from_uri: &str,
+    from_display: &str,
+    to_uri: &str,
+    call_id: &str,
+    from_tag: &str,
+    cseq: u32,
+    local_addr: SocketAddr,
+    via_branch: &str,
+) -> String {
+    let local_ip = local_addr.ip();
+    let local_port = local_addr.port();
+
+    format!(
+        "CANCEL {} SIP/2.0\r\n\
+         Via: SIP/2.0/UDP {}:{};branch={};rport\r\n\
+         Max-Forwards: 70\r\n\
+         From: \"{}\" <{}>;tag={}\r\n\
+         To: <{}>\r\n\
+         Call-ID: {}\r\n\
+         CSeq: {} CANCEL\r\n\
+         Content-Length: 0\r\n\
+         \r\n",
+        target_uri,
+        local_ip,
+        local_port,
+        via_branch,
+        from_display,
+        from_uri,
+        from_tag,
+        to_uri,
+        call_id,
+        cseq
+    )
+}
+
 /// Build SIP REGISTER request
 ///
 /// Registers the client with the SIP server, authorizing our IP
@@ -385,6 +458,17 @@ pub fn extract_via_branch(response: &str) -> Option<String> {
     None
 }
 
+/// Extract the method from the CSeq header (e.g. "INVITE" or "CANCEL")
+/// Used to tell apart responses to different requests in the same transaction
+pub fn extract_cseq_method(response: &str) -> Option<String> {
+    for line in response.lines() {
+        if line.to_lowercase().starts_with("cseq:") {
+            return line.get(5..)?.split_whitespace().nth(1).map(|m| m.to_uppercase());
+        }
+    }
+    None
+}
+
 /// Extract the `received` parameter from Via header.
 /// This is our public IP as seen by the SIP server — more reliable than STUN
 /// under CGNAT where different destinations see different public IPs.
@@ -446,6 +530,57 @@ pub fn extract_rtp_address(response: &str) -> Option<std::net::SocketAddr> {
     }
 }
 
+/// Where to send RTCP per the SDP answer, and whether it is muxed with RTP
+///
+/// The RTP address itself when the answer accepted rtcp-mux (RFC 5761),
+/// else the `a=rtcp` attribute (RFC 3605) if present, else the port above
+/// the RTP port.
+pub fn extract_rtcp_address(response: &str) -> Option<(std::net::SocketAddr, bool)> {
+    let rtp = extract_rtp_address(response)?;
+    let sdp_start = response.find("\r\n\r\n").map(|i| i + 4)
+        .or_else(|| response.find("\n\n").map(|i| i + 2))?;
+    let sdp = &response[sdp_start..];
+
+    let mut rtcp = None;
+    for line in sdp.lines() {
+        let line = line.trim();
+        if line == "a=rtcp-mux" {
+            return Some((rtp, true));
+        }
+        // a=rtcp:<port> [IN IP4 <address>]
+        if let Some(rest) = line.strip_prefix("a=rtcp:") {
+            let mut fields = rest.split_whitespace();
+            if let Some(Ok(port)) = fields.next().map(str::parse::<u16>) {
+                let ip = fields.nth(2).and_then(|a| a.parse().ok()).unwrap_or(rtp.ip());
+                rtcp = Some(std::net::SocketAddr::new(ip, port));
+            }
+        }
+    }
+
+    let fallback = std::net::SocketAddr::new(rtp.ip(), rtp.port().checked_add(1)?);
+    Some((rtcp.unwrap_or(fallback), false))
+}
+
+/// Extract the telephone-event payload type from the SDP answer
+/// Returns None if the answer did not accept RFC 4733 events
+pub fn extract_telephone_event_payload_type(response: &str) -> Option<u8> {
+    let sdp_start = response.find("\r\n\r\n").map(|i| i + 4)
+        .or_else(|| response.find("\n\n").map(|i| i + 2))?;
+    let sdp = &response[sdp_start..];
+
+    for line in sdp.lines() {
+        let line = line.trim();
+        if let Some(rest) = line.strip_prefix("a=rtpmap:") {
+            if let Some((pt, encoding)) = rest.split_once(' ') {
+                if encoding.to_ascii_lowercase().starts_with("telephone-event/") {
+                    return pt.trim().parse().ok();
+                }
+            }
+        }
+    }
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -585,6 +720,7 @@ mod tests {
             "192.168.1.1:5060".parse().unwrap(),
             10000,
             None,
+            None,
         );
 
         assert!(invite.starts_with("INVITE sip:1234@example.com SIP/2.0\r\n"));
@@ -598,6 +734,72 @@ mod tests {
         assert!(invite.contains("a=rtpmap:0 PCMU/8000"));
     }
 
+    #[test]
+    fn test_build_invite_offers_telephone_event() {
+        let invite = build_invite(
+            "sip:1234@example.com",
+            "sip:caller@example.com",
+            "Caller",
+            "callid123@host",
+            "fromtag",
+            1,
+            "192.168.1.1:5060".parse().unwrap(),
+            10000,
+            None,
+            None,
+        );
+
+        assert!(invite.contains("m=audio 10000 RTP/AVP 0 8 101\r\n"));
+        assert!(invite.contains("a=rtpmap:101 telephone-event/8000"));
+        assert!(invite.contains("a=fmtp:101 0-16"));
+        assert!(invite.contains("a=sendrecv"));
+        assert!(!invite.contains("a=recvonly"));
+        assert!(invite.contains("a=rtcp-mux\r\n"));
+        // No RTCP socket of our own: RTCP comes to the RTP port
+        assert!(invite.contains("a=rtcp:10000\r\n"));
+    }
+
+    #[test]
+    fn test_extract_rtcp_address() {
+        let answer = |attributes: &str| {
+            format!(
+                "SIP/2.0 200 OK\r\n\r\nv=0\r\nc=IN IP4 192.168.1.100\r\nm=audio 16384 RTP/AVP 0\r\n{}",
+                attributes
+            )
+        };
+        let at = |addr: &str| addr.parse::<std::net::SocketAddr>().unwrap();
+
+        assert_eq!(extract_rtcp_address(&answer("a=rtcp-mux\r\n")), Some((at("192.168.1.100:16384"), true)));
+        assert_eq!(extract_rtcp_address(&answer("")), Some((at("192.168.1.100:16385"), false)));
+        assert_eq!(extract_rtcp_address(&answer("a=rtcp:20001\r\n")), Some((at("192.168.1.100:20001"), false)));
+        assert_eq!(
+            extract_rtcp_address(&answer("a=rtcp:20001 IN IP4 10.0.0.5\r\n")),
+            Some((at("10.0.0.5:20001"), false))
+        );
+        assert_eq!(extract_rtcp_address("SIP/2.0 200 OK\r\n\r\n"), None);
+    }
+
+    #[test]
+    fn test_extract_telephone_event_payload_type() {
+        let answer = "SIP/2.0 200 OK\r\n\
+                      Content-Type: application/sdp\r\n\
+                      \r\n\
+                      v=0\r\n\
+                      c=IN IP4 192.168.1.100\r\n\
+                      m=audio 16384 RTP/AVP 0 96\r\n\
+                      a=rtpmap:0 PCMU/8000\r\n\
+                      a=rtpmap:96 TELEPHONE-EVENT/8000\r\n";
+        assert_eq!(extract_telephone_event_payload_type(answer), Some(96));
+
+        let no_events = "SIP/2.0 200 OK\r\n\
+                         \r\n\
+                         v=0\r\n\
+                         m=audio 16384 RTP/AVP 0\r\n\
+                         a=rtpmap:0 PCMU/8000\r\n";
+        assert_eq!(extract_telephone_event_payload_type(no_events), None);
+        assert_eq!(extract_telephone_event_payload_type("SIP/2.0 200 OK\r\n"), None);
+    }
+
     #[test]
     fn test_build_invite_with_external_addr() {
         let invite = build_invite(
@@ -609,14 +811,32 @@ mod tests {
             1,
             "192.168.1.1:5060".parse().unwrap(),
             10000,
+            Some(10001),
             Some("203.0.113.50:10000".parse().unwrap()),
         );
 
         // SDP should contain the external IP, not the local IP
         assert!(invite.contains("c=IN IP4 203.0.113.50"));
         assert!(invite.contains("m=audio 10000"));
+        assert!(invite.contains("a=rtcp:10001\r\n"));
         // Local IP should still be in Via header
         assert!(invite.contains("Via: SIP/2.0/UDP 192.168.1.1:5060"));
+
+        // The NAT moved the RTP port, so where it put RTCP is unknown
+        let invite = build_invite(
+            "sip:1234@example.com",
+            "sip:caller@example.com",
+            "Caller",
+            "callid123@host",
+            "fromtag",
+            1,
+            "192.168.1.1:5060".parse().unwrap(),
+            10000,
+            Some(10001),
+            Some("203.0.113.50:40712".parse().unwrap()),
+        );
+        assert!(invite.contains("m=audio 40712"));
+        assert!(invite.contains("a=rtcp:40712\r\n"));
     }
 
     #[test]
@@ -632,6 +852,7 @@ mod tests {
             "192.168.1.1:5060".parse().unwrap(),
             10000,
             None,
+            None,
             auth_header,
         );
 
@@ -695,6 +916,43 @@ mod tests {
         assert!(bye.contains("Content-Length: 0"));
     }
 
+    #[test]
+    fn test_build_cancel_matches_invite_transaction() {
+        let cancel = build_cancel(
+            "sip:1234@example.com",
+            "sip:caller@example.com",
+            "Caller",
+            "sip:1234@example.com",
+            "callid123@host",
+            "fromtag",
+            3,
+            "192.168.1.1:5060".parse().unwrap(),
+            "z9hG4bKinvite",
+        );
+
+        assert!(cancel.starts_with("CANCEL sip:1234@example.com SIP/2.0\r\n"));
+        assert_eq!(extract_via_branch(&cancel), Some("z9hG4bKinvite".to_string()));
+        assert!(cancel.contains("To: <sip:1234@example.com>\r\n"));
+        assert!(cancel.contains("tag=fromtag"));
+        assert!(cancel.contains("Call-ID: callid123@host"));
+        assert!(cancel.contains("CSeq: 3 CANCEL"));
+        assert!(cancel.contains("Content-Length: 0"));
+    }
+
+    #[test]
+    fn test_extract_cseq_method() {
+        assert_eq!(
+            extract_cseq_method("SIP/2.0 200 OK\r\nCSeq: 1 CANCEL\r\n\r\n"),
+            Some("CANCEL".to_string())
+        );
+        assert_eq!(
+            extract_cseq_method("SIP/2.0 487 Request Terminated\r\ncseq: 1 invite\r\n\r\n"),
+            Some("INVITE".to_string())
+        );
+        assert_eq!(extract_cseq_method("SIP/2.0 200 OK\r\n\r\n"), None);
+        assert_eq!(extract_cseq_method("SIP/2.0 200 OK\r\nCSeq: 1\r\n"), None);
+    }
+
     #[test]
     fn test_build_register_contains_required_headers() {
         let register = build_register(
@@ -841,6 +1099,11 @@ mod proptests {
             let _ = extract_via_branch(&input);
         }
 
+        #[test]
+        fn extract_cseq_method_never_panics(input in ".*") {
+            let _ = extract_cseq_method(&input);
+        }
+
         /// Valid SIP status lines are parsed correctly
         #[test]
         fn valid_status_codes_parsed(code in 100u16..700u16) {
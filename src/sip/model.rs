@@ -1,5 +1,7 @@
 /// SIP Call State Machine - Stateright Model
-/// Formally verifies the call flow: INVITE → 200 OK → ACK → RTP → BYE
+/// Formally verifies the call flow: INVITE → 200 OK → ACK → RTP → BYE,
+/// and INVITE → 1xx → CANCEL → 487 → ACK for calls abandoned before answer
+/// (never before a provisional response, RFC 3261 Section 9.1)
 ///
 /// Run with: cargo test --release sip_model -- --nocapture
 
@@ -15,6 +17,8 @@ pub enum CallState {
     Authenticating,
     /// Sent authenticated INVITE, waiting for response
     InvitingWithAuth { retries: u8 },
+    /// Sent CANCEL for an unanswered INVITE, waiting for 487
+    Cancelling,
     Established,
     Terminating,
     Terminated,
@@ -34,6 +38,12 @@ pub enum SipAction {
     InviteTimeout,
     /// Send ACK for 401/407 then retry with auth
     SendAuthenticatedInvite,
+    /// Ring timeout or shutdown before a final response: send CANCEL
+    SendCancel,
+    /// 487 Request Terminated for the cancelled INVITE (we ACK it)
+    Receive487,
+    /// No final response to the cancelled INVITE
+    CancelTimeout,
     ReceiveRtp,
     AudioComplete,
     SendBye,
@@ -52,6 +62,8 @@ pub struct CallModel {
     pub has_password: bool,
     /// Whether we've already attempted authentication
     pub auth_attempted: bool,
+    /// Whether CANCEL was sent for the INVITE
+    pub cancel_sent: bool,
 }
 
 /// Configuration for the model checker
@@ -84,6 +96,7 @@ impl Model for SipCallChecker {
                 bye_sent: false,
                 has_password: true,
                 auth_attempted: false,
+                cancel_sent: false,
             },
             CallModel {
                 state: CallState::Idle,
@@ -92,6 +105,7 @@ impl Model for SipCallChecker {
                 bye_sent: false,
                 has_password: false,
                 auth_attempted: false,
+                cancel_sent: false,
             },
         ]
     }
@@ -114,6 +128,7 @@ impl Model for SipCallChecker {
                 if *retries < self.max_retries {
                     actions.push(SipAction::InviteTimeout);
                 }
+                // No CANCEL before a provisional response (RFC 3261 9.1)
             }
 
             CallState::Proceeding => {
@@ -122,6 +137,14 @@ impl Model for SipCallChecker {
                 actions.push(SipAction::Receive401Unauthorized);
                 actions.push(SipAction::Receive4xx);
                 actions.push(SipAction::Receive5xx);
+                actions.push(SipAction::SendCancel);
+            }
+
+            CallState::Cancelling => {
+                actions.push(SipAction::Receive487);
+                // Callee answered before the CANCEL was processed
+                actions.push(SipAction::Receive200Ok);
+                actions.push(SipAction::CancelTimeout);
             }
 
             CallState::Authenticating => {
@@ -144,6 +167,7 @@ impl Model for SipCallChecker {
                 if *retries < self.max_retries {
                     actions.push(SipAction::InviteTimeout);
                 }
+                // No CANCEL before a provisional response (RFC 3261 9.1)
             }
 
             CallState::Established => {
@@ -205,7 +229,11 @@ impl Model for SipCallChecker {
             }
 
             SipAction::Receive200Ok => {
-                if matches!(
+                if state.state == CallState::Cancelling {
+                    // Answered despite CANCEL: ACK the 2xx, then hang up with BYE
+                    next.state = CallState::Terminating;
+                    next.bye_sent = true;
+                } else if matches!(
                     state.state,
                     CallState::Inviting { .. }
                         | CallState::InvitingWithAuth { .. }
@@ -282,6 +310,19 @@ impl Model for SipCallChecker {
                 }
             }
 
+            SipAction::SendCancel => {
+                if state.state == CallState::Proceeding {
+                    next.state = CallState::Cancelling;
+                    next.cancel_sent = true;
+                }
+            }
+
+            SipAction::Receive487 | SipAction::CancelTimeout => {
+                if state.state == CallState::Cancelling {
+                    next.state = CallState::Failed;
+                }
+            }
+
             SipAction::ReceiveRtp => {
                 if state.state == CallState::Established && state.rtp_active {
                     next.rtp_packets = state.rtp_packets.saturating_add(1);
@@ -330,6 +371,14 @@ impl Model for SipCallChecker {
             Property::always("bye_before_terminating", |_, state: &CallModel| {
                 state.state != CallState::Terminating || state.bye_sent
             }),
+            // Safety: CANCEL only targets unanswered INVITEs, never an established dialog
+            Property::always("cancel_never_establishes", |_, state: &CallModel| {
+                !state.cancel_sent || state.state != CallState::Established
+            }),
+            // Safety: Cancelling implies CANCEL was actually sent
+            Property::always("cancelling_after_cancel", |_, state: &CallModel| {
+                state.state != CallState::Cancelling || state.cancel_sent
+            }),
             // Safety: Cannot have RTP packets without having been established
             Property::always("no_orphan_rtp", |_, state: &CallModel| {
                 state.rtp_packets == 0
@@ -360,6 +409,7 @@ mod tests {
             bye_sent: false,
             has_password: true,
             auth_attempted: false,
+            cancel_sent: false,
         }
     }
 
@@ -372,6 +422,7 @@ mod tests {
             bye_sent: false,
             has_password: false,
             auth_attempted: false,
+            cancel_sent: false,
         }
     }
 
@@ -574,4 +625,58 @@ mod tests {
             .unwrap();
         assert_eq!(state.state, CallState::Failed);
     }
+
+    #[test]
+    fn sip_model_cancel_path() {
+        // Idle → Inviting → Proceeding → Cancelling → (487) → Failed
+        let model = SipCallChecker::default();
+
+        let mut state = init_with_password();
+        state = model.next_state(&state, SipAction::SendInvite).unwrap();
+        state = model.next_state(&state, SipAction::Receive180Ringing).unwrap();
+        assert_eq!(state.state, CallState::Proceeding);
+
+        state = model.next_state(&state, SipAction::SendCancel).unwrap();
+        assert_eq!(state.state, CallState::Cancelling);
+        assert!(state.cancel_sent);
+        assert!(!state.bye_sent, "BYE must not be sent outside a dialog");
+
+        state = model.next_state(&state, SipAction::Receive487).unwrap();
+        assert_eq!(state.state, CallState::Failed);
+        assert!(!state.rtp_active);
+    }
+
+    #[test]
+    fn sip_model_no_cancel_before_provisional() {
+        let model = SipCallChecker::default();
+
+        let mut state = init_with_password();
+        state = model.next_state(&state, SipAction::SendInvite).unwrap();
+        let mut actions = Vec::new();
+        model.actions(&state, &mut actions);
+        assert!(!actions.contains(&SipAction::SendCancel));
+
+        let unchanged = model.next_state(&state, SipAction::SendCancel).unwrap();
+        assert_eq!(unchanged.state, state.state);
+        assert!(!unchanged.cancel_sent);
+    }
+
+    #[test]
+    fn sip_model_cancel_answer_race() {
+        // 200 OK crossing the CANCEL must be ACKed and torn down with BYE
+        let model = SipCallChecker::default();
+
+        let mut state = init_with_password();
+        state = model.next_state(&state, SipAction::SendInvite).unwrap();
+        state = model.next_state(&state, SipAction::Receive100Trying).unwrap();
+        state = model.next_state(&state, SipAction::SendCancel).unwrap();
+        state = model.next_state(&state, SipAction::Receive200Ok).unwrap();
+
+        assert_eq!(state.state, CallState::Terminating);
+        assert!(state.bye_sent);
+        assert!(!state.rtp_active, "cancelled call must not start media");
+
+        state = model.next_state(&state, SipAction::ByeAcked).unwrap();
+        assert_eq!(state.state, CallState::Terminated);
+    }
 }
@@ -11,11 +11,15 @@ use std::net::SocketAddr;
 use std::time::Duration;
 use tokio::net::UdpSocket;
 use tokio::time::timeout;
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, trace, warn};
 
 /// RFC 3261 Timer T1 - RTT estimate (500ms default)
 pub const T1: Duration = Duration::from_millis(500);
 
+/// RFC 3261 Timer T2 - maximum retransmit interval for non-INVITE requests
+pub const T2: Duration = Duration::from_secs(4);
+
 /// RFC 3261 Timer B - INVITE transaction timeout (64 * T1 = 32s)
 pub const TIMER_B: Duration = Duration::from_secs(32);
 
@@ -192,19 +196,164 @@ impl SipTransport {
     /// Combines send_invite_with_retransmit with provisional response handling.
     /// Returns the final (2xx-6xx) response.
     pub async fn send_invite_await_final(&self, invite: &str) -> Result<String> {
+        let never = CancellationToken::new();
+        match self.send_invite_await_response(invite, false, TIMER_B, &never).await? {
+            InviteResponse::Final(response) | InviteResponse::EarlyMedia { response, .. } => {
+                Ok(response)
+            }
+            InviteResponse::Abandoned { .. } | InviteResponse::AbandonedFinal(_) => {
+                anyhow::bail!("INVITE transaction timeout (Timer B = {:?})", TIMER_B)
+            }
+        }
+    }
+
+    /// Send INVITE and wait for a final response or early media
+    ///
+    /// Same transaction handling as `send_invite_await_final`, but a 18x
+    /// response carrying SDP (RFC 3960 early media, usually 183 Session
+    /// Progress) is returned immediately so the caller can start receiving
+    /// RTP before the call is answered. Use `await_final_response` afterwards
+    /// to collect the final response.
+    ///
+    /// Once the INVITE is proceeding, `ring_timeout` (measured from the
+    /// initial send) replaces Timer B. If it expires or `cancel` fires, the
+    /// INVITE is reported as `Abandoned` and the caller must CANCEL it.
+    /// Before any provisional response a CANCEL is not allowed (RFC 3261
+    /// Section 9.1: it could overtake the INVITE), so the transaction runs on
+    /// until one arrives, a final response arrives (`AbandonedFinal`) or
+    /// Timer B ends it (`Abandoned` without `proceeding`, nothing to cancel).
+    pub async fn send_invite_await_early_or_final(
+        &self,
+        invite: &str,
+        ring_timeout: Duration,
+        cancel: &CancellationToken,
+    ) -> Result<InviteResponse> {
+        self.send_invite_await_response(invite, true, ring_timeout, cancel).await
+    }
+
+    /// Wait for the final response of an INVITE that is already proceeding
+    ///
+    /// Further provisional responses are ignored. Returns `None` if no final
+    /// response arrives within `wait`.
+    pub async fn await_final_response(&self, wait: Duration) -> Result<Option<String>> {
+        let deadline = tokio::time::Instant::now() + wait;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            if remaining.is_zero() {
+                return Ok(None);
+            }
+
+            match self.receive(remaining).await {
+                Ok(response) => {
+                    if let Some(code) = super::messages::parse_status_code(&response) {
+                        if code >= 200 {
+                            debug!("Received final response {} after early media", code);
+                            return Ok(Some(response));
+                        }
+                        debug!("Received provisional response {} during early media", code);
+                    }
+                }
+                Err(e) => {
+                    let err_str = e.to_string().to_lowercase();
+                    if !err_str.contains("timeout") {
+                        return Err(e);
+                    }
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    /// Send CANCEL for a pending INVITE and wait for the INVITE's final response
+    ///
+    /// Implements the client side of the RFC 3261 Section 9.1 handshake:
+    /// the CANCEL is retransmitted per Timer E (T1 doubling, capped at T2)
+    /// until the server answers it with 200 OK, then we wait for the INVITE
+    /// to complete, normally with 487 Request Terminated. A 2xx can still
+    /// arrive if the callee answered before the CANCEL was processed.
+    ///
+    /// Returns the INVITE's final response, or `None` if none arrived in `wait`.
+    pub async fn send_cancel(&self, cancel: &str, wait: Duration) -> Result<Option<String>> {
+        let deadline = tokio::time::Instant::now() + wait;
+        let mut timer_e = T1;
+        let mut next_retransmit = tokio::time::Instant::now() + timer_e;
+        let mut cancel_answered = false;
+
+        self.send(cancel).await?;
+        debug!("Sent CANCEL, Timer E = {:?}", timer_e);
+
+        loop {
+            let now = tokio::time::Instant::now();
+            if now >= deadline {
+                if !cancel_answered {
+                    warn!("No response to CANCEL within {:?}", wait);
+                }
+                return Ok(None);
+            }
+
+            let wake = if cancel_answered { deadline } else { next_retransmit.min(deadline) };
+
+            match self.receive(wake - now).await {
+                Ok(response) => {
+                    let code = super::messages::parse_status_code(&response).unwrap_or(0);
+                    let method = super::messages::extract_cseq_method(&response);
+                    match method.as_deref() {
+                        Some("CANCEL") if code >= 200 => {
+                            debug!("CANCEL answered with {}", code);
+                            cancel_answered = true;
+                        }
+                        Some("INVITE") if code >= 200 => {
+                            debug!("INVITE completed with {} after CANCEL", code);
+                            return Ok(Some(response));
+                        }
+                        _ => trace!("Ignoring {} response while cancelling", code),
+                    }
+                }
+                Err(e) => {
+                    let err_str = e.to_string().to_lowercase();
+                    if !err_str.contains("timeout") {
+                        return Err(e);
+                    }
+
+                    if !cancel_answered && tokio::time::Instant::now() >= next_retransmit {
+                        self.send(cancel).await?;
+                        timer_e = timer_e.saturating_mul(2).min(T2);
+                        next_retransmit = tokio::time::Instant::now() + timer_e;
+                        debug!("Retransmitted CANCEL, Timer E = {:?}", timer_e);
+                    }
+                }
+            }
+        }
+    }
+
+    async fn send_invite_await_response(
+        &self,
+        invite: &str,
+        return_early_media: bool,
+        ring_timeout: Duration,
+        cancel: &CancellationToken,
+    ) -> Result<InviteResponse> {
         let transaction_start = tokio::time::Instant::now();
         let mut timer_a = T1;
         let mut retransmit_count = 0u32;
         let mut in_proceeding = false; // True after receiving 1xx
+        let mut abandoned = false; // Given up on before any 1xx arrived
 
         // Send initial INVITE
         self.send(invite).await?;
         debug!("Sent INVITE (initial), Timer A = {:?}", timer_a);
 
         loop {
-            // Check Timer B
             let elapsed = transaction_start.elapsed();
-            if elapsed >= TIMER_B {
+
+            // Timer B only guards the Calling state; once the server has sent
+            // a provisional response the ring timeout decides how long to wait
+            if !in_proceeding && elapsed >= TIMER_B {
+                if abandoned {
+                    debug!("No provisional response to the abandoned INVITE, ending it without CANCEL");
+                    return Ok(InviteResponse::Abandoned { proceeding: false });
+                }
                 anyhow::bail!(
                     "INVITE transaction timeout (Timer B = {:?}) after {} retransmits",
                     TIMER_B,
@@ -212,26 +361,59 @@ impl SipTransport {
                 );
             }
 
-            let remaining = TIMER_B.saturating_sub(elapsed);
-            let wait_time = timer_a.min(remaining);
+            if !abandoned && elapsed >= ring_timeout {
+                debug!("Ring timeout ({:?}) expired, abandoning INVITE", ring_timeout);
+                if in_proceeding {
+                    return Ok(InviteResponse::Abandoned { proceeding: true });
+                }
+                abandoned = true;
+            }
+
+            let ring_remaining = ring_timeout.saturating_sub(elapsed);
+            let wait_time = if in_proceeding {
+                ring_remaining
+            } else if abandoned {
+                timer_a.min(TIMER_B.saturating_sub(elapsed))
+            } else {
+                timer_a.min(TIMER_B.saturating_sub(elapsed)).min(ring_remaining)
+            };
+
+            let received = tokio::select! {
+                r = self.receive(wait_time) => r,
+                _ = cancel.cancelled(), if !abandoned => {
+                    debug!("INVITE abandoned by cancellation");
+                    if in_proceeding {
+                        return Ok(InviteResponse::Abandoned { proceeding: true });
+                    }
+                    abandoned = true;
+                    continue;
+                }
+            };
 
-            match self.receive(wait_time).await {
+            match received {
                 Ok(response) => {
                     if let Some(code) = super::messages::parse_status_code(&response) {
-                        if code >= 200 {
+                        if code >= 200 && abandoned {
+                            debug!("Abandoned INVITE completed with {}", code);
+                            return Ok(InviteResponse::AbandonedFinal(response));
+                        } else if code >= 200 {
                             // Final response
                             debug!(
                                 "Received final response {} after {} retransmits",
                                 code, retransmit_count
                             );
-                            return Ok(response);
+                            return Ok(InviteResponse::Final(response));
+                        } else if abandoned {
+                            // Proceeding at last: the INVITE can be cancelled
+                            debug!("Received provisional response {} to the abandoned INVITE", code);
+                            return Ok(InviteResponse::Abandoned { proceeding: true });
+                        } else if return_early_media && is_early_media(code, &response) {
+                            debug!("Received provisional response {} with early media", code);
+                            return Ok(InviteResponse::EarlyMedia { code, response });
                         } else {
                             // Provisional response - stop retransmitting, wait for final
                             debug!("Received provisional response {}", code);
                             in_proceeding = true;
-                            // After receiving 1xx, we stop retransmitting and just wait
-                            // Reset timer to wait for final response
-                            timer_a = TIMER_B.saturating_sub(transaction_start.elapsed());
                         }
                     }
                 }
@@ -242,11 +424,16 @@ impl SipTransport {
                     }
 
                     // Only retransmit if we haven't received any provisional response
-                    if !in_proceeding {
+                    // and Timer A (rather than the ring timeout) is what expired
+                    if !in_proceeding && (abandoned || transaction_start.elapsed() < ring_timeout) {
                         retransmit_count += 1;
 
-                        // Check Timer B before retransmitting
+                        // Check Timer B before retransmitting (an abandoned
+                        // INVITE just ends, at the top of the loop)
                         if transaction_start.elapsed() >= TIMER_B {
+                            if abandoned {
+                                continue;
+                            }
                             anyhow::bail!(
                                 "INVITE transaction timeout (Timer B = {:?}) after {} retransmits",
                                 TIMER_B,
@@ -272,6 +459,27 @@ impl SipTransport {
     }
 }
 
+/// Response that ends the wait in `send_invite_await_early_or_final`
+#[derive(Debug, Clone, PartialEq)]
+pub enum InviteResponse {
+    /// Final (2xx-6xx) response
+    Final(String),
+    /// 18x provisional response with an SDP media address
+    EarlyMedia { code: u16, response: String },
+    /// Ring timeout expired or the call was cancelled before a final
+    /// response. With `proceeding` the INVITE must be CANCELled; without, it
+    /// never got a provisional response and Timer B ended it
+    Abandoned { proceeding: bool },
+    /// Final response to an INVITE abandoned before any provisional response
+    /// (it must still be ACKed, and a 2xx hung up)
+    AbandonedFinal(String),
+}
+
+/// A 18x carrying an SDP answer means the far end is already sending media
+fn is_early_media(code: u16, response: &str) -> bool {
+    (180..200).contains(&code) && super::messages::extract_rtp_address(response).is_some()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -316,6 +524,221 @@ mod tests {
         let err = result.unwrap_err().to_string();
         assert!(err.contains("Timeout") || err.contains("timeout"));
     }
+
+    const EARLY_MEDIA_183: &str = "SIP/2.0 183 Session Progress\r\n\
+        CSeq: 1 INVITE\r\n\
+        Content-Type: application/sdp\r\n\r\n\
+        v=0\r\n\
+        c=IN IP4 127.0.0.1\r\n\
+        m=audio 40000 RTP/AVP 0\r\n";
+
+    /// Bind a fake server socket and a transport pointed at it
+    async fn transport_pair() -> (SipTransport, UdpSocket) {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let transport = SipTransport::new(server.local_addr().unwrap()).await.unwrap();
+        (transport, server)
+    }
+
+    /// Receive the INVITE on the fake server and reply with each response in turn
+    async fn reply_to_invite(server: UdpSocket, responses: Vec<&'static str>) {
+        let mut buf = [0u8; 4096];
+        let (_, client) = server.recv_from(&mut buf).await.unwrap();
+        for response in responses {
+            server.send_to(response.as_bytes(), client).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_early_media_returned_before_final() {
+        let (transport, server) = transport_pair().await;
+        tokio::spawn(reply_to_invite(server, vec![
+            "SIP/2.0 100 Trying\r\nCSeq: 1 INVITE\r\n\r\n",
+            EARLY_MEDIA_183,
+        ]));
+
+        let response = transport.send_invite_await_early_or_final("INVITE", TIMER_B, &CancellationToken::new()).await.unwrap();
+        match response {
+            InviteResponse::EarlyMedia { code, response } => {
+                assert_eq!(code, 183);
+                assert!(response.contains("m=audio 40000"));
+            }
+            other => panic!("expected early media, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ringing_without_sdp_is_not_early_media() {
+        let (transport, server) = transport_pair().await;
+        tokio::spawn(reply_to_invite(server, vec![
+            "SIP/2.0 180 Ringing\r\nCSeq: 1 INVITE\r\n\r\n",
+            "SIP/2.0 486 Busy Here\r\nCSeq: 1 INVITE\r\n\r\n",
+        ]));
+
+        let response = transport.send_invite_await_early_or_final("INVITE", TIMER_B, &CancellationToken::new()).await.unwrap();
+        assert!(matches!(response, InviteResponse::Final(ref r) if r.starts_with("SIP/2.0 486")));
+    }
+
+    #[tokio::test]
+    async fn test_await_final_skips_early_media() {
+        let (transport, server) = transport_pair().await;
+        tokio::spawn(reply_to_invite(server, vec![
+            EARLY_MEDIA_183,
+            "SIP/2.0 404 Not Found\r\nCSeq: 1 INVITE\r\n\r\n",
+        ]));
+
+        let response = transport.send_invite_await_final("INVITE").await.unwrap();
+        assert_eq!(super::super::messages::parse_status_code(&response), Some(404));
+    }
+
+    #[tokio::test]
+    async fn test_await_final_response_after_early_media() {
+        let (transport, server) = transport_pair().await;
+        tokio::spawn(reply_to_invite(server, vec![
+            EARLY_MEDIA_183,
+            EARLY_MEDIA_183,
+            "SIP/2.0 200 OK\r\nCSeq: 1 INVITE\r\n\r\n",
+        ]));
+
+        let early = transport.send_invite_await_early_or_final("INVITE", TIMER_B, &CancellationToken::new()).await.unwrap();
+        assert!(matches!(early, InviteResponse::EarlyMedia { .. }));
+
+        // The repeated 183 is skipped, the 200 OK ends the wait
+        let response = transport.await_final_response(Duration::from_secs(2)).await.unwrap();
+        assert!(response.unwrap().starts_with("SIP/2.0 200"));
+    }
+
+    #[tokio::test]
+    async fn test_await_final_response_times_out() {
+        let (transport, _server) = transport_pair().await;
+        let response = transport.await_final_response(Duration::from_millis(20)).await.unwrap();
+        assert!(response.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_ring_timeout_abandons_proceeding_invite() {
+        let (transport, server) = transport_pair().await;
+        tokio::spawn(reply_to_invite(server, vec![
+            "SIP/2.0 180 Ringing\r\nCSeq: 1 INVITE\r\n\r\n",
+        ]));
+
+        let response = transport
+            .send_invite_await_early_or_final("INVITE", Duration::from_millis(200), &CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(response, InviteResponse::Abandoned { proceeding: true });
+    }
+
+    #[tokio::test]
+    async fn test_cancellation_waits_for_provisional_before_cancel() {
+        let (transport, server) = transport_pair().await;
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            reply_to_invite(server, vec!["SIP/2.0 100 Trying\r\nCSeq: 1 INVITE\r\n\r\n"]).await;
+        });
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            trigger.cancel();
+        });
+
+        // Cancelled at 50 ms, but only reported once the 100 Trying arrives
+        let started = tokio::time::Instant::now();
+        let response = transport
+            .send_invite_await_early_or_final("INVITE", TIMER_B, &cancel)
+            .await
+            .unwrap();
+        assert_eq!(response, InviteResponse::Abandoned { proceeding: true });
+        assert!(started.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn test_final_response_to_abandoned_invite() {
+        let (transport, server) = transport_pair().await;
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            reply_to_invite(server, vec!["SIP/2.0 486 Busy Here\r\nCSeq: 1 INVITE\r\n\r\n"]).await;
+        });
+
+        let response = transport
+            .send_invite_await_early_or_final("INVITE", Duration::from_millis(50), &CancellationToken::new())
+            .await
+            .unwrap();
+        assert!(matches!(response, InviteResponse::AbandonedFinal(r) if r.starts_with("SIP/2.0 486")));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_abandoned_invite_without_provisional_ends_at_timer_b() {
+        let (transport, server) = transport_pair().await;
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+
+        // Never CANCELled: the INVITE is retransmitted per Timer A until
+        // Timer B ends the transaction
+        let started = tokio::time::Instant::now();
+        let response = transport
+            .send_invite_await_early_or_final("INVITE", TIMER_B, &cancel)
+            .await
+            .unwrap();
+        assert_eq!(response, InviteResponse::Abandoned { proceeding: false });
+        assert!(started.elapsed() >= TIMER_B);
+
+        let mut buf = [0u8; 64];
+        let mut sent = Vec::new();
+        while let Ok((len, _)) = server.try_recv_from(&mut buf) {
+            sent.push(String::from_utf8_lossy(&buf[..len]).into_owned());
+        }
+        assert!(sent.len() > 1 && sent.iter().all(|m| m == "INVITE"), "{:?}", sent);
+    }
+
+    #[tokio::test]
+    async fn test_send_cancel_returns_487() {
+        let (transport, server) = transport_pair().await;
+        tokio::spawn(reply_to_invite(server, vec![
+            "SIP/2.0 200 OK\r\nCSeq: 1 CANCEL\r\n\r\n",
+            "SIP/2.0 487 Request Terminated\r\nCSeq: 1 INVITE\r\n\r\n",
+        ]));
+
+        let response = transport.send_cancel("CANCEL", Duration::from_secs(2)).await.unwrap();
+        assert!(response.unwrap().starts_with("SIP/2.0 487"));
+    }
+
+    #[tokio::test]
+    async fn test_send_cancel_retransmits_until_answered() {
+        let (transport, server) = transport_pair().await;
+        let server_task = tokio::spawn(async move {
+            let mut buf = [0u8; 4096];
+            // Drop the first CANCEL, answer the retransmission
+            server.recv_from(&mut buf).await.unwrap();
+            let (_, client) = server.recv_from(&mut buf).await.unwrap();
+            for response in [
+                "SIP/2.0 200 OK\r\nCSeq: 1 CANCEL\r\n\r\n",
+                "SIP/2.0 487 Request Terminated\r\nCSeq: 1 INVITE\r\n\r\n",
+            ] {
+                server.send_to(response.as_bytes(), client).await.unwrap();
+            }
+        });
+
+        let response = transport.send_cancel("CANCEL", Duration::from_secs(3)).await.unwrap();
+        assert!(response.unwrap().starts_with("SIP/2.0 487"));
+        server_task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_send_cancel_without_answer() {
+        let (transport, _server) = transport_pair().await;
+        let response = transport.send_cancel("CANCEL", Duration::from_millis(50)).await.unwrap();
+        assert!(response.is_none());
+    }
+
+    #[test]
+    fn test_is_early_media() {
+        assert!(is_early_media(183, EARLY_MEDIA_183));
+        assert!(is_early_media(180, &EARLY_MEDIA_183.replace("183 Session Progress", "180 Ringing")));
+        assert!(!is_early_media(180, "SIP/2.0 180 Ringing\r\n\r\n"));
+        assert!(!is_early_media(100, EARLY_MEDIA_183));
+        assert!(!is_early_media(200, EARLY_MEDIA_183));
+    }
 }
 
 #[cfg(test)]
@@ -360,6 +783,33 @@ mod timer_tests {
         assert_eq!(TIMER_B, T1 * 64);
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn test_timer_e_capped_at_t2() {
+        // RFC 3261: Timer E (non-INVITE retransmit, used for CANCEL) doubles up to T2
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let transport = SipTransport::new(server.local_addr().unwrap()).await.unwrap();
+        let started = tokio::time::Instant::now();
+        // The server never answers; note when each CANCEL arrives
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 64];
+            while let Ok((len, _)) = server.recv_from(&mut buf).await {
+                assert_eq!(&buf[..len], b"CANCEL");
+                let _ = tx.send(started.elapsed().as_millis() as u64);
+            }
+        });
+
+        let response = transport.send_cancel("CANCEL", Duration::from_secs(12)).await.unwrap();
+        assert!(response.is_none());
+
+        let mut arrivals = Vec::new();
+        while let Ok(ms) = rx.try_recv() {
+            arrivals.push(ms);
+        }
+        let intervals: Vec<u64> = arrivals.windows(2).map(|w| w[1] - w[0]).collect();
+        assert_eq!(intervals, vec![500, 1000, 2000, 4000, 4000], "sent at {:?}", arrivals);
+    }
+
     #[test]
     fn test_exponential_backoff_sequence() {
         // Verify the Timer A doubling sequence
@@ -1,19 +1,72 @@
 /// Speech recognition and audio matching
 ///
 /// Uses Whisper for transcription (logging/debugging) and Wav2Vec2 embeddings
-/// for semantic audio similarity matching.
+/// for semantic audio similarity matching, or one of the other matchers in
+/// [`crate::matcher`].
 ///
 /// Both Whisper and Wav2Vec2 models are loaded via the singleton ModelManager
 /// to ensure they are only loaded once per process and properly cleaned up.
 
 use anyhow::{Context, Result};
+use std::collections::HashMap;
 use tracing::{debug, info, warn};
 
 use crate::embedding::{AudioEmbedder, DEFAULT_SIMILARITY_THRESHOLD};
-use crate::model_manager::{ModelManager, REFERENCE_EMBEDDING_PATH};
+use crate::embedding_file::{self, ModelHash, StoredEmbedding};
+use crate::fingerprint::DEFAULT_FINGERPRINT_THRESHOLD;
+use crate::matcher::{AudioMatcher, CrossCheck, Features, MatchScore, Matcher};
+use crate::model_manager::{step_reference_path, ModelManager, EMBEDDER_MODEL_PATH, REFERENCE_EMBEDDING_PATH};
+use crate::phrase::{self, MatchPolicy, PhraseMatch, PhrasePolicy, Score};
+use crate::reference::{Enrollment, ReferenceSource, ReferenceStore, DEFAULT_MAX_DRIFT};
+use crate::rtp::resample::resample;
+use crate::rtp::WHISPER_SAMPLE_RATE;
+
+/// Default similarity above which a match replaces the active reference
+pub const DEFAULT_UPDATE_THRESHOLD: f32 = 0.95;
+
+/// Scores a check is held to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds {
+    /// Similarity the embedding and aligned matchers need
+    pub similarity: f32,
+    /// Similarity (below `similarity`) from which a miss is only a warning
+    pub warning: Option<f32>,
+    /// Fraction of a reference's landmarks the fingerprint matcher needs
+    pub fingerprint: f32,
+    /// Similarity above which a match replaces the active reference (if it
+    /// also stays within the drift limit of the anchor)
+    pub update: f32,
+}
 
-/// Default similarity threshold for embedding-based matching
-const SIMILARITY_THRESHOLD: f32 = DEFAULT_SIMILARITY_THRESHOLD;
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            similarity: DEFAULT_SIMILARITY_THRESHOLD,
+            warning: None,
+            fingerprint: DEFAULT_FINGERPRINT_THRESHOLD,
+            update: DEFAULT_UPDATE_THRESHOLD,
+        }
+    }
+}
+
+impl Thresholds {
+    /// Threshold a score from `kind` has to reach
+    pub fn for_matcher(&self, kind: Matcher) -> f32 {
+        match kind {
+            Matcher::Fingerprint => self.fingerprint,
+            Matcher::Embedding | Matcher::Aligned => self.similarity,
+        }
+    }
+
+    /// Start of the warning band for scores from `kind`. Fingerprint scores
+    /// are on their own scale and have none.
+    pub fn warning_for(&self, kind: Matcher) -> Option<f32> {
+        match kind {
+            Matcher::Fingerprint => None,
+            Matcher::Embedding | Matcher::Aligned => self.warning,
+        }
+    }
+}
 
 /// Type alias for the singleton mutex type
 type ModelManagerMutex = &'static std::sync::Mutex<Option<ModelManager>>;
@@ -21,8 +74,23 @@ type ModelManagerMutex = &'static std::sync::Mutex<Option<ModelManager>>;
 pub struct SpeechRecognizer {
     /// Model path (stored for singleton access)
     model_path: String,
-    /// Pre-computed reference embedding for expected phrase audio
-    reference_embedding: Option<Vec<f32>>,
+    /// Reference embeddings keyed by file path (main greeting, IVR capture
+    /// steps and per-target reference sets), loaded on first use and checked
+    /// against the loaded model
+    references: HashMap<String, Vec<f32>>,
+    /// Features of each reference's approved audio clip by matcher (none for
+    /// references without one), for matchers other than the mean embedding
+    reference_features: HashMap<(Matcher, String), Option<Features>>,
+    /// Matcher that decides the result
+    matcher: Matcher,
+    /// Matcher whose opinion is only recorded and logged if it disagrees
+    secondary_matcher: Option<Matcher>,
+    matchers: HashMap<Matcher, Box<dyn AudioMatcher>>,
+    /// Whether missing references may be proposed from, and close matches
+    /// may replace, the audio being checked (off for offline analysis)
+    update_references: bool,
+    /// Largest cosine distance from the anchor an update may reach
+    max_drift: f32,
 }
 
 impl SpeechRecognizer {
@@ -35,28 +103,46 @@ impl SpeechRecognizer {
             anyhow::bail!("Failed to initialize ModelManager - check model files");
         }
 
-        // Load cached reference embedding if available
-        let reference_embedding = Self::load_cached_reference(model_path);
-
-        if reference_embedding.is_some() {
-            info!("Using cached reference embedding for phrase matching");
-        }
-
         Ok(Self {
             model_path: model_path.to_string(),
-            reference_embedding,
+            references: HashMap::new(),
+            reference_features: HashMap::new(),
+            matcher: Matcher::default(),
+            secondary_matcher: None,
+            matchers: HashMap::new(),
+            update_references: true,
+            max_drift: DEFAULT_MAX_DRIFT,
         })
     }
 
-    /// Load cached reference embedding from disk
-    fn load_cached_reference(model_path: &str) -> Option<Vec<f32>> {
-        // Access singleton only for loading the reference (no models needed)
-        ModelManager::get(model_path)?;
-        ModelManager::load_reference_embedding()
+    /// Never write reference embeddings: a missing reference is reported as
+    /// no match instead of being proposed, and close matches don't replace it
+    pub fn without_reference_updates(mut self) -> Self {
+        self.update_references = false;
+        self
+    }
+
+    /// Compare audio with references using `matcher`
+    pub fn with_matcher(mut self, matcher: Matcher) -> Self {
+        self.matcher = matcher;
+        self
+    }
+
+    /// Also score audio with `matcher` to cross-check the verdict
+    pub fn with_secondary_matcher(mut self, matcher: Option<Matcher>) -> Self {
+        self.secondary_matcher = matcher;
+        self
+    }
+
+    /// Limit how far (in cosine distance) checks may move a reference away
+    /// from its anchor; 0 stops checks from updating references
+    pub fn with_max_drift(mut self, max_drift: f32) -> Self {
+        self.max_drift = max_drift;
+        self
     }
 
     /// Transcribe audio using Whisper (immutable access)
-    fn transcribe_audio(&self, audio_samples: &[f32]) -> Result<String> {
+    pub fn transcribe_audio(&self, audio_samples: &[f32]) -> Result<String> {
         let guard = ModelManager::get(&self.model_path)
             .and_then(|m: ModelManagerMutex| m.lock().ok())
             .context("Failed to access ModelManager")?;
@@ -81,8 +167,37 @@ impl SpeechRecognizer {
         Ok(model_manager.has_embedder())
     }
 
+    /// SHA-256 of the loaded Wav2Vec2 model
+    pub fn embedder_sha256(&self) -> Result<ModelHash> {
+        let guard = ModelManager::get(&self.model_path)
+            .and_then(|m: ModelManagerMutex| m.lock().ok())
+            .context("Failed to access ModelManager")?;
+
+        guard
+            .as_ref()
+            .context("ModelManager not initialized")?
+            .embedder_sha256()
+            .context("Wav2Vec2 embedder not available")
+    }
+
+    /// Read the reference at `path`, failing if it is damaged or was computed
+    /// by a different model than the one loaded
+    fn load_reference(&self, path: &str) -> Result<Option<Vec<f32>>> {
+        let Some(stored) = embedding_file::read(path)? else {
+            return Ok(None);
+        };
+        stored
+            .verify_model(&self.embedder_sha256()?)
+            .with_context(|| format!("Reference {}", path))?;
+        if let StoredEmbedding::Legacy(_) = stored {
+            warn!("Reference {} has no header; run `phonecheck reference migrate`", path);
+        }
+        info!("Loaded reference embedding {} ({} dimensions)", path, stored.embedding().len());
+        Ok(Some(stored.into_embedding()))
+    }
+
     /// Compute embedding using Wav2Vec2 (mutable access)
-    fn compute_embedding(&mut self, audio_samples: &[f32]) -> Result<Vec<f32>> {
+    pub fn compute_embedding(&mut self, audio_samples: &[f32]) -> Result<Vec<f32>> {
         let mut guard = ModelManager::get(&self.model_path)
             .and_then(|m: ModelManagerMutex| m.lock().ok())
             .context("Failed to access ModelManager for embedding")?;
@@ -94,15 +209,78 @@ impl SpeechRecognizer {
         model_manager.embed(audio_samples)
     }
 
+    /// The matcher of kind `kind`, built on first use
+    fn audio_matcher(&mut self, kind: Matcher) -> &mut Box<dyn AudioMatcher> {
+        let model_path = &self.model_path;
+        self.matchers.entry(kind).or_insert_with(|| kind.build(model_path))
+    }
+
+    /// Cache `kind` features of the audio clip the approved version of the
+    /// reference at `reference_path` was computed from. A reference without
+    /// a clip is remembered as such; a missing reference is looked for again
+    /// on the next check.
+    fn load_reference_features(&mut self, kind: Matcher, reference_path: &str) -> Result<()> {
+        let key = (kind, reference_path.to_string());
+        if self.reference_features.contains_key(&key) {
+            return Ok(());
+        }
+        let Some(audio_path) = ReferenceStore::for_path(reference_path).active_audio_path() else {
+            if self.references.contains_key(reference_path) {
+                warn!(
+                    "Reference {} has no audio clip for the {} matcher (re-enroll it to use it)",
+                    reference_path, kind
+                );
+                self.reference_features.insert(key, None);
+            }
+            return Ok(());
+        };
+        let (samples, sample_rate) = crate::rtp::load_wav(&audio_path)?;
+        let features = self
+            .audio_matcher(kind)
+            .analyze(&resample(&samples, sample_rate, WHISPER_SAMPLE_RATE))?;
+        info!("Loaded {} features of {}", kind, audio_path.display());
+        self.reference_features.insert(key, Some(features));
+        Ok(())
+    }
+
     /// Transcribe audio and check if expected phrase is present using embedding similarity
     /// Audio should be 16kHz mono f32 samples
     pub fn check_audio(&mut self, audio_samples: &[f32]) -> Result<CheckResult> {
+        self.check_reference(audio_samples, REFERENCE_EMBEDDING_PATH, &Thresholds::default())
+    }
+
+    /// Check the audio of an IVR capture step against that step's own
+    /// reference embedding (steps are numbered from 1)
+    pub fn check_capture(&mut self, audio_samples: &[f32], step: usize) -> Result<CheckResult> {
+        self.check_reference(audio_samples, &step_reference_path(step), &Thresholds::default())
+    }
+
+    /// Check audio against the reference embedding stored at `reference_path`
+    /// using the given thresholds. Without a reference the audio is saved as
+    /// a candidate awaiting approval.
+    pub fn check_reference(
+        &mut self,
+        audio_samples: &[f32],
+        reference_path: &str,
+        thresholds: &Thresholds,
+    ) -> Result<CheckResult> {
+        let name = ReferenceStore::for_path(reference_path).slot().to_string();
+        self.check_references(audio_samples, &[(&name, reference_path)], thresholds)
+    }
+
+    /// Check audio against a set of accepted references, given as
+    /// (name, path) pairs, using the given thresholds. The closest reference
+    /// decides the result. If none of them exist yet the audio is saved as
+    /// a candidate for the first.
+    pub fn check_references(
+        &mut self,
+        audio_samples: &[f32],
+        references: &[(&str, &str)],
+        thresholds: &Thresholds,
+    ) -> Result<CheckResult> {
+        anyhow::ensure!(!references.is_empty(), "No references to check against");
         if audio_samples.is_empty() {
-            return Ok(CheckResult {
-                transcript: String::new(),
-                phrase_found: false,
-                similarity: None,
-            });
+            return Ok(CheckResult::no_match(String::new()));
         }
 
         // First, transcribe with Whisper for logging/debugging
@@ -111,70 +289,263 @@ impl SpeechRecognizer {
 
         // Check if embedder is available
         let has_embedder = self.has_embedder()?;
-        if !has_embedder {
-            warn!("No Wav2Vec2 embedder available - phrase matching will not work!");
+        if !has_embedder && self.matcher.needs_embedder() {
+            warn!("No Wav2Vec2 embedder available - phrase matching will not work! (MATCHER=fingerprint needs no model)");
+            return Ok(CheckResult::no_match(transcript));
+        }
+
+        self.match_references(audio_samples, references, thresholds, transcript, has_embedder)
+    }
+
+    /// Score audio against each of `references` with the configured
+    /// matcher, keeping the closest. References whose approved clip can't be
+    /// used (legacy references without one) are scored by mean embedding.
+    fn match_references(
+        &mut self,
+        audio_samples: &[f32],
+        references: &[(&str, &str)],
+        thresholds: &Thresholds,
+        transcript: String,
+        has_embedder: bool,
+    ) -> Result<CheckResult> {
+        // The mean embedding is needed to propose and update references even
+        // when another matcher decides
+        let (current_embedding, model_sha256) = if has_embedder {
+            (Some(self.compute_embedding(audio_samples)?), Some(self.embedder_sha256()?))
+        } else {
+            (None, None)
+        };
+
+        // Load errors and missing references are not cached: a fixed or
+        // approved reference is used on the next check
+        let clip_matcher = (self.matcher != Matcher::Embedding).then_some(self.matcher);
+        for &(_, path) in references {
+            if has_embedder && !self.references.contains_key(path) {
+                if let Some(reference) = self.load_reference(path)? {
+                    self.references.insert(path.to_string(), reference);
+                }
+            }
+            // Without an embedder the clip alone makes a reference
+            if let Some(kind) = clip_matcher.filter(|_| self.references.contains_key(path) || !has_embedder) {
+                self.load_reference_features(kind, path)?;
+            }
+        }
+        let current_features = match clip_matcher {
+            Some(kind) => Some(self.audio_matcher(kind).analyze(audio_samples)?),
+            None => None,
+        };
+
+        let closest = references
+            .iter()
+            .filter_map(|&(name, path)| {
+                let clip = clip_matcher.and_then(|kind| {
+                    let reference = self.reference_features.get(&(kind, path.to_string()))?.as_ref()?;
+                    Some((kind, reference, current_features.as_ref()?))
+                });
+                let (kind, score) = match clip {
+                    Some((kind, reference, current)) => {
+                        let score = self.matchers[&kind].compare(reference, current).unwrap_or_else(|e| {
+                            warn!("Can't score reference '{}' with the {} matcher: {:#}", name, kind, e);
+                            MatchScore { similarity: 0.0, offset_ms: None }
+                        });
+                        (kind, score)
+                    }
+                    None => {
+                        let similarity =
+                            AudioEmbedder::cosine_similarity(self.references.get(path)?, current_embedding.as_ref()?);
+                        (Matcher::Embedding, MatchScore { similarity, offset_ms: None })
+                    }
+                };
+                debug!("Similarity to reference '{}': {:.4} ({})", name, score.similarity, kind);
+                Some((name, path, kind, score))
+            })
+            // Scores from different matchers are compared by their margin
+            .max_by(|a, b| {
+                let margin = |&(_, _, kind, score): &(&str, &str, Matcher, MatchScore)| {
+                    score.similarity - thresholds.for_matcher(kind)
+                };
+                margin(a).total_cmp(&margin(b))
+            });
+
+        let Some((name, reference_path, scored_by, score)) = closest else {
+            let (name, reference_path) = references[0];
+            if !self.update_references {
+                warn!("No reference embedding at {}", reference_path);
+                return Ok(CheckResult::no_match(transcript));
+            }
+            let (Some(current_embedding), Some(model_sha256)) = (current_embedding, model_sha256) else {
+                warn!(
+                    "No approved reference at {}; enroll one on a machine with the Wav2Vec2 model and copy the reference directory",
+                    reference_path
+                );
+                return Ok(CheckResult::no_match(transcript));
+            };
+            // No reference yet - keep this audio for an operator to approve
+            // rather than trusting whatever the first call heard
+            let store = ReferenceStore::for_path(reference_path);
+            let proposed = store.propose(Enrollment {
+                embedding: &current_embedding,
+                audio: audio_samples,
+                transcript: Some(transcript.clone()),
+                source: ReferenceSource::Check,
+                embedder: EMBEDDER_MODEL_PATH.to_string(),
+                whisper: self.model_path.clone(),
+                model_sha256,
+            })?;
+            warn!("No approved reference at {}; run `phonecheck reference approve`", reference_path);
             return Ok(CheckResult {
-                transcript,
-                phrase_found: false,
-                similarity: None,
+                awaiting_approval: true,
+                reference_update: proposed.then(|| ReferenceUpdate::Proposed { reference: name.to_string() }),
+                ..CheckResult::no_match(transcript)
             });
+        };
+
+        let similarity = score.similarity;
+        let threshold = thresholds.for_matcher(scored_by);
+        info!(
+            "Audio {} similarity: {:.4} to reference '{}' (threshold: {:.2})",
+            scored_by, similarity, name, threshold
+        );
+        if let Some(offset_ms) = score.offset_ms {
+            info!("Found reference '{}' at {:.2}s", name, offset_ms as f64 / 1000.0);
         }
 
-        // Use embedding-based matching
-        let (phrase_found, similarity) = self.check_embedding_similarity(audio_samples)?;
+        let phrase_found = similarity >= threshold;
+        let in_warning_band = !phrase_found && thresholds.warning_for(scored_by).is_some_and(|w| similarity >= w);
+
+        let cross_check = match self.secondary_matcher {
+            Some(kind) => self.cross_check(kind, audio_samples, reference_path, thresholds, has_embedder),
+            None => None,
+        };
+        if let Some(check) = cross_check.filter(|check| check.matched != phrase_found) {
+            warn!(
+                "The {} matcher disagrees with the {} verdict for reference '{}' ({:.4}, {})",
+                check.matcher,
+                scored_by,
+                name,
+                check.similarity,
+                if check.matched { "match" } else { "no match" }
+            );
+        }
+
+        // A close match may replace the active reference, but never moves it
+        // beyond the drift limit of the anchor. Other matchers score against
+        // the approved clip, so only mean-embedding scores count.
+        let mut reference_update = None;
+        if let (Matcher::Embedding, Some(current_embedding), Some(model_sha256)) =
+            (scored_by, current_embedding, model_sha256)
+        {
+            if phrase_found && similarity > thresholds.update && self.update_references {
+                match self.anchor_similarity(reference_path, &current_embedding, &model_sha256) {
+                    Ok(Some(anchor_similarity)) if 1.0 - anchor_similarity <= self.max_drift => {
+                        match ModelManager::save_reference_embedding_to(reference_path, &current_embedding, &model_sha256) {
+                            Ok(()) => {
+                                self.references.insert(reference_path.to_string(), current_embedding);
+                                reference_update = Some(ReferenceUpdate::Updated {
+                                    reference: name.to_string(),
+                                    similarity,
+                                    anchor_similarity,
+                                });
+                            }
+                            Err(e) => warn!("Failed to update reference embedding: {}", e),
+                        }
+                    }
+                    Ok(Some(anchor_similarity)) => info!(
+                        "Not updating reference: {:.4} from its anchor exceeds the drift limit {:.2}",
+                        1.0 - anchor_similarity,
+                        self.max_drift
+                    ),
+                    Ok(None) => {}
+                    Err(e) => warn!("Not updating reference: {:#}", e),
+                }
+            }
+        }
 
         Ok(CheckResult {
             transcript,
             phrase_found,
-            similarity,
+            similarity: Some(similarity),
+            threshold: Some(threshold),
+            in_warning_band,
+            matched_reference: phrase_found.then(|| name.to_string()),
+            offset_ms: score.offset_ms,
+            cross_check,
+            phrase: None,
+            awaiting_approval: false,
+            reference_update,
         })
     }
 
-    /// Check audio similarity using Wav2Vec2 embeddings
-    fn check_embedding_similarity(
+    /// Score audio against the clip of the reference at `reference_path`
+    /// with a second matcher. Failures only lose the cross-check.
+    fn cross_check(
         &mut self,
+        kind: Matcher,
         audio_samples: &[f32],
-    ) -> Result<(bool, Option<f32>)> {
-        // Compute embedding for current audio
-        let current_embedding = self.compute_embedding(audio_samples)?;
-
-        // Check against reference embedding
-        if let Some(ref reference) = self.reference_embedding {
-            let similarity = AudioEmbedder::cosine_similarity(reference, &current_embedding);
-            info!(
-                "Audio embedding similarity: {:.4} (threshold: {:.2})",
-                similarity, SIMILARITY_THRESHOLD
-            );
-
-            let phrase_found = similarity >= SIMILARITY_THRESHOLD;
-
-            // If match found and this is a better reference, update it
-            if phrase_found && similarity > 0.95 {
-                self.reference_embedding = Some(current_embedding.clone());
-                if let Err(e) = ModelManager::save_reference_embedding(&current_embedding) {
-                    warn!("Failed to update reference embedding: {}", e);
-                }
+        reference_path: &str,
+        thresholds: &Thresholds,
+        has_embedder: bool,
+    ) -> Option<CrossCheck> {
+        if kind.needs_embedder() && !has_embedder {
+            debug!("No Wav2Vec2 embedder for the {} cross-check", kind);
+            return None;
+        }
+        let result = self.secondary_score(kind, audio_samples, reference_path);
+        match result {
+            Ok(Some(score)) => {
+                debug!("Cross-check {} similarity: {:.4}", kind, score.similarity);
+                Some(CrossCheck {
+                    matcher: kind,
+                    similarity: score.similarity,
+                    matched: score.similarity >= thresholds.for_matcher(kind),
+                })
             }
-
-            Ok((phrase_found, Some(similarity)))
-        } else {
-            // No reference yet - save this as the reference (bootstrap)
-            info!("No reference embedding found, saving current audio as reference");
-            self.reference_embedding = Some(current_embedding.clone());
-            if let Err(e) = ModelManager::save_reference_embedding(&current_embedding) {
-                warn!("Failed to save reference embedding: {}", e);
+            Ok(None) => None,
+            Err(e) => {
+                warn!("{} cross-check failed: {:#}", kind, e);
+                None
             }
-            // Assume first capture is correct (user should verify)
-            Ok((true, Some(1.0)))
         }
     }
 
+    /// Score of audio against the clip of the reference at `reference_path`
+    /// with `kind` (none if the reference has no clip)
+    fn secondary_score(&mut self, kind: Matcher, audio_samples: &[f32], reference_path: &str) -> Result<Option<MatchScore>> {
+        self.load_reference_features(kind, reference_path)?;
+        let key = (kind, reference_path.to_string());
+        if !matches!(self.reference_features.get(&key), Some(Some(_))) {
+            return Ok(None);
+        }
+        let current = self.audio_matcher(kind).analyze(audio_samples)?;
+        let Some(Some(reference)) = self.reference_features.get(&key) else {
+            return Ok(None);
+        };
+        self.matchers[&kind].compare(reference, &current).map(Some)
+    }
+
+    /// Similarity of `embedding` to the anchor of the reference at
+    /// `reference_path` (none if there is no anchor)
+    fn anchor_similarity(&self, reference_path: &str, embedding: &[f32], model_sha256: &ModelHash) -> Result<Option<f32>> {
+        let Some(anchor) = ReferenceStore::for_path(reference_path).anchor()? else {
+            return Ok(None);
+        };
+        anchor.verify_model(model_sha256).context("Reference anchor")?;
+        if anchor.embedding().len() != embedding.len() {
+            anyhow::bail!("Reference anchor has {} dimensions, expected {}", anchor.embedding().len(), embedding.len());
+        }
+        Ok(Some(AudioEmbedder::cosine_similarity(anchor.embedding(), embedding)))
+    }
+
     /// Load a new reference embedding from disk
     pub fn reload_reference(&mut self) -> Result<()> {
-        let new_ref = ModelManager::load_reference_embedding()
+        let new_ref = self
+            .load_reference(REFERENCE_EMBEDDING_PATH)?
             .context("No reference embedding file found")?;
 
-        self.reference_embedding = Some(new_ref);
+        // Step and per-target references are reloaded lazily on their next check
+        self.references.clear();
+        self.reference_features.clear();
+        self.references.insert(REFERENCE_EMBEDDING_PATH.to_string(), new_ref);
         info!("Reloaded reference embedding from {}", REFERENCE_EMBEDDING_PATH);
         Ok(())
     }
@@ -185,6 +556,90 @@ pub struct CheckResult {
     pub transcript: String,
     pub phrase_found: bool,
     pub similarity: Option<f32>,
+    /// Threshold the similarity was held to (it depends on the matcher)
+    pub threshold: Option<f32>,
+    /// The audio missed its threshold but reached the warning threshold
+    pub in_warning_band: bool,
+    /// Name of the accepted reference the audio matched
+    pub matched_reference: Option<String>,
+    /// Where the closest reference starts in the audio, if the matcher
+    /// locates it
+    pub offset_ms: Option<u64>,
+    /// The secondary matcher's opinion, if one is configured
+    pub cross_check: Option<CrossCheck>,
+    /// The expected phrase closest to the transcript, once scored by
+    /// [`CheckResult::apply_phrase_policy`]
+    pub phrase: Option<PhraseMatch>,
+    /// No approved reference yet (a candidate awaits `reference approve`)
+    pub awaiting_approval: bool,
+    /// What the check changed about the reference
+    pub reference_update: Option<ReferenceUpdate>,
+}
+
+impl CheckResult {
+    /// A result that matched nothing
+    fn no_match(transcript: String) -> Self {
+        Self {
+            transcript,
+            phrase_found: false,
+            similarity: None,
+            threshold: None,
+            in_warning_band: false,
+            matched_reference: None,
+            offset_ms: None,
+            cross_check: None,
+            phrase: None,
+            awaiting_approval: false,
+            reference_update: None,
+        }
+    }
+
+    /// Score the transcript against the expected phrases and let `policy`
+    /// decide the result from both scores. A check that passes on its
+    /// transcript no longer waits for a reference to be approved, and only
+    /// policies that weigh the audio keep its warning band.
+    pub fn apply_phrase_policy(&mut self, policy: &PhrasePolicy<'_>) {
+        self.phrase = phrase::best_match(&self.transcript, &policy.phrases);
+        if let Some(found) = &self.phrase {
+            info!(
+                "Transcript phrase score: {:.4} for \"{}\" (threshold: {:.2})",
+                found.score, found.phrase, policy.threshold
+            );
+        }
+
+        let audio = self.similarity.zip(self.threshold).map(|(value, threshold)| Score { value, threshold });
+        let transcript = self.phrase.as_ref().map(|found| Score { value: found.score, threshold: policy.threshold });
+        let phrase_found = policy.policy.decide(audio, transcript, policy.transcript_weight);
+        if phrase_found != self.phrase_found {
+            info!(
+                "The {} policy {} the audio verdict",
+                policy.policy,
+                if phrase_found { "overrides" } else { "rejects" }
+            );
+        }
+        if phrase_found || policy.policy == MatchPolicy::Transcript {
+            self.in_warning_band = false;
+        }
+        if phrase_found {
+            self.awaiting_approval = false;
+        } else {
+            self.matched_reference = None;
+        }
+        self.phrase_found = phrase_found;
+    }
+}
+
+/// A change a check made to one of its references
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReferenceUpdate {
+    /// The audio was saved as a candidate awaiting approval
+    Proposed { reference: String },
+    /// The active reference was replaced by the audio's embedding
+    Updated {
+        reference: String,
+        similarity: f32,
+        anchor_similarity: f32,
+    },
 }
 
 #[cfg(test)]
@@ -197,8 +652,83 @@ mod tests {
             transcript: "test".to_string(),
             phrase_found: true,
             similarity: Some(0.95),
+            threshold: Some(0.75),
+            in_warning_band: false,
+            matched_reference: Some("greeting".to_string()),
+            offset_ms: None,
+            cross_check: None,
+            phrase: None,
+            awaiting_approval: false,
+            reference_update: None,
         };
         assert!(result.phrase_found);
         assert_eq!(result.similarity, Some(0.95));
     }
+
+    #[test]
+    fn test_apply_phrase_policy() {
+        let heard = |similarity: Option<f32>| CheckResult {
+            similarity,
+            threshold: similarity.map(|_| 0.75),
+            phrase_found: similarity.is_some_and(|s| s >= 0.75),
+            matched_reference: similarity.filter(|&s| s >= 0.75).map(|_| "greeting".to_string()),
+            ..CheckResult::no_match("Thank you for calling Cubic Machinery, how can I help?".to_string())
+        };
+        let policy = |policy: MatchPolicy, phrases: Vec<&'static str>| PhrasePolicy {
+            policy,
+            phrases,
+            threshold: 0.8,
+            transcript_weight: 0.5,
+        };
+        let greeting = vec!["thank you for calling cubic machinery"];
+
+        // The audio decides, but the phrase score is recorded
+        let mut result = heard(Some(0.5));
+        result.apply_phrase_policy(&policy(MatchPolicy::Audio, greeting.clone()));
+        assert!(!result.phrase_found);
+        assert!((result.phrase.as_ref().unwrap().score - 1.0).abs() < 1e-6);
+
+        let mut result = heard(Some(0.5));
+        result.apply_phrase_policy(&policy(MatchPolicy::Either, greeting.clone()));
+        assert!(result.phrase_found);
+
+        // No approved reference yet, but the transcript is enough
+        let mut result = CheckResult { awaiting_approval: true, ..heard(None) };
+        result.apply_phrase_policy(&policy(MatchPolicy::Transcript, greeting.clone()));
+        assert!(result.phrase_found && !result.awaiting_approval);
+
+        let mut result = heard(Some(0.9));
+        result.apply_phrase_policy(&policy(MatchPolicy::Both, vec!["you have reached the night line"]));
+        assert!(!result.phrase_found);
+        assert_eq!(result.matched_reference, None);
+
+        // A near miss stays a warning only while the audio counts
+        let near_miss = || CheckResult { in_warning_band: true, ..heard(Some(0.7)) };
+        let mut result = near_miss();
+        result.apply_phrase_policy(&policy(MatchPolicy::Audio, greeting.clone()));
+        assert!(result.in_warning_band);
+        let mut result = near_miss();
+        result.apply_phrase_policy(&policy(MatchPolicy::Either, greeting.clone()));
+        assert!(result.phrase_found && !result.in_warning_band);
+        let mut result = near_miss();
+        result.apply_phrase_policy(&policy(MatchPolicy::Transcript, vec!["you have reached the night line"]));
+        assert!(!result.phrase_found && !result.in_warning_band);
+    }
+
+    #[test]
+    fn test_thresholds_by_matcher() {
+        let thresholds = Thresholds { warning: Some(0.6), ..Thresholds::default() };
+        assert_eq!(thresholds.for_matcher(Matcher::Embedding), DEFAULT_SIMILARITY_THRESHOLD);
+        assert_eq!(thresholds.for_matcher(Matcher::Aligned), DEFAULT_SIMILARITY_THRESHOLD);
+        assert_eq!(thresholds.for_matcher(Matcher::Fingerprint), DEFAULT_FINGERPRINT_THRESHOLD);
+        assert_eq!(thresholds.warning_for(Matcher::Aligned), Some(0.6));
+        assert_eq!(thresholds.warning_for(Matcher::Fingerprint), None);
+        assert_eq!(thresholds.update, DEFAULT_UPDATE_THRESHOLD);
+    }
+
+    #[test]
+    fn test_check_references_needs_a_reference() {
+        let mut recognizer = SpeechRecognizer::new("/nonexistent/ggml-model.bin").unwrap();
+        assert!(recognizer.check_references(&[0.0; 160], &[], &Thresholds::default()).is_err());
+    }
 }
@@ -0,0 +1,855 @@
+//! Check targets
+//!
+//! A target is one phone number to monitor, with its own listen settings,
+//! thresholds, reference set, schedule and alert recipient. Without a
+//! checks file, a single `default` target is built from the environment.
+//!
+//! With `CHECKS_FILE`, targets are listed in TOML. Unset fields fall back to
+//! the environment settings (except `ivr_script`, which is per target):
+//! ```toml
+//! [[target]]
+//! name = "main"
+//! phone = "9095551234"
+//! expected_phrase = "thank you for calling"
+//!
+//! [[target]]
+//! name = "after-hours"
+//! phone = "9095550000"
+//! listen_duration_secs = 15
+//! threshold = 0.8
+//! warning_threshold = 0.7
+//! schedule = "0 18-23,0-7 * * *"
+//! timezone = "America/New_York"
+//! pushover_user_key = "uOnCallGroupKey"
+//! ```
+//!
+//! The other detection settings may be set per target too:
+//! `reference_update_threshold`, `fingerprint_threshold`,
+//! `min_audio_duration_ms`, `match_policy`, `phrase_threshold`,
+//! `transcript_weight` and `min_mos` (see the environment variables of the
+//! same name).
+//!
+//! A target whose PBX plays different greetings at different times lists
+//! them as named references. Each may carry a `window` (cron expressions in
+//! the target's time zone, like `schedule`), a `dates` file (only on those
+//! dates) and `excluded_dates`; a check accepts any reference that applies at
+//! that moment:
+//! ```toml
+//! [[target.reference]]
+//! name = "day"
+//! window = "* 8-16 * * MON-FRI"
+//! excluded_dates = "./holidays.ics"
+//!
+//! [[target.reference]]
+//! name = "night"
+//! window = "* 17-23,0-7 * * *; * * * * SAT,SUN"
+//!
+//! [[target.reference]]
+//! name = "holiday"
+//! dates = "./holidays.ics"
+//! ```
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::ivr::IvrScript;
+use crate::model_manager::{named_reference_path_in, reference_path_in, DEFAULT_REFERENCE_DIR};
+use crate::phrase::MatchPolicy;
+use crate::reference::slot_name;
+use crate::schedule::{ExcludedDates, Schedule};
+use crate::speech::Thresholds;
+
+/// Name of the single target built from the environment
+pub const DEFAULT_TARGET_NAME: &str = "default";
+
+/// A phone number to check, with its resolved settings
+#[derive(Debug, Clone)]
+pub struct CheckTarget {
+    /// Unique name, used in alerts, metric labels and the reference directory
+    pub name: String,
+    /// Call and detection settings (phone, phrase, durations, IVR script,
+    /// thresholds) for this target
+    pub config: Arc<Config>,
+    /// Minimum cosine similarity for the greeting to match
+    pub threshold: f32,
+    /// Directory holding this target's reference embeddings
+    pub reference_dir: String,
+    /// When the target is checked
+    pub schedule: Schedule,
+    /// Pushover user or group key that receives this target's alerts
+    pub pushover_user_key: String,
+    /// Named greetings accepted at different times (empty: the single
+    /// greeting reference)
+    pub references: Vec<GreetingReference>,
+}
+
+/// One of several greetings a target may play (e.g. day, night or holiday)
+#[derive(Debug, Clone)]
+pub struct GreetingReference {
+    pub name: String,
+    /// When the greeting may be heard, in the target's time zone (any time
+    /// unless `window` or `excluded_dates` is set)
+    pub window: Schedule,
+    /// Only on these dates (e.g. holidays)
+    pub dates: Option<ExcludedDates>,
+}
+
+impl GreetingReference {
+    /// Whether this greeting is expected at `now`
+    pub fn applies_at(&self, now: DateTime<Utc>) -> bool {
+        let local = self.window.local_time(now);
+        self.window.matches_local(local) && self.dates.as_ref().is_none_or(|dates| dates.contains(local.date()))
+    }
+}
+
+impl CheckTarget {
+    /// The single target described by the environment settings
+    pub fn from_config(config: Arc<Config>) -> Result<Self> {
+        let schedule = with_excluded_dates(config.schedule.clone(), config.excluded_dates_file.as_deref())?;
+        let pushover_user_key = config.pushover_user_key.clone();
+        Ok(Self {
+            name: DEFAULT_TARGET_NAME.to_string(),
+            threshold: config.similarity_threshold,
+            config,
+            reference_dir: DEFAULT_REFERENCE_DIR.to_string(),
+            schedule,
+            pushover_user_key,
+            references: Vec::new(),
+        })
+    }
+
+    /// Scores this target's checks are held to
+    pub fn thresholds(&self) -> Thresholds {
+        Thresholds {
+            similarity: self.threshold,
+            ..self.config.thresholds()
+        }
+    }
+
+    /// Whether this is the environment-only target (alerts are not prefixed)
+    pub fn is_default(&self) -> bool {
+        self.name == DEFAULT_TARGET_NAME
+    }
+
+    /// Reference embedding path for the greeting (`None`) or an IVR capture step
+    pub fn reference_path(&self, step: Option<usize>) -> String {
+        reference_path_in(&self.reference_dir, step)
+    }
+
+    /// Every greeting reference as (name, embedding path): the named
+    /// references, or the single `greeting` reference
+    pub fn greeting_references(&self) -> Vec<(String, String)> {
+        if self.references.is_empty() {
+            return vec![(slot_name(None), self.reference_path(None))];
+        }
+        self.references
+            .iter()
+            .map(|r| (r.name.clone(), named_reference_path_in(&self.reference_dir, &r.name)))
+            .collect()
+    }
+
+    /// Greeting references accepted at `now`: the named references whose
+    /// window applies, or all of them when none does
+    pub fn greeting_references_at(&self, now: DateTime<Utc>) -> Vec<(String, String)> {
+        let all = self.greeting_references();
+        if self.references.is_empty() {
+            return all;
+        }
+        let applicable: Vec<(String, String)> = self
+            .references
+            .iter()
+            .zip(&all)
+            .filter(|(reference, _)| reference.applies_at(now))
+            .map(|(_, named)| named.clone())
+            .collect();
+        if applicable.is_empty() {
+            tracing::warn!("No reference of target '{}' applies now; accepting any of them", self.name);
+            return all;
+        }
+        applicable
+    }
+
+    /// The named reference called `name`
+    pub fn find_reference(&self, name: &str) -> Result<&GreetingReference> {
+        self.references.iter().find(|r| r.name == name).with_context(|| {
+            let names: Vec<&str> = self.references.iter().map(|r| r.name.as_str()).collect();
+            if names.is_empty() {
+                format!("Target '{}' has no named references", self.name)
+            } else {
+                format!("Target '{}' has no reference named '{}' (references: {})", self.name, name, names.join(", "))
+            }
+        })
+    }
+}
+
+/// Checks file layout: a list of `[[target]]` tables
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ChecksFile {
+    #[serde(default, rename = "target")]
+    targets: Vec<TargetEntry>,
+}
+
+/// One `[[target]]` table, before defaults are applied
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TargetEntry {
+    name: String,
+    phone: String,
+    expected_phrase: Option<String>,
+    listen_duration_secs: Option<u64>,
+    ring_timeout_secs: Option<u64>,
+    ivr_script: Option<String>,
+    threshold: Option<f32>,
+    warning_threshold: Option<f32>,
+    reference_update_threshold: Option<f32>,
+    fingerprint_threshold: Option<f32>,
+    min_audio_duration_ms: Option<u64>,
+    match_policy: Option<MatchPolicy>,
+    phrase_threshold: Option<f32>,
+    transcript_weight: Option<f32>,
+    min_mos: Option<f32>,
+    reference_dir: Option<String>,
+    schedule: Option<String>,
+    timezone: Option<String>,
+    excluded_dates: Option<String>,
+    pushover_user_key: Option<String>,
+    #[serde(default, rename = "reference")]
+    references: Vec<ReferenceEntry>,
+}
+
+/// One `[[target.reference]]` table
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReferenceEntry {
+    name: String,
+    window: Option<String>,
+    dates: Option<String>,
+    excluded_dates: Option<String>,
+}
+
+/// Targets to check: from `CHECKS_FILE` if set, otherwise the single
+/// environment target
+pub fn load_targets(config: &Arc<Config>) -> Result<Vec<CheckTarget>> {
+    match config.checks_file {
+        Some(ref path) => load_checks_file(path, config),
+        None => Ok(vec![CheckTarget::from_config(Arc::clone(config))?]),
+    }
+}
+
+/// The target called `name`, or the only target when no name is given
+pub fn find_target<'a>(targets: &'a [CheckTarget], name: Option<&str>) -> Result<&'a CheckTarget> {
+    match name {
+        Some(name) => targets.iter().find(|t| t.name == name).with_context(|| {
+            let names: Vec<&str> = targets.iter().map(|t| t.name.as_str()).collect();
+            format!("No target named '{}' (targets: {})", name, names.join(", "))
+        }),
+        None => match targets {
+            [target] => Ok(target),
+            _ => bail!("Several targets are configured; choose one with --target"),
+        },
+    }
+}
+
+/// Read and parse a checks file, using `base` for unset fields
+pub fn load_checks_file(path: &str, base: &Config) -> Result<Vec<CheckTarget>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read checks file '{}'", path))?;
+    parse_checks(&contents, base).with_context(|| format!("Invalid checks file '{}'", path))
+}
+
+/// Parse checks file contents, using `base` for unset fields
+pub fn parse_checks(contents: &str, base: &Config) -> Result<Vec<CheckTarget>> {
+    let file: ChecksFile = toml::from_str(contents).context("Failed to parse TOML")?;
+    if file.targets.is_empty() {
+        bail!("No [[target]] entries");
+    }
+
+    let targets = file
+        .targets
+        .into_iter()
+        .map(|entry| {
+            let name = entry.name.clone();
+            build_target(entry, base).with_context(|| format!("target '{}'", name))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    validate_targets(&targets)?;
+    Ok(targets)
+}
+
+fn build_target(entry: TargetEntry, base: &Config) -> Result<CheckTarget> {
+    let mut config = base.clone();
+    config.target_phone = entry.phone;
+    if let Some(phrase) = entry.expected_phrase {
+        config.expected_phrase = phrase.to_lowercase();
+    }
+    if let Some(secs) = entry.listen_duration_secs {
+        config.listen_duration_secs = secs;
+    }
+    if let Some(secs) = entry.ring_timeout_secs {
+        config.ring_timeout_secs = secs;
+    }
+    if let Some(threshold) = entry.threshold {
+        config.similarity_threshold = threshold;
+    }
+    if let Some(threshold) = entry.warning_threshold {
+        config.warning_threshold = Some(threshold);
+    }
+    if let Some(threshold) = entry.reference_update_threshold {
+        config.reference_update_threshold = threshold;
+    }
+    if let Some(threshold) = entry.fingerprint_threshold {
+        config.fingerprint_threshold = threshold;
+    }
+    if let Some(ms) = entry.min_audio_duration_ms {
+        config.min_audio_duration_ms = ms;
+    }
+    if let Some(policy) = entry.match_policy {
+        config.match_policy = policy;
+    }
+    if let Some(threshold) = entry.phrase_threshold {
+        config.phrase_threshold = threshold;
+    }
+    if let Some(weight) = entry.transcript_weight {
+        config.transcript_weight = weight;
+    }
+    if let Some(mos) = entry.min_mos {
+        config.min_mos = Some(mos);
+    }
+    // Menus differ between numbers, so IVR_SCRIPT is not inherited
+    config.ivr_script = entry
+        .ivr_script
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| IvrScript::parse(&s))
+        .transpose()
+        .context("ivr_script is not a valid IVR script")?;
+
+    let schedule = match (entry.schedule, entry.timezone) {
+        (None, None) => base.schedule.clone(),
+        (cron, timezone) => Schedule::new(
+            cron.as_deref().unwrap_or(base.schedule.cron()),
+            timezone.as_deref().unwrap_or(base.schedule.timezone().name()),
+        )
+        .context("schedule is not valid")?,
+    };
+    let excluded_dates = entry.excluded_dates.or_else(|| base.excluded_dates_file.clone());
+    let schedule = with_excluded_dates(schedule, excluded_dates.as_deref())?;
+
+    let references = entry
+        .references
+        .into_iter()
+        .map(|reference| {
+            let name = reference.name.clone();
+            build_reference(reference, &schedule).with_context(|| format!("reference '{}'", name))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(CheckTarget {
+        reference_dir: entry
+            .reference_dir
+            .unwrap_or_else(|| format!("{}/{}", DEFAULT_REFERENCE_DIR, entry.name)),
+        name: entry.name,
+        threshold: config.similarity_threshold,
+        config: Arc::new(config),
+        schedule,
+        pushover_user_key: entry
+            .pushover_user_key
+            .unwrap_or_else(|| base.pushover_user_key.clone()),
+        references,
+    })
+}
+
+/// A named reference, with its window in the time zone of the target's schedule
+fn build_reference(entry: ReferenceEntry, schedule: &Schedule) -> Result<GreetingReference> {
+    let window = Schedule::new(entry.window.as_deref().unwrap_or("* * * * *"), schedule.timezone().name())
+        .context("window is not valid")?;
+    Ok(GreetingReference {
+        name: entry.name,
+        window: with_excluded_dates(window, entry.excluded_dates.as_deref())?,
+        dates: entry.dates.as_deref().map(ExcludedDates::load).transpose()?,
+    })
+}
+
+/// Attach the excluded dates from `path`, if any
+fn with_excluded_dates(schedule: Schedule, path: Option<&str>) -> Result<Schedule> {
+    match path {
+        Some(path) => Ok(schedule.with_excluded_dates(ExcludedDates::load(path)?)),
+        None => Ok(schedule),
+    }
+}
+
+/// Validate target settings, reporting every problem at once
+fn validate_targets(targets: &[CheckTarget]) -> Result<()> {
+    let mut errors: Vec<String> = Vec::new();
+    let mut names = HashSet::new();
+
+    for target in targets {
+        let name = &target.name;
+        let config = &target.config;
+
+        if !is_valid_name(name) {
+            errors.push(format!(
+                "target '{}': name must be letters, digits, '-' or '_'.",
+                name
+            ));
+        }
+        if !names.insert(name.as_str()) {
+            errors.push(format!("target '{}': duplicate name.", name));
+        }
+
+        if !Config::is_valid_phone(&config.target_phone) {
+            errors.push(format!(
+                "target '{}': phone '{}' invalid. Expected 10 digits.",
+                name, config.target_phone
+            ));
+        }
+
+        if config.expected_phrase.trim().is_empty() {
+            errors.push(format!("target '{}': expected_phrase cannot be empty.", name));
+        }
+
+        if config.listen_duration_secs == 0 || config.listen_duration_secs > 300 {
+            errors.push(format!(
+                "target '{}': listen_duration_secs={} must be between 1 and 300.",
+                name, config.listen_duration_secs
+            ));
+        }
+
+        if config.ring_timeout_secs == 0 || config.ring_timeout_secs > 300 {
+            errors.push(format!(
+                "target '{}': ring_timeout_secs={} must be between 1 and 300.",
+                name, config.ring_timeout_secs
+            ));
+        }
+
+        if let Some(ref script) = config.ivr_script {
+            if script.captures() == 0 {
+                errors.push(format!(
+                    "target '{}': ivr_script must contain at least one capture step.",
+                    name
+                ));
+            }
+            if script.total_duration().as_secs() > 300 {
+                errors.push(format!(
+                    "target '{}': ivr_script runs for more than 300s.",
+                    name
+                ));
+            }
+        }
+
+        if config.min_audio_duration_ms > config.listen_duration_secs * 1000 {
+            errors.push(format!(
+                "target '{}': min_audio_duration_ms={} must not exceed listen_duration_secs.",
+                name, config.min_audio_duration_ms
+            ));
+        }
+
+        if !(target.threshold > 0.0 && target.threshold <= 1.0) {
+            errors.push(format!(
+                "target '{}': threshold {} must be in (0, 1].",
+                name, target.threshold
+            ));
+        }
+        if let Some(warning) = config.warning_threshold {
+            if !(warning > 0.0 && warning < target.threshold) {
+                errors.push(format!(
+                    "target '{}': warning_threshold {} must be in (0, threshold {}).",
+                    name, warning, target.threshold
+                ));
+            }
+        }
+        if !(target.threshold..=1.0).contains(&config.reference_update_threshold) {
+            errors.push(format!(
+                "target '{}': reference_update_threshold {} must be in [threshold {}, 1].",
+                name, config.reference_update_threshold, target.threshold
+            ));
+        }
+        if !(config.fingerprint_threshold > 0.0 && config.fingerprint_threshold <= 1.0) {
+            errors.push(format!(
+                "target '{}': fingerprint_threshold {} must be in (0, 1].",
+                name, config.fingerprint_threshold
+            ));
+        }
+        for (field, value) in [
+            ("phrase_threshold", config.phrase_threshold),
+            ("transcript_weight", config.transcript_weight),
+        ] {
+            if !(0.0..=1.0).contains(&value) {
+                errors.push(format!("target '{}': {} {} must be in [0, 1].", name, field, value));
+            }
+        }
+        if let Some(mos) = config.min_mos {
+            if !(1.0..=4.5).contains(&mos) {
+                errors.push(format!("target '{}': min_mos {} must be in [1, 4.5].", name, mos));
+            }
+        }
+
+        if target.pushover_user_key.trim().is_empty() {
+            errors.push(format!("target '{}': pushover_user_key cannot be empty.", name));
+        }
+
+        let mut reference_names = HashSet::new();
+        for reference in &target.references {
+            if !is_valid_name(&reference.name) || is_reserved_reference_name(&reference.name) {
+                errors.push(format!(
+                    "target '{}': reference name '{}' must be letters, digits, '-' or '_' (and not 'greeting' or 'stepN').",
+                    name, reference.name
+                ));
+            }
+            if !reference_names.insert(reference.name.as_str()) {
+                errors.push(format!("target '{}': duplicate reference '{}'.", name, reference.name));
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        bail!("{}", errors.join("\n  - "))
+    }
+}
+
+/// Names appear in metric labels and directory names
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Names used by the unnamed greeting and IVR step references
+fn is_reserved_reference_name(name: &str) -> bool {
+    name == slot_name(None)
+        || name
+            .strip_prefix("step")
+            .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn base_config() -> Config {
+        let mut m = HashMap::new();
+        m.insert("SIP_USERNAME", "testuser");
+        m.insert("SIP_PASSWORD", "testpass");
+        m.insert("SIP_SERVER", "sip.example.com");
+        m.insert("CHECKS_FILE", "./checks.toml");
+        m.insert("EXPECTED_PHRASE", "Thank you for calling");
+        m.insert("PUSHOVER_USER_KEY", "user123");
+        m.insert("PUSHOVER_API_TOKEN", "token456");
+        Config::from_map(&m).unwrap()
+    }
+
+    use chrono::TimeZone;
+
+    fn local(hour: u32, minute: u32) -> chrono::NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2024, 1, 15).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    const EXAMPLE: &str = r#"
+        [[target]]
+        name = "main"
+        phone = "9095551234"
+
+        [[target]]
+        name = "support"
+        phone = "9095555678"
+        expected_phrase = "You have reached Support"
+        ivr_script = "wait 3s, send 2, capture 8s"
+        threshold = 0.8
+
+        [[target]]
+        name = "after-hours"
+        phone = "(909) 555-0000"
+        listen_duration_secs = 15
+        ring_timeout_secs = 45
+        schedule = "0 18-23,0-7 * * *"
+        timezone = "America/New_York"
+        reference_dir = "/var/lib/phonecheck/after-hours"
+        pushover_user_key = "oncall456"
+    "#;
+
+    #[test]
+    fn test_parse_example() {
+        let targets = parse_checks(EXAMPLE, &base_config()).unwrap();
+        let names: Vec<&str> = targets.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["main", "support", "after-hours"]);
+
+        // Unset fields come from the environment
+        let main = &targets[0];
+        assert_eq!(main.config.target_phone, "9095551234");
+        assert_eq!(main.config.expected_phrase, "thank you for calling");
+        assert_eq!(main.config.listen_duration_secs, 10);
+        assert_eq!(main.threshold, crate::embedding::DEFAULT_SIMILARITY_THRESHOLD);
+        assert_eq!(main.thresholds(), Thresholds::default());
+        assert_eq!(main.reference_dir, "./models/main");
+        assert_eq!(main.schedule, Schedule::business_hours());
+        assert_eq!(main.pushover_user_key, "user123");
+        assert!(!main.is_default());
+
+        let support = &targets[1];
+        assert_eq!(support.config.expected_phrase, "you have reached support");
+        assert_eq!(support.config.ivr_script.as_ref().unwrap().captures(), 1);
+        assert_eq!(support.threshold, 0.8);
+        assert!(main.config.ivr_script.is_none());
+
+        let after_hours = &targets[2];
+        assert_eq!(after_hours.config.listen_duration_secs, 15);
+        assert_eq!(after_hours.config.ring_timeout_secs, 45);
+        assert_eq!(after_hours.schedule.timezone(), chrono_tz::America::New_York);
+        assert!(after_hours.schedule.matches_local(local(2, 0)));
+        assert!(!after_hours.schedule.matches_local(local(12, 0)));
+        assert_eq!(after_hours.pushover_user_key, "oncall456");
+        assert_eq!(
+            after_hours.reference_path(Some(1)),
+            "/var/lib/phonecheck/after-hours/reference_embedding_step1.bin"
+        );
+    }
+
+    #[test]
+    fn test_example_checks_file_parses() {
+        let targets = parse_checks(include_str!("../checks.example.toml"), &base_config()).unwrap();
+        assert_eq!(targets.len(), 4);
+    }
+
+    #[test]
+    fn test_named_references_by_time() {
+        let path = std::env::temp_dir().join(format!("phonecheck_reference_holidays_{}.txt", std::process::id()));
+        std::fs::write(&path, "2024-01-15\n").unwrap();
+        let toml = format!(
+            r#"
+            [[target]]
+            name = "main"
+            phone = "9095551234"
+            timezone = "America/New_York"
+
+            [[target.reference]]
+            name = "day"
+            window = "* 8-16 * * MON-FRI"
+            excluded_dates = "{holidays}"
+
+            [[target.reference]]
+            name = "night"
+            window = "* 17-23,0-7 * * *; * * * * SAT,SUN"
+
+            [[target.reference]]
+            name = "holiday"
+            dates = "{holidays}"
+            "#,
+            holidays = path.display()
+        );
+        let targets = parse_checks(&toml, &base_config()).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let main = &targets[0];
+
+        let names_at = |y: i32, m: u32, d: u32, h: i64| -> Vec<String> {
+            // Eastern standard time is UTC-5
+            let now = Utc.with_ymd_and_hms(y, m, d, 0, 0, 0).unwrap() + chrono::Duration::hours(h + 5);
+            main.greeting_references_at(now).into_iter().map(|(name, _)| name).collect()
+        };
+        // Tuesday 10am, Tuesday 8pm, Saturday 10am, holiday Monday 10am
+        assert_eq!(names_at(2024, 1, 16, 10), vec!["day"]);
+        assert_eq!(names_at(2024, 1, 16, 20), vec!["night"]);
+        assert_eq!(names_at(2024, 1, 20, 10), vec!["night"]);
+        assert_eq!(names_at(2024, 1, 15, 10), vec!["holiday"]);
+
+        assert_eq!(
+            main.greeting_references()[1],
+            ("night".to_string(), "./models/main/reference_embedding_night.bin".to_string())
+        );
+        assert!(main.find_reference("night").is_ok());
+        assert!(format!("{:#}", main.find_reference("lunch").unwrap_err()).contains("day, night, holiday"));
+    }
+
+    #[test]
+    fn test_greeting_references_fall_back() {
+        // Without named references there is the single greeting
+        let target = CheckTarget::from_config(Arc::new(base_config())).unwrap();
+        assert_eq!(
+            target.greeting_references_at(Utc::now()),
+            vec![("greeting".to_string(), crate::model_manager::REFERENCE_EMBEDDING_PATH.to_string())]
+        );
+
+        // When no window applies, every reference is accepted
+        let toml = "[[target]]\nname = \"a\"\nphone = \"9095551234\"\n\
+                    [[target.reference]]\nname = \"lunch\"\nwindow = \"* 12 * * *\"\n\
+                    [[target.reference]]\nname = \"morning\"\nwindow = \"* 9 * * *\"";
+        let targets = parse_checks(toml, &base_config()).unwrap();
+        let midnight = Utc.with_ymd_and_hms(2024, 1, 16, 8, 0, 0).unwrap();
+        assert_eq!(targets[0].greeting_references_at(midnight).len(), 2);
+    }
+
+    #[test]
+    fn test_rejects_bad_references() {
+        let base = base_config();
+        let err = |references: &str| {
+            let toml = format!("[[target]]\nname = \"a\"\nphone = \"9095551234\"\n{}", references);
+            format!("{:#}", parse_checks(&toml, &base).unwrap_err())
+        };
+
+        let dup = "[[target.reference]]\nname = \"day\"\n[[target.reference]]\nname = \"day\"";
+        assert!(err(dup).contains("duplicate reference 'day'"));
+        assert!(err("[[target.reference]]\nname = \"step2\"").contains("not 'greeting' or 'stepN'"));
+        assert!(err("[[target.reference]]\nname = \"greeting\"").contains("not 'greeting' or 'stepN'"));
+        assert!(err("[[target.reference]]\nname = \"day\"\nwindow = \"9-17\"").contains("reference 'day'"));
+        assert!(err("[[target.reference]]\nname = \"day\"\ncolour = \"red\"").contains("unknown field"));
+    }
+
+    #[test]
+    fn test_from_config_is_default_target() {
+        let target = CheckTarget::from_config(Arc::new(base_config())).unwrap();
+        assert!(target.is_default());
+        assert_eq!(target.schedule, Schedule::business_hours());
+        assert_eq!(target.reference_path(None), crate::model_manager::REFERENCE_EMBEDDING_PATH);
+    }
+
+    #[test]
+    fn test_detection_settings_per_target() {
+        let mut base = base_config();
+        base.warning_threshold = Some(0.6);
+        let toml = r#"
+            [[target]]
+            name = "main"
+            phone = "9095551234"
+
+            [[target]]
+            name = "noisy"
+            phone = "9095555678"
+            threshold = 0.7
+            warning_threshold = 0.5
+            reference_update_threshold = 0.9
+            fingerprint_threshold = 0.2
+            min_audio_duration_ms = 1500
+            match_policy = "either"
+            phrase_threshold = 0.7
+            transcript_weight = 0.3
+            min_mos = 3.5
+        "#;
+        let targets = parse_checks(toml, &base).unwrap();
+
+        // Unset settings come from the environment
+        let main = targets[0].thresholds();
+        assert_eq!((main.similarity, main.warning), (0.75, Some(0.6)));
+
+        let noisy = &targets[1];
+        assert_eq!(
+            noisy.thresholds(),
+            Thresholds { similarity: 0.7, warning: Some(0.5), fingerprint: 0.2, update: 0.9 }
+        );
+        assert_eq!(noisy.config.min_audio_duration_ms, 1500);
+        let policy = noisy.config.phrase_policy();
+        assert_eq!(policy.policy, MatchPolicy::Either);
+        assert_eq!((policy.threshold, policy.transcript_weight), (0.7, 0.3));
+        assert_eq!((targets[0].config.min_mos, noisy.config.min_mos), (None, Some(3.5)));
+    }
+
+    #[test]
+    fn test_rejects_bad_detection_settings() {
+        let base = base_config();
+        let err = |settings: &str| {
+            let toml = format!("[[target]]\nname = \"a\"\nphone = \"9095551234\"\n{}", settings);
+            format!("{:#}", parse_checks(&toml, &base).unwrap_err())
+        };
+
+        assert!(err("threshold = 0.8\nwarning_threshold = 0.8").contains("warning_threshold 0.8"));
+        assert!(err("reference_update_threshold = 0.7").contains("reference_update_threshold 0.7"));
+        assert!(err("fingerprint_threshold = 0").contains("fingerprint_threshold 0"));
+        assert!(err("transcript_weight = 1.5").contains("transcript_weight 1.5"));
+        assert!(err("min_audio_duration_ms = 20000").contains("min_audio_duration_ms=20000"));
+        assert!(err("min_mos = 0.5").contains("min_mos 0.5"));
+        assert!(err("match_policy = \"majority\"").contains("unknown variant"));
+    }
+
+    #[test]
+    fn test_excluded_dates_inherited_from_environment() {
+        let path = std::env::temp_dir().join(format!("phonecheck_holidays_{}.txt", std::process::id()));
+        std::fs::write(&path, "2024-01-15\n").unwrap();
+
+        let mut base = base_config();
+        base.excluded_dates_file = Some(path.to_string_lossy().into_owned());
+        let day = chrono::NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+
+        let target = CheckTarget::from_config(Arc::new(base.clone())).unwrap();
+        assert!(!target.schedule.runs_on(day));
+
+        let targets = parse_checks("[[target]]\nname = \"a\"\nphone = \"9095551234\"", &base).unwrap();
+        assert!(!targets[0].schedule.runs_on(day));
+        assert_eq!(targets[0].schedule.excluded_dates().len(), 1);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_load_targets_without_checks_file() {
+        let mut config = base_config();
+        config.checks_file = None;
+        let targets = load_targets(&Arc::new(config)).unwrap();
+        assert_eq!(targets.len(), 1);
+        assert!(targets[0].is_default());
+    }
+
+    #[test]
+    fn test_find_target() {
+        let targets = parse_checks(EXAMPLE, &base_config()).unwrap();
+        assert_eq!(find_target(&targets, Some("support")).unwrap().name, "support");
+        assert!(format!("{:#}", find_target(&targets, Some("sales")).unwrap_err()).contains("main, support, after-hours"));
+        assert!(find_target(&targets, None).is_err());
+        assert_eq!(find_target(&targets[..1], None).unwrap().name, "main");
+    }
+
+    #[test]
+    fn test_rejects_bad_targets() {
+        let base = base_config();
+        let err = |toml: &str| format!("{:#}", parse_checks(toml, &base).unwrap_err());
+
+        assert!(err("").contains("No [[target]]"));
+        assert!(err("[[target]]\nname = \"a\"").contains("phone"));
+        assert!(err("[[target]]\nname = \"a\"\nphone = \"9095551234\"\ncolour = \"red\"")
+            .contains("unknown field"));
+
+        let dup = "[[target]]\nname = \"a\"\nphone = \"9095551234\"\n\
+                   [[target]]\nname = \"a\"\nphone = \"9095551235\"";
+        assert!(err(dup).contains("duplicate name"));
+
+        let bad = "[[target]]\nname = \"main line\"\nphone = \"123\"\nthreshold = 1.5";
+        let msg = err(bad);
+        assert!(msg.contains("name must be"), "{}", msg);
+        assert!(msg.contains("Expected 10 digits"), "{}", msg);
+        assert!(msg.contains("threshold"), "{}", msg);
+
+        let ivr = "[[target]]\nname = \"a\"\nphone = \"9095551234\"\nivr_script = \"wait 3s\"";
+        assert!(err(ivr).contains("capture step"));
+
+        let schedule = "[[target]]\nname = \"a\"\nphone = \"9095551234\"\nschedule = \"0 25 * * *\"";
+        assert!(err(schedule).contains("target 'a'"));
+
+        let timezone = "[[target]]\nname = \"a\"\nphone = \"9095551234\"\ntimezone = \"PST\"";
+        assert!(err(timezone).contains("Unknown time zone"));
+
+        let excluded = "[[target]]\nname = \"a\"\nphone = \"9095551234\"\nexcluded_dates = \"/nonexistent/holidays.ics\"";
+        assert!(err(excluded).contains("target 'a'"));
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        /// Only names that are safe as metric labels and directory names pass
+        #[test]
+        fn valid_names_are_label_safe(name in ".{0,20}") {
+            if is_valid_name(&name) {
+                prop_assert!(!name.contains('"') && !name.contains('/') && !name.contains('\\'));
+                prop_assert!(!name.is_empty());
+            }
+        }
+    }
+}
@@ -222,6 +222,7 @@ fn make_packet(seq: u16) -> BufferedPacket {
     BufferedPacket {
         sequence: seq,
         timestamp: seq as u32 * 160,
+        marker: false,
         payload: vec![0u8; 160],
     }
 }
@@ -259,6 +260,7 @@ proptest! {
             target_depth: 0,  // Don't hold packets
             max_size: 1000,   // Large enough for test
             max_gap: 200,     // Allow gaps in test data
+            ..Default::default()
         });
 
         // Generate sequences as base + offset, deduplicate, then SORT
@@ -321,6 +323,7 @@ proptest! {
             target_depth: 0,
             max_size: 20,
             max_gap: 10,
+            ..Default::default()
         });
 
         // Insert packets around wraparound in order
@@ -358,6 +361,7 @@ fn test_jitter_buffer_enforces_max_size() {
         target_depth: 3,
         max_size: 10,
         max_gap: 5,
+        ..Default::default()
     });
 
     // Insert 20 packets (more than max_size)
@@ -376,6 +380,7 @@ fn test_jitter_buffer_drops_oldest_on_overflow() {
         target_depth: 2,
         max_size: 5,
         max_gap: 10,
+        ..Default::default()
     });
 
     // Insert packets 0-9
@@ -404,6 +409,7 @@ fn test_jitter_buffer_handles_wraparound() {
         target_depth: 2,
         max_size: 20,
         max_gap: 5,
+        ..Default::default()
     });
 
     // Insert packets around wraparound: 65533, 65534, 65535, 0, 1, 2
@@ -428,6 +434,7 @@ fn test_jitter_buffer_late_packet_after_wraparound() {
         target_depth: 2,
         max_size: 20,
         max_gap: 5,
+        ..Default::default()
     });
 
     // Insert 0, 1, 2, 3
@@ -474,6 +481,7 @@ fn test_jitter_buffer_skips_large_gaps() {
         target_depth: 2,
         max_size: 20,
         max_gap: 3,
+        ..Default::default()
     });
 
     // Insert 0, 1, then skip to 20
@@ -737,6 +745,7 @@ fn test_jitter_buffer_zero_config() {
         target_depth: 0,
         max_size: 0,
         max_gap: 0,
+        ..Default::default()
     });
 
     // Should still not panic
@@ -751,6 +760,7 @@ fn test_jitter_buffer_max_config() {
         target_depth: u16::MAX,
         max_size: u16::MAX,
         max_gap: u16::MAX,
+        ..Default::default()
     });
 
     // Insert a few packets
@@ -778,6 +788,7 @@ fn test_jitter_buffer_large_payload() {
     let large_packet = BufferedPacket {
         sequence: 0,
         timestamp: 0,
+        marker: false,
         payload: vec![0u8; 1_000_000],
     };
 
@@ -798,6 +809,7 @@ fn test_jitter_buffer_deterministic() {
             target_depth: 2,
             max_size: 10,
             max_gap: 5,
+            ..Default::default()
         });
 
         for seq in [5u16, 3, 1, 4, 2, 0] {
@@ -392,6 +392,7 @@ fn test_invite_header_injection_in_display_name() {
         addr,
         10000,
         None,
+        None,
     );
 
     // The message should NOT have a valid "Evil-Header" at the start of a line
@@ -421,6 +422,7 @@ fn test_invite_header_injection_in_uri() {
         addr,
         10000,
         None,
+        None,
     );
 
     // Check if injection succeeded
@@ -542,6 +544,7 @@ proptest! {
             addr,
             rtp_port,
             None,
+            None,
         );
         // Must be valid UTF-8 (would panic on invalid)
         prop_assert!(invite.is_ascii() || !invite.is_empty());
@@ -624,6 +627,7 @@ fn test_invite_structure_deterministic() {
         addr,
         10000,
         None,
+        None,
     );
 
     let invite2 = build_invite(
@@ -636,6 +640,7 @@ fn test_invite_structure_deterministic() {
         addr,
         10000,
         None,
+        None,
     );
 
     // The only difference should be in the random Via branch and SDP session IDs
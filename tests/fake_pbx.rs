@@ -0,0 +1,325 @@
+/// End-to-end tests of the full check against the fake PBX simulator
+/// Each test starts `fake_pbx` on an ephemeral port and runs
+/// `orchestrator::run_check` against it. No Whisper model is loaded, so a
+/// healthy call ends in "Speech recognition failed": what is checked here is
+/// the SIP/RTP path up to the audio handed to recognition.
+
+use async_trait::async_trait;
+use phonecheck::alerts::AlertTracker;
+use phonecheck::config::Config;
+use phonecheck::health::HealthMetrics;
+use phonecheck::history::{CheckRecord, HistoryQuery, HistoryStore};
+use phonecheck::notify::{Alert, NotificationSink, Notifier, Severity};
+use phonecheck::orchestrator;
+use phonecheck::speech::SpeechRecognizer;
+use phonecheck::targets::CheckTarget;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Lines, Write};
+use std::net::SocketAddr;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use tokio_util::sync::CancellationToken;
+
+/// A running simulator, killed on drop
+struct FakePbx {
+    child: Child,
+    addr: SocketAddr,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+}
+
+impl FakePbx {
+    fn start(args: &[&str]) -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_fake_pbx"))
+            .args(["--bind", "127.0.0.1:0", "--ring-ms", "100"])
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("fake_pbx should start");
+        let stdin = child.stdin.take().unwrap();
+        let mut stdout = BufReader::new(child.stdout.take().unwrap()).lines();
+        let line = stdout.next().expect("fake_pbx should print its address").unwrap();
+        let addr = line.strip_prefix("listening on ").expect("address line").parse().unwrap();
+        Self { child, addr, stdin, stdout }
+    }
+
+    /// Change the faults for the next calls and wait for the acknowledgement
+    fn set_faults(&mut self, faults: &str) {
+        writeln!(self.stdin, "{}", faults).unwrap();
+        let ack = self.stdout.next().expect("fault acknowledgement").unwrap();
+        assert_eq!(ack, format!("faults {}", faults));
+    }
+}
+
+impl Drop for FakePbx {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Records alerts instead of delivering them
+struct RecordingSink(Arc<Mutex<Vec<(Severity, String)>>>);
+
+#[async_trait]
+impl NotificationSink for RecordingSink {
+    fn name(&self) -> &str {
+        "recording"
+    }
+
+    async fn send(&self, alert: &Alert) -> anyhow::Result<()> {
+        self.0.lock().unwrap().push((alert.severity, alert.message.clone()));
+        Ok(())
+    }
+}
+
+/// Outcome of one check
+struct Checked {
+    alerts: Vec<(Severity, String)>,
+    record: CheckRecord,
+}
+
+fn test_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("phonecheck_fake_pbx_{}_{}", name, std::process::id()))
+}
+
+/// Two seconds of a 440 Hz tone at 16 kHz
+fn write_test_wav(name: &str) -> String {
+    let path = test_path(name).with_extension("wav");
+    let samples: Vec<f32> = (0..32000)
+        .map(|i| (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 16000.0).sin() * 0.3)
+        .collect();
+    phonecheck::rtp::save_wav(&samples, &path).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+async fn run_check(pbx: &FakePbx, name: &str, overrides: &[(&str, &str)]) -> Checked {
+    let port = pbx.addr.port().to_string();
+    let mut env: HashMap<&str, &str> = HashMap::new();
+    env.insert("SIP_USERNAME", "phonecheck");
+    env.insert("SIP_PASSWORD", "secret");
+    env.insert("SIP_SERVER", "127.0.0.1");
+    env.insert("SIP_PORT", &port);
+    env.insert("TARGET_PHONE", "100");
+    env.insert("PUSHOVER_USER_KEY", "user123");
+    env.insert("PUSHOVER_API_TOKEN", "token456");
+    env.insert("LISTEN_DURATION_SECS", "1");
+    env.insert("RING_TIMEOUT_SECS", "3");
+    env.insert("MIN_AUDIO_DURATION_MS", "500");
+    env.insert("CONFIRM_ATTEMPTS", "1");
+    env.insert("CONFIRM_FAILURES", "1");
+    env.insert("WHISPER_MODEL_PATH", "/nonexistent/ggml-model.bin");
+    env.extend(overrides.iter().copied());
+    let config = Arc::new(Config::from_getter(|key| env.get(key.env_var()).map(|v| v.to_string())).unwrap());
+    let target = CheckTarget::from_config(config.clone()).unwrap();
+
+    let alerts = Arc::new(Mutex::new(Vec::new()));
+    let notifier = Notifier::with_sinks(vec![Box::new(RecordingSink(alerts.clone()))], Vec::new()).unwrap();
+    let history_path = test_path(name).with_extension("jsonl");
+    let _ = std::fs::remove_file(&history_path);
+    let health = HealthMetrics::with_history(HistoryStore::open(&history_path, 1 << 20, 1).unwrap());
+    let recognizer = Mutex::new(SpeechRecognizer::new(&config.whisper_model_path).unwrap());
+
+    orchestrator::run_check(&target, &recognizer, &notifier, &AlertTracker::new(0), &health, CancellationToken::new(), None)
+        .await;
+
+    let record = health.history().unwrap().query(&HistoryQuery::parse("").unwrap()).remove(0);
+    let _ = std::fs::remove_file(&history_path);
+    let alerts = alerts.lock().unwrap().clone();
+    Checked { alerts, record }
+}
+
+fn assert_alert(checked: &Checked, severity: Severity, text: &str) {
+    assert_eq!(checked.alerts.len(), 1, "{:?}", checked.alerts);
+    assert_eq!(checked.alerts[0].0, severity, "{:?}", checked.alerts);
+    assert!(checked.alerts[0].1.contains(text), "{:?}", checked.alerts);
+}
+
+#[tokio::test]
+async fn test_answered_call_streams_audio() {
+    let wav = write_test_wav("answered");
+    let pbx = FakePbx::start(&["--wav", &wav, "--auth", "phonecheck:secret"]);
+    let checked = run_check(&pbx, "answered", &[]).await;
+    let _ = std::fs::remove_file(&wav);
+
+    // The audio made it through to speech recognition
+    assert_alert(&checked, Severity::Warning, "Speech recognition failed");
+    let attempt = &checked.record.attempts[0];
+    assert_eq!(attempt.sip_status, Some(200));
+    assert!(attempt.answer_ms.unwrap() >= 100, "{:?}", attempt.answer_ms);
+    assert!(attempt.audio_ms >= 800, "{} ms", attempt.audio_ms);
+    assert!(attempt.packets.received >= 40, "{:?}", attempt.packets);
+    assert_eq!(attempt.packets.lost, 0);
+}
+
+#[tokio::test]
+async fn test_answered_call_with_pcma() {
+    let pbx = FakePbx::start(&["--codec", "pcma"]);
+    let checked = run_check(&pbx, "pcma", &[]).await;
+
+    assert_alert(&checked, Severity::Warning, "Speech recognition failed");
+    assert!(checked.record.attempts[0].audio_ms >= 800);
+}
+
+#[tokio::test]
+async fn test_wrong_password_is_rejected() {
+    let pbx = FakePbx::start(&["--auth", "phonecheck:other"]);
+    let checked = run_check(&pbx, "auth", &[]).await;
+
+    assert_alert(&checked, Severity::Critical, "401: SIP authentication required");
+    assert_eq!(checked.record.attempts[0].sip_status, Some(401));
+}
+
+#[tokio::test]
+async fn test_busy() {
+    let pbx = FakePbx::start(&["--fault", "busy"]);
+    let checked = run_check(&pbx, "busy", &[]).await;
+
+    assert_alert(&checked, Severity::Critical, "486: Line busy or call declined");
+    assert_eq!(checked.record.attempts[0].sip_status, Some(486));
+}
+
+#[tokio::test]
+async fn test_service_unavailable() {
+    let pbx = FakePbx::start(&["--fault", "unavailable"]);
+    let checked = run_check(&pbx, "unavailable", &[]).await;
+
+    assert_alert(&checked, Severity::Critical, "503: SIP server error");
+    assert_eq!(checked.record.attempts[0].sip_status, Some(503));
+}
+
+#[tokio::test]
+async fn test_no_answer_is_cancelled() {
+    let pbx = FakePbx::start(&["--fault", "no-answer"]);
+    let checked = run_check(&pbx, "no_answer", &[("RING_TIMEOUT_SECS", "1")]).await;
+
+    assert_alert(&checked, Severity::Critical, "Call not answered within 1s");
+    // The simulator completes the INVITE with 487 after our CANCEL
+    assert_eq!(checked.record.attempts[0].sip_status, Some(487));
+}
+
+#[tokio::test]
+async fn test_one_way_audio() {
+    let pbx = FakePbx::start(&["--fault", "one-way"]);
+    let checked = run_check(&pbx, "one_way", &[]).await;
+
+    assert_alert(&checked, Severity::Critical, "Call connected but no audio received");
+    assert_eq!(checked.record.attempts[0].packets.received, 0);
+}
+
+#[tokio::test]
+async fn test_packet_loss_is_concealed() {
+    let pbx = FakePbx::start(&["--fault", "loss=50"]);
+    let checked = run_check(&pbx, "loss", &[]).await;
+
+    // About 50 packets are sent in the one second of listening; every other
+    // one is dropped and filled in, so the audio keeps its length
+    let attempt = &checked.record.attempts[0];
+    assert!(attempt.packets.received > 10 && attempt.packets.received <= 35, "{:?}", attempt.packets);
+    assert!(attempt.packets.concealed + 1 >= attempt.packets.received, "{:?}", attempt.packets);
+    assert!(attempt.audio_ms >= 800, "{} ms", attempt.audio_ms);
+    let stats = attempt.call_stats.as_ref().unwrap();
+    assert!((stats.loss_fraction() - 0.5).abs() < 0.1, "{}", stats);
+    // Waveform concealment (Bpl 25.1) softens 50% loss to R ~30, still poor
+    let mos = attempt.quality.unwrap().mos;
+    assert!((1.4..2.0).contains(&mos), "{:?}", attempt.quality);
+}
+
+
+#[tokio::test]
+async fn test_rtcp_reports_call_quality() {
+    let pbx = FakePbx::start(&[]);
+    // Our first report goes out 2.5 s in; the simulator answers it at once
+    let checked = run_check(&pbx, "rtcp", &[("LISTEN_DURATION_SECS", "3")]).await;
+
+    let stats = checked.record.attempts[0].call_stats.clone().unwrap();
+    assert_eq!(stats.lost, 0, "{}", stats);
+    assert!(stats.expected >= 100, "{}", stats);
+    assert!(stats.sender_reports >= 2, "{:?}", stats);
+    assert_eq!(stats.remote_cname.as_deref(), Some("fake_pbx"));
+    let rtt = stats.rtt_ms.unwrap();
+    assert!((0.0..100.0).contains(&rtt), "{}", stats);
+    assert!(checked.record.attempts[0].quality.unwrap().mos > 4.3);
+}
+
+#[tokio::test]
+async fn test_reordered_packets_are_resequenced() {
+    let pbx = FakePbx::start(&["--fault", "reorder"]);
+    let checked = run_check(&pbx, "reorder", &[]).await;
+
+    assert_alert(&checked, Severity::Warning, "Speech recognition failed");
+    let attempt = &checked.record.attempts[0];
+    assert!(attempt.audio_ms >= 800, "{} ms", attempt.audio_ms);
+    assert_eq!(attempt.packets.lost, 0, "{:?}", attempt.packets);
+}
+
+#[tokio::test]
+async fn test_transfer_switches_rtp_stream() {
+    let pbx = FakePbx::start(&["--fault", "transfer=400"]);
+    let checked = run_check(&pbx, "transfer", &[]).await;
+
+    // The new stream's sequence numbers restart behind the old one's; its
+    // audio is kept, not dropped as late
+    assert_alert(&checked, Severity::Warning, "Speech recognition failed");
+    let attempt = &checked.record.attempts[0];
+    assert!(attempt.audio_ms >= 800, "{} ms", attempt.audio_ms);
+    assert_eq!(attempt.packets.lost, 0, "{:?}", attempt.packets);
+    assert_eq!(attempt.packets.dropped, 0, "{:?}", attempt.packets);
+
+    let streams = &attempt.streams;
+    assert_eq!(streams.len(), 2, "{:?}", streams);
+    assert_ne!(streams[0].ssrc, streams[1].ssrc);
+    assert_ne!(streams[0].source, streams[1].source);
+    assert_eq!(streams[0].start_ms, 0);
+    assert!((300..600).contains(&streams[1].start_ms), "{:?}", streams);
+
+    let timeline = &attempt.stream_timeline;
+    assert_eq!(timeline.len(), 2, "{:?}", timeline);
+    assert_eq!((timeline[0].start_ms, timeline[0].end_ms), (0, timeline[1].start_ms));
+    assert_eq!(timeline[1].ssrc, streams[1].ssrc);
+    assert!(timeline[1].end_ms >= 800, "{:?}", timeline);
+}
+
+#[tokio::test]
+async fn test_early_media_is_kept_out_of_the_answered_audio() {
+    let pbx = FakePbx::start(&["--fault", "early=1500"]);
+    let checked = run_check(&pbx, "early_answered", &[]).await;
+
+    // 1.5 s of early media before the answer, then a 1 s listen: only the
+    // answered second is checked
+    assert_alert(&checked, Severity::Warning, "Speech recognition failed");
+    let attempt = &checked.record.attempts[0];
+    assert_eq!(attempt.sip_status, Some(200));
+    assert_eq!(attempt.early_media_status, Some(183));
+    assert!(attempt.answer_ms.unwrap() >= 1400, "{:?}", attempt.answer_ms);
+    assert!((800..1400).contains(&attempt.audio_ms), "{} ms", attempt.audio_ms);
+}
+
+#[tokio::test]
+async fn test_mid_call_bye_cuts_audio() {
+    let pbx = FakePbx::start(&["--fault", "bye=200"]);
+    let checked = run_check(&pbx, "bye", &[("LISTEN_DURATION_SECS", "2")]).await;
+
+    assert_alert(&checked, Severity::Critical, "Call connected but no audio received");
+    let attempt = &checked.record.attempts[0];
+    assert_eq!(attempt.sip_status, Some(200));
+    assert!(attempt.audio_ms < 500, "{} ms", attempt.audio_ms);
+}
+
+#[tokio::test]
+async fn test_faults_follow_the_plan_and_stdin() {
+    let mut pbx = FakePbx::start(&["--fault", "busy,unavailable"]);
+    let checked = run_check(&pbx, "plan", &[("CONFIRM_ATTEMPTS", "2"), ("CONFIRM_FAILURES", "2"), ("CONFIRM_DELAY_SECS", "0")]).await;
+
+    // The worst failure is reported, the latest one on ties
+    assert_alert(&checked, Severity::Critical, "503: SIP server error");
+    assert!(checked.alerts[0].1.contains("(2 of 2 attempts failed)"), "{:?}", checked.alerts);
+    let statuses: Vec<_> = checked.record.attempts.iter().map(|a| a.sip_status).collect();
+    assert_eq!(statuses, vec![Some(486), Some(503)]);
+
+    pbx.set_faults("busy");
+    let checked = run_check(&pbx, "plan_stdin", &[]).await;
+    assert_alert(&checked, Severity::Critical, "486");
+}
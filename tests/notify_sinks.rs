@@ -0,0 +1,397 @@
+/// Notification sink tests against local stand-ins
+/// Each sink is pointed at a small HTTP (or SMTP) server on localhost that
+/// records the request and returns a canned response.
+
+use phonecheck::config::Config;
+use phonecheck::notify::{
+    http_client, Alert, EmailSink, NotificationSink, Notifier, NtfyConfig, NtfySink, PushoverSink, Severity,
+    SlackSink, SmsConfig, SmsSink, SmtpConfig, SmtpSecurity, WebhookSink,
+};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+
+/// A request received by the stand-in
+#[derive(Debug)]
+struct Recorded {
+    method: String,
+    /// Path and query string
+    target: String,
+    headers: HashMap<String, String>,
+    body: String,
+}
+
+impl Recorded {
+    fn form(&self) -> HashMap<String, String> {
+        parse_urlencoded(&self.body)
+    }
+
+    fn query(&self) -> HashMap<String, String> {
+        parse_urlencoded(self.target.split_once('?').map(|(_, q)| q).unwrap_or(""))
+    }
+
+    fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).expect("body should be JSON")
+    }
+}
+
+fn parse_urlencoded(s: &str) -> HashMap<String, String> {
+    s.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(k, v)| (decode(k), decode(v)))
+        .collect()
+}
+
+fn decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                out.push(u8::from_str_radix(&s[i + 1..i + 3], 16).unwrap());
+                i += 2;
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8(out).unwrap()
+}
+
+/// Start an HTTP stand-in that answers every request with `status` and
+/// `body`. Returns its base URL and a channel of received requests.
+async fn http_stand_in(status: u16, body: &'static str) -> (String, mpsc::UnboundedReceiver<Recorded>) {
+    http_stand_in_with(move |_| (status, body.to_string())).await
+}
+
+/// Like `http_stand_in`, answering each request with `respond(request)`
+async fn http_stand_in_with<F>(respond: F) -> (String, mpsc::UnboundedReceiver<Recorded>)
+where
+    F: Fn(&Recorded) -> (u16, String) + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let mut reader = BufReader::new(stream);
+
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).await.unwrap();
+            let mut parts = request_line.split_whitespace();
+            let method = parts.next().unwrap_or("").to_string();
+            let target = parts.next().unwrap_or("").to_string();
+
+            let mut headers = HashMap::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).await.unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+                }
+            }
+
+            let length: usize = headers.get("content-length").and_then(|l| l.parse().ok()).unwrap_or(0);
+            let mut buf = vec![0u8; length];
+            reader.read_exact(&mut buf).await.unwrap();
+            let request = Recorded { method, target, headers, body: String::from_utf8_lossy(&buf).into_owned() };
+            let (status, body) = respond(&request);
+            let _ = tx.send(request);
+
+            let response = format!(
+                "HTTP/1.1 {} Stand-in\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            let mut stream = reader.into_inner();
+            let _ = stream.write_all(response.as_bytes()).await;
+            let _ = stream.shutdown().await;
+        }
+    });
+
+    (url, rx)
+}
+
+/// Start an SMTP stand-in that accepts one message and returns the DATA
+/// section through the channel
+async fn smtp_stand_in() -> (u16, mpsc::UnboundedReceiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (read, mut write) = stream.into_split();
+        let mut reader = BufReader::new(read);
+        write.write_all(b"220 stand-in ESMTP\r\n").await.unwrap();
+
+        let mut in_data = false;
+        let mut data = String::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).await.unwrap() == 0 {
+                break;
+            }
+            if in_data {
+                if line == ".\r\n" {
+                    in_data = false;
+                    let _ = tx.send(std::mem::take(&mut data));
+                    write.write_all(b"250 OK queued\r\n").await.unwrap();
+                } else {
+                    data.push_str(&line);
+                }
+                continue;
+            }
+
+            let command = line.trim_end().to_ascii_uppercase();
+            let reply: &[u8] = if command.starts_with("EHLO") {
+                b"250-stand-in\r\n250 8BITMIME\r\n"
+            } else if command == "DATA" {
+                in_data = true;
+                b"354 End data with <CR><LF>.<CR><LF>\r\n"
+            } else if command == "QUIT" {
+                write.write_all(b"221 Bye\r\n").await.unwrap();
+                break;
+            } else {
+                b"250 OK\r\n"
+            };
+            write.write_all(reply).await.unwrap();
+        }
+    });
+
+    (port, rx)
+}
+
+fn critical(target: &str, message: &str) -> Alert {
+    Alert::new(Severity::Critical, target, message)
+}
+
+#[tokio::test]
+async fn test_pushover_sink() {
+    let (url, mut rx) = http_stand_in(200, r#"{"status":1,"request":"abc"}"#).await;
+    let sink = PushoverSink::new(http_client(), &format!("{}/1/messages.json", url), "token456", "user123");
+
+    let alert = Alert { pushover_user_key: Some("oncall789".to_string()), ..critical("main", "Call did not connect") };
+    sink.send(&alert).await.unwrap();
+
+    let request = rx.recv().await.unwrap();
+    assert_eq!(request.method, "POST");
+    assert_eq!(request.target, "/1/messages.json");
+    let form = request.form();
+    assert_eq!(form["token"], "token456");
+    assert_eq!(form["user"], "oncall789");
+    assert_eq!(form["message"], "Call did not connect");
+    assert_eq!(form["priority"], "1");
+}
+
+#[tokio::test]
+async fn test_pushover_sink_api_error() {
+    let (url, _rx) = http_stand_in(400, r#"{"status":0,"errors":["user key is invalid"]}"#).await;
+    let sink = PushoverSink::new(http_client(), &url, "token456", "user123");
+
+    let err = sink.send(&critical("main", "down")).await.unwrap_err();
+    assert!(err.to_string().contains("user key is invalid"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_webhook_sink() {
+    let (url, mut rx) = http_stand_in(204, "").await;
+    let sink = WebhookSink::new(http_client(), &format!("{}/hooks/phonecheck", url));
+
+    sink.send(&Alert::new(Severity::Warning, "support", "Speech recognition failed")).await.unwrap();
+
+    let request = rx.recv().await.unwrap();
+    assert_eq!(request.target, "/hooks/phonecheck");
+    assert_eq!(request.headers["content-type"], "application/json");
+    let json = request.json();
+    assert_eq!(json["severity"], "warning");
+    assert_eq!(json["target"], "support");
+    assert_eq!(json["message"], "Speech recognition failed");
+}
+
+#[tokio::test]
+async fn test_webhook_sink_http_error() {
+    let (url, _rx) = http_stand_in(500, "boom").await;
+    let sink = WebhookSink::new(http_client(), &url);
+
+    let err = sink.send(&critical("main", "down")).await.unwrap_err();
+    assert!(err.to_string().contains("HTTP 500"), "{}", err);
+    assert!(err.to_string().contains("boom"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_slack_sink() {
+    let (url, mut rx) = http_stand_in(200, "ok").await;
+    let sink = SlackSink::new(http_client(), &format!("{}/services/T0/B0/X", url));
+
+    sink.send(&critical("main", "Expected greeting not detected")).await.unwrap();
+
+    let json = rx.recv().await.unwrap().json();
+    let text = json["text"].as_str().unwrap();
+    assert!(text.contains("*PhoneCheck Alert*"), "{}", text);
+    assert!(text.ends_with("Expected greeting not detected"), "{}", text);
+}
+
+#[tokio::test]
+async fn test_ntfy_sink() {
+    let (url, mut rx) = http_stand_in(200, r#"{"id":"x"}"#).await;
+    let config = NtfyConfig { url, topic: "phonecheck-alerts".to_string(), token: Some("tk_secret".to_string()) };
+    let sink = NtfySink::new(http_client(), &config);
+
+    sink.send(&critical("main", "Call did not connect")).await.unwrap();
+
+    let request = rx.recv().await.unwrap();
+    assert_eq!(request.method, "POST");
+    assert_eq!(request.target, "/phonecheck-alerts");
+    assert_eq!(request.headers["priority"], "5");
+    assert_eq!(request.headers["title"], "PhoneCheck Alert");
+    assert_eq!(request.headers["authorization"], "Bearer tk_secret");
+    assert_eq!(request.body, "Call did not connect");
+}
+
+#[tokio::test]
+async fn test_sms_sink_texts_every_number() {
+    let (url, mut rx) = http_stand_in(200, r#"{"status":"success","sms":"123"}"#).await;
+    let config = SmsConfig {
+        api_url: format!("{}/api/v1/rest.php", url),
+        api_username: "me@example.com".to_string(),
+        api_password: "apipass".to_string(),
+        did: "9095550100".to_string(),
+        to: vec!["(909) 555-0101".to_string(), "9095550102".to_string()],
+    };
+    let sink = SmsSink::new(http_client(), &config);
+
+    sink.send(&critical("main", &"x".repeat(300))).await.unwrap();
+
+    for dst in ["9095550101", "9095550102"] {
+        let request = rx.recv().await.unwrap();
+        assert_eq!(request.method, "GET");
+        assert!(request.target.starts_with("/api/v1/rest.php?"));
+        let query = request.query();
+        assert_eq!(query["method"], "sendSMS");
+        assert_eq!(query["api_username"], "me@example.com");
+        assert_eq!(query["did"], "9095550100");
+        assert_eq!(query["dst"], dst);
+        assert_eq!(query["message"].len(), 160);
+    }
+}
+
+#[tokio::test]
+async fn test_sms_sink_api_error() {
+    let (url, _rx) = http_stand_in(200, r#"{"status":"invalid_credentials"}"#).await;
+    let config = SmsConfig {
+        api_url: url,
+        api_username: "me@example.com".to_string(),
+        api_password: "wrong".to_string(),
+        did: "9095550100".to_string(),
+        to: vec!["9095550101".to_string()],
+    };
+
+    let err = SmsSink::new(http_client(), &config).send(&critical("main", "down")).await.unwrap_err();
+    assert!(err.to_string().contains("invalid_credentials"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_sms_sink_retries_only_failed_numbers() {
+    // The second number fails once
+    let failures = AtomicUsize::new(0);
+    let (url, mut rx) = http_stand_in_with(move |request| {
+        if request.query()["dst"] == "9095550102" && failures.fetch_add(1, Ordering::SeqCst) == 0 {
+            (200, r#"{"status":"invalid_dst"}"#.to_string())
+        } else {
+            (200, r#"{"status":"success","sms":"123"}"#.to_string())
+        }
+    })
+    .await;
+    let config = SmsConfig {
+        api_url: url,
+        api_username: "me@example.com".to_string(),
+        api_password: "apipass".to_string(),
+        did: "9095550100".to_string(),
+        to: vec!["9095550101".to_string(), "9095550102".to_string(), "9095550103".to_string()],
+    };
+    let sink = SmsSink::new(http_client(), &config);
+    let mut texted = || {
+        let mut dsts = Vec::new();
+        while let Ok(request) = rx.try_recv() {
+            dsts.push(request.query()["dst"].clone());
+        }
+        dsts
+    };
+
+    let alert = critical("main", "down");
+    let err = sink.send(&alert).await.unwrap_err().to_string();
+    assert!(err.contains("******0102") && err.contains("invalid_dst"), "{}", err);
+    assert!(!err.contains("0101") && !err.contains("0103"), "{}", err);
+    assert_eq!(texted(), vec!["9095550101", "9095550102", "9095550103"]);
+
+    // The notifier's retry of the same alert
+    sink.send(&alert.clone()).await.unwrap();
+    assert_eq!(texted(), vec!["9095550102"]);
+
+    // A new alert goes to everyone
+    sink.send(&critical("main", "still down")).await.unwrap();
+    assert_eq!(texted(), vec!["9095550101", "9095550102", "9095550103"]);
+}
+
+#[tokio::test]
+async fn test_email_sink() {
+    let (port, mut rx) = smtp_stand_in().await;
+    let config = SmtpConfig {
+        host: "127.0.0.1".to_string(),
+        port,
+        security: SmtpSecurity::None,
+        username: None,
+        password: None,
+        from: "PhoneCheck <alerts@example.com>".to_string(),
+        to: vec!["ops@example.com".to_string()],
+    };
+
+    EmailSink::new(&config).unwrap().send(&critical("support", "Call did not connect")).await.unwrap();
+
+    let data = rx.recv().await.unwrap();
+    assert!(data.contains("Subject: PhoneCheck Alert (critical): support"), "{}", data);
+    assert!(data.contains("To: ops@example.com"), "{}", data);
+    assert!(data.contains("Call did not connect"), "{}", data);
+}
+
+#[tokio::test]
+async fn test_notifier_routes_configured_sinks() {
+    let (pushover_url, mut pushover_rx) = http_stand_in(200, r#"{"status":1}"#).await;
+    let (webhook_url, mut webhook_rx) = http_stand_in(200, "").await;
+
+    let mut env = HashMap::new();
+    env.insert("SIP_USERNAME", "testuser".to_string());
+    env.insert("SIP_PASSWORD", "testpass".to_string());
+    env.insert("SIP_SERVER", "sip.example.com".to_string());
+    env.insert("TARGET_PHONE", "5551234567".to_string());
+    env.insert("PUSHOVER_USER_KEY", "user123".to_string());
+    env.insert("PUSHOVER_API_TOKEN", "token456".to_string());
+    env.insert("PUSHOVER_API_URL", pushover_url);
+    env.insert("WEBHOOK_URL", webhook_url);
+    env.insert("NOTIFY_ROUTES", "pushover; webhook severity=critical".to_string());
+    let config = Config::from_getter(|key| env.get(key.env_var()).cloned()).unwrap();
+
+    let notifier = Notifier::new(&config).unwrap();
+    assert_eq!(notifier.sink_names(), vec!["pushover", "webhook"]);
+
+    // Warnings only go to Pushover
+    notifier.send(&Alert::new(Severity::Warning, "default", "Speech recognition failed")).await.unwrap();
+    assert_eq!(pushover_rx.recv().await.unwrap().form()["priority"], "0");
+
+    // Critical alerts go to both
+    notifier.send(&critical("default", "Call did not connect")).await.unwrap();
+    assert_eq!(pushover_rx.recv().await.unwrap().form()["message"], "Call did not connect");
+    assert_eq!(webhook_rx.recv().await.unwrap().json()["severity"], "critical");
+    assert!(webhook_rx.try_recv().is_err());
+}